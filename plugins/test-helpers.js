@@ -13,6 +13,15 @@ export const makeCtx = () => {
       pluginDataDir: "/tmp/openusage-test/plugin",
     },
     host: {
+      capabilities: {
+        platform: "macos",
+        keychain: true,
+        ls: true,
+        sqlite: true,
+        ccusage: { available: true, runners: ["bunx"] },
+        proxy: false,
+        apis: [],
+      },
       fs: {
         exists: (path) => {
           if (files.has(path)) return true
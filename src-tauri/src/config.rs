@@ -10,10 +10,29 @@ pub struct ProxyConfig {
     pub url: String,
 }
 
+/// Plugin trust settings loaded from the `plugins` section of ~/.openusage/config.json
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct PluginsConfig {
+    /// Run plugins that carry no signature from a trusted publisher.
+    pub allow_unsigned: bool,
+    pub trusted_publishers: Vec<TrustedPublisherConfig>,
+}
+
+/// A publisher whose ed25519 key (base64) may sign plugin bundles.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TrustedPublisherConfig {
+    pub name: String,
+    pub public_key: String,
+}
+
 /// Top-level application config
 #[derive(Debug, Clone, Deserialize)]
 pub struct AppConfig {
     pub proxy: Option<ProxyConfig>,
+    #[serde(default)]
+    pub plugins: PluginsConfig,
 }
 
 /// Resolved proxy state — computed once at startup, used per-request.
@@ -26,36 +45,54 @@ pub struct ResolvedProxy {
 /// Global resolved proxy: Some(active) or None(disabled).
 static RESOLVED_PROXY: OnceLock<Option<ResolvedProxy>> = OnceLock::new();
 
+/// Plugin trust settings, loaded once like the proxy.
+static PLUGINS_CONFIG: OnceLock<PluginsConfig> = OnceLock::new();
+
 /// Returns the resolved proxy, or None if disabled/invalid/missing.
 /// Loaded once from disk on first call; subsequent calls are zero-cost.
 pub fn get_resolved_proxy() -> Option<&'static ResolvedProxy> {
     RESOLVED_PROXY.get_or_init(|| load_and_resolve_proxy()).as_ref()
 }
 
+/// Returns the plugin trust settings; defaults (unsigned plugins disabled) if the config is
+/// missing or invalid.
+pub fn get_plugins_config() -> &'static PluginsConfig {
+    PLUGINS_CONFIG.get_or_init(|| {
+        read_app_config()
+            .map(|config| config.plugins)
+            .unwrap_or_default()
+    })
+}
+
 /// Config file path: ~/.openusage/config.json
 fn config_path() -> Option<PathBuf> {
     dirs::home_dir().map(|home| home.join(".openusage").join("config.json"))
 }
 
-/// Loads config from disk, resolves proxy, logs result.
-fn load_and_resolve_proxy() -> Option<ResolvedProxy> {
+/// Reads and parses the config file; None if missing or invalid.
+fn read_app_config() -> Option<AppConfig> {
     let Some(path) = config_path() else {
-        log::debug!("[config] no home directory, proxy disabled");
+        log::debug!("[config] no home directory, using defaults");
         return None;
     };
-    let config = match std::fs::read_to_string(&path) {
+    match std::fs::read_to_string(&path) {
         Ok(contents) => match serde_json::from_str::<AppConfig>(&contents) {
-            Ok(cfg) => cfg,
+            Ok(cfg) => Some(cfg),
             Err(e) => {
                 log::warn!("[config] failed to parse {}: {}, using defaults", path.display(), e);
-                return None;
+                None
             }
         },
         Err(_) => {
             log::debug!("[config] no config file at {}, using defaults", path.display());
-            return None;
+            None
         }
-    };
+    }
+}
+
+/// Loads config from disk, resolves proxy, logs result.
+fn load_and_resolve_proxy() -> Option<ResolvedProxy> {
+    let config = read_app_config()?;
 
     let Some(proxy_cfg) = config.proxy.as_ref().filter(|p| p.enabled) else {
         log::debug!("[config] proxy disabled");
@@ -122,6 +159,7 @@ mod tests {
                 enabled: false,
                 url: "http://127.0.0.1:10808".to_string(),
             }),
+            plugins: PluginsConfig::default(),
         };
         assert!(config.proxy.as_ref().filter(|p| p.enabled).is_none());
     }
@@ -133,7 +171,22 @@ mod tests {
                 enabled: true,
                 url: "http://127.0.0.1:10808".to_string(),
             }),
+            plugins: PluginsConfig::default(),
         };
         assert!(config.proxy.as_ref().filter(|p| p.enabled).is_some());
     }
+
+    #[test]
+    fn plugins_config_defaults_to_unsigned_disabled() {
+        let config: AppConfig = serde_json::from_str(r#"{"proxy":null}"#).unwrap();
+        assert!(!config.plugins.allow_unsigned);
+        assert!(config.plugins.trusted_publishers.is_empty());
+
+        let config: AppConfig = serde_json::from_str(
+            r#"{"plugins":{"allowUnsigned":true,"trustedPublishers":[{"name":"Acme","publicKey":"AAAA"}]}}"#,
+        )
+        .unwrap();
+        assert!(config.plugins.allow_unsigned);
+        assert_eq!(config.plugins.trusted_publishers[0].name, "Acme");
+    }
 }
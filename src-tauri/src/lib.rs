@@ -134,8 +134,45 @@ fn handle_global_shortcut(
 
 pub struct AppState {
     pub plugins: Vec<plugin_engine::manifest::LoadedPlugin>,
+    pub plugins_dir: PathBuf,
+    pub bundled_plugin_ids: Vec<String>,
+    pub trust_policy: plugin_engine::signing::TrustPolicy,
     pub app_data_dir: PathBuf,
     pub app_version: String,
+    /// Result of the manifest `detect` rules. Some rules spawn processes, so this is evaluated
+    /// at startup and by the `detect_plugins` command only, not on every reload.
+    pub detections: Vec<plugin_engine::detect::PluginDetection>,
+}
+
+impl AppState {
+    /// Re-read the plugins directory after an install, uninstall or rollback.
+    fn reload_plugins(&mut self) {
+        self.plugins = plugin_engine::manifest::load_plugins_from_dir(&self.plugins_dir);
+        log::info!("reloaded {} plugins", self.plugins.len());
+        self.plugins_changed();
+    }
+
+    /// Reload only the plugin directories the dev watcher saw change; returns the affected ids.
+    fn reload_changed_plugins(&mut self, changed_dirs: &[String]) -> Vec<String> {
+        let plugin_ids = plugin_engine::watcher::reload_changed(
+            &mut self.plugins,
+            &self.plugins_dir,
+            changed_dirs,
+        );
+        self.plugins_changed();
+        plugin_ids
+    }
+
+    /// Re-check trust and refresh the HTTP API's plugin list.
+    fn plugins_changed(&mut self) {
+        plugin_engine::signing::apply_trust(&mut self.plugins, &self.trust_policy);
+        local_http_api::set_known_plugin_ids(
+            self.plugins.iter().map(|p| p.manifest.id.clone()).collect(),
+        );
+        local_http_api::set_default_enabled_plugin_ids(plugin_engine::detect::enabled_by_default(
+            &self.detections,
+        ));
+    }
 }
 
 #[derive(Debug, Clone, Serialize)]
@@ -144,17 +181,26 @@ pub struct PluginMeta {
     pub id: String,
     pub name: String,
     pub icon_url: String,
+    /// Theme-specific icons from the manifest `icons`; `icon_url` covers a missing variant.
+    pub icon_url_light: Option<String>,
+    pub icon_url_dark: Option<String>,
     pub brand_color: Option<String>,
     pub lines: Vec<ManifestLineDto>,
     pub links: Vec<PluginLinkDto>,
     /// Ordered list of primary metric candidates (sorted by primaryOrder).
     /// Frontend picks the first one that exists in runtime data.
     pub primary_candidates: Vec<String>,
+    pub trust: plugin_engine::signing::PluginTrust,
+    /// Set when this app cannot run the plugin; the plugin is listed but not probed.
+    pub incompatibility: Option<String>,
+    /// Detected on this machine and enabled for users who have not chosen plugins yet.
+    pub enabled_by_default: bool,
 }
 
 #[derive(Debug, Clone, Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct ManifestLineDto {
+    pub id: String,
     #[serde(rename = "type")]
     pub line_type: String,
     pub label: String,
@@ -188,6 +234,12 @@ pub struct ProbeBatchComplete {
     pub batch_id: String,
 }
 
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PluginsChanged {
+    pub plugin_ids: Vec<String>,
+}
+
 #[tauri::command]
 fn init_panel(app_handle: tauri::AppHandle) {
     panel::init(&app_handle).expect("Failed to initialize panel");
@@ -295,36 +347,67 @@ async fn start_probe_batch(
         tauri::async_runtime::spawn_blocking(move || {
             let plugin_id = plugin.manifest.id.clone();
             let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
-                plugin_engine::runtime::run_probe(&plugin, &data_dir, &version)
-            }));
-
-            match result {
-                Ok(output) => {
-                    let has_error = output.lines.iter().any(|line| {
-                        matches!(line, plugin_engine::runtime::MetricLine::Badge { label, .. } if label == "Error")
-                    });
-                    if has_error {
-                        log::warn!("probe {} completed with error", plugin_id);
-                    } else {
-                        log::info!(
-                            "probe {} completed ok ({} lines)",
-                            plugin_id,
-                            output.lines.len()
-                        );
-                        local_http_api::cache_successful_output(&output);
-                    }
-                    let _ = handle.emit(
-                        "probe:result",
-                        ProbeResult {
-                            batch_id: bid,
-                            output,
-                        },
+                if !plugin.trust.allowed {
+                    return plugin_engine::runtime::error_output(
+                        &plugin,
+                        plugin_engine::runtime::ProbeError::new(
+                            plugin_engine::runtime::ProbeErrorCode::Untrusted,
+                            "This plugin is not signed by a trusted publisher.",
+                        ),
+                    );
+                }
+                if let Some(reason) = &plugin.incompatibility {
+                    return plugin_engine::runtime::error_output(
+                        &plugin,
+                        plugin_engine::runtime::ProbeError::new(
+                            plugin_engine::runtime::ProbeErrorCode::Incompatible,
+                            format!(
+                                "This plugin cannot run on this version of the app: {}.",
+                                reason
+                            ),
+                        ),
                     );
                 }
+                plugin_engine::probe_runner::run_probe_isolated(&plugin, &data_dir, &version)
+            }));
+
+            let output = match result {
+                Ok(output) => output,
                 Err(_) => {
                     log::error!("probe {} panicked", plugin_id);
+                    plugin_engine::runtime::error_output(
+                        &plugin,
+                        plugin_engine::runtime::ProbeError::plugin_bug(
+                            "The plugin failed, try again or contact plugin author.",
+                        ),
+                    )
+                }
+            };
+            match &output.error {
+                Some(error) => {
+                    log::warn!(
+                        "probe {} completed with error ({})",
+                        plugin_id,
+                        error.code.as_str()
+                    );
+                    local_http_api::record_failed_output(&output);
+                }
+                None => {
+                    log::info!(
+                        "probe {} completed ok ({} lines)",
+                        plugin_id,
+                        output.lines.len()
+                    );
+                    local_http_api::cache_successful_output(&output);
                 }
             }
+            let _ = handle.emit(
+                "probe:result",
+                ProbeResult {
+                    batch_id: bid,
+                    output,
+                },
+            );
 
             if counter.fetch_sub(1, Ordering::SeqCst) == 1 {
                 log::info!("probe batch {} complete", completion_bid);
@@ -414,14 +497,17 @@ fn update_global_shortcut(
 }
 
 #[tauri::command]
-fn list_plugins(state: tauri::State<'_, Mutex<AppState>>) -> Vec<PluginMeta> {
-    let plugins = {
-        let locked = state.lock().expect("plugin state poisoned");
-        locked.plugins.clone()
+fn list_plugins(state: tauri::State<'_, Mutex<AppState>>) -> Result<Vec<PluginMeta>, String> {
+    let (plugins, enabled_by_default) = {
+        let locked = state.lock().map_err(|e| e.to_string())?;
+        (
+            locked.plugins.clone(),
+            plugin_engine::detect::enabled_by_default(&locked.detections),
+        )
     };
     log::debug!("list_plugins: {} plugins", plugins.len());
 
-    plugins
+    Ok(plugins
         .into_iter()
         .map(|plugin| {
             // Extract primary candidates: progress lines with primary_order, sorted by order
@@ -429,7 +515,10 @@ fn list_plugins(state: tauri::State<'_, Mutex<AppState>>) -> Vec<PluginMeta> {
                 .manifest
                 .lines
                 .iter()
-                .filter(|line| line.line_type == "progress" && line.primary_order.is_some())
+                .filter(|line| {
+                    line.line_type == plugin_engine::manifest::LineType::Progress
+                        && line.primary_order.is_some()
+                })
                 .collect();
             candidates.sort_by_key(|line| line.primary_order.unwrap());
             let primary_candidates: Vec<String> =
@@ -439,15 +528,18 @@ fn list_plugins(state: tauri::State<'_, Mutex<AppState>>) -> Vec<PluginMeta> {
                 id: plugin.manifest.id,
                 name: plugin.manifest.name,
                 icon_url: plugin.icon_data_url,
+                icon_url_light: plugin.icon_variant_data_urls.light,
+                icon_url_dark: plugin.icon_variant_data_urls.dark,
                 brand_color: plugin.manifest.brand_color,
                 lines: plugin
                     .manifest
                     .lines
                     .iter()
                     .map(|line| ManifestLineDto {
-                        line_type: line.line_type.clone(),
+                        id: line.id.clone(),
+                        line_type: line.line_type.as_str().to_string(),
                         label: line.label.clone(),
-                        scope: line.scope.clone(),
+                        scope: line.scope.as_str().to_string(),
                     })
                     .collect(),
                 links: plugin
@@ -460,9 +552,191 @@ fn list_plugins(state: tauri::State<'_, Mutex<AppState>>) -> Vec<PluginMeta> {
                     })
                     .collect(),
                 primary_candidates,
+                trust: plugin.trust,
+                incompatibility: plugin.incompatibility,
+                enabled_by_default: enabled_by_default.contains(&plugin.manifest.id),
             }
         })
-        .collect()
+        .collect())
+}
+
+fn find_plugin_manifest(
+    state: &tauri::State<'_, Mutex<AppState>>,
+    plugin_id: &str,
+) -> Result<(plugin_engine::manifest::PluginManifest, PathBuf), String> {
+    let locked = state.lock().map_err(|e| e.to_string())?;
+    let plugin = locked
+        .plugins
+        .iter()
+        .find(|plugin| plugin.manifest.id == plugin_id)
+        .ok_or_else(|| format!("unknown plugin: {}", plugin_id))?;
+    Ok((plugin.manifest.clone(), locked.app_data_dir.clone()))
+}
+
+#[tauri::command]
+fn get_plugin_settings(
+    state: tauri::State<'_, Mutex<AppState>>,
+    plugin_id: String,
+) -> Result<plugin_engine::settings::PluginSettingsView, String> {
+    let (manifest, app_data_dir) = find_plugin_manifest(&state, &plugin_id)?;
+    Ok(plugin_engine::settings::read_settings(
+        &manifest,
+        &app_data_dir,
+        &plugin_engine::settings::KeychainSecrets,
+    ))
+}
+
+#[tauri::command]
+fn set_plugin_settings(
+    state: tauri::State<'_, Mutex<AppState>>,
+    plugin_id: String,
+    values: serde_json::Map<String, serde_json::Value>,
+) -> Result<plugin_engine::settings::PluginSettingsView, String> {
+    let (manifest, app_data_dir) = find_plugin_manifest(&state, &plugin_id)?;
+    log::info!("set_plugin_settings: {} ({} keys)", plugin_id, values.len());
+    plugin_engine::settings::write_settings(
+        &manifest,
+        &app_data_dir,
+        &plugin_engine::settings::KeychainSecrets,
+        values,
+    )
+}
+
+#[tauri::command]
+fn list_installed_plugins(
+    state: tauri::State<'_, Mutex<AppState>>,
+) -> Result<Vec<plugin_engine::install::InstalledPluginInfo>, String> {
+    let locked = state.lock().map_err(|e| e.to_string())?;
+    Ok(plugin_engine::install::list_installed_plugins(
+        &locked.plugins,
+        &locked.plugins_dir,
+        &locked.bundled_plugin_ids,
+    ))
+}
+
+/// Where installs go, with the ids they must not replace. Plugins served from the development
+/// checkout are edited in place, so installing into it is refused.
+fn install_target(state: &Mutex<AppState>) -> Result<(PathBuf, Vec<String>), String> {
+    let locked = state.lock().map_err(|e| e.to_string())?;
+    if plugin_engine::is_dev_plugins_dir(&locked.plugins_dir) {
+        return Err(format!(
+            "plugins are loaded from the development checkout {}; edit them there instead",
+            locked.plugins_dir.display()
+        ));
+    }
+    Ok((
+        locked.plugins_dir.clone(),
+        locked.bundled_plugin_ids.clone(),
+    ))
+}
+
+#[tauri::command]
+async fn install_plugin(
+    app_handle: tauri::AppHandle,
+    path: String,
+    force: Option<bool>,
+) -> Result<plugin_engine::install::InstalledPluginInfo, String> {
+    use tauri::Manager;
+
+    log::info!("install_plugin: {}", path);
+    tauri::async_runtime::spawn_blocking(move || -> Result<_, String> {
+        let state = app_handle.state::<Mutex<AppState>>();
+        let (plugins_dir, bundled_ids) = install_target(&state)?;
+        // Unpacking and validating can take a while; probes keep running meanwhile.
+        let staged = plugin_engine::install::stage_plugin(
+            std::path::Path::new(&path),
+            &plugins_dir,
+            &bundled_ids,
+            force.unwrap_or(false),
+        )?;
+        let mut locked = state.lock().map_err(|e| e.to_string())?;
+        let installed = staged.install()?;
+        locked.reload_plugins();
+        plugin_engine::install::list_installed_plugins(
+            &locked.plugins,
+            &locked.plugins_dir,
+            &locked.bundled_plugin_ids,
+        )
+        .into_iter()
+        .find(|info| info.id == installed.manifest.id)
+        .ok_or_else(|| {
+            format!(
+                "plugin {} did not load after install",
+                installed.manifest.id
+            )
+        })
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+#[tauri::command]
+async fn uninstall_plugin(app_handle: tauri::AppHandle, plugin_id: String) -> Result<(), String> {
+    use tauri::Manager;
+
+    log::info!("uninstall_plugin: {}", plugin_id);
+    tauri::async_runtime::spawn_blocking(move || -> Result<_, String> {
+        let state = app_handle.state::<Mutex<AppState>>();
+        let (plugins_dir, bundled_ids) = install_target(&state)?;
+        let mut locked = state.lock().map_err(|e| e.to_string())?;
+        plugin_engine::install::uninstall_plugin(&plugin_id, &plugins_dir, &bundled_ids)?;
+        locked.reload_plugins();
+        Ok(())
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+#[tauri::command]
+async fn rollback_plugin(app_handle: tauri::AppHandle, plugin_id: String) -> Result<(), String> {
+    use tauri::Manager;
+
+    log::info!("rollback_plugin: {}", plugin_id);
+    tauri::async_runtime::spawn_blocking(move || -> Result<_, String> {
+        let state = app_handle.state::<Mutex<AppState>>();
+        let (plugins_dir, _) = install_target(&state)?;
+        let mut locked = state.lock().map_err(|e| e.to_string())?;
+        plugin_engine::install::rollback_plugin(&plugin_id, &plugins_dir)?;
+        locked.reload_plugins();
+        Ok(())
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+/// Evaluate every plugin's `detect` rules now and keep the result for `list_plugins`.
+#[tauri::command]
+fn detect_plugins(
+    state: tauri::State<'_, Mutex<AppState>>,
+) -> Result<Vec<plugin_engine::detect::PluginDetection>, String> {
+    let plugins = state.lock().map_err(|e| e.to_string())?.plugins.clone();
+    let detections = plugin_engine::detect::detect_plugins(&plugins);
+    local_http_api::set_default_enabled_plugin_ids(plugin_engine::detect::enabled_by_default(
+        &detections,
+    ));
+    state.lock().map_err(|e| e.to_string())?.detections = detections.clone();
+    Ok(detections)
+}
+
+#[tauri::command]
+fn validate_plugins(
+    state: tauri::State<'_, Mutex<AppState>>,
+) -> Result<plugin_engine::validate::ValidationReport, String> {
+    let plugins_dir = state.lock().map_err(|e| e.to_string())?.plugins_dir.clone();
+    Ok(plugin_engine::validate::validate_plugins_dir(&plugins_dir))
+}
+
+pub use plugin_engine::probe_runner::PROBE_RUNNER_ARG;
+pub use plugin_engine::validate::VALIDATE_PLUGINS_ARG;
+
+/// Entry point for the isolated probe runner child process; returns its exit code.
+pub fn run_probe_runner() -> i32 {
+    plugin_engine::probe_runner::run_from_stdio()
+}
+
+/// Entry point for `--validate-plugins [dir]`; prints a JSON report and returns the exit code.
+pub fn run_plugin_validation(dir: Option<PathBuf>) -> i32 {
+    plugin_engine::validate::run_cli(dir)
 }
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
@@ -498,6 +772,14 @@ pub fn run() {
             open_devtools,
             start_probe_batch,
             list_plugins,
+            get_plugin_settings,
+            set_plugin_settings,
+            list_installed_plugins,
+            install_plugin,
+            uninstall_plugin,
+            rollback_plugin,
+            validate_plugins,
+            detect_plugins,
             get_log_path,
             update_global_shortcut
         ])
@@ -537,18 +819,62 @@ pub fn run() {
                 redacted_app_data_dir
             );
 
-            let (_, plugins) = plugin_engine::initialize_plugins(&app_data_dir, &resource_dir);
+            let (plugins_dir, mut plugins) =
+                plugin_engine::initialize_plugins(&app_data_dir, &resource_dir);
+            let bundled_plugin_ids = plugin_engine::bundled_plugin_ids(&resource_dir);
+            let trust_policy = plugin_engine::trust_policy(
+                &plugins_dir,
+                &resource_dir,
+                config::get_plugins_config(),
+            );
+            plugin_engine::signing::apply_trust(&mut plugins, &trust_policy);
             let known_plugin_ids: Vec<String> =
                 plugins.iter().map(|p| p.manifest.id.clone()).collect();
+            let detections = plugin_engine::detect::detect_plugins(&plugins);
+            let default_enabled_plugin_ids = plugin_engine::detect::enabled_by_default(&detections);
+            log::info!(
+                "plugins enabled by default: {:?}",
+                default_enabled_plugin_ids
+            );
+            let app_version = app.package_info().version.to_string();
+
+            // Probe runners keep bytecode in memory now; drop what older versions left on disk.
+            plugin_engine::bytecode::remove_legacy_disk_cache(&app_data_dir);
+
+            let watch_dir =
+                plugin_engine::is_dev_plugins_dir(&plugins_dir).then(|| plugins_dir.clone());
+
             app.manage(Mutex::new(AppState {
                 plugins,
+                plugins_dir,
+                bundled_plugin_ids,
+                trust_policy,
                 app_data_dir: app_data_dir.clone(),
-                app_version: app.package_info().version.to_string(),
+                app_version,
+                detections,
             }));
 
-            local_http_api::init(&app_data_dir, known_plugin_ids);
+            local_http_api::init(&app_data_dir, known_plugin_ids, default_enabled_plugin_ids);
             local_http_api::start_server();
 
+            // In development, pick up edits to ./plugins without restarting the app.
+            if let Some(watch_dir) = watch_dir {
+                let handle = app.handle().clone();
+                plugin_engine::watcher::spawn(watch_dir, move |changed_dirs| {
+                    let plugin_ids = {
+                        let state = handle.state::<Mutex<AppState>>();
+                        let Ok(mut locked) = state.lock() else {
+                            log::error!("plugin state lock poisoned; skipping plugin reload");
+                            return;
+                        };
+                        locked.reload_changed_plugins(&changed_dirs)
+                    };
+                    if !plugin_ids.is_empty() {
+                        let _ = handle.emit("plugins:changed", PluginsChanged { plugin_ids });
+                    }
+                });
+            }
+
             tray::create(app.handle())?;
 
             app.handle()
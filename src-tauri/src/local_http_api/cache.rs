@@ -1,4 +1,4 @@
-use crate::plugin_engine::runtime::{MetricLine, PluginOutput};
+use crate::plugin_engine::runtime::{AccountOutput, MetricLine, PluginOutput, ProbeError};
 use serde::{Deserialize, Serialize};
 use std::collections::{HashMap, HashSet};
 use std::path::{Path, PathBuf};
@@ -6,7 +6,6 @@ use std::sync::{Mutex, OnceLock};
 
 const CACHE_FILE_NAME: &str = "usage-api-cache.json";
 const SETTINGS_FILE_NAME: &str = "settings.json";
-const DEFAULT_ENABLED_PLUGINS: &[&str] = &["claude", "codex", "cursor"];
 
 // ---------------------------------------------------------------------------
 // Types
@@ -19,7 +18,39 @@ pub struct CachedPluginSnapshot {
     pub display_name: String,
     pub plan: Option<String>,
     pub lines: Vec<MetricLine>,
+    /// All accounts from a multi-account probe; empty for single-account providers.
+    #[serde(default)]
+    pub accounts: Vec<AccountOutput>,
     pub fetched_at: String,
+    /// Error from the most recent probe, if it failed. `lines` and `fetchedAt` still
+    /// describe the last successful probe.
+    #[serde(default)]
+    pub error: Option<ProbeError>,
+}
+
+/// One account of a cached snapshot, as served by GET /v1/usage/:providerId/:accountKey.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(super) struct CachedAccountSnapshot {
+    pub provider_id: String,
+    pub display_name: String,
+    #[serde(flatten)]
+    pub account: AccountOutput,
+    pub fetched_at: String,
+    pub error: Option<ProbeError>,
+}
+
+impl CachedPluginSnapshot {
+    pub(super) fn account(&self, key: &str) -> Option<CachedAccountSnapshot> {
+        let account = self.accounts.iter().find(|account| account.key == key)?;
+        Some(CachedAccountSnapshot {
+            provider_id: self.provider_id.clone(),
+            display_name: self.display_name.clone(),
+            account: account.clone(),
+            fetched_at: self.fetched_at.clone(),
+            error: self.error.clone(),
+        })
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -33,6 +64,8 @@ pub(super) struct CacheState {
     pub snapshots: HashMap<String, CachedPluginSnapshot>,
     pub app_data_dir: PathBuf,
     pub known_plugin_ids: Vec<String>,
+    /// Plugins enabled when the user has no saved plugin settings yet (see `detect`).
+    pub default_enabled_plugin_ids: Vec<String>,
 }
 
 // ---------------------------------------------------------------------------
@@ -46,6 +79,7 @@ pub(super) fn cache_state() -> &'static Mutex<CacheState> {
             snapshots: HashMap::new(),
             app_data_dir: PathBuf::new(),
             known_plugin_ids: Vec::new(),
+            default_enabled_plugin_ids: Vec::new(),
         })
     })
 }
@@ -98,25 +132,46 @@ fn save_cache(app_data_dir: &Path, snapshots: &HashMap<String, CachedPluginSnaps
 // Public API: initialise + update cache
 // ---------------------------------------------------------------------------
 
-pub fn init(app_data_dir: &Path, known_plugin_ids: Vec<String>) {
+pub fn init(
+    app_data_dir: &Path,
+    known_plugin_ids: Vec<String>,
+    default_enabled_plugin_ids: Vec<String>,
+) {
     let snapshots = load_cache(app_data_dir);
     let mut state = cache_state().lock().expect("cache state poisoned");
     state.snapshots = snapshots;
     state.app_data_dir = app_data_dir.to_path_buf();
     state.known_plugin_ids = known_plugin_ids;
+    state.default_enabled_plugin_ids = default_enabled_plugin_ids;
 }
 
-pub fn cache_successful_output(output: &PluginOutput) {
-    let fetched_at = time::OffsetDateTime::now_utc()
+/// Replace the served plugin ids after plugins are installed or removed at runtime.
+pub fn set_known_plugin_ids(known_plugin_ids: Vec<String>) {
+    let mut state = cache_state().lock().expect("cache state poisoned");
+    state.known_plugin_ids = known_plugin_ids;
+}
+
+/// Replace the plugins enabled by default after detection runs again.
+pub fn set_default_enabled_plugin_ids(default_enabled_plugin_ids: Vec<String>) {
+    let mut state = cache_state().lock().expect("cache state poisoned");
+    state.default_enabled_plugin_ids = default_enabled_plugin_ids;
+}
+
+fn now_rfc3339() -> String {
+    time::OffsetDateTime::now_utc()
         .format(&time::format_description::well_known::Rfc3339)
-        .unwrap_or_default();
+        .unwrap_or_default()
+}
 
+pub fn cache_successful_output(output: &PluginOutput) {
     let snapshot = CachedPluginSnapshot {
         provider_id: output.provider_id.clone(),
         display_name: output.display_name.clone(),
         plan: output.plan.clone(),
         lines: output.lines.clone(),
-        fetched_at,
+        accounts: output.accounts.clone(),
+        fetched_at: now_rfc3339(),
+        error: None,
     };
 
     let mut state = cache_state().lock().expect("cache state poisoned");
@@ -126,6 +181,40 @@ pub fn cache_successful_output(output: &PluginOutput) {
     save_cache(&state.app_data_dir, &state.snapshots);
 }
 
+/// Attach a failed probe's error to the cached snapshot, keeping the last good data.
+pub fn record_failed_output(output: &PluginOutput) {
+    let Some(error) = output.error.clone() else {
+        return;
+    };
+    let mut state = cache_state().lock().expect("cache state poisoned");
+    apply_failed_output(&mut state.snapshots, output, error);
+    save_cache(&state.app_data_dir, &state.snapshots);
+}
+
+fn apply_failed_output(
+    snapshots: &mut HashMap<String, CachedPluginSnapshot>,
+    output: &PluginOutput,
+    error: ProbeError,
+) {
+    match snapshots.get_mut(&output.provider_id) {
+        Some(snapshot) => snapshot.error = Some(error),
+        None => {
+            snapshots.insert(
+                output.provider_id.clone(),
+                CachedPluginSnapshot {
+                    provider_id: output.provider_id.clone(),
+                    display_name: output.display_name.clone(),
+                    plan: None,
+                    lines: Vec::new(),
+                    accounts: Vec::new(),
+                    fetched_at: now_rfc3339(),
+                    error: Some(error),
+                },
+            );
+        }
+    }
+}
+
 // ---------------------------------------------------------------------------
 // Settings reader (reads settings.json directly, not via tauri_plugin_store)
 // ---------------------------------------------------------------------------
@@ -166,7 +255,11 @@ fn read_plugin_settings(app_data_dir: &Path) -> (Vec<String>, HashSet<String>, b
 pub(super) fn enabled_snapshots_ordered(state: &CacheState) -> Vec<CachedPluginSnapshot> {
     let (settings_order, disabled, has_settings) = read_plugin_settings(&state.app_data_dir);
 
-    let default_enabled: HashSet<&str> = DEFAULT_ENABLED_PLUGINS.iter().copied().collect();
+    let default_enabled: HashSet<&str> = state
+        .default_enabled_plugin_ids
+        .iter()
+        .map(String::as_str)
+        .collect();
 
     let is_enabled = |id: &str| -> bool {
         if has_settings {
@@ -200,7 +293,9 @@ pub(super) fn enabled_snapshots_ordered(state: &CacheState) -> Vec<CachedPluginS
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::plugin_engine::runtime::ProgressFormat;
+    use crate::plugin_engine::runtime::{
+        ProbeErrorCode, ProbeStatus, ProgressFormat, ProgressSegment, SeriesPoint,
+    };
 
     fn make_snapshot(id: &str, name: &str) -> CachedPluginSnapshot {
         CachedPluginSnapshot {
@@ -208,7 +303,22 @@ mod tests {
             display_name: name.to_string(),
             plan: Some("Pro".to_string()),
             lines: vec![],
+            accounts: vec![],
             fetched_at: "2026-03-26T08:15:30Z".to_string(),
+            error: None,
+        }
+    }
+
+    fn failed_output(id: &str, code: ProbeErrorCode) -> PluginOutput {
+        PluginOutput {
+            provider_id: id.to_string(),
+            display_name: "Claude".to_string(),
+            status: ProbeStatus::Error,
+            error: Some(ProbeError::new(code, "Sign in again")),
+            plan: None,
+            lines: vec![],
+            accounts: vec![],
+            icon_url: String::new(),
         }
     }
 
@@ -245,6 +355,45 @@ mod tests {
         let _ = std::fs::remove_dir_all(&dir);
     }
 
+    #[test]
+    fn enabled_snapshots_use_detected_defaults_until_settings_exist() {
+        let dir = std::env::temp_dir().join(format!(
+            "openusage-test-defaults-{}",
+            std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .unwrap()
+                .as_nanos()
+        ));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let mut snapshots = HashMap::new();
+        for id in ["claude", "codex", "cursor"] {
+            snapshots.insert(id.to_string(), make_snapshot(id, id));
+        }
+        let state = CacheState {
+            snapshots,
+            app_data_dir: dir.clone(),
+            known_plugin_ids: vec!["claude".into(), "codex".into(), "cursor".into()],
+            default_enabled_plugin_ids: vec!["codex".into()],
+        };
+        let ids = |state: &CacheState| -> Vec<String> {
+            enabled_snapshots_ordered(state)
+                .into_iter()
+                .map(|snap| snap.provider_id)
+                .collect()
+        };
+        assert_eq!(ids(&state), vec!["codex"]);
+
+        std::fs::write(
+            dir.join(SETTINGS_FILE_NAME),
+            r#"{"plugins":{"order":["cursor","claude"],"disabled":["claude"]}}"#,
+        )
+        .unwrap();
+        assert_eq!(ids(&state), vec!["cursor", "codex"]);
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
     #[test]
     fn load_cache_returns_empty_on_missing_file() {
         let dir = std::env::temp_dir().join(format!(
@@ -291,7 +440,9 @@ mod tests {
                 period_duration_ms: Some(14400000),
                 color: None,
             }],
+            accounts: vec![],
             fetched_at: "2026-03-26T08:00:00Z".to_string(),
+            error: None,
         };
 
         let json = serde_json::to_string(&snap).unwrap();
@@ -299,4 +450,147 @@ mod tests {
         assert_eq!(deserialized.provider_id, "claude");
         assert_eq!(deserialized.lines.len(), 1);
     }
+
+    #[test]
+    fn snapshot_with_segmented_line_round_trips() {
+        let mut snap = make_snapshot("cursor", "Cursor");
+        snap.lines = vec![MetricLine::Segmented {
+            label: "Usage".to_string(),
+            segments: vec![
+                ProgressSegment {
+                    label: "Included".to_string(),
+                    used: 20.0,
+                    color: None,
+                },
+                ProgressSegment {
+                    label: "On-demand".to_string(),
+                    used: 7.5,
+                    color: Some("#f59e0b".to_string()),
+                },
+            ],
+            limit: 40.0,
+            format: ProgressFormat::Dollars,
+            resets_at: Some("2026-04-01T00:00:00Z".to_string()),
+            period_duration_ms: None,
+        }];
+
+        let json = serde_json::to_value(&snap).unwrap();
+        assert_eq!(json["lines"][0]["type"], "segmented");
+        assert_eq!(json["lines"][0]["segments"][1]["label"], "On-demand");
+        assert_eq!(json["lines"][0]["resetsAt"], "2026-04-01T00:00:00Z");
+
+        let deserialized: CachedPluginSnapshot = serde_json::from_value(json).unwrap();
+        match &deserialized.lines[0] {
+            MetricLine::Segmented {
+                segments, limit, ..
+            } => {
+                assert_eq!(segments.len(), 2);
+                assert_eq!(segments[1].used, 7.5);
+                assert_eq!(*limit, 40.0);
+            }
+            other => panic!("unexpected line: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn snapshot_with_series_line_round_trips() {
+        let mut snap = make_snapshot("claude", "Claude");
+        snap.lines = vec![MetricLine::Series {
+            label: "Daily cost".to_string(),
+            points: vec![
+                SeriesPoint {
+                    at: "2026-03-25T00:00:00.000Z".to_string(),
+                    value: 3.2,
+                },
+                SeriesPoint {
+                    at: "2026-03-26T00:00:00.000Z".to_string(),
+                    value: 5.0,
+                },
+            ],
+            format: ProgressFormat::Dollars,
+            color: None,
+        }];
+
+        let json = serde_json::to_value(&snap).unwrap();
+        assert_eq!(json["lines"][0]["type"], "series");
+        assert_eq!(
+            json["lines"][0]["points"][1]["at"],
+            "2026-03-26T00:00:00.000Z"
+        );
+        assert_eq!(json["lines"][0]["format"]["kind"], "dollars");
+
+        let deserialized: CachedPluginSnapshot = serde_json::from_value(json).unwrap();
+        match &deserialized.lines[0] {
+            MetricLine::Series { points, .. } => {
+                assert_eq!(points.len(), 2);
+                assert_eq!(points[0].value, 3.2);
+            }
+            other => panic!("unexpected line: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn failed_output_keeps_last_good_snapshot() {
+        let mut snapshots = HashMap::new();
+        let mut snap = make_snapshot("claude", "Claude");
+        snap.lines = vec![MetricLine::Text {
+            label: "Plan".to_string(),
+            value: "Pro".to_string(),
+            color: None,
+            subtitle: None,
+        }];
+        snapshots.insert("claude".to_string(), snap);
+
+        let output = failed_output("claude", ProbeErrorCode::AuthRequired);
+        apply_failed_output(&mut snapshots, &output, output.error.clone().unwrap());
+
+        let snap = &snapshots["claude"];
+        assert_eq!(snap.lines.len(), 1);
+        assert_eq!(snap.fetched_at, "2026-03-26T08:15:30Z");
+        assert_eq!(
+            snap.error.as_ref().map(|e| e.code),
+            Some(ProbeErrorCode::AuthRequired)
+        );
+    }
+
+    #[test]
+    fn failed_output_without_snapshot_inserts_empty_one() {
+        let mut snapshots = HashMap::new();
+        let output = failed_output("claude", ProbeErrorCode::Network);
+        apply_failed_output(&mut snapshots, &output, output.error.clone().unwrap());
+
+        let json = serde_json::to_value(&snapshots["claude"]).unwrap();
+        assert_eq!(json["lines"], serde_json::json!([]));
+        assert_eq!(json["error"]["code"], "network");
+        assert_eq!(json["error"]["retryable"], true);
+    }
+
+    #[test]
+    fn snapshot_without_error_field_still_loads() {
+        let json = r#"{"providerId":"claude","displayName":"Claude","plan":null,"lines":[],"fetchedAt":"2026-03-26T08:00:00Z"}"#;
+        let snap: CachedPluginSnapshot = serde_json::from_str(json).unwrap();
+        assert!(snap.error.is_none());
+        assert!(snap.accounts.is_empty());
+    }
+
+    #[test]
+    fn account_lookup_carries_snapshot_metadata() {
+        let mut snap = make_snapshot("copilot", "Copilot");
+        snap.accounts = vec![AccountOutput {
+            key: "seat-2".to_string(),
+            label: "Work seat".to_string(),
+            plan: Some("Business".to_string()),
+            lines: vec![],
+        }];
+        snap.error = Some(ProbeError::new(ProbeErrorCode::Network, "offline"));
+
+        assert!(snap.account("seat-1").is_none());
+        let json = serde_json::to_value(snap.account("seat-2").unwrap()).unwrap();
+        assert_eq!(json["providerId"], "copilot");
+        assert_eq!(json["key"], "seat-2");
+        assert_eq!(json["label"], "Work seat");
+        assert_eq!(json["plan"], "Business");
+        assert_eq!(json["fetchedAt"], "2026-03-26T08:15:30Z");
+        assert_eq!(json["error"]["code"], "network");
+    }
 }
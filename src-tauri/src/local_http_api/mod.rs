@@ -1,5 +1,8 @@
 pub(crate) mod cache;
 mod server;
 
-pub use cache::{cache_successful_output, init};
+pub use cache::{
+    cache_successful_output, init, record_failed_output, set_default_enabled_plugin_ids,
+    set_known_plugin_ids,
+};
 pub use server::start_server;
@@ -76,13 +76,26 @@ fn route(method: &str, path: &str) -> String {
         };
     }
 
-    if let Some(provider_id) = path.strip_prefix("/v1/usage/") {
-        if !provider_id.is_empty() && !provider_id.contains('/') {
-            return match method {
-                "GET" => handle_get_usage_single(provider_id),
-                "OPTIONS" => response_no_content(),
-                _ => response_method_not_allowed(),
-            };
+    if let Some(rest) = path.strip_prefix("/v1/usage/") {
+        let segments: Vec<&str> = rest.split('/').collect();
+        if segments.iter().all(|segment| !segment.is_empty()) {
+            match segments.as_slice() {
+                [provider_id] => {
+                    return match method {
+                        "GET" => handle_get_usage_single(provider_id),
+                        "OPTIONS" => response_no_content(),
+                        _ => response_method_not_allowed(),
+                    };
+                }
+                [provider_id, account_key] => {
+                    return match method {
+                        "GET" => handle_get_usage_account(provider_id, account_key),
+                        "OPTIONS" => response_no_content(),
+                        _ => response_method_not_allowed(),
+                    };
+                }
+                _ => {}
+            }
         }
     }
 
@@ -116,6 +129,26 @@ fn handle_get_usage_single(provider_id: &str) -> String {
     }
 }
 
+fn handle_get_usage_account(provider_id: &str, account_key: &str) -> String {
+    let state = cache_state().lock().expect("cache state poisoned");
+
+    let is_known = state.known_plugin_ids.iter().any(|id| id == provider_id);
+    if !is_known {
+        return response_not_found("provider_not_found");
+    }
+
+    let Some(snapshot) = state.snapshots.get(provider_id) else {
+        return response_no_content();
+    };
+    match snapshot.account(account_key) {
+        Some(account) => {
+            let body = serde_json::to_string(&account).unwrap_or_else(|_| "{}".to_string());
+            response_json(200, "OK", &body)
+        }
+        None => response_not_found("account_not_found"),
+    }
+}
+
 // ---------------------------------------------------------------------------
 // HTTP response builders
 // ---------------------------------------------------------------------------
@@ -157,6 +190,7 @@ fn response_method_not_allowed() -> String {
 mod tests {
     use super::super::cache::{cache_state, CachedPluginSnapshot};
     use super::*;
+    use crate::plugin_engine::runtime::AccountOutput;
     use serial_test::serial;
 
     fn make_snapshot(id: &str, name: &str) -> CachedPluginSnapshot {
@@ -165,7 +199,9 @@ mod tests {
             display_name: name.to_string(),
             plan: Some("Pro".to_string()),
             lines: vec![],
+            accounts: vec![],
             fetched_at: "2026-03-26T08:15:30Z".to_string(),
+            error: None,
         }
     }
 
@@ -237,6 +273,63 @@ mod tests {
         assert!(resp.contains("fetchedAt"));
     }
 
+    #[test]
+    #[serial]
+    fn route_account_returns_that_account_only() {
+        {
+            let mut state = cache_state().lock().unwrap();
+            state.known_plugin_ids = vec!["claude".to_string()];
+            let mut snapshot = make_snapshot("claude", "Claude");
+            snapshot.accounts = vec![
+                AccountOutput {
+                    key: "personal".to_string(),
+                    label: "Personal".to_string(),
+                    plan: Some("Pro".to_string()),
+                    lines: vec![],
+                },
+                AccountOutput {
+                    key: "org-acme".to_string(),
+                    label: "Acme".to_string(),
+                    plan: Some("Team".to_string()),
+                    lines: vec![],
+                },
+            ];
+            state.snapshots.insert("claude".to_string(), snapshot);
+        }
+
+        let resp = route("GET", "/v1/usage/claude/org-acme");
+        assert!(resp.starts_with("HTTP/1.1 200"));
+        let body = resp.split("\r\n\r\n").nth(1).unwrap();
+        let json: serde_json::Value = serde_json::from_str(body).unwrap();
+        assert_eq!(json["providerId"], "claude");
+        assert_eq!(json["key"], "org-acme");
+        assert_eq!(json["plan"], "Team");
+        assert_eq!(json["fetchedAt"], "2026-03-26T08:15:30Z");
+
+        let resp = route("GET", "/v1/usage/claude/missing");
+        assert!(resp.starts_with("HTTP/1.1 404"));
+        assert!(resp.contains("account_not_found"));
+
+        let resp = route("GET", "/v1/usage/claude/org-acme/extra");
+        assert!(resp.starts_with("HTTP/1.1 404"));
+        assert!(resp.contains("\"not_found\""));
+    }
+
+    #[test]
+    #[serial]
+    fn route_account_of_uncached_provider_returns_204() {
+        {
+            let mut state = cache_state().lock().unwrap();
+            state.known_plugin_ids = vec!["claude".to_string()];
+            state.snapshots.clear();
+        }
+
+        let resp = route("GET", "/v1/usage/claude/personal");
+        assert!(resp.starts_with("HTTP/1.1 204"));
+        let resp = route("GET", "/v1/usage/nonexistent/personal");
+        assert!(resp.contains("provider_not_found"));
+    }
+
     #[test]
     fn route_options_on_provider_returns_204() {
         let resp = route("OPTIONS", "/v1/usage/claude");
@@ -2,5 +2,12 @@
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
 fn main() {
+    if std::env::args().nth(1).as_deref() == Some(openusage_lib::PROBE_RUNNER_ARG) {
+        std::process::exit(openusage_lib::run_probe_runner());
+    }
+    if std::env::args().nth(1).as_deref() == Some(openusage_lib::VALIDATE_PLUGINS_ARG) {
+        let dir = std::env::args().nth(2).map(std::path::PathBuf::from);
+        std::process::exit(openusage_lib::run_plugin_validation(dir));
+    }
     openusage_lib::run()
 }
@@ -0,0 +1,586 @@
+//! Keeps the install directory in step with the plugins bundled in the app.
+//!
+//! Each bundled plugin is compared with the installed copy by manifest version: missing or older
+//! copies are replaced as a whole (so files the bundle dropped go away), newer ones are left
+//! alone. A same-version copy whose files differ is refreshed only in development builds or when
+//! it is still exactly what an earlier sync placed (recorded in a state file in the install
+//! directory); anything else, such as a forced install, is kept. Everything except "unchanged"
+//! is appended to a sync log in the app data directory.
+
+use crate::plugin_engine::manifest::{self, LoadedPlugin};
+use crate::plugin_engine::signing;
+use std::cmp::Ordering;
+use std::collections::BTreeMap;
+use std::path::Path;
+
+pub const SYNC_LOG_FILE_NAME: &str = "bundled-plugin-sync.log";
+/// File hashes of every copy the sync placed, keyed by plugin directory name.
+pub const SYNC_STATE_FILE_NAME: &str = "bundled-sync-state.json";
+/// The log is trimmed to this many lines so it cannot grow without bound.
+const SYNC_LOG_MAX_LINES: usize = 1_000;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SyncAction {
+    /// Not installed before, or the installed copy could not be loaded.
+    Installed {
+        version: String,
+    },
+    Updated {
+        from: String,
+        to: String,
+    },
+    /// Same version but different files, e.g. a development build.
+    Refreshed {
+        version: String,
+    },
+    /// Same version but different files that the sync did not place, e.g. a forced install.
+    KeptModified {
+        version: String,
+    },
+    /// The installed copy is newer than the bundle and was kept.
+    KeptNewer {
+        installed: String,
+        bundled: String,
+    },
+    Unchanged,
+    Failed {
+        error: String,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SyncEntry {
+    pub plugin_id: String,
+    pub action: SyncAction,
+}
+
+impl std::fmt::Display for SyncEntry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let id = &self.plugin_id;
+        match &self.action {
+            SyncAction::Installed { version } => write!(f, "{} installed {}", id, version),
+            SyncAction::Updated { from, to } => write!(f, "{} updated {} -> {}", id, from, to),
+            SyncAction::Refreshed { version } => write!(f, "{} refreshed {}", id, version),
+            SyncAction::KeptNewer { installed, bundled } => write!(
+                f,
+                "{} kept installed {} (bundled {})",
+                id, installed, bundled
+            ),
+            SyncAction::KeptModified { version } => {
+                write!(f, "{} kept modified {}", id, version)
+            }
+            SyncAction::Unchanged => write!(f, "{} unchanged", id),
+            SyncAction::Failed { error } => write!(f, "{} failed: {}", id, error),
+        }
+    }
+}
+
+/// Sync every plugin in `bundled_dir` into `install_dir`. `refresh_same_version` replaces
+/// modified same-version copies even when the sync did not place them; development builds set
+/// it so edits to bundled plugins show up without a version bump.
+pub fn sync_bundled_plugins(
+    bundled_dir: &Path,
+    install_dir: &Path,
+    refresh_same_version: bool,
+) -> Vec<SyncEntry> {
+    let entries = match std::fs::read_dir(bundled_dir) {
+        Ok(entries) => entries,
+        Err(err) => {
+            log::warn!("failed to read dir {}: {}", bundled_dir.display(), err);
+            return Vec::new();
+        }
+    };
+
+    let state_path = install_dir.join(SYNC_STATE_FILE_NAME);
+    let mut state = read_sync_state(&state_path);
+    let mut results = Vec::new();
+    for entry in entries.flatten() {
+        let source = entry.path();
+        if !source.join("plugin.json").is_file() {
+            continue;
+        }
+        let dir_name = entry.file_name().to_string_lossy().to_string();
+        let bundled = match manifest::load_single_plugin(&source) {
+            Ok(bundled) => bundled,
+            Err(err) => {
+                results.push(SyncEntry {
+                    plugin_id: dir_name,
+                    action: SyncAction::Failed {
+                        error: format!("bundled copy does not load: {}", err),
+                    },
+                });
+                continue;
+            }
+        };
+        let target = install_dir.join(&dir_name);
+        let placed = state.get(&dir_name);
+        let action = match sync_one(&bundled, &target, install_dir, placed, refresh_same_version) {
+            Ok(action) => action,
+            Err(error) => SyncAction::Failed { error },
+        };
+        if matches!(
+            action,
+            SyncAction::Installed { .. }
+                | SyncAction::Updated { .. }
+                | SyncAction::Refreshed { .. }
+                | SyncAction::Unchanged
+        ) && let Ok(files) = signing::hash_files(&target)
+        {
+            state.insert(dir_name, files);
+        }
+        results.push(SyncEntry {
+            plugin_id: bundled.manifest.id,
+            action,
+        });
+    }
+    results.sort_by(|a, b| a.plugin_id.cmp(&b.plugin_id));
+    write_sync_state(&state_path, &state);
+
+    for entry in &results {
+        match entry.action {
+            SyncAction::Unchanged => {}
+            SyncAction::Failed { .. } => log::warn!("bundled sync: {}", entry),
+            _ => log::info!("bundled sync: {}", entry),
+        }
+    }
+    results
+}
+
+fn sync_one(
+    bundled: &LoadedPlugin,
+    target: &Path,
+    install_dir: &Path,
+    placed: Option<&BTreeMap<String, String>>,
+    refresh_same_version: bool,
+) -> Result<SyncAction, String> {
+    let bundled_version = bundled.manifest.version.clone();
+    let installed = if target.join("plugin.json").is_file() {
+        manifest::load_single_plugin(target).ok()
+    } else {
+        None
+    };
+    let Some(installed) = installed else {
+        replace_dir(&bundled.plugin_dir, target, install_dir)?;
+        return Ok(SyncAction::Installed {
+            version: bundled_version,
+        });
+    };
+
+    let installed_version = installed.manifest.version;
+    match compare_versions(&bundled_version, &installed_version) {
+        Ordering::Greater => {
+            replace_dir(&bundled.plugin_dir, target, install_dir)?;
+            Ok(SyncAction::Updated {
+                from: installed_version,
+                to: bundled_version,
+            })
+        }
+        Ordering::Less => Ok(SyncAction::KeptNewer {
+            installed: installed_version,
+            bundled: bundled_version,
+        }),
+        Ordering::Equal => {
+            let installed_files = signing::hash_files(target)?;
+            if signing::hash_files(&bundled.plugin_dir)? == installed_files {
+                return Ok(SyncAction::Unchanged);
+            }
+            if !refresh_same_version && placed != Some(&installed_files) {
+                return Ok(SyncAction::KeptModified {
+                    version: bundled_version,
+                });
+            }
+            replace_dir(&bundled.plugin_dir, target, install_dir)?;
+            Ok(SyncAction::Refreshed {
+                version: bundled_version,
+            })
+        }
+    }
+}
+
+fn read_sync_state(path: &Path) -> BTreeMap<String, BTreeMap<String, String>> {
+    std::fs::read_to_string(path)
+        .ok()
+        .and_then(|text| serde_json::from_str(&text).ok())
+        .unwrap_or_default()
+}
+
+fn write_sync_state(path: &Path, state: &BTreeMap<String, BTreeMap<String, String>>) {
+    let result = serde_json::to_string_pretty(state)
+        .map_err(|err| err.to_string())
+        .and_then(|text| std::fs::write(path, text).map_err(|err| err.to_string()));
+    if let Err(err) = result {
+        log::warn!("failed to write {}: {}", path.display(), err);
+    }
+}
+
+/// Copy `source` next to `target` first, then swap it in, so a failed copy leaves the old
+/// plugin untouched.
+fn replace_dir(source: &Path, target: &Path, install_dir: &Path) -> Result<(), String> {
+    let staging = install_dir.join(format!(".staging-sync-{}", uuid::Uuid::new_v4()));
+    if let Err(err) = copy_dir_recursive(source, &staging) {
+        let _ = std::fs::remove_dir_all(&staging);
+        return Err(err);
+    }
+
+    let retired = install_dir.join(format!(".staging-retired-{}", uuid::Uuid::new_v4()));
+    let had_target = target.exists();
+    if had_target && let Err(err) = std::fs::rename(target, &retired) {
+        let _ = std::fs::remove_dir_all(&staging);
+        return Err(format!(
+            "failed to move {} aside: {}",
+            target.display(),
+            err
+        ));
+    }
+    if let Err(err) = std::fs::rename(&staging, target) {
+        if had_target {
+            let _ = std::fs::rename(&retired, target);
+        }
+        let _ = std::fs::remove_dir_all(&staging);
+        return Err(format!("failed to replace {}: {}", target.display(), err));
+    }
+    if had_target && let Err(err) = std::fs::remove_dir_all(&retired) {
+        log::warn!("failed to remove {}: {}", retired.display(), err);
+    }
+    Ok(())
+}
+
+fn copy_dir_recursive(src: &Path, dst: &Path) -> Result<(), String> {
+    std::fs::create_dir_all(dst)
+        .map_err(|err| format!("failed to create dir {}: {}", dst.display(), err))?;
+    let entries = std::fs::read_dir(src)
+        .map_err(|err| format!("failed to read dir {}: {}", src.display(), err))?;
+    for entry in entries {
+        let entry = entry.map_err(|err| err.to_string())?;
+        let src_path = entry.path();
+        let dst_path = dst.join(entry.file_name());
+        let file_type = entry.file_type().map_err(|err| err.to_string())?;
+        if file_type.is_symlink() {
+            continue;
+        }
+        if file_type.is_dir() {
+            copy_dir_recursive(&src_path, &dst_path)?;
+        } else if file_type.is_file() {
+            std::fs::copy(&src_path, &dst_path).map_err(|err| {
+                format!(
+                    "failed to copy {} to {}: {}",
+                    src_path.display(),
+                    dst_path.display(),
+                    err
+                )
+            })?;
+        }
+    }
+    Ok(())
+}
+
+/// Compare dotted versions numerically (`1.10.0` > `1.9.2`); a pre-release suffix sorts before
+/// the plain release (`1.0.0-beta` < `1.0.0`). Pre-release identifiers are compared the same
+/// way (`beta.9` < `beta.10`). Non-numeric parts fall back to string order.
+pub fn compare_versions(a: &str, b: &str) -> Ordering {
+    let (a_core, a_pre) = split_prerelease(a.trim().trim_start_matches('v'));
+    let (b_core, b_pre) = split_prerelease(b.trim().trim_start_matches('v'));
+
+    let a_parts: Vec<&str> = a_core.split('.').collect();
+    let b_parts: Vec<&str> = b_core.split('.').collect();
+    for index in 0..a_parts.len().max(b_parts.len()) {
+        let a_part = a_parts.get(index).copied().unwrap_or("0");
+        let b_part = b_parts.get(index).copied().unwrap_or("0");
+        let ordering = match (a_part.parse::<u64>(), b_part.parse::<u64>()) {
+            (Ok(a_num), Ok(b_num)) => a_num.cmp(&b_num),
+            _ => a_part.cmp(b_part),
+        };
+        if ordering != Ordering::Equal {
+            return ordering;
+        }
+    }
+
+    match (a_pre, b_pre) {
+        (None, None) => Ordering::Equal,
+        (Some(_), None) => Ordering::Less,
+        (None, Some(_)) => Ordering::Greater,
+        (Some(a_pre), Some(b_pre)) => compare_prerelease(a_pre, b_pre),
+    }
+}
+
+/// Semver precedence: numeric identifiers compare numerically and sort before alphanumeric
+/// ones; a shorter list of otherwise equal identifiers sorts first.
+fn compare_prerelease(a: &str, b: &str) -> Ordering {
+    let mut a_ids = a.split('.');
+    let mut b_ids = b.split('.');
+    loop {
+        let ordering = match (a_ids.next(), b_ids.next()) {
+            (None, None) => return Ordering::Equal,
+            (None, Some(_)) => return Ordering::Less,
+            (Some(_), None) => return Ordering::Greater,
+            (Some(a_id), Some(b_id)) => match (a_id.parse::<u64>(), b_id.parse::<u64>()) {
+                (Ok(a_num), Ok(b_num)) => a_num.cmp(&b_num),
+                (Ok(_), Err(_)) => Ordering::Less,
+                (Err(_), Ok(_)) => Ordering::Greater,
+                (Err(_), Err(_)) => a_id.cmp(b_id),
+            },
+        };
+        if ordering != Ordering::Equal {
+            return ordering;
+        }
+    }
+}
+
+fn split_prerelease(version: &str) -> (&str, Option<&str>) {
+    match version.split_once('-') {
+        Some((core, pre)) => (core, Some(pre)),
+        None => (version, None),
+    }
+}
+
+/// Append every entry that changed something (or failed) to the sync log.
+pub fn append_sync_log(log_path: &Path, entries: &[SyncEntry]) {
+    let timestamp = time::OffsetDateTime::now_utc()
+        .format(&time::format_description::well_known::Rfc3339)
+        .unwrap_or_default();
+    let new_lines: Vec<String> = entries
+        .iter()
+        .filter(|entry| entry.action != SyncAction::Unchanged)
+        .map(|entry| format!("{} {}", timestamp, entry))
+        .collect();
+    if new_lines.is_empty() {
+        return;
+    }
+
+    let existing = std::fs::read_to_string(log_path).unwrap_or_default();
+    let mut lines: Vec<&str> = existing.lines().collect();
+    lines.extend(new_lines.iter().map(String::as_str));
+    let skip = lines.len().saturating_sub(SYNC_LOG_MAX_LINES);
+    let mut contents = lines[skip..].join("\n");
+    contents.push('\n');
+    if let Err(err) = std::fs::write(log_path, contents) {
+        log::warn!("failed to write {}: {}", log_path.display(), err);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    fn temp_dir(label: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("openusage-sync-{}-{}", label, uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).expect("create dir");
+        dir
+    }
+
+    fn write_plugin(root: &Path, id: &str, version: &str, extra: &[(&str, &str)]) {
+        let dir = root.join(id);
+        std::fs::create_dir_all(&dir).expect("create plugin dir");
+        std::fs::write(
+            dir.join("plugin.json"),
+            format!(
+                r#"{{"schemaVersion":1,"id":"{}","name":"Demo","version":"{}","entry":"plugin.js","icon":"icon.svg","brandColor":null,"lines":[]}}"#,
+                id, version
+            ),
+        )
+        .expect("write manifest");
+        std::fs::write(dir.join("plugin.js"), "globalThis.__openusage_plugin = {}")
+            .expect("write entry");
+        std::fs::write(dir.join("icon.svg"), "<svg/>").expect("write icon");
+        for (name, content) in extra {
+            let path = dir.join(name);
+            std::fs::create_dir_all(path.parent().unwrap()).expect("create parent");
+            std::fs::write(path, content).expect("write extra");
+        }
+    }
+
+    fn action_for(entries: &[SyncEntry], id: &str) -> SyncAction {
+        entries
+            .iter()
+            .find(|entry| entry.plugin_id == id)
+            .map(|entry| entry.action.clone())
+            .expect("entry for plugin")
+    }
+
+    #[test]
+    fn installs_updates_and_removes_stale_files() {
+        let root = temp_dir("update");
+        let bundled = root.join("bundled");
+        let install = root.join("install");
+        write_plugin(&bundled, "demo", "1.0.0", &[("lib/old.js", "old")]);
+
+        let first = sync_bundled_plugins(&bundled, &install, false);
+        assert_eq!(
+            action_for(&first, "demo"),
+            SyncAction::Installed {
+                version: "1.0.0".to_string()
+            }
+        );
+        assert!(install.join("demo/lib/old.js").exists());
+
+        std::fs::remove_dir_all(bundled.join("demo")).expect("clear bundle");
+        write_plugin(&bundled, "demo", "1.1.0", &[("lib/new.js", "new")]);
+        let second = sync_bundled_plugins(&bundled, &install, false);
+        assert_eq!(
+            action_for(&second, "demo"),
+            SyncAction::Updated {
+                from: "1.0.0".to_string(),
+                to: "1.1.0".to_string()
+            }
+        );
+        assert!(install.join("demo/lib/new.js").exists());
+        assert!(
+            !install.join("demo/lib/old.js").exists(),
+            "files dropped from the bundle must be removed"
+        );
+
+        let third = sync_bundled_plugins(&bundled, &install, false);
+        assert_eq!(action_for(&third, "demo"), SyncAction::Unchanged);
+
+        let leftovers: Vec<_> = std::fs::read_dir(&install)
+            .expect("read install dir")
+            .flatten()
+            .filter(|entry| entry.file_name().to_string_lossy().starts_with('.'))
+            .collect();
+        assert!(leftovers.is_empty(), "staging dirs must be cleaned up");
+        let _ = std::fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn keeps_newer_installs_and_same_version_copies_it_did_not_place() {
+        let root = temp_dir("keep");
+        let bundled = root.join("bundled");
+        let install = root.join("install");
+        write_plugin(&bundled, "newer", "1.0.0", &[]);
+        write_plugin(&install, "newer", "2.0.0", &[("custom.js", "mine")]);
+        write_plugin(&bundled, "same", "1.0.0", &[("lib/a.js", "v2")]);
+        write_plugin(&install, "same", "1.0.0", &[("lib/a.js", "v1")]);
+
+        let entries = sync_bundled_plugins(&bundled, &install, false);
+        assert_eq!(
+            action_for(&entries, "newer"),
+            SyncAction::KeptNewer {
+                installed: "2.0.0".to_string(),
+                bundled: "1.0.0".to_string()
+            }
+        );
+        assert!(install.join("newer/custom.js").exists());
+        assert_eq!(
+            action_for(&entries, "same"),
+            SyncAction::KeptModified {
+                version: "1.0.0".to_string()
+            }
+        );
+        assert_eq!(
+            std::fs::read_to_string(install.join("same/lib/a.js")).unwrap(),
+            "v1"
+        );
+
+        let entries = sync_bundled_plugins(&bundled, &install, true);
+        assert_eq!(
+            action_for(&entries, "same"),
+            SyncAction::Refreshed {
+                version: "1.0.0".to_string()
+            },
+            "development builds refresh same-version copies"
+        );
+        assert_eq!(
+            std::fs::read_to_string(install.join("same/lib/a.js")).unwrap(),
+            "v2"
+        );
+        let _ = std::fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn refreshes_same_version_copies_only_while_untouched_since_sync() {
+        let root = temp_dir("placed");
+        let bundled = root.join("bundled");
+        let install = root.join("install");
+        write_plugin(&bundled, "demo", "1.0.0", &[("lib/a.js", "v1")]);
+        sync_bundled_plugins(&bundled, &install, false);
+
+        write_plugin(&bundled, "demo", "1.0.0", &[("lib/a.js", "v2")]);
+        let entries = sync_bundled_plugins(&bundled, &install, false);
+        assert_eq!(
+            action_for(&entries, "demo"),
+            SyncAction::Refreshed {
+                version: "1.0.0".to_string()
+            }
+        );
+        assert_eq!(
+            std::fs::read_to_string(install.join("demo/lib/a.js")).unwrap(),
+            "v2"
+        );
+
+        // A forced install of the same version replaces what the sync placed.
+        std::fs::write(install.join("demo/lib/a.js"), "forced").expect("force install");
+        let entries = sync_bundled_plugins(&bundled, &install, false);
+        assert_eq!(
+            action_for(&entries, "demo"),
+            SyncAction::KeptModified {
+                version: "1.0.0".to_string()
+            }
+        );
+        assert_eq!(
+            std::fs::read_to_string(install.join("demo/lib/a.js")).unwrap(),
+            "forced"
+        );
+        let _ = std::fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn broken_install_is_replaced_and_log_records_changes() {
+        let root = temp_dir("log");
+        let bundled = root.join("bundled");
+        let install = root.join("install");
+        write_plugin(&bundled, "demo", "1.0.0", &[]);
+        write_plugin(&bundled, "quiet", "1.0.0", &[]);
+        sync_bundled_plugins(&bundled, &install, false);
+        std::fs::write(install.join("demo/plugin.json"), "{").expect("break manifest");
+
+        let entries = sync_bundled_plugins(&bundled, &install, false);
+        assert_eq!(
+            action_for(&entries, "demo"),
+            SyncAction::Installed {
+                version: "1.0.0".to_string()
+            }
+        );
+        assert_eq!(action_for(&entries, "quiet"), SyncAction::Unchanged);
+
+        let log_path = root.join(SYNC_LOG_FILE_NAME);
+        append_sync_log(&log_path, &entries);
+        append_sync_log(&log_path, &entries);
+        let log = std::fs::read_to_string(&log_path).expect("read log");
+        let lines: Vec<&str> = log.lines().collect();
+        assert_eq!(
+            lines.len(),
+            2,
+            "unchanged plugins are not logged: {:?}",
+            lines
+        );
+        assert!(lines[0].ends_with(" demo installed 1.0.0"), "{}", lines[0]);
+        let _ = std::fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn compares_versions_numerically() {
+        assert_eq!(compare_versions("1.10.0", "1.9.2"), Ordering::Greater);
+        assert_eq!(compare_versions("1.0", "1.0.0"), Ordering::Equal);
+        assert_eq!(compare_versions("v2.0.0", "1.9.9"), Ordering::Greater);
+        assert_eq!(compare_versions("1.0.0-beta", "1.0.0"), Ordering::Less);
+        assert_eq!(
+            compare_versions("1.0.0-beta.2", "1.0.0-beta.1"),
+            Ordering::Greater
+        );
+        assert_eq!(compare_versions("0.9.0", "0.10.0"), Ordering::Less);
+        assert_eq!(
+            compare_versions("1.0.0-beta.9", "1.0.0-beta.10"),
+            Ordering::Less
+        );
+        assert_eq!(
+            compare_versions("1.0.0-alpha", "1.0.0-alpha.1"),
+            Ordering::Less
+        );
+        assert_eq!(
+            compare_versions("1.0.0-rc.1", "1.0.0-beta.11"),
+            Ordering::Greater
+        );
+    }
+}
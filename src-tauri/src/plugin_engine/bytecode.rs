@@ -0,0 +1,359 @@
+//! Compile-once QuickJS bytecode for plugin scripts and the host utility prelude.
+//!
+//! Bytecode is kept in memory for the life of a probe runner, keyed by plugin id, plugin
+//! version and a SHA-256 of the source, so a reused runner only parses each script once.
+//! Nothing is written to disk: QuickJS cannot safely load bytecode it did not produce itself,
+//! and a file on disk could be swapped for code the plugin signature never covered.
+//!
+//! ES module plugins are cached file by file as the module loader first reaches each import.
+
+use crate::plugin_engine::manifest::LoadedPlugin;
+use rquickjs::module::{Declared, WriteOptions};
+use rquickjs::{Ctx, Error, Module, Value, qjs};
+use sha2::{Digest, Sha256};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::ffi::CString;
+use std::path::Path;
+use std::rc::Rc;
+
+/// Where earlier versions kept bytecode on disk; removed at startup.
+const LEGACY_CACHE_DIR_NAME: &str = "bytecode-cache";
+
+/// Cache key for a plugin's entry script.
+pub fn plugin_cache_key(plugin: &LoadedPlugin, app_version: &str) -> String {
+    let hash = source_digest(&[
+        app_version.as_bytes(),
+        plugin.manifest.entry.as_bytes(),
+        plugin.entry_script.as_bytes(),
+    ]);
+    format!("{}{}", plugin_key_prefix(plugin), hash)
+}
+
+/// Cache key for one file of an ES module plugin; `name` is its path inside the plugin dir.
+pub fn module_cache_key(
+    plugin: &LoadedPlugin,
+    app_version: &str,
+    name: &str,
+    source: &str,
+) -> String {
+    let hash = source_digest(&[
+        app_version.as_bytes(),
+        b"module",
+        name.as_bytes(),
+        source.as_bytes(),
+    ]);
+    format!("{}{}", plugin_key_prefix(plugin), hash)
+}
+
+fn plugin_key_prefix(plugin: &LoadedPlugin) -> String {
+    format!("{}-{}-", plugin.manifest.id, plugin.manifest.version)
+}
+
+/// Cache key for a host-provided script such as the utility prelude.
+pub fn host_script_cache_key(name: &str, source: &str, app_version: &str) -> String {
+    let hash = source_digest(&[app_version.as_bytes(), source.as_bytes()]);
+    format!("host-{}-{}", name, hash)
+}
+
+/// Hex SHA-256 over length-prefixed `parts`, so no two part lists share a digest input.
+fn source_digest(parts: &[&[u8]]) -> String {
+    let mut hasher = Sha256::new();
+    for part in parts {
+        hasher.update((part.len() as u64).to_le_bytes());
+        hasher.update(part);
+    }
+    hasher
+        .finalize()
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+/// Compile a global (non-module) strict-mode script to bytecode without running it, matching
+/// how `Ctx::eval` runs scripts by default.
+pub fn compile_script(ctx: &Ctx<'_>, filename: &str, source: &str) -> rquickjs::Result<Vec<u8>> {
+    let source = CString::new(source)?;
+    let filename = CString::new(filename)?;
+    let raw = ctx.as_raw().as_ptr();
+    unsafe {
+        let compiled = qjs::JS_Eval(
+            raw,
+            source.as_ptr(),
+            source.as_bytes().len() as _,
+            filename.as_ptr(),
+            (qjs::JS_EVAL_TYPE_GLOBAL | qjs::JS_EVAL_FLAG_STRICT | qjs::JS_EVAL_FLAG_COMPILE_ONLY)
+                as i32,
+        );
+        if qjs::JS_IsException(compiled) {
+            return Err(Error::Exception);
+        }
+        // Owning wrapper frees the compiled function when done.
+        let compiled = Value::from_raw(ctx.clone(), compiled);
+        let mut len = 0;
+        let buf = qjs::JS_WriteObject(
+            raw,
+            &mut len,
+            compiled.as_raw(),
+            qjs::JS_WRITE_OBJ_BYTECODE as i32,
+        );
+        if buf.is_null() {
+            return Err(Error::Exception);
+        }
+        let bytes = std::slice::from_raw_parts(buf, len as usize).to_vec();
+        qjs::js_free(raw, buf.cast());
+        Ok(bytes)
+    }
+}
+
+/// Outcome of evaluating cached bytecode.
+enum BytecodeEval {
+    /// The script ran (possibly throwing; the result carries that).
+    Ran(rquickjs::Result<()>),
+    /// The bytecode could not be loaded; nothing was executed.
+    Unreadable,
+}
+
+/// `bytes` must come from `compile_script` in this process; QuickJS does not validate
+/// bytecode thoroughly enough to load anything else.
+fn eval_bytecode(ctx: &Ctx<'_>, bytes: &[u8]) -> BytecodeEval {
+    let raw = ctx.as_raw().as_ptr();
+    unsafe {
+        let function = qjs::JS_ReadObject(
+            raw,
+            bytes.as_ptr(),
+            bytes.len() as _,
+            qjs::JS_READ_OBJ_BYTECODE as i32,
+        );
+        if qjs::JS_IsException(function) {
+            // Clear the pending read error so it is not mistaken for a script failure.
+            let _ = ctx.catch();
+            return BytecodeEval::Unreadable;
+        }
+        // JS_EvalFunction takes ownership of `function`.
+        let result = qjs::JS_EvalFunction(raw, function);
+        if qjs::JS_IsException(result) {
+            return BytecodeEval::Ran(Err(Error::Exception));
+        }
+        drop(Value::from_raw(ctx.clone(), result));
+        BytecodeEval::Ran(Ok(()))
+    }
+}
+
+/// Bytecode compiled in this process, shared by the contexts of one runtime.
+#[derive(Clone, Default)]
+pub struct BytecodeCache {
+    memory: Rc<RefCell<HashMap<String, Rc<Vec<u8>>>>>,
+}
+
+impl BytecodeCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn lookup(&self, key: &str) -> Option<Rc<Vec<u8>>> {
+        self.memory.borrow().get(key).map(Rc::clone)
+    }
+
+    fn store(&self, key: &str, bytecode: Vec<u8>) -> Rc<Vec<u8>> {
+        let bytes = Rc::new(bytecode);
+        self.memory
+            .borrow_mut()
+            .insert(key.to_string(), Rc::clone(&bytes));
+        bytes
+    }
+
+    fn evict(&self, key: &str) {
+        self.memory.borrow_mut().remove(key);
+    }
+
+    /// Evaluate `source` as a global script, preferring cached bytecode.
+    pub fn eval_script(
+        &self,
+        ctx: &Ctx<'_>,
+        key: &str,
+        filename: &str,
+        source: &str,
+    ) -> rquickjs::Result<()> {
+        if let Some(bytes) = self.lookup(key) {
+            match eval_bytecode(ctx, &bytes) {
+                BytecodeEval::Ran(result) => return result,
+                BytecodeEval::Unreadable => {
+                    log::warn!("discarding unreadable bytecode cache entry {}", key);
+                    self.evict(key);
+                }
+            }
+        }
+        let bytes = self.store(key, compile_script(ctx, filename, source)?);
+        match eval_bytecode(ctx, &bytes) {
+            BytecodeEval::Ran(result) => result,
+            BytecodeEval::Unreadable => Err(Error::Unknown),
+        }
+    }
+
+    /// Declare (but do not evaluate) an ES module, preferring cached bytecode.
+    pub fn declare_module<'js>(
+        &self,
+        ctx: &Ctx<'js>,
+        key: &str,
+        name: &str,
+        source: &str,
+    ) -> rquickjs::Result<Module<'js, Declared>> {
+        if let Some(bytes) = self.lookup(key) {
+            // SAFETY: QuickJS bytecode loading is not hardened against crafted input, so only
+            // bytes produced by `Module::write` in this process are ever loaded. They come from
+            // `source` (the key is its digest) and never leave memory.
+            match unsafe { Module::load(ctx.clone(), &bytes) } {
+                Ok(module) => return Ok(module),
+                Err(_) => {
+                    let _ = ctx.catch();
+                    log::warn!("discarding unreadable bytecode cache entry {}", key);
+                    self.evict(key);
+                }
+            }
+        }
+        let module = Module::declare(ctx.clone(), name, source)?;
+        match module.write(WriteOptions::default()) {
+            Ok(bytecode) => {
+                self.store(key, bytecode);
+            }
+            Err(err) => log::warn!("failed to serialize module {}: {}", name, err),
+        }
+        Ok(module)
+    }
+}
+
+/// Delete the on-disk bytecode cache left by earlier versions; it is never read.
+pub fn remove_legacy_disk_cache(app_data_dir: &Path) {
+    let dir = app_data_dir.join(LEGACY_CACHE_DIR_NAME);
+    if dir.exists()
+        && let Err(err) = std::fs::remove_dir_all(&dir)
+    {
+        log::warn!("failed to remove {}: {}", dir.display(), err);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::plugin_engine::manifest::{EntryType, PluginManifest};
+    use rquickjs::{Context, Runtime};
+    use std::path::PathBuf;
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    fn temp_dir(label: &str) -> PathBuf {
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .expect("time")
+            .as_nanos();
+        std::env::temp_dir().join(format!("openusage-bytecode-{}-{}", label, nanos))
+    }
+
+    fn test_plugin(script: &str) -> LoadedPlugin {
+        LoadedPlugin {
+            manifest: PluginManifest {
+                schema_version: 1,
+                id: "test".to_string(),
+                name: "Test".to_string(),
+                version: "1.0.0".to_string(),
+                entry: "plugin.js".to_string(),
+                entry_type: EntryType::Script,
+                min_app_version: None,
+                requires: Vec::new(),
+                icon: "icon.svg".to_string(),
+                icons: Default::default(),
+                brand_color: None,
+                lines: vec![],
+                links: vec![],
+                probe_timeout_ms: None,
+                settings: Vec::new(),
+                env: Vec::new(),
+                capabilities: Default::default(),
+                detect: Default::default(),
+                http: None,
+            },
+            plugin_dir: PathBuf::from("."),
+            entry_script: script.to_string(),
+            module_sources: Default::default(),
+            icon_data_url: String::new(),
+            icon_variant_data_urls: Default::default(),
+            trust: Default::default(),
+            incompatibility: None,
+        }
+    }
+
+    #[test]
+    fn cache_key_changes_with_source_and_version() {
+        let plugin = test_plugin("globalThis.a = 1;");
+        let key = plugin_cache_key(&plugin, "1.0.0");
+        assert!(key.starts_with("test-1.0.0-"));
+        assert_eq!(key, plugin_cache_key(&plugin, "1.0.0"));
+        assert_ne!(key, plugin_cache_key(&plugin, "1.0.1"));
+        assert_ne!(
+            key,
+            plugin_cache_key(&test_plugin("globalThis.a = 2;"), "1.0.0")
+        );
+    }
+
+    #[test]
+    fn bytecode_keeps_filename_for_stack_traces() {
+        let rt = Runtime::new().expect("runtime");
+        let ctx = Context::full(&rt).expect("context");
+        ctx.with(|ctx| {
+            BytecodeCache::new()
+                .eval_script(
+                    &ctx,
+                    "k",
+                    "plugin.js",
+                    "globalThis.stack = new Error('x').stack;",
+                )
+                .expect("eval");
+            let stack: String = ctx.globals().get("stack").expect("stack");
+            assert!(stack.contains("plugin.js:1"), "{}", stack);
+        });
+    }
+
+    #[test]
+    fn eval_script_reuses_bytecode_from_memory_only() {
+        let source = "globalThis.answer = 40 + 2;";
+        let rt = Runtime::new().expect("runtime");
+
+        let cache = BytecodeCache::new();
+        for _ in 0..2 {
+            let ctx = Context::full(&rt).expect("context");
+            ctx.with(|ctx| {
+                cache
+                    .eval_script(&ctx, "k", "plugin.js", source)
+                    .expect("eval");
+                let answer: i32 = ctx.globals().get("answer").expect("answer");
+                assert_eq!(answer, 42);
+            });
+        }
+        assert_eq!(cache.memory.borrow().len(), 1);
+
+        // A new cache compiles from the source it is given; nothing is read back from disk.
+        let ctx = Context::full(&rt).expect("context");
+        ctx.with(|ctx| {
+            assert!(
+                BytecodeCache::new()
+                    .eval_script(&ctx, "k", "plugin.js", "not valid js (")
+                    .is_err()
+            );
+            let _ = ctx.catch();
+        });
+    }
+
+    #[test]
+    fn remove_legacy_disk_cache_deletes_old_entries() {
+        let dir = temp_dir("legacy");
+        let legacy = dir.join(LEGACY_CACHE_DIR_NAME);
+        std::fs::create_dir_all(&legacy).expect("create dir");
+        std::fs::write(legacy.join("test-1.0.0-0000000000000000.qbc"), b"old").expect("write");
+
+        remove_legacy_disk_cache(&dir);
+        assert!(!legacy.exists());
+        remove_legacy_disk_cache(&dir);
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
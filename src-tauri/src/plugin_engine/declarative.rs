@@ -0,0 +1,834 @@
+//! Runs declarative plugins, whose manifest `http` block replaces a JavaScript entry.
+//!
+//! The app resolves a credential from the listed sources, makes the requests in order and maps
+//! fields of the JSON responses to the manifest's lines. Requests go through `host_api::http_send`,
+//! so capabilities, the proxy and log redaction are the same as for `host.http`.
+//!
+//! Paths look like `$.quota.data.limits[0].used`: the first key is a request id, followed by
+//! `.key`, `["key"]` or `[index]` steps (negative indexes count from the end).
+
+use crate::plugin_engine::host_api::{self, HostCallError, HttpReqParams, ProbeDeadline};
+use crate::plugin_engine::manifest::{
+    self, AuthSource, HttpPluginSpec, LineMapping, LineType, LoadedPlugin, PluginManifest,
+};
+use crate::plugin_engine::permissions::Permissions;
+use crate::plugin_engine::runtime::{
+    MetricLine, PluginOutput, ProbeError, ProbeErrorCode, ProbeStatus, ProgressFormat,
+    error_output, timeout_output,
+};
+use crate::plugin_engine::settings::{self, KeychainSecrets};
+use serde_json::{Map, Value};
+use std::collections::HashSet;
+use std::path::Path;
+use std::time::Duration;
+
+#[derive(Debug, Clone, PartialEq)]
+enum Step {
+    Key(String),
+    Index(i64),
+}
+
+/// Problems with a manifest's `http` block, empty when it is usable (or absent).
+pub fn check_spec(manifest: &PluginManifest) -> Vec<String> {
+    let Some(spec) = &manifest.http else {
+        return Vec::new();
+    };
+    let mut problems = Vec::new();
+    if !manifest.entry.trim().is_empty() {
+        problems.push("declarative plugins have no entry; remove entry or http".to_string());
+    }
+    let has_setting = |key: &str| manifest.settings.iter().any(|s| s.key == key);
+
+    for source in &spec.auth {
+        match source {
+            AuthSource::Env { name } if !manifest.env.contains(name) => problems.push(format!(
+                "auth env var {} is not listed in plugin.json \"env\"",
+                name
+            )),
+            AuthSource::File {
+                field: Some(field), ..
+            } => {
+                if let Err(e) = parse_path(field) {
+                    problems.push(format!("auth file field: {}", e));
+                }
+            }
+            AuthSource::Setting { key } if !has_setting(key) => problems.push(format!(
+                "auth setting '{}' is not declared in settings",
+                key
+            )),
+            _ => {}
+        }
+    }
+
+    if spec.requests.is_empty() {
+        problems.push("http.requests cannot be empty".to_string());
+    }
+    let mut request_ids = HashSet::new();
+    for request in &spec.requests {
+        if !manifest::is_line_id(&request.id) {
+            problems.push(format!(
+                "request id '{}' must use lowercase letters, digits, '-' and '_'",
+                request.id
+            ));
+        }
+        if !request_ids.insert(request.id.as_str()) {
+            problems.push(format!("duplicate request id '{}'", request.id));
+        }
+        if !manifest::is_http_url(&request.url) && !request.url.starts_with("{{config.") {
+            problems.push(format!(
+                "request '{}' url must be an http(s) URL",
+                request.id
+            ));
+        }
+        let templates = std::iter::once(&request.url)
+            .chain(request.headers.values())
+            .chain(request.body.as_ref());
+        for template in templates {
+            let checked = fill(template, &mut |name| {
+                if name == "auth" && spec.auth.is_empty() {
+                    return Err("{{auth}} is used but http.auth is empty".to_string());
+                }
+                match name.strip_prefix("config.") {
+                    _ if name == "auth" => Ok(String::new()),
+                    Some(key) if has_setting(key) => Ok(String::new()),
+                    Some(key) => Err(format!("setting '{}' is not declared in settings", key)),
+                    None => Err(format!("unknown placeholder {{{{{}}}}}", name)),
+                }
+            });
+            if let Err(e) = checked {
+                problems.push(format!("request '{}': {}", request.id, e));
+            }
+        }
+    }
+
+    let check_value = |what: &str, value: &Value, problems: &mut Vec<String>| match value {
+        Value::String(path) if path.starts_with('$') => match parse_path(path) {
+            Ok(steps) => match steps.first() {
+                Some(Step::Key(id)) if request_ids.contains(id.as_str()) => {}
+                _ => problems.push(format!(
+                    "{} path '{}' must start with a request id",
+                    what, path
+                )),
+            },
+            Err(e) => problems.push(format!("{}: {}", what, e)),
+        },
+        Value::Array(_) | Value::Object(_) => {
+            problems.push(format!("{} must be a number, a string or a $ path", what))
+        }
+        _ => {}
+    };
+
+    if let Some(plan) = &spec.plan {
+        check_value("plan", &Value::String(plan.clone()), &mut problems);
+    }
+    for mapping in &spec.lines {
+        let Some(line) = manifest.lines.iter().find(|line| line.id == mapping.line) else {
+            problems.push(format!("http.lines maps unknown line '{}'", mapping.line));
+            continue;
+        };
+        let what = format!("line '{}'", mapping.line);
+        for problem in check_mapping(mapping, line.line_type) {
+            problems.push(format!("{} {}", what, problem));
+        }
+        let values = [
+            &mapping.used,
+            &mapping.remaining,
+            &mapping.limit,
+            &mapping.resets_at,
+            &mapping.value,
+            &mapping.text,
+        ];
+        for value in values.into_iter().flatten() {
+            check_value(&what, value, &mut problems);
+        }
+    }
+    problems
+}
+
+/// Which fields a mapping needs for the type of its line.
+fn check_mapping(mapping: &LineMapping, line_type: LineType) -> Vec<String> {
+    let mut problems = Vec::new();
+    match line_type {
+        LineType::Progress => {
+            if mapping.used.is_some() == mapping.remaining.is_some() {
+                problems.push("needs exactly one of used or remaining".to_string());
+            }
+            match &mapping.format {
+                None => problems.push("needs a format".to_string()),
+                Some(ProgressFormat::Percent) => {
+                    if mapping
+                        .limit
+                        .as_ref()
+                        .is_some_and(|limit| number(limit) != Some(100.0))
+                    {
+                        problems.push("uses percent, so its limit must be 100".to_string());
+                    }
+                }
+                Some(format) => {
+                    if mapping.limit.is_none() {
+                        problems.push("needs a limit".to_string());
+                    }
+                    match format {
+                        ProgressFormat::Count { suffix } if suffix.trim().is_empty() => {
+                            problems.push("count format suffix must be non-empty".to_string())
+                        }
+                        ProgressFormat::Currency { code }
+                            if code.len() != 3 || !code.chars().all(|c| c.is_ascii_uppercase()) =>
+                        {
+                            problems.push(
+                                "currency format code must be an uppercase ISO 4217 code"
+                                    .to_string(),
+                            )
+                        }
+                        _ => {}
+                    }
+                }
+            }
+        }
+        LineType::Text if mapping.value.is_none() => problems.push("needs a value".to_string()),
+        LineType::Badge if mapping.text.is_none() => problems.push("needs a text".to_string()),
+        LineType::Text | LineType::Badge => {}
+        other => problems.push(format!(
+            "is a {} line; declarative plugins fill progress, text and badge lines",
+            other.as_str()
+        )),
+    }
+    problems
+}
+
+/// Run the plugin's requests and map the responses, within `timeout`.
+pub fn run_probe(
+    plugin: &LoadedPlugin,
+    spec: &HttpPluginSpec,
+    app_data_dir: &Path,
+    timeout: Duration,
+) -> PluginOutput {
+    let deadline = ProbeDeadline::after(timeout);
+    match probe(plugin, spec, app_data_dir, &deadline) {
+        Ok(output) => output,
+        // A request cut short by the deadline fails like any other.
+        Err(_) if deadline.is_expired() => {
+            log::warn!(
+                "[plugin:{}] probe timed out after {}ms",
+                plugin.manifest.id,
+                timeout.as_millis()
+            );
+            timeout_output(plugin, timeout)
+        }
+        Err(error) => {
+            log::error!(
+                "[plugin:{}] probe failed: {}",
+                plugin.manifest.id,
+                host_api::redact_log_message(&error.message)
+            );
+            error_output(plugin, error)
+        }
+    }
+}
+
+fn probe(
+    plugin: &LoadedPlugin,
+    spec: &HttpPluginSpec,
+    app_data_dir: &Path,
+    deadline: &ProbeDeadline,
+) -> Result<PluginOutput, ProbeError> {
+    let manifest = &plugin.manifest;
+    let plugin_id = &manifest.id;
+    let config = settings::resolve_config(manifest, app_data_dir, &KeychainSecrets);
+    let permissions = Permissions::new(
+        plugin_id,
+        &manifest.env,
+        &manifest.capabilities,
+        &host_api::plugin_data_dir(app_data_dir, plugin_id),
+    );
+
+    let auth = if spec.auth.is_empty() {
+        None
+    } else {
+        let auth = resolve_auth(plugin_id, &spec.auth, &permissions, &config)?;
+        if auth.is_none() {
+            let tried: Vec<String> = spec.auth.iter().map(describe_source).collect();
+            return Err(ProbeError::new(
+                ProbeErrorCode::AuthRequired,
+                format!("No credentials found. Checked {}.", tried.join(", ")),
+            ));
+        }
+        auth
+    };
+
+    let mut responses = Map::new();
+    for request in &spec.requests {
+        let fill_field = |template: &str| {
+            fill(template, &mut |name| {
+                placeholder_value(name, auth.as_deref(), &config)
+            })
+            .map_err(ProbeError::plugin_bug)
+        };
+        let mut headers = std::collections::HashMap::new();
+        for (name, value) in &request.headers {
+            headers.insert(name.clone(), fill_field(value)?);
+        }
+        let params = HttpReqParams {
+            url: fill_field(&request.url)?,
+            method: request.method.clone(),
+            headers: Some(headers),
+            body_text: request.body.as_deref().map(fill_field).transpose()?,
+            timeout_ms: request.timeout_ms,
+            dangerously_ignore_tls: None,
+        };
+        let resp = host_api::http_send(plugin_id, &permissions, params, deadline)
+            .map_err(host_call_error)?;
+        check_status(&request.id, resp.status)?;
+        let json: Value = serde_json::from_str(&resp.body_text).map_err(|_| {
+            ProbeError::new(
+                ProbeErrorCode::Unknown,
+                format!("Response to '{}' is not JSON.", request.id),
+            )
+        })?;
+        responses.insert(request.id.clone(), json);
+    }
+
+    let responses = Value::Object(responses);
+    let lines: Vec<MetricLine> = spec
+        .lines
+        .iter()
+        .filter_map(|mapping| map_line(manifest, mapping, &responses))
+        .collect();
+    if lines.is_empty() {
+        return Err(ProbeError::plugin_bug(
+            "no mapped values were found in the response",
+        ));
+    }
+    let plan = spec
+        .plan
+        .as_ref()
+        .and_then(|path| resolve(&Value::String(path.clone()), &responses).and_then(text));
+
+    Ok(PluginOutput {
+        provider_id: plugin_id.clone(),
+        display_name: manifest.name.clone(),
+        status: ProbeStatus::Ok,
+        error: None,
+        plan,
+        lines,
+        accounts: Vec::new(),
+        icon_url: plugin.icon_data_url.clone(),
+    })
+}
+
+fn host_call_error(error: HostCallError) -> ProbeError {
+    ProbeError::new(error.code.unwrap_or(ProbeErrorCode::Unknown), error.message)
+}
+
+/// Same statuses `ctx.util.isAuthStatus` treats as auth failures.
+fn check_status(request_id: &str, status: u16) -> Result<(), ProbeError> {
+    let code = match status {
+        200..=299 => return Ok(()),
+        401 | 403 => ProbeErrorCode::AuthRequired,
+        429 => ProbeErrorCode::RateLimited,
+        _ => ProbeErrorCode::Unknown,
+    };
+    let message = match code {
+        ProbeErrorCode::AuthRequired => {
+            format!("Credentials were rejected (HTTP {}).", status)
+        }
+        _ => format!("Request '{}' failed (HTTP {}).", request_id, status),
+    };
+    Err(ProbeError::new(code, message))
+}
+
+/// The first credential found, trimmed. An undeclared file or keychain source is an error, like
+/// the matching host API call.
+fn resolve_auth(
+    plugin_id: &str,
+    sources: &[AuthSource],
+    permissions: &Permissions,
+    config: &Map<String, Value>,
+) -> Result<Option<String>, ProbeError> {
+    let denied = |message| ProbeError::new(ProbeErrorCode::PermissionDenied, message);
+    for source in sources {
+        let value = match source {
+            AuthSource::Env { name } => permissions
+                .allows_env(name)
+                .then(|| host_api::resolve_env_value(name))
+                .flatten(),
+            AuthSource::File { path, field } => {
+                permissions.check_fs_read(path).map_err(denied)?;
+                read_auth_file(path, field.as_deref())
+            }
+            AuthSource::Keychain { service } => {
+                permissions.check_keychain(service).map_err(denied)?;
+                host_api::read_generic_password_prefer_current_user(service)
+            }
+            AuthSource::Setting { key } => config.get(key).and_then(text),
+        };
+        if let Some(value) = value
+            .map(|v| v.trim().to_string())
+            .filter(|v| !v.is_empty())
+        {
+            log::info!(
+                "[plugin:{}] using credential from {}",
+                plugin_id,
+                describe_source(source)
+            );
+            return Ok(Some(value));
+        }
+    }
+    Ok(None)
+}
+
+fn describe_source(source: &AuthSource) -> String {
+    match source {
+        AuthSource::Env { name } => format!("env {}", name),
+        AuthSource::File { path, .. } => format!("file {}", path),
+        AuthSource::Keychain { service } => format!("keychain {}", service),
+        AuthSource::Setting { key } => format!("setting {}", key),
+    }
+}
+
+fn read_auth_file(path: &str, field: Option<&str>) -> Option<String> {
+    let contents = std::fs::read_to_string(host_api::expand_path(path)).ok()?;
+    let Some(field) = field else {
+        return Some(contents);
+    };
+    let json: Value = serde_json::from_str(&contents).ok()?;
+    let steps = parse_path(field).ok()?;
+    lookup(&json, &steps).and_then(text)
+}
+
+fn placeholder_value(
+    name: &str,
+    auth: Option<&str>,
+    config: &Map<String, Value>,
+) -> Result<String, String> {
+    if name == "auth" {
+        return auth
+            .map(str::to_string)
+            .ok_or_else(|| "{{auth}} is used but http.auth is empty".to_string());
+    }
+    let key = name
+        .strip_prefix("config.")
+        .ok_or_else(|| format!("unknown placeholder {{{{{}}}}}", name))?;
+    config
+        .get(key)
+        .and_then(text)
+        .ok_or_else(|| format!("setting '{}' has no value", key))
+}
+
+/// Replace each `{{name}}` in `template` with `value(name)`.
+fn fill(
+    template: &str,
+    value: &mut dyn FnMut(&str) -> Result<String, String>,
+) -> Result<String, String> {
+    let mut out = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find("{{") {
+        let end = rest[start..]
+            .find("}}")
+            .map(|end| start + end)
+            .ok_or_else(|| format!("unclosed placeholder in '{}'", template))?;
+        out.push_str(&rest[..start]);
+        out.push_str(&value(rest[start + 2..end].trim())?);
+        rest = &rest[end + 2..];
+    }
+    out.push_str(rest);
+    Ok(out)
+}
+
+fn map_line(
+    manifest: &PluginManifest,
+    mapping: &LineMapping,
+    responses: &Value,
+) -> Option<MetricLine> {
+    let line = manifest.lines.iter().find(|line| line.id == mapping.line)?;
+    let label = line.label.clone();
+    let color = mapping.color.clone();
+    let mapped = match line.line_type {
+        LineType::Progress => {
+            let format = mapping.format.clone()?;
+            let limit = match (&format, &mapping.limit) {
+                (ProgressFormat::Percent, None) => 100.0,
+                (_, limit) => field(limit, responses, number)?,
+            };
+            let used = match &mapping.used {
+                Some(_) => field(&mapping.used, responses, number)?,
+                None => (limit - field(&mapping.remaining, responses, number)?).max(0.0),
+            };
+            if used < 0.0 || limit <= 0.0 {
+                log::warn!(
+                    "[plugin:{}] line '{}' has used {} and limit {}; omitting",
+                    manifest.id,
+                    mapping.line,
+                    used,
+                    limit
+                );
+                return None;
+            }
+            MetricLine::Progress {
+                label,
+                used,
+                limit,
+                format,
+                resets_at: field(&mapping.resets_at, responses, timestamp),
+                period_duration_ms: mapping.period_duration_ms.filter(|ms| *ms > 0),
+                color,
+            }
+        }
+        LineType::Text => MetricLine::Text {
+            label,
+            value: field(&mapping.value, responses, text)?,
+            color,
+            subtitle: None,
+        },
+        LineType::Badge => MetricLine::Badge {
+            label,
+            text: field(&mapping.text, responses, text)?,
+            color,
+            subtitle: None,
+        },
+        LineType::Segmented | LineType::Series => return None,
+    };
+    Some(mapped)
+}
+
+/// `convert` a mapping field's value; `None` if the field is unset or missing from the response.
+fn field<T>(
+    source: &Option<Value>,
+    responses: &Value,
+    convert: fn(&Value) -> Option<T>,
+) -> Option<T> {
+    source
+        .as_ref()
+        .and_then(|source| resolve(source, responses))
+        .and_then(convert)
+}
+
+/// The value a mapping field stands for: a literal, or what its `$` path points at.
+fn resolve<'a>(source: &'a Value, responses: &'a Value) -> Option<&'a Value> {
+    match source {
+        Value::String(path) if path.starts_with('$') => lookup(responses, &parse_path(path).ok()?),
+        Value::Null => None,
+        literal => Some(literal),
+    }
+}
+
+fn parse_path(path: &str) -> Result<Vec<Step>, String> {
+    let invalid = |why: &str| format!("invalid path '{}': {}", path, why);
+    let mut rest = path
+        .strip_prefix('$')
+        .ok_or_else(|| invalid("must start with $"))?;
+    let mut steps = Vec::new();
+    while !rest.is_empty() {
+        if let Some(after) = rest.strip_prefix('.') {
+            let end = after.find(['.', '[']).unwrap_or(after.len());
+            if end == 0 {
+                return Err(invalid("empty key"));
+            }
+            steps.push(Step::Key(after[..end].to_string()));
+            rest = &after[end..];
+        } else if let Some(after) = rest.strip_prefix('[') {
+            let end = after.find(']').ok_or_else(|| invalid("unclosed ["))?;
+            let inner = after[..end].trim();
+            let quoted = ['"', '\'']
+                .iter()
+                .find_map(|q| inner.strip_prefix(*q)?.strip_suffix(*q));
+            let step = match quoted {
+                Some(key) => Step::Key(key.to_string()),
+                None => Step::Index(
+                    inner
+                        .parse()
+                        .map_err(|_| invalid("[…] must hold an index or a quoted key"))?,
+                ),
+            };
+            steps.push(step);
+            rest = &after[end + 1..];
+        } else {
+            return Err(invalid("expected . or ["));
+        }
+    }
+    Ok(steps)
+}
+
+fn lookup<'a>(root: &'a Value, steps: &[Step]) -> Option<&'a Value> {
+    steps.iter().try_fold(root, |value, step| match step {
+        Step::Key(key) => value.get(key),
+        Step::Index(index) => {
+            let items = value.as_array()?;
+            let index = if *index < 0 {
+                items.len().checked_sub(index.unsigned_abs() as usize)?
+            } else {
+                *index as usize
+            };
+            items.get(index)
+        }
+    })
+}
+
+/// Numbers, and strings holding one (many APIs send amounts as strings).
+fn number(value: &Value) -> Option<f64> {
+    match value {
+        Value::Number(n) => n.as_f64(),
+        Value::String(s) => s.trim().parse().ok(),
+        _ => None,
+    }
+    .filter(|n: &f64| n.is_finite())
+}
+
+fn text(value: &Value) -> Option<String> {
+    let text = match value {
+        Value::String(s) => s.trim().to_string(),
+        Value::Number(n) => n.to_string(),
+        Value::Bool(b) => b.to_string(),
+        _ => return None,
+    };
+    (!text.is_empty()).then_some(text)
+}
+
+/// RFC 3339 (a missing timezone is taken as UTC) or Unix time in seconds or milliseconds.
+fn timestamp(value: &Value) -> Option<String> {
+    use time::OffsetDateTime;
+    use time::format_description::well_known::Rfc3339;
+    if let Some(n) = number(value) {
+        let millis = if n >= 1e12 { n } else { n * 1000.0 };
+        let nanos = millis.round() as i128 * 1_000_000;
+        return OffsetDateTime::from_unix_timestamp_nanos(nanos)
+            .ok()?
+            .format(&Rfc3339)
+            .ok();
+    }
+    let value = value.as_str()?.trim();
+    [value.to_string(), format!("{}Z", value)]
+        .into_iter()
+        .find(|candidate| OffsetDateTime::parse(candidate, &Rfc3339).is_ok())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::plugin_engine::manifest::parse_manifest;
+    use std::io::{Read, Write};
+
+    fn manifest(extra: &str) -> PluginManifest {
+        parse_manifest(&format!(
+            r#"{{"schemaVersion":2,"id":"decl","name":"Decl","version":"1.0.0","icon":"icon.svg","brandColor":null,
+            "lines":[
+                {{"id":"session","type":"progress","label":"Session","scope":"overview"}},
+                {{"id":"weekly","type":"progress","label":"Weekly","scope":"overview"}},
+                {{"id":"balance","type":"text","label":"Balance","scope":"detail"}},
+                {{"id":"status","type":"badge","label":"Status","scope":"detail"}}
+            ],
+            "env":["DECL_TEST_KEY"],{}}}"#,
+            extra
+        ))
+        .expect("manifest")
+    }
+
+    fn loaded(manifest: PluginManifest) -> LoadedPlugin {
+        LoadedPlugin {
+            manifest,
+            plugin_dir: std::env::temp_dir(),
+            entry_script: String::new(),
+            module_sources: Default::default(),
+            icon_data_url: String::new(),
+            icon_variant_data_urls: Default::default(),
+            trust: Default::default(),
+            incompatibility: None,
+        }
+    }
+
+    /// Answer one request with `status` and `body`, handing back the request it received.
+    fn serve_once(status: u16, body: &'static str) -> (u16, std::sync::mpsc::Receiver<String>) {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").expect("bind");
+        let port = listener.local_addr().expect("local addr").port();
+        let (tx, rx) = std::sync::mpsc::channel();
+        std::thread::spawn(move || {
+            let Ok((mut stream, _)) = listener.accept() else {
+                return;
+            };
+            let mut buf = [0u8; 4096];
+            let n = stream.read(&mut buf).unwrap_or(0);
+            let _ = tx.send(String::from_utf8_lossy(&buf[..n]).to_string());
+            let _ = stream.write_all(
+                format!(
+                    "HTTP/1.1 {} X\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    status,
+                    body.len(),
+                    body
+                )
+                .as_bytes(),
+            );
+        });
+        (port, rx)
+    }
+
+    fn spec_for(port: u16) -> String {
+        format!(
+            r#""capabilities":{{"http":["127.0.0.1"]}},
+            "http":{{
+                "auth":[{{"source":"setting","key":"missing"}},{{"source":"env","name":"DECL_TEST_KEY"}}],
+                "requests":[{{"id":"quota","url":"http://127.0.0.1:{}/quota","headers":{{"Authorization":"Bearer {{{{auth}}}}"}}}}],
+                "plan":"$.quota.plan",
+                "lines":[
+                    {{"line":"session","used":"$.quota.limits[0].used","limit":"$.quota.limits[0].max","format":{{"kind":"count","suffix":"requests"}},"resetsAt":"$.quota.limits[0].reset"}},
+                    {{"line":"weekly","remaining":"$.quota.limits[-1].left","format":{{"kind":"percent"}}}},
+                    {{"line":"balance","value":"$.quota['balance usd']"}},
+                    {{"line":"status","text":"$.quota.absent"}}
+                ]
+            }},
+            "settings":[{{"key":"missing","label":"Missing","type":"string"}}]"#,
+            port
+        )
+    }
+
+    #[test]
+    fn maps_json_fields_to_lines() {
+        let (port, requests) = serve_once(
+            200,
+            r#"{"plan":"Pro","balance usd":"12.50","limits":[{"used":"30","max":120,"reset":1767225600},{"left":75}]}"#,
+        );
+        let plugin = loaded(manifest(&spec_for(port)));
+        let spec = plugin.manifest.http.clone().expect("spec");
+        assert_eq!(check_spec(&plugin.manifest), Vec::<String>::new());
+
+        struct RestoreEnvVar {
+            name: &'static str,
+            old: Option<String>,
+        }
+
+        impl Drop for RestoreEnvVar {
+            fn drop(&mut self) {
+                if let Some(value) = self.old.take() {
+                    // SAFETY: see the `set_var` below; this puts back the value it replaced.
+                    unsafe { std::env::set_var(self.name, value) };
+                } else {
+                    // SAFETY: see the `set_var` below; the variable was unset before it.
+                    unsafe { std::env::remove_var(self.name) };
+                }
+            }
+        }
+
+        let name = "DECL_TEST_KEY";
+        let old = std::env::var(name).ok();
+        let _restore = RestoreEnvVar { name, old };
+        // SAFETY: no other test reads or writes `DECL_TEST_KEY`, so parallel tests never see it
+        // change; nothing here takes a lock, so this relies on the name staying unique.
+        unsafe { std::env::set_var(name, "  sk-test  ") };
+        let dir = std::env::temp_dir().join(format!("openusage-decl-{}", uuid::Uuid::new_v4()));
+        let output = run_probe(&plugin, &spec, &dir, Duration::from_secs(5));
+
+        let request = requests.recv().expect("request");
+        assert!(request.starts_with("GET /quota "), "{}", request);
+        assert!(
+            request
+                .to_ascii_lowercase()
+                .contains("authorization: bearer sk-test\r\n"),
+            "{}",
+            request
+        );
+        assert!(output.error.is_none(), "{:?}", output.error);
+        assert_eq!(output.plan.as_deref(), Some("Pro"));
+        let lines = serde_json::to_value(&output.lines).expect("lines");
+        assert_eq!(
+            lines,
+            serde_json::json!([
+                {"type":"progress","label":"Session","used":30.0,"limit":120.0,
+                 "format":{"kind":"count","suffix":"requests"},
+                 "resetsAt":"2026-01-01T00:00:00Z","periodDurationMs":null,"color":null},
+                {"type":"progress","label":"Weekly","used":25.0,"limit":100.0,
+                 "format":{"kind":"percent"},"resetsAt":null,"periodDurationMs":null,"color":null},
+                {"type":"text","label":"Balance","value":"12.50","color":null,"subtitle":null}
+            ])
+        );
+    }
+
+    #[test]
+    fn reports_auth_and_status_errors() {
+        let undeclared = manifest(
+            r#""http":{"auth":[{"source":"env","name":"OTHER_KEY"}],
+            "requests":[{"id":"q","url":"https://example.com"}],
+            "lines":[{"line":"balance","value":"$.q.x"}]}"#,
+        );
+        assert_eq!(
+            check_spec(&undeclared),
+            vec!["auth env var OTHER_KEY is not listed in plugin.json \"env\""]
+        );
+
+        let plugin = loaded(manifest(
+            r#""http":{"auth":[{"source":"setting","key":"token"}],
+            "requests":[{"id":"q","url":"https://example.com"}],
+            "lines":[{"line":"balance","value":"$.q.x"}]},
+            "settings":[{"key":"token","label":"Token","type":"string"}]"#,
+        ));
+        let spec = plugin.manifest.http.clone().expect("spec");
+        let dir = std::env::temp_dir().join(format!("openusage-decl-{}", uuid::Uuid::new_v4()));
+        let error = run_probe(&plugin, &spec, &dir, Duration::from_secs(5))
+            .error
+            .expect("error");
+        assert_eq!(error.code, ProbeErrorCode::AuthRequired);
+        assert_eq!(
+            error.message,
+            "No credentials found. Checked setting token."
+        );
+
+        assert_eq!(
+            check_status("q", 401).unwrap_err().code,
+            ProbeErrorCode::AuthRequired
+        );
+        assert_eq!(
+            check_status("q", 429).unwrap_err().code,
+            ProbeErrorCode::RateLimited
+        );
+        assert_eq!(
+            check_status("q", 500).unwrap_err().message,
+            "Request 'q' failed (HTTP 500)."
+        );
+    }
+
+    #[test]
+    fn check_spec_rejects_bad_mappings() {
+        let manifest = manifest(
+            r#""entry":"plugin.js","http":{
+                "requests":[{"id":"q","url":"ftp://x","headers":{"X":"{{auth}} {{config.nope}} {{other}}"}}],
+                "plan":"$.other.plan",
+                "lines":[
+                    {"line":"session","used":1,"remaining":2,"format":{"kind":"percent"},"limit":50},
+                    {"line":"balance"},
+                    {"line":"gone","value":"$.q.x"},
+                    {"line":"status","text":"$.q[x]"}
+                ]}"#,
+        );
+        assert_eq!(
+            check_spec(&manifest),
+            vec![
+                "declarative plugins have no entry; remove entry or http",
+                "request 'q' url must be an http(s) URL",
+                "request 'q': {{auth}} is used but http.auth is empty",
+                "plan path '$.other.plan' must start with a request id",
+                "line 'session' needs exactly one of used or remaining",
+                "line 'session' uses percent, so its limit must be 100",
+                "line 'balance' needs a value",
+                "http.lines maps unknown line 'gone'",
+                "line 'status': invalid path '$.q[x]': […] must hold an index or a quoted key",
+            ]
+        );
+    }
+
+    #[test]
+    fn paths_and_values() {
+        let json = serde_json::json!({"a": {"b c": [1, {"d": "2.5"}]}});
+        let steps = parse_path(r#"$.a["b c"][-1].d"#).expect("path");
+        assert_eq!(lookup(&json, &steps).and_then(number), Some(2.5));
+        assert_eq!(lookup(&json, &parse_path("$.a.missing").unwrap()), None);
+        assert!(parse_path("a.b").is_err());
+        assert!(parse_path("$.a..b").is_err());
+        assert_eq!(
+            timestamp(&Value::from(1767225600000_i64)).as_deref(),
+            Some("2026-01-01T00:00:00Z")
+        );
+        assert_eq!(
+            timestamp(&Value::from("2026-01-01T00:00:00")).as_deref(),
+            Some("2026-01-01T00:00:00Z")
+        );
+        assert_eq!(timestamp(&Value::from("soon")), None);
+    }
+}
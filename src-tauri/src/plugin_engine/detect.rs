@@ -0,0 +1,261 @@
+//! Evaluates the manifest `detect` rules to find which providers look installed on this machine.
+//!
+//! Detected plugins whose rules set `autoEnable` are enabled by default for new users; the rest
+//! are only reported as suggestions.
+
+use crate::plugin_engine::host_api::expand_path;
+use crate::plugin_engine::manifest::{LoadedPlugin, PluginManifest};
+use serde::Serialize;
+use std::collections::HashSet;
+use std::io::Read;
+
+const SQLITE_HEADER: &[u8] = b"SQLite format 3\0";
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PluginDetection {
+    pub plugin_id: String,
+    /// At least one rule matched.
+    pub detected: bool,
+    /// The rules that matched, e.g. `file ~/.codex/auth.json` or `process Cursor`.
+    pub matched: Vec<String>,
+    /// Detected and the manifest asks to be enabled for new users.
+    pub enabled_by_default: bool,
+}
+
+/// Evaluate every plugin's rules. Environment variables are read from the app's own process
+/// environment; the process list is only read if some plugin has process rules.
+pub fn detect_plugins(plugins: &[LoadedPlugin]) -> Vec<PluginDetection> {
+    let wants_processes = plugins
+        .iter()
+        .any(|plugin| !plugin.manifest.detect.processes.is_empty());
+    let running = if wants_processes {
+        running_process_names()
+    } else {
+        HashSet::new()
+    };
+    let env = |name: &str| std::env::var(name).ok().filter(|value| !value.is_empty());
+    plugins
+        .iter()
+        .map(|plugin| detect_plugin(&plugin.manifest, &running, &env))
+        .collect()
+}
+
+/// Ids of the plugins to enable by default.
+pub fn enabled_by_default(detections: &[PluginDetection]) -> Vec<String> {
+    detections
+        .iter()
+        .filter(|detection| detection.enabled_by_default)
+        .map(|detection| detection.plugin_id.clone())
+        .collect()
+}
+
+/// `running` holds lowercase executable names.
+fn detect_plugin(
+    manifest: &PluginManifest,
+    running: &HashSet<String>,
+    env: &dyn Fn(&str) -> Option<String>,
+) -> PluginDetection {
+    let rules = &manifest.detect;
+    let mut matched = Vec::new();
+
+    for pattern in &rules.files {
+        if let Some(path) = expand_rule_path(manifest, pattern, env)
+            && std::path::Path::new(&path).exists()
+        {
+            matched.push(format!("file {}", pattern));
+        }
+    }
+    for name in &rules.env {
+        if declares_env(manifest, name) && env(name).is_some() {
+            matched.push(format!("env {}", name));
+        }
+    }
+    for pattern in &rules.sqlite {
+        if let Some(path) = expand_rule_path(manifest, pattern, env)
+            && is_sqlite_file(&path)
+        {
+            matched.push(format!("sqlite {}", pattern));
+        }
+    }
+    for name in &rules.processes {
+        if running.contains(&name.to_lowercase()) {
+            matched.push(format!("process {}", name));
+        }
+    }
+
+    let detected = !matched.is_empty();
+    PluginDetection {
+        plugin_id: manifest.id.clone(),
+        detected,
+        matched,
+        enabled_by_default: detected && rules.auto_enable,
+    }
+}
+
+fn declares_env(manifest: &PluginManifest, name: &str) -> bool {
+    let declared = manifest.env.iter().any(|declared| declared == name);
+    if !declared {
+        log::warn!(
+            "plugin {} detect rule uses undeclared env var {}; ignoring",
+            manifest.id,
+            name
+        );
+    }
+    declared
+}
+
+/// Expand `~/` and `${VAR}`; `None` if a variable is undeclared or unset.
+fn expand_rule_path(
+    manifest: &PluginManifest,
+    pattern: &str,
+    env: &dyn Fn(&str) -> Option<String>,
+) -> Option<String> {
+    let mut expanded = String::with_capacity(pattern.len());
+    let mut rest = pattern;
+    while let Some(start) = rest.find("${") {
+        let end = start + rest[start..].find('}')?;
+        let name = &rest[start + 2..end];
+        if !declares_env(manifest, name) {
+            return None;
+        }
+        let value = env(name)?;
+        expanded.push_str(&rest[..start]);
+        expanded.push_str(value.trim_end_matches('/'));
+        rest = &rest[end + 1..];
+    }
+    expanded.push_str(rest);
+    Some(expand_path(&expanded))
+}
+
+fn is_sqlite_file(path: &str) -> bool {
+    let mut header = [0_u8; 16];
+    std::fs::File::open(path)
+        .and_then(|mut file| file.read_exact(&mut header))
+        .is_ok_and(|()| header == SQLITE_HEADER)
+}
+
+/// Lowercase executable names of running processes (without `.exe` on Windows).
+fn running_process_names() -> HashSet<String> {
+    let output = if cfg!(windows) {
+        std::process::Command::new("tasklist")
+            .args(["/fo", "csv", "/nh"])
+            .output()
+    } else {
+        std::process::Command::new("/bin/ps")
+            .args(["-ax", "-o", "comm="])
+            .output()
+    };
+    match output {
+        Ok(output) => parse_process_names(&String::from_utf8_lossy(&output.stdout)),
+        Err(e) => {
+            log::warn!("failed to list processes for plugin detection: {}", e);
+            HashSet::new()
+        }
+    }
+}
+
+/// Accepts `ps -o comm=` lines (full paths) and `tasklist /fo csv` lines (quoted image name first).
+fn parse_process_names(text: &str) -> HashSet<String> {
+    text.lines()
+        .filter_map(|line| {
+            let line = line.trim();
+            let command = match line.strip_prefix('"') {
+                Some(quoted) => quoted.split('"').next()?,
+                None => line,
+            };
+            let name = command.rsplit(['/', '\\']).next()?.trim();
+            let name = name.strip_suffix(".exe").unwrap_or(name);
+            (!name.is_empty()).then(|| name.to_lowercase())
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::plugin_engine::manifest::parse_manifest;
+
+    fn manifest(detect: &str) -> PluginManifest {
+        parse_manifest(&format!(
+            r#"{{"schemaVersion":2,"id":"x","name":"X","version":"1.0.0","entry":"plugin.js","icon":"icon.svg","brandColor":null,"lines":[],"env":["X_HOME","X_TOKEN"],"detect":{}}}"#,
+            detect
+        ))
+        .expect("manifest")
+    }
+
+    fn no_env(_: &str) -> Option<String> {
+        None
+    }
+
+    #[test]
+    fn matches_files_env_sqlite_and_processes() {
+        let dir = std::env::temp_dir().join(format!("openusage-detect-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).expect("create dir");
+        std::fs::write(dir.join("auth.json"), "{}").expect("write auth");
+        let mut db = SQLITE_HEADER.to_vec();
+        db.extend_from_slice(&[0; 84]);
+        std::fs::write(dir.join("state.vscdb"), db).expect("write db");
+        std::fs::write(dir.join("fake.vscdb"), "not a database").expect("write fake db");
+        let home = dir.to_string_lossy().to_string();
+        let env = move |name: &str| match name {
+            "X_HOME" => Some(home.clone()),
+            "X_TOKEN" => Some("secret".to_string()),
+            _ => None,
+        };
+        let running: HashSet<String> = ["cursor".to_string()].into();
+
+        let rules = format!(
+            r#"{{"files":["${{X_HOME}}/auth.json","${{X_HOME}}/missing.json"],"env":["X_TOKEN","UNDECLARED"],"sqlite":["{0}/state.vscdb","{0}/fake.vscdb"],"processes":["Cursor","Windsurf"],"autoEnable":true}}"#,
+            dir.to_string_lossy()
+        );
+        let detection = detect_plugin(&manifest(&rules), &running, &env);
+        assert!(detection.detected && detection.enabled_by_default);
+        assert_eq!(
+            detection.matched,
+            vec![
+                "file ${X_HOME}/auth.json".to_string(),
+                "env X_TOKEN".to_string(),
+                format!("sqlite {}/state.vscdb", dir.to_string_lossy()),
+                "process Cursor".to_string(),
+            ]
+        );
+
+        let suggested = detect_plugin(&manifest(r#"{"processes":["cursor"]}"#), &running, &no_env);
+        assert!(suggested.detected);
+        assert!(
+            !suggested.enabled_by_default,
+            "autoEnable defaults to false"
+        );
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn nothing_matches_without_rules_or_when_variables_are_unset() {
+        let running = HashSet::new();
+        let none = detect_plugin(&manifest("{}"), &running, &no_env);
+        assert!(!none.detected && none.matched.is_empty());
+
+        let unset = detect_plugin(
+            &manifest(r#"{"files":["${X_HOME}"],"autoEnable":true}"#),
+            &running,
+            &no_env,
+        );
+        assert!(!unset.detected && !unset.enabled_by_default);
+        assert!(enabled_by_default(&[none, unset]).is_empty());
+    }
+
+    #[test]
+    fn parses_ps_and_tasklist_output() {
+        let ps = "/Applications/Cursor.app/Contents/MacOS/Cursor\n/bin/zsh\n  launchd\n";
+        assert_eq!(
+            parse_process_names(ps),
+            ["cursor", "zsh", "launchd"].map(String::from).into()
+        );
+        let tasklist = "\"Cursor.exe\",\"1234\",\"Console\",\"1\",\"120,000 K\"\r\n";
+        assert_eq!(
+            parse_process_names(tasklist),
+            ["cursor"].map(String::from).into()
+        );
+    }
+}
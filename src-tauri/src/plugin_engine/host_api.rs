@@ -1,3 +1,7 @@
+use crate::plugin_engine::bytecode::{self, BytecodeCache};
+use crate::plugin_engine::host_jobs::HostJobs;
+use crate::plugin_engine::permissions::Permissions;
+use crate::plugin_engine::runtime::ProbeErrorCode;
 use aes_gcm::{
     AesGcm, Nonce,
     aead::{Aead, KeyInit, OsRng, generic_array::typenum::U16, rand_core::RngCore},
@@ -9,26 +13,9 @@ use std::collections::HashMap;
 use std::ffi::{OsStr, OsString};
 use std::path::{Path, PathBuf};
 use std::process::Command;
-use std::sync::{Mutex, OnceLock};
-
-const WHITELISTED_ENV_VARS: [&str; 16] = [
-    "CODEX_HOME",
-    "CLAUDE_CONFIG_DIR",
-    "CLAUDE_CODE_OAUTH_TOKEN",
-    "USER_TYPE",
-    "USE_STAGING_OAUTH",
-    "USE_LOCAL_OAUTH",
-    "CLAUDE_CODE_CUSTOM_OAUTH_URL",
-    "CLAUDE_CODE_OAUTH_CLIENT_ID",
-    "CLAUDE_LOCAL_OAUTH_API_BASE",
-    "ZAI_API_KEY",
-    "GLM_API_KEY",
-    "MINIMAX_API_KEY",
-    "MINIMAX_API_TOKEN",
-    "MINIMAX_CN_API_KEY",
-    "SYNTHETIC_API_KEY",
-    "PI_CODING_AGENT_DIR",
-];
+use std::rc::Rc;
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::{Duration, Instant};
 
 fn last_non_empty_trimmed_line(text: &str) -> Option<String> {
     text.lines()
@@ -108,6 +95,30 @@ fn current_macos_keychain_account() -> String {
     current_macos_keychain_account_from_user_env(read_env_from_process("USER"))
 }
 
+/// Read a generic password for the current macOS user, falling back to an item under any
+/// account. `None` off macOS or when neither lookup finds it.
+pub(crate) fn read_generic_password_prefer_current_user(service: &str) -> Option<String> {
+    if !cfg!(target_os = "macos") {
+        return None;
+    }
+    let account = current_macos_keychain_account();
+    [
+        keychain_find_generic_password_args_for_account(service, &account),
+        keychain_find_generic_password_args(service),
+    ]
+    .into_iter()
+    .find_map(|args| {
+        let output = std::process::Command::new("security")
+            .args(args)
+            .output()
+            .ok()?;
+        output
+            .status
+            .success()
+            .then(|| String::from_utf8_lossy(&output.stdout).trim().to_string())
+    })
+}
+
 fn keychain_find_generic_password_args(service: &str) -> Vec<OsString> {
     vec![
         OsString::from("find-generic-password"),
@@ -117,7 +128,10 @@ fn keychain_find_generic_password_args(service: &str) -> Vec<OsString> {
     ]
 }
 
-fn keychain_find_generic_password_args_for_account(service: &str, account: &str) -> Vec<OsString> {
+pub(crate) fn keychain_find_generic_password_args_for_account(
+    service: &str,
+    account: &str,
+) -> Vec<OsString> {
     vec![
         OsString::from("find-generic-password"),
         OsString::from("-a"),
@@ -139,7 +153,7 @@ fn keychain_add_generic_password_args(service: &str, value: &str) -> Vec<OsStrin
     ]
 }
 
-fn keychain_add_generic_password_args_for_account(
+pub(crate) fn keychain_add_generic_password_args_for_account(
     service: &str,
     account: &str,
     value: &str,
@@ -216,7 +230,7 @@ fn read_env_from_interactive_shells(name: &str) -> Option<String> {
     None
 }
 
-fn resolve_env_value(name: &str) -> Option<String> {
+pub(crate) fn resolve_env_value(name: &str) -> Option<String> {
     // Prefer the current process env (fast + supports launchctl/terminal-launch).
     if let Some(value) = read_env_from_process(name) {
         return Some(value);
@@ -510,22 +524,272 @@ fn encrypt_aes_256_gcm_envelope(plaintext: &str, key_b64: &str) -> Result<String
     ))
 }
 
+/// Wall-clock deadline for one probe, shared by every blocking host call it makes.
+#[derive(Debug, Clone, Copy)]
+pub struct ProbeDeadline {
+    at: Instant,
+}
+
+impl ProbeDeadline {
+    pub fn after(timeout: Duration) -> Self {
+        Self {
+            at: Instant::now() + timeout,
+        }
+    }
+
+    pub fn remaining(&self) -> Duration {
+        self.at.saturating_duration_since(Instant::now())
+    }
+
+    pub fn is_expired(&self) -> bool {
+        Instant::now() >= self.at
+    }
+
+    /// Clamp a host-call timeout so it never outlives the probe.
+    fn cap(&self, requested: Duration) -> Duration {
+        requested.min(self.remaining())
+    }
+}
+
+/// Throw a JS `Error` tagged with a probe error `code` so an uncaught host failure is
+/// classified instead of reported as a plugin bug.
+fn throw_coded(ctx: &Ctx<'_>, code: ProbeErrorCode, message: &str) -> rquickjs::Error {
+    let exception = match Exception::from_message(ctx.clone(), message) {
+        Ok(exception) => exception,
+        Err(err) => return err,
+    };
+    if let Err(err) = exception.as_object().set("code", code.as_str()) {
+        return err;
+    }
+    exception.throw()
+}
+
+/// Failure of a host call made outside the JS context; thrown directly by the blocking API
+/// and used to reject the promise of the async one.
+#[derive(Debug, Clone, PartialEq)]
+pub struct HostCallError {
+    pub code: Option<ProbeErrorCode>,
+    pub message: String,
+}
+
+impl HostCallError {
+    fn new(message: impl Into<String>) -> Self {
+        Self {
+            code: None,
+            message: message.into(),
+        }
+    }
+
+    fn permission_denied(message: impl Into<String>) -> Self {
+        Self::coded(ProbeErrorCode::PermissionDenied, message)
+    }
+
+    fn coded(code: ProbeErrorCode, message: impl Into<String>) -> Self {
+        Self {
+            code: Some(code),
+            message: message.into(),
+        }
+    }
+
+    fn throw(&self, ctx: &Ctx<'_>) -> rquickjs::Error {
+        match self.code {
+            Some(code) => throw_coded(ctx, code, &self.message),
+            None => Exception::throw_message(ctx, &self.message),
+        }
+    }
+}
+
+fn ensure_deadline(ctx: &Ctx<'_>, deadline: &ProbeDeadline) -> rquickjs::Result<()> {
+    if deadline.is_expired() {
+        return Err(throw_coded(
+            ctx,
+            ProbeErrorCode::Timeout,
+            "probe deadline exceeded",
+        ));
+    }
+    Ok(())
+}
+
+/// Directory a plugin can always read and write, exposed as `ctx.app.pluginDataDir`.
+pub(crate) fn plugin_data_dir(app_data_dir: &Path, plugin_id: &str) -> PathBuf {
+    app_data_dir.join("plugins_data").join(plugin_id)
+}
+
+/// Throw a `permission_denied` error for a denial returned by `Permissions`.
+fn throw_permission_denied(ctx: &Ctx<'_>, message: String) -> rquickjs::Error {
+    throw_coded(ctx, ProbeErrorCode::PermissionDenied, &message)
+}
+
+/// Host API functions this app provides, as `namespace.function` under `ctx.host`. Plugins list
+/// the ones they need in the manifest's `requires`. Keep in sync with `inject_host_api` and the
+/// `patch_*_wrapper` scripts; a test checks every entry is a function.
+pub const HOST_API_FEATURES: &[&str] = &[
+    "log.info",
+    "log.warn",
+    "log.error",
+    "fs.exists",
+    "fs.readText",
+    "fs.writeText",
+    "fs.listDir",
+    "crypto.decryptAes256Gcm",
+    "crypto.encryptAes256Gcm",
+    "env.get",
+    "http.request",
+    "http.requestAsync",
+    "keychain.readGenericPassword",
+    "keychain.readGenericPasswordForCurrentUser",
+    "keychain.writeGenericPassword",
+    "keychain.writeGenericPasswordForCurrentUser",
+    "sqlite.query",
+    "sqlite.exec",
+    "sqlite.queryAsync",
+    "sqlite.execAsync",
+    "ls.discover",
+    "ccusage.query",
+    "ccusage.queryAsync",
+];
+
+const LSOF_PATHS: [&str; 2] = ["/usr/sbin/lsof", "/usr/bin/lsof"];
+
+/// What the host can actually do on this machine, published as `ctx.host.capabilities`. Every
+/// function in `HOST_API_FEATURES` exists on every platform; this says which ones will work.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HostCapabilities {
+    /// Same as `ctx.app.platform`.
+    pub platform: String,
+    /// `host.keychain.*` (macOS Keychain via `security`).
+    pub keychain: bool,
+    /// `host.ls.discover` (needs `ps` and `lsof`).
+    pub ls: bool,
+    /// `host.sqlite.*` (needs the `sqlite3` binary).
+    pub sqlite: bool,
+    pub ccusage: CcusageCapability,
+    /// `host.http` requests go through the proxy from the app config.
+    pub proxy: bool,
+    /// Every host API function this build provides (`HOST_API_FEATURES`).
+    pub apis: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CcusageCapability {
+    pub available: bool,
+    /// Package runners found, in the order `host.ccusage.query` tries them (e.g. `"bunx"`).
+    pub runners: Vec<String>,
+}
+
+/// Detect capabilities for the running platform. Only looks for files; nothing is spawned.
+/// Detected once per process, like the proxy config it reports on.
+pub fn detect_host_capabilities() -> &'static HostCapabilities {
+    static CAPABILITIES: OnceLock<HostCapabilities> = OnceLock::new();
+    CAPABILITIES.get_or_init(|| {
+        let path = ccusage_enriched_path();
+        let proxy = crate::config::get_resolved_proxy().is_some();
+        detect_host_capabilities_with(
+            std::env::consts::OS,
+            |program| match program {
+                "sqlite3" => sqlite3_binary().is_some(),
+                _ => program_exists(program, path.as_deref()),
+            },
+            proxy,
+        )
+    })
+}
+
+/// `exists` reports whether a program (absolute path or bare name looked up on `PATH`) exists.
+fn detect_host_capabilities_with<F>(os: &str, exists: F, proxy: bool) -> HostCapabilities
+where
+    F: Fn(&str) -> bool,
+{
+    let unix = os != "windows";
+    let runners: Vec<String> = ccusage_runner_order()
+        .into_iter()
+        .filter(|kind| {
+            ccusage_runner_candidates(*kind)
+                .iter()
+                .any(|candidate| exists(candidate))
+        })
+        .map(|kind| ccusage_runner_label(kind).to_string())
+        .collect();
+    HostCapabilities {
+        platform: os.to_string(),
+        keychain: os == "macos" && exists("/usr/bin/security"),
+        ls: unix && exists("/bin/ps") && LSOF_PATHS.iter().any(|path| exists(path)),
+        sqlite: exists("sqlite3"),
+        ccusage: CcusageCapability {
+            available: !runners.is_empty(),
+            runners,
+        },
+        proxy,
+        apis: HOST_API_FEATURES
+            .iter()
+            .map(|api| api.to_string())
+            .collect(),
+    }
+}
+
+fn program_exists(program: &str, search_path: Option<&OsStr>) -> bool {
+    find_program(program, search_path).is_some()
+}
+
+fn find_program(program: &str, search_path: Option<&OsStr>) -> Option<PathBuf> {
+    let candidate = Path::new(program);
+    if candidate.is_absolute() || program.contains('/') {
+        return candidate.is_file().then(|| candidate.to_path_buf());
+    }
+    std::env::split_paths(search_path?).find_map(|dir| {
+        let path = dir.join(program);
+        if path.is_file() {
+            return Some(path);
+        }
+        if !cfg!(windows) {
+            return None;
+        }
+        ["exe", "cmd", "bat"]
+            .iter()
+            .map(|ext| path.with_extension(ext))
+            .find(|path| path.is_file())
+    })
+}
+
+/// The `sqlite3` that `host.sqlite` runs, looked up once on the same enriched `PATH` that
+/// capability detection reports on (GUI apps on macOS start with a minimal `PATH`).
+fn sqlite3_binary() -> Option<&'static Path> {
+    static SQLITE3: OnceLock<Option<PathBuf>> = OnceLock::new();
+    SQLITE3
+        .get_or_init(|| find_program("sqlite3", ccusage_enriched_path().as_deref()))
+        .as_deref()
+}
+
+fn sqlite3_command() -> std::process::Command {
+    std::process::Command::new(sqlite3_binary().unwrap_or(Path::new("sqlite3")))
+}
+
+#[allow(clippy::too_many_arguments)]
 pub fn inject_host_api<'js>(
     ctx: &Ctx<'js>,
     plugin_id: &str,
     app_data_dir: &PathBuf,
     app_version: &str,
+    config: &serde_json::Map<String, serde_json::Value>,
+    permissions: &Arc<Permissions>,
+    deadline: ProbeDeadline,
+    jobs: &Rc<HostJobs>,
 ) -> rquickjs::Result<()> {
     let globals = ctx.globals();
     let probe_ctx = Object::new(ctx.clone())?;
 
     probe_ctx.set("nowIso", iso_now())?;
+    // Resolved manifest settings; see `settings::resolve_config`.
+    let config_json = serde_json::to_string(config).unwrap_or_else(|_| "{}".to_string());
+    probe_ctx.set("config", ctx.json_parse(config_json)?)?;
 
     let app_obj = Object::new(ctx.clone())?;
     app_obj.set("version", app_version)?;
     app_obj.set("platform", std::env::consts::OS)?;
     app_obj.set("appDataDir", app_data_dir.to_string_lossy().to_string())?;
-    let plugin_data_dir = app_data_dir.join("plugins_data").join(plugin_id);
+    let plugin_data_dir = plugin_data_dir(app_data_dir, plugin_id);
     if let Err(err) = std::fs::create_dir_all(&plugin_data_dir) {
         log::warn!(
             "[plugin:{}] failed to create plugin data dir: {}",
@@ -541,14 +805,17 @@ pub fn inject_host_api<'js>(
 
     let host = Object::new(ctx.clone())?;
     inject_log(ctx, &host, plugin_id)?;
-    inject_fs(ctx, &host)?;
+    inject_fs(ctx, &host, permissions)?;
     inject_crypto(ctx, &host)?;
-    inject_env(ctx, &host, plugin_id)?;
-    inject_http(ctx, &host, plugin_id)?;
-    inject_keychain(ctx, &host, plugin_id)?;
-    inject_sqlite(ctx, &host)?;
-    inject_ls(ctx, &host, plugin_id)?;
-    inject_ccusage(ctx, &host, plugin_id)?;
+    inject_env(ctx, &host, permissions)?;
+    inject_http(ctx, &host, plugin_id, permissions, deadline, jobs)?;
+    inject_keychain(ctx, &host, plugin_id, permissions)?;
+    inject_sqlite(ctx, &host, permissions, deadline, jobs)?;
+    inject_ls(ctx, &host, plugin_id, permissions, deadline)?;
+    inject_ccusage(ctx, &host, plugin_id, permissions, deadline, jobs)?;
+    let capabilities_json =
+        serde_json::to_string(detect_host_capabilities()).unwrap_or_else(|_| "{}".to_string());
+    host.set("capabilities", ctx.json_parse(capabilities_json)?)?;
 
     probe_ctx.set("host", host)?;
     globals.set("__openusage_ctx", probe_ctx)?;
@@ -587,22 +854,37 @@ fn inject_log<'js>(ctx: &Ctx<'js>, host: &Object<'js>, plugin_id: &str) -> rquic
     Ok(())
 }
 
-fn inject_fs<'js>(ctx: &Ctx<'js>, host: &Object<'js>) -> rquickjs::Result<()> {
+fn inject_fs<'js>(
+    ctx: &Ctx<'js>,
+    host: &Object<'js>,
+    permissions: &Arc<Permissions>,
+) -> rquickjs::Result<()> {
     let fs_obj = Object::new(ctx.clone())?;
 
+    let perms = Arc::clone(permissions);
     fs_obj.set(
         "exists",
-        Function::new(ctx.clone(), move |path: String| -> bool {
-            let expanded = expand_path(&path);
-            std::path::Path::new(&expanded).exists()
-        })?,
+        Function::new(
+            ctx.clone(),
+            move |ctx_inner: Ctx<'_>, path: String| -> rquickjs::Result<bool> {
+                perms
+                    .check_fs_read(&path)
+                    .map_err(|e| throw_permission_denied(&ctx_inner, e))?;
+                let expanded = expand_path(&path);
+                Ok(std::path::Path::new(&expanded).exists())
+            },
+        )?,
     )?;
 
+    let perms = Arc::clone(permissions);
     fs_obj.set(
         "readText",
         Function::new(
             ctx.clone(),
             move |ctx_inner: Ctx<'_>, path: String| -> rquickjs::Result<String> {
+                perms
+                    .check_fs_read(&path)
+                    .map_err(|e| throw_permission_denied(&ctx_inner, e))?;
                 let expanded = expand_path(&path);
                 std::fs::read_to_string(&expanded)
                     .map_err(|e| Exception::throw_message(&ctx_inner, &e.to_string()))
@@ -610,11 +892,15 @@ fn inject_fs<'js>(ctx: &Ctx<'js>, host: &Object<'js>) -> rquickjs::Result<()> {
         )?,
     )?;
 
+    let perms = Arc::clone(permissions);
     fs_obj.set(
         "writeText",
         Function::new(
             ctx.clone(),
             move |ctx_inner: Ctx<'_>, path: String, content: String| -> rquickjs::Result<()> {
+                perms
+                    .check_fs_write(&path)
+                    .map_err(|e| throw_permission_denied(&ctx_inner, e))?;
                 let expanded = expand_path(&path);
                 std::fs::write(&expanded, &content)
                     .map_err(|e| Exception::throw_message(&ctx_inner, &e.to_string()))
@@ -622,11 +908,15 @@ fn inject_fs<'js>(ctx: &Ctx<'js>, host: &Object<'js>) -> rquickjs::Result<()> {
         )?,
     )?;
 
+    let perms = Arc::clone(permissions);
     fs_obj.set(
         "listDir",
         Function::new(
             ctx.clone(),
             move |ctx_inner: Ctx<'_>, path: String| -> rquickjs::Result<Vec<String>> {
+                perms
+                    .check_fs_read(&path)
+                    .map_err(|e| throw_permission_denied(&ctx_inner, e))?;
                 let expanded = expand_path(&path);
                 let entries = std::fs::read_dir(&expanded)
                     .map_err(|e| Exception::throw_message(&ctx_inner, &e.to_string()))?;
@@ -688,12 +978,17 @@ fn inject_crypto<'js>(ctx: &Ctx<'js>, host: &Object<'js>) -> rquickjs::Result<()
     Ok(())
 }
 
-fn inject_env<'js>(ctx: &Ctx<'js>, host: &Object<'js>, _plugin_id: &str) -> rquickjs::Result<()> {
+fn inject_env<'js>(
+    ctx: &Ctx<'js>,
+    host: &Object<'js>,
+    permissions: &Arc<Permissions>,
+) -> rquickjs::Result<()> {
     let env_obj = Object::new(ctx.clone())?;
+    let perms = Arc::clone(permissions);
     env_obj.set(
         "get",
         Function::new(ctx.clone(), move |name: String| -> Option<String> {
-            if !WHITELISTED_ENV_VARS.contains(&name.as_str()) {
+            if !perms.allows_env(&name) {
                 return None;
             }
 
@@ -704,126 +999,41 @@ fn inject_env<'js>(ctx: &Ctx<'js>, host: &Object<'js>, _plugin_id: &str) -> rqui
     Ok(())
 }
 
-fn inject_http<'js>(ctx: &Ctx<'js>, host: &Object<'js>, plugin_id: &str) -> rquickjs::Result<()> {
+fn inject_http<'js>(
+    ctx: &Ctx<'js>,
+    host: &Object<'js>,
+    plugin_id: &str,
+    permissions: &Arc<Permissions>,
+    deadline: ProbeDeadline,
+    jobs: &Rc<HostJobs>,
+) -> rquickjs::Result<()> {
     let http_obj = Object::new(ctx.clone())?;
     let pid = plugin_id.to_string();
+    let perms = Arc::clone(permissions);
 
     http_obj.set(
         "_requestRaw",
         Function::new(
             ctx.clone(),
             move |ctx_inner: Ctx<'_>, req_json: String| -> rquickjs::Result<String> {
-                let req: HttpReqParams = serde_json::from_str(&req_json).map_err(|e| {
-                    Exception::throw_message(&ctx_inner, &format!("invalid request: {}", e))
-                })?;
-
-                let method_str = req.method.as_deref().unwrap_or("GET");
-                let redacted_url = redact_url(&req.url);
-                log::info!("[plugin:{}] HTTP {} {}", pid, method_str, redacted_url);
-
-                let mut header_map = reqwest::header::HeaderMap::new();
-                if let Some(headers) = &req.headers {
-                    for (key, val) in headers {
-                        let name = reqwest::header::HeaderName::from_bytes(key.as_bytes())
-                            .map_err(|e| {
-                                Exception::throw_message(
-                                    &ctx_inner,
-                                    &format!("invalid header name '{}': {}", key, e),
-                                )
-                            })?;
-                        let value = reqwest::header::HeaderValue::from_str(val).map_err(|e| {
-                            Exception::throw_message(
-                                &ctx_inner,
-                                &format!("invalid header value for '{}': {}", key, e),
-                            )
-                        })?;
-                        header_map.insert(name, value);
-                    }
-                }
-
-                let timeout_ms = req.timeout_ms.unwrap_or(10_000);
-                let mut builder = reqwest::blocking::Client::builder()
-                    .timeout(std::time::Duration::from_millis(timeout_ms))
-                    .connect_timeout(std::time::Duration::from_millis(timeout_ms))
-                    .redirect(reqwest::redirect::Policy::none());
-
-                // Apply pre-resolved proxy (localhost bypass already configured)
-                if let Some(resolved) = crate::config::get_resolved_proxy() {
-                    builder = builder.proxy(resolved.proxy.clone());
-                    log::debug!("[http] proxy active");
-                } else {
-                    log::debug!("[http] proxy not used");
-                }
-
-                if req.dangerously_ignore_tls.unwrap_or(false) {
-                    builder = builder.danger_accept_invalid_certs(true);
-                }
-                let client = builder
-                    .build()
-                    .map_err(|e| Exception::throw_message(&ctx_inner, &e.to_string()))?;
-
-                let method = req.method.as_deref().unwrap_or("GET");
-                let method = reqwest::Method::from_bytes(method.as_bytes()).map_err(|e| {
-                    Exception::throw_message(
-                        &ctx_inner,
-                        &format!("invalid http method '{}': {}", method, e),
-                    )
-                })?;
-                let mut builder = client.request(method, &req.url);
-                builder = builder.headers(header_map);
-                if let Some(body) = req.body_text {
-                    builder = builder.body(body);
-                }
-
-                let response = builder
-                    .send()
-                    .map_err(|e| Exception::throw_message(&ctx_inner, &e.to_string()))?;
-
-                let status = response.status().as_u16();
-                let mut resp_headers = std::collections::HashMap::new();
-                for (key, value) in response.headers().iter() {
-                    let header_value = value.to_str().map_err(|e| {
-                        Exception::throw_message(
-                            &ctx_inner,
-                            &format!("invalid response header '{}': {}", key, e),
-                        )
-                    })?;
-                    resp_headers.insert(key.to_string(), header_value.to_string());
-                }
-                let body = response
-                    .text()
-                    .map_err(|e| Exception::throw_message(&ctx_inner, &e.to_string()))?;
-
-                // Redact BEFORE truncation to ensure sensitive values are caught while intact
-                let redacted_body = redact_body(&body);
-                let body_preview = if redacted_body.len() > 500 {
-                    // UTF-8 safe truncation: find valid char boundary at or before 500
-                    let truncated: String = redacted_body
-                        .char_indices()
-                        .take_while(|(i, _)| *i < 500)
-                        .map(|(_, c)| c)
-                        .collect();
-                    format!("{}... ({} bytes total)", truncated, body.len())
-                } else {
-                    redacted_body
-                };
-                log::info!(
-                    "[plugin:{}] HTTP {} {} -> {} | {}",
-                    pid,
-                    method_str,
-                    redacted_url,
-                    status,
-                    body_preview
-                );
-
-                let resp = HttpRespParams {
-                    status,
-                    headers: resp_headers,
-                    body_text: body,
-                };
+                ensure_deadline(&ctx_inner, &deadline)?;
+                http_request(&pid, &perms, &req_json, &deadline).map_err(|e| e.throw(&ctx_inner))
+            },
+        )?,
+    )?;
 
-                serde_json::to_string(&resp)
-                    .map_err(|e| Exception::throw_message(&ctx_inner, &e.to_string()))
+    let pid = plugin_id.to_string();
+    let perms = Arc::clone(permissions);
+    let jobs = Rc::clone(jobs);
+    http_obj.set(
+        "_requestAsyncRaw",
+        Function::new(
+            ctx.clone(),
+            move |ctx_inner: Ctx<'_>, req_json: String| -> rquickjs::Result<u64> {
+                ensure_deadline(&ctx_inner, &deadline)?;
+                let pid = pid.clone();
+                let perms = Arc::clone(&perms);
+                Ok(jobs.spawn(move || http_request(&pid, &perms, &req_json, &deadline)))
             },
         )?,
     )?;
@@ -845,13 +1055,130 @@ fn inject_http<'js>(ctx: &Ctx<'js>, host: &Object<'js>, plugin_id: &str) -> rqui
     Ok(())
 }
 
+/// Perform one `host.http` request. Shared by the blocking and promise-returning variants.
+fn http_request(
+    pid: &str,
+    permissions: &Permissions,
+    req_json: &str,
+    deadline: &ProbeDeadline,
+) -> Result<String, HostCallError> {
+    let req: HttpReqParams = serde_json::from_str(req_json)
+        .map_err(|e| HostCallError::new(format!("invalid request: {}", e)))?;
+    let resp = http_send(pid, permissions, req, deadline)?;
+    serde_json::to_string(&resp).map_err(|e| HostCallError::new(e.to_string()))
+}
+
+/// Send `req` through the proxy and capability checks and log it redacted. Also used by
+/// declarative plugins.
+pub(crate) fn http_send(
+    pid: &str,
+    permissions: &Permissions,
+    req: HttpReqParams,
+    deadline: &ProbeDeadline,
+) -> Result<HttpRespParams, HostCallError> {
+    permissions
+        .check_http(&req.url, req.dangerously_ignore_tls.unwrap_or(false))
+        .map_err(HostCallError::permission_denied)?;
+
+    let method_str = req.method.as_deref().unwrap_or("GET");
+    let redacted_url = redact_url(&req.url);
+    log::info!("[plugin:{}] HTTP {} {}", pid, method_str, redacted_url);
+
+    let mut header_map = reqwest::header::HeaderMap::new();
+    if let Some(headers) = &req.headers {
+        for (key, val) in headers {
+            let name = reqwest::header::HeaderName::from_bytes(key.as_bytes())
+                .map_err(|e| HostCallError::new(format!("invalid header name '{}': {}", key, e)))?;
+            let value = reqwest::header::HeaderValue::from_str(val).map_err(|e| {
+                HostCallError::new(format!("invalid header value for '{}': {}", key, e))
+            })?;
+            header_map.insert(name, value);
+        }
+    }
+
+    let timeout = deadline.cap(Duration::from_millis(req.timeout_ms.unwrap_or(10_000)));
+    let mut builder = reqwest::blocking::Client::builder()
+        .timeout(timeout)
+        .connect_timeout(timeout)
+        .redirect(reqwest::redirect::Policy::none());
+
+    // Apply pre-resolved proxy (localhost bypass already configured)
+    if let Some(resolved) = crate::config::get_resolved_proxy() {
+        builder = builder.proxy(resolved.proxy.clone());
+        log::debug!("[http] proxy active");
+    } else {
+        log::debug!("[http] proxy not used");
+    }
+
+    if req.dangerously_ignore_tls.unwrap_or(false) {
+        builder = builder.danger_accept_invalid_certs(true);
+    }
+    let client = builder
+        .build()
+        .map_err(|e| HostCallError::new(e.to_string()))?;
+
+    let method = req.method.as_deref().unwrap_or("GET");
+    let method = reqwest::Method::from_bytes(method.as_bytes())
+        .map_err(|e| HostCallError::new(format!("invalid http method '{}': {}", method, e)))?;
+    let mut builder = client.request(method, &req.url);
+    builder = builder.headers(header_map);
+    if let Some(body) = req.body_text {
+        builder = builder.body(body);
+    }
+
+    let response = builder
+        .send()
+        .map_err(|e| HostCallError::coded(ProbeErrorCode::Network, e.to_string()))?;
+
+    let status = response.status().as_u16();
+    let mut resp_headers = std::collections::HashMap::new();
+    for (key, value) in response.headers().iter() {
+        let header_value = value
+            .to_str()
+            .map_err(|e| HostCallError::new(format!("invalid response header '{}': {}", key, e)))?;
+        resp_headers.insert(key.to_string(), header_value.to_string());
+    }
+    let body = response
+        .text()
+        .map_err(|e| HostCallError::coded(ProbeErrorCode::Network, e.to_string()))?;
+
+    // Redact BEFORE truncation to ensure sensitive values are caught while intact
+    let redacted_body = redact_body(&body);
+    let body_preview = if redacted_body.len() > 500 {
+        // UTF-8 safe truncation: find valid char boundary at or before 500
+        let truncated: String = redacted_body
+            .char_indices()
+            .take_while(|(i, _)| *i < 500)
+            .map(|(_, c)| c)
+            .collect();
+        format!("{}... ({} bytes total)", truncated, body.len())
+    } else {
+        redacted_body
+    };
+    log::info!(
+        "[plugin:{}] HTTP {} {} -> {} | {}",
+        pid,
+        method_str,
+        redacted_url,
+        status,
+        body_preview
+    );
+
+    Ok(HttpRespParams {
+        status,
+        headers: resp_headers,
+        body_text: body,
+    })
+}
+
 pub fn patch_http_wrapper(ctx: &rquickjs::Ctx<'_>) -> rquickjs::Result<()> {
     ctx.eval::<(), _>(
         r#"
         (function() {
             var rawFn = __openusage_ctx.host.http._requestRaw;
-            __openusage_ctx.host.http.request = function(req) {
-                var json = JSON.stringify({
+            var rawAsyncFn = __openusage_ctx.host.http._requestAsyncRaw;
+            function toJson(req) {
+                return JSON.stringify({
                     url: req.url,
                     method: req.method || "GET",
                     headers: req.headers || null,
@@ -859,8 +1186,18 @@ pub fn patch_http_wrapper(ctx: &rquickjs::Ctx<'_>) -> rquickjs::Result<()> {
                     timeoutMs: req.timeoutMs || 10000,
                     dangerouslyIgnoreTls: req.dangerouslyIgnoreTls || false
                 });
-                var respJson = rawFn(json);
-                return JSON.parse(respJson);
+            }
+            __openusage_ctx.host.http.request = function(req) {
+                return JSON.parse(rawFn(toJson(req)));
+            };
+            __openusage_ctx.host.http.requestAsync = function(req) {
+                var json;
+                try {
+                    json = toJson(req);
+                    return __openusage_jobs.wait(rawAsyncFn(json)).then(JSON.parse);
+                } catch (e) {
+                    return Promise.reject(e);
+                }
             };
         })();
         "#
@@ -869,9 +1206,12 @@ pub fn patch_http_wrapper(ctx: &rquickjs::Ctx<'_>) -> rquickjs::Result<()> {
 }
 
 /// Inject utility APIs (line builders, formatters, base64, jwt) onto __openusage_ctx
-pub fn inject_utils(ctx: &rquickjs::Ctx<'_>) -> rquickjs::Result<()> {
-    ctx.eval::<(), _>(
-        r#"
+/// Name the utility prelude is compiled under. It matches QuickJS's default script name so its
+/// stack frames are labelled as host code.
+pub(crate) const UTILS_PRELUDE_NAME: &str = "eval_script";
+
+/// Helpers added to `ctx` (line builders, formatting, base64, JWT, ...) for every probe.
+pub(crate) const UTILS_PRELUDE: &str = r#"
         (function() {
             var ctx = __openusage_ctx;
 
@@ -895,6 +1235,26 @@ pub fn inject_utils(ctx: &rquickjs::Ctx<'_>) -> rquickjs::Result<()> {
                     if (opts.color) line.color = opts.color;
                     if (opts.subtitle) line.subtitle = opts.subtitle;
                     return line;
+                },
+                segmented: function(opts) {
+                    var segments = (opts.segments || []).map(function(segment) {
+                        var out = { label: segment.label, used: segment.used };
+                        if (segment.color) out.color = segment.color;
+                        return out;
+                    });
+                    var line = { type: "segmented", label: opts.label, segments: segments, limit: opts.limit, format: opts.format };
+                    if (opts.resetsAt) line.resetsAt = opts.resetsAt;
+                    if (opts.periodDurationMs) line.periodDurationMs = opts.periodDurationMs;
+                    return line;
+                },
+                series: function(opts) {
+                    var points = (opts.points || []).map(function(point) {
+                        var at = ctx.util.toIso(point.at);
+                        return { at: at === null ? point.at : at, value: point.value };
+                    });
+                    var line = { type: "series", label: opts.label, points: points, format: opts.format };
+                    if (opts.color) line.color = opts.color;
+                    return line;
                 }
             };
 
@@ -1146,28 +1506,34 @@ pub fn inject_utils(ctx: &rquickjs::Ctx<'_>) -> rquickjs::Result<()> {
                 }
             };
         })();
-        "#
-        .as_bytes(),
-    )
+        "#;
+
+pub fn inject_utils(
+    ctx: &rquickjs::Ctx<'_>,
+    cache: &BytecodeCache,
+    app_version: &str,
+) -> rquickjs::Result<()> {
+    let key = bytecode::host_script_cache_key(UTILS_PRELUDE_NAME, UTILS_PRELUDE, app_version);
+    cache.eval_script(ctx, &key, UTILS_PRELUDE_NAME, UTILS_PRELUDE)
 }
 
 #[derive(serde::Deserialize)]
 #[serde(rename_all = "camelCase")]
-struct HttpReqParams {
-    url: String,
-    method: Option<String>,
-    headers: Option<std::collections::HashMap<String, String>>,
-    body_text: Option<String>,
-    timeout_ms: Option<u64>,
-    dangerously_ignore_tls: Option<bool>,
+pub(crate) struct HttpReqParams {
+    pub url: String,
+    pub method: Option<String>,
+    pub headers: Option<std::collections::HashMap<String, String>>,
+    pub body_text: Option<String>,
+    pub timeout_ms: Option<u64>,
+    pub dangerously_ignore_tls: Option<bool>,
 }
 
 #[derive(serde::Serialize)]
 #[serde(rename_all = "camelCase")]
-struct HttpRespParams {
-    status: u16,
-    headers: std::collections::HashMap<String, String>,
-    body_text: String,
+pub(crate) struct HttpRespParams {
+    pub status: u16,
+    pub headers: std::collections::HashMap<String, String>,
+    pub body_text: String,
 }
 
 // --- Language Server Discovery ---
@@ -1192,15 +1558,26 @@ struct LsDiscoverResult {
     extension_port: Option<i32>,
 }
 
-fn inject_ls<'js>(ctx: &Ctx<'js>, host: &Object<'js>, plugin_id: &str) -> rquickjs::Result<()> {
+fn inject_ls<'js>(
+    ctx: &Ctx<'js>,
+    host: &Object<'js>,
+    plugin_id: &str,
+    permissions: &Arc<Permissions>,
+    deadline: ProbeDeadline,
+) -> rquickjs::Result<()> {
     let ls_obj = Object::new(ctx.clone())?;
     let pid = plugin_id.to_string();
+    let perms = Arc::clone(permissions);
 
     ls_obj.set(
         "_discoverRaw",
         Function::new(
             ctx.clone(),
             move |ctx_inner: Ctx<'_>, opts_json: String| -> rquickjs::Result<String> {
+                ensure_deadline(&ctx_inner, &deadline)?;
+                perms
+                    .check_ls()
+                    .map_err(|e| throw_permission_denied(&ctx_inner, e))?;
                 let opts: LsDiscoverOpts = serde_json::from_str(&opts_json).map_err(|e| {
                     Exception::throw_message(&ctx_inner, &format!("invalid discover opts: {}", e))
                 })?;
@@ -1323,7 +1700,7 @@ fn inject_ls<'js>(ctx: &Ctx<'js>, host: &Object<'js>, plugin_id: &str) -> rquick
                 }
 
                 // Find lsof binary
-                let lsof_path = ["/usr/sbin/lsof", "/usr/bin/lsof"]
+                let lsof_path = LSOF_PATHS
                     .iter()
                     .find(|p| std::path::Path::new(p).exists())
                     .copied();
@@ -1837,6 +2214,7 @@ fn run_ccusage_with_runner(
     opts: &CcusageQueryOpts,
     provider: CcusageProvider,
     plugin_id: &str,
+    timeout: Duration,
 ) -> Option<String> {
     let args = ccusage_runner_args(kind, opts, provider);
     let enriched_path = ccusage_enriched_path();
@@ -1887,7 +2265,6 @@ fn run_ccusage_with_runner(
         })
     });
 
-    let timeout = std::time::Duration::from_secs(CCUSAGE_TIMEOUT_SECS);
     let start = std::time::Instant::now();
     loop {
         match child.try_wait() {
@@ -1930,9 +2307,9 @@ fn run_ccusage_with_runner(
                     let _ = stdout_reader.take().and_then(|reader| reader.join().ok());
                     let _ = stderr_reader.take().and_then(|reader| reader.join().ok());
                     log::warn!(
-                        "[plugin:{}] ccusage timed out after {}s for {}",
+                        "[plugin:{}] ccusage timed out after {}ms for {}",
                         plugin_id,
-                        CCUSAGE_TIMEOUT_SECS,
+                        timeout.as_millis(),
                         ccusage_runner_label(kind)
                     );
                     return None;
@@ -1956,53 +2333,42 @@ fn inject_ccusage<'js>(
     ctx: &Ctx<'js>,
     host: &Object<'js>,
     plugin_id: &str,
+    permissions: &Arc<Permissions>,
+    deadline: ProbeDeadline,
+    jobs: &Rc<HostJobs>,
 ) -> rquickjs::Result<()> {
     let ccusage_obj = Object::new(ctx.clone())?;
     let pid = plugin_id.to_string();
+    let perms = Arc::clone(permissions);
 
     ccusage_obj.set(
         "_queryRaw",
         Function::new(
             ctx.clone(),
-            move |_ctx_inner: Ctx<'_>, opts_json: String| -> rquickjs::Result<String> {
-                let opts: CcusageQueryOpts = match serde_json::from_str(&opts_json) {
-                    Ok(v) => v,
-                    Err(e) => {
-                        log::warn!("[plugin:{}] invalid ccusage opts JSON: {}", pid, e);
-                        CcusageQueryOpts::default()
-                    }
-                };
-                let provider = resolve_ccusage_provider(&opts, &pid);
-                let runners = collect_ccusage_runners();
-                if runners.is_empty() {
-                    log::warn!("[plugin:{}] no package runner found for ccusage query", pid);
-                    return Ok(serde_json::json!({ "status": "no_runner" }).to_string());
-                }
-
-                for (kind, program) in runners {
-                    if let Some(result) =
-                        run_ccusage_with_runner(kind, &program, &opts, provider, &pid)
-                    {
-                        let data: serde_json::Value = match serde_json::from_str(&result) {
-                            Ok(v) => v,
-                            Err(e) => {
-                                log::warn!(
-                                    "[plugin:{}] ccusage normalized payload parse failed: {}",
-                                    pid,
-                                    e
-                                );
-                                continue;
-                            }
-                        };
-                        return Ok(serde_json::json!({ "status": "ok", "data": data }).to_string());
-                    }
-                }
+            move |ctx_inner: Ctx<'_>, opts_json: String| -> rquickjs::Result<String> {
+                ensure_deadline(&ctx_inner, &deadline)?;
+                perms
+                    .check_ccusage()
+                    .map_err(|e| throw_permission_denied(&ctx_inner, e))?;
+                Ok(ccusage_query(&pid, &opts_json, &deadline))
+            },
+        )?,
+    )?;
 
-                log::warn!(
-                    "[plugin:{}] ccusage query failed with all available runners",
-                    pid
-                );
-                Ok(serde_json::json!({ "status": "runner_failed" }).to_string())
+    let pid = plugin_id.to_string();
+    let perms = Arc::clone(permissions);
+    let jobs = Rc::clone(jobs);
+    ccusage_obj.set(
+        "_queryAsyncRaw",
+        Function::new(
+            ctx.clone(),
+            move |ctx_inner: Ctx<'_>, opts_json: String| -> rquickjs::Result<u64> {
+                ensure_deadline(&ctx_inner, &deadline)?;
+                perms
+                    .check_ccusage()
+                    .map_err(|e| throw_permission_denied(&ctx_inner, e))?;
+                let pid = pid.clone();
+                Ok(jobs.spawn(move || Ok(ccusage_query(&pid, &opts_json, &deadline))))
             },
         )?,
     )?;
@@ -2011,13 +2377,60 @@ fn inject_ccusage<'js>(
     Ok(())
 }
 
+/// Run a ccusage query through the first package runner that succeeds. Always returns a
+/// `{ status, data? }` JSON payload.
+fn ccusage_query(pid: &str, opts_json: &str, deadline: &ProbeDeadline) -> String {
+    let opts: CcusageQueryOpts = match serde_json::from_str(opts_json) {
+        Ok(v) => v,
+        Err(e) => {
+            log::warn!("[plugin:{}] invalid ccusage opts JSON: {}", pid, e);
+            CcusageQueryOpts::default()
+        }
+    };
+    let provider = resolve_ccusage_provider(&opts, pid);
+    let runners = collect_ccusage_runners();
+    if runners.is_empty() {
+        log::warn!("[plugin:{}] no package runner found for ccusage query", pid);
+        return serde_json::json!({ "status": "no_runner" }).to_string();
+    }
+
+    for (kind, program) in runners {
+        if deadline.is_expired() {
+            log::warn!("[plugin:{}] ccusage query hit probe deadline", pid);
+            break;
+        }
+        let timeout = deadline.cap(Duration::from_secs(CCUSAGE_TIMEOUT_SECS));
+        if let Some(result) = run_ccusage_with_runner(kind, &program, &opts, provider, pid, timeout)
+        {
+            let data: serde_json::Value = match serde_json::from_str(&result) {
+                Ok(v) => v,
+                Err(e) => {
+                    log::warn!(
+                        "[plugin:{}] ccusage normalized payload parse failed: {}",
+                        pid,
+                        e
+                    );
+                    continue;
+                }
+            };
+            return serde_json::json!({ "status": "ok", "data": data }).to_string();
+        }
+    }
+
+    log::warn!(
+        "[plugin:{}] ccusage query failed with all available runners",
+        pid
+    );
+    serde_json::json!({ "status": "runner_failed" }).to_string()
+}
+
 pub fn patch_ccusage_wrapper(ctx: &rquickjs::Ctx<'_>) -> rquickjs::Result<()> {
     ctx.eval::<(), _>(
         r#"
         (function() {
             var rawFn = __openusage_ctx.host.ccusage._queryRaw;
-            __openusage_ctx.host.ccusage.query = function(opts) {
-                var result = rawFn(JSON.stringify(opts || {}));
+            var rawAsyncFn = __openusage_ctx.host.ccusage._queryAsyncRaw;
+            function parseResult(result) {
                 try {
                     var parsed = JSON.parse(result);
                     if (parsed && typeof parsed === "object" && typeof parsed.status === "string") {
@@ -2025,6 +2438,18 @@ pub fn patch_ccusage_wrapper(ctx: &rquickjs::Ctx<'_>) -> rquickjs::Result<()> {
                     }
                 } catch (e) {}
                 return { status: "runner_failed" };
+            }
+            __openusage_ctx.host.ccusage.query = function(opts) {
+                return parseResult(rawFn(JSON.stringify(opts || {})));
+            };
+            __openusage_ctx.host.ccusage.queryAsync = function(opts) {
+                try {
+                    return __openusage_jobs
+                        .wait(rawAsyncFn(JSON.stringify(opts || {})))
+                        .then(parseResult);
+                } catch (e) {
+                    return Promise.reject(e);
+                }
             };
         })();
         "#
@@ -2036,9 +2461,11 @@ fn inject_keychain<'js>(
     ctx: &Ctx<'js>,
     host: &Object<'js>,
     plugin_id: &str,
+    permissions: &Arc<Permissions>,
 ) -> rquickjs::Result<()> {
     let keychain_obj = Object::new(ctx.clone())?;
     let pid_read = plugin_id.to_string();
+    let perms = Arc::clone(permissions);
 
     keychain_obj.set(
         "readGenericPassword",
@@ -2046,11 +2473,15 @@ fn inject_keychain<'js>(
             ctx.clone(),
             move |ctx_inner: Ctx<'_>, service: String| -> rquickjs::Result<String> {
                 if !cfg!(target_os = "macos") {
-                    return Err(Exception::throw_message(
+                    return Err(throw_coded(
                         &ctx_inner,
+                        ProbeErrorCode::UnsupportedPlatform,
                         "keychain API is only supported on macOS",
                     ));
                 }
+                perms
+                    .check_keychain(&service)
+                    .map_err(|e| throw_permission_denied(&ctx_inner, e))?;
                 log::info!("[plugin:{}] keychain read: service={}", pid_read, service);
                 let output = std::process::Command::new("security")
                     .args(keychain_find_generic_password_args(&service))
@@ -2088,17 +2519,22 @@ fn inject_keychain<'js>(
     )?;
 
     let pid_read_current_user = plugin_id.to_string();
+    let perms = Arc::clone(permissions);
     keychain_obj.set(
         "readGenericPasswordForCurrentUser",
         Function::new(
             ctx.clone(),
             move |ctx_inner: Ctx<'_>, service: String| -> rquickjs::Result<String> {
                 if !cfg!(target_os = "macos") {
-                    return Err(Exception::throw_message(
+                    return Err(throw_coded(
                         &ctx_inner,
+                        ProbeErrorCode::UnsupportedPlatform,
                         "keychain API is only supported on macOS",
                     ));
                 }
+                perms
+                    .check_keychain(&service)
+                    .map_err(|e| throw_permission_denied(&ctx_inner, e))?;
                 let account = current_macos_keychain_account();
                 let args = keychain_find_generic_password_args_for_account(&service, &account);
                 let redacted_account = redact_value(&account);
@@ -2146,17 +2582,22 @@ fn inject_keychain<'js>(
     )?;
 
     let pid_write = plugin_id.to_string();
+    let perms = Arc::clone(permissions);
     keychain_obj.set(
         "writeGenericPassword",
         Function::new(
             ctx.clone(),
             move |ctx_inner: Ctx<'_>, service: String, value: String| -> rquickjs::Result<()> {
                 if !cfg!(target_os = "macos") {
-                    return Err(Exception::throw_message(
+                    return Err(throw_coded(
                         &ctx_inner,
+                        ProbeErrorCode::UnsupportedPlatform,
                         "keychain API is only supported on macOS",
                     ));
                 }
+                perms
+                    .check_keychain(&service)
+                    .map_err(|e| throw_permission_denied(&ctx_inner, e))?;
                 log::info!("[plugin:{}] keychain write: service={}", pid_write, service);
 
                 let mut account_arg: Option<String> = None;
@@ -2220,17 +2661,22 @@ fn inject_keychain<'js>(
     )?;
 
     let pid_write_current_user = plugin_id.to_string();
+    let perms = Arc::clone(permissions);
     keychain_obj.set(
         "writeGenericPasswordForCurrentUser",
         Function::new(
             ctx.clone(),
             move |ctx_inner: Ctx<'_>, service: String, value: String| -> rquickjs::Result<()> {
                 if !cfg!(target_os = "macos") {
-                    return Err(Exception::throw_message(
+                    return Err(throw_coded(
                         &ctx_inner,
+                        ProbeErrorCode::UnsupportedPlatform,
                         "keychain API is only supported on macOS",
                     ));
                 }
+                perms
+                    .check_keychain(&service)
+                    .map_err(|e| throw_permission_denied(&ctx_inner, e))?;
                 let account = current_macos_keychain_account();
                 let args =
                     keychain_add_generic_password_args_for_account(&service, &account, &value);
@@ -2282,95 +2728,73 @@ fn inject_keychain<'js>(
     Ok(())
 }
 
-fn inject_sqlite<'js>(ctx: &Ctx<'js>, host: &Object<'js>) -> rquickjs::Result<()> {
+fn inject_sqlite<'js>(
+    ctx: &Ctx<'js>,
+    host: &Object<'js>,
+    permissions: &Arc<Permissions>,
+    deadline: ProbeDeadline,
+    jobs: &Rc<HostJobs>,
+) -> rquickjs::Result<()> {
     let sqlite_obj = Object::new(ctx.clone())?;
 
+    let perms = Arc::clone(permissions);
     sqlite_obj.set(
         "query",
         Function::new(
             ctx.clone(),
             move |ctx_inner: Ctx<'_>, db_path: String, sql: String| -> rquickjs::Result<String> {
-                if sql.lines().any(|line| line.trim_start().starts_with('.')) {
-                    return Err(Exception::throw_message(
-                        &ctx_inner,
-                        "sqlite3 dot-commands are not allowed",
-                    ));
-                }
-                let expanded = expand_path(&db_path);
-
-                // Prefer a normal read-only open so WAL contents are visible (common for app state DBs).
-                // Fall back to immutable=1 to bypass WAL/SHM lock issues after macOS sleep.
-                let primary = std::process::Command::new("sqlite3")
-                    .args(["-readonly", "-json", &expanded, &sql])
-                    .output()
-                    .map_err(|e| {
-                        Exception::throw_message(&ctx_inner, &format!("sqlite3 exec failed: {}", e))
-                    })?;
-
-                if primary.status.success() {
-                    return Ok(String::from_utf8_lossy(&primary.stdout).to_string());
-                }
-
-                // Percent-encode special chars for valid URI (% must be first!)
-                let encoded = expanded
-                    .replace('%', "%25")
-                    .replace(' ', "%20")
-                    .replace('#', "%23")
-                    .replace('?', "%3F");
-                let uri_path = format!("file:{}?immutable=1", encoded);
-                let fallback = std::process::Command::new("sqlite3")
-                    .args(["-readonly", "-json", &uri_path, &sql])
-                    .output()
-                    .map_err(|e| {
-                        Exception::throw_message(&ctx_inner, &format!("sqlite3 exec failed: {}", e))
-                    })?;
-
-                if !fallback.status.success() {
-                    let stderr_primary = String::from_utf8_lossy(&primary.stderr);
-                    let stderr_fallback = String::from_utf8_lossy(&fallback.stderr);
-                    return Err(Exception::throw_message(
-                        &ctx_inner,
-                        &format!(
-                            "sqlite3 error: {} (fallback: {})",
-                            stderr_primary.trim(),
-                            stderr_fallback.trim()
-                        ),
-                    ));
-                }
-
-                Ok(String::from_utf8_lossy(&fallback.stdout).to_string())
+                ensure_deadline(&ctx_inner, &deadline)?;
+                perms
+                    .check_sqlite(&db_path)
+                    .map_err(|e| throw_permission_denied(&ctx_inner, e))?;
+                sqlite_query(&db_path, &sql).map_err(|e| e.throw(&ctx_inner))
             },
         )?,
     )?;
 
+    let perms = Arc::clone(permissions);
     sqlite_obj.set(
         "exec",
         Function::new(
             ctx.clone(),
             move |ctx_inner: Ctx<'_>, db_path: String, sql: String| -> rquickjs::Result<()> {
-                if sql.lines().any(|line| line.trim_start().starts_with('.')) {
-                    return Err(Exception::throw_message(
-                        &ctx_inner,
-                        "sqlite3 dot-commands are not allowed",
-                    ));
-                }
-                let expanded = expand_path(&db_path);
-                let output = std::process::Command::new("sqlite3")
-                    .args([&expanded, &sql])
-                    .output()
-                    .map_err(|e| {
-                        Exception::throw_message(&ctx_inner, &format!("sqlite3 exec failed: {}", e))
-                    })?;
+                ensure_deadline(&ctx_inner, &deadline)?;
+                perms
+                    .check_sqlite(&db_path)
+                    .map_err(|e| throw_permission_denied(&ctx_inner, e))?;
+                sqlite_exec(&db_path, &sql).map_err(|e| e.throw(&ctx_inner))
+            },
+        )?,
+    )?;
 
-                if !output.status.success() {
-                    let stderr = String::from_utf8_lossy(&output.stderr);
-                    return Err(Exception::throw_message(
-                        &ctx_inner,
-                        &format!("sqlite3 error: {}", stderr.trim()),
-                    ));
-                }
+    let perms = Arc::clone(permissions);
+    let query_jobs = Rc::clone(jobs);
+    sqlite_obj.set(
+        "_queryAsyncRaw",
+        Function::new(
+            ctx.clone(),
+            move |ctx_inner: Ctx<'_>, db_path: String, sql: String| -> rquickjs::Result<u64> {
+                ensure_deadline(&ctx_inner, &deadline)?;
+                perms
+                    .check_sqlite(&db_path)
+                    .map_err(|e| throw_permission_denied(&ctx_inner, e))?;
+                Ok(query_jobs.spawn(move || sqlite_query(&db_path, &sql)))
+            },
+        )?,
+    )?;
 
-                Ok(())
+    let perms = Arc::clone(permissions);
+    let exec_jobs = Rc::clone(jobs);
+    sqlite_obj.set(
+        "_execAsyncRaw",
+        Function::new(
+            ctx.clone(),
+            move |ctx_inner: Ctx<'_>, db_path: String, sql: String| -> rquickjs::Result<u64> {
+                ensure_deadline(&ctx_inner, &deadline)?;
+                perms
+                    .check_sqlite(&db_path)
+                    .map_err(|e| throw_permission_denied(&ctx_inner, e))?;
+                Ok(exec_jobs.spawn(move || sqlite_exec(&db_path, &sql).map(|_| String::new())))
             },
         )?,
     )?;
@@ -2379,6 +2803,96 @@ fn inject_sqlite<'js>(ctx: &Ctx<'js>, host: &Object<'js>) -> rquickjs::Result<()
     Ok(())
 }
 
+fn sqlite_query(db_path: &str, sql: &str) -> Result<String, HostCallError> {
+    if sql.lines().any(|line| line.trim_start().starts_with('.')) {
+        return Err(HostCallError::new("sqlite3 dot-commands are not allowed"));
+    }
+    let expanded = expand_path(db_path);
+
+    // Prefer a normal read-only open so WAL contents are visible (common for app state DBs).
+    // Fall back to immutable=1 to bypass WAL/SHM lock issues after macOS sleep.
+    let primary = sqlite3_command()
+        .args(["-readonly", "-json", expanded.as_str(), sql])
+        .output()
+        .map_err(|e| HostCallError::new(format!("sqlite3 exec failed: {}", e)))?;
+
+    if primary.status.success() {
+        return Ok(String::from_utf8_lossy(&primary.stdout).to_string());
+    }
+
+    // Percent-encode special chars for valid URI (% must be first!)
+    let encoded = expanded
+        .replace('%', "%25")
+        .replace(' ', "%20")
+        .replace('#', "%23")
+        .replace('?', "%3F");
+    let uri_path = format!("file:{}?immutable=1", encoded);
+    let fallback = sqlite3_command()
+        .args(["-readonly", "-json", uri_path.as_str(), sql])
+        .output()
+        .map_err(|e| HostCallError::new(format!("sqlite3 exec failed: {}", e)))?;
+
+    if !fallback.status.success() {
+        let stderr_primary = String::from_utf8_lossy(&primary.stderr);
+        let stderr_fallback = String::from_utf8_lossy(&fallback.stderr);
+        return Err(HostCallError::new(format!(
+            "sqlite3 error: {} (fallback: {})",
+            stderr_primary.trim(),
+            stderr_fallback.trim()
+        )));
+    }
+
+    Ok(String::from_utf8_lossy(&fallback.stdout).to_string())
+}
+
+fn sqlite_exec(db_path: &str, sql: &str) -> Result<(), HostCallError> {
+    if sql.lines().any(|line| line.trim_start().starts_with('.')) {
+        return Err(HostCallError::new("sqlite3 dot-commands are not allowed"));
+    }
+    let expanded = expand_path(db_path);
+    let output = sqlite3_command()
+        .args([expanded.as_str(), sql])
+        .output()
+        .map_err(|e| HostCallError::new(format!("sqlite3 exec failed: {}", e)))?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(HostCallError::new(format!(
+            "sqlite3 error: {}",
+            stderr.trim()
+        )));
+    }
+
+    Ok(())
+}
+
+pub fn patch_sqlite_wrapper(ctx: &rquickjs::Ctx<'_>) -> rquickjs::Result<()> {
+    ctx.eval::<(), _>(
+        r#"
+        (function() {
+            var sqlite = __openusage_ctx.host.sqlite;
+            var queryAsyncRaw = sqlite._queryAsyncRaw;
+            var execAsyncRaw = sqlite._execAsyncRaw;
+            sqlite.queryAsync = function(dbPath, sql) {
+                try {
+                    return __openusage_jobs.wait(queryAsyncRaw(dbPath, sql));
+                } catch (e) {
+                    return Promise.reject(e);
+                }
+            };
+            sqlite.execAsync = function(dbPath, sql) {
+                try {
+                    return __openusage_jobs.wait(execAsyncRaw(dbPath, sql)).then(function() {});
+                } catch (e) {
+                    return Promise.reject(e);
+                }
+            };
+        })();
+        "#
+        .as_bytes(),
+    )
+}
+
 fn iso_now() -> String {
     time::OffsetDateTime::now_utc()
         .format(&time::format_description::well_known::Rfc3339)
@@ -2388,7 +2902,7 @@ fn iso_now() -> String {
         })
 }
 
-fn expand_path(path: &str) -> String {
+pub(crate) fn expand_path(path: &str) -> String {
     if path == "~" {
         if let Some(home) = dirs::home_dir() {
             return home.to_string_lossy().to_string();
@@ -2405,6 +2919,7 @@ fn expand_path(path: &str) -> String {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::plugin_engine::manifest::Capabilities;
     use rquickjs::{Context, Function, Object, Runtime};
 
     fn encrypt_aes_256_gcm_envelope_for_test(key: &[u8], plaintext: &str) -> String {
@@ -2426,6 +2941,23 @@ mod tests {
         )
     }
 
+    fn test_jobs() -> Rc<HostJobs> {
+        Rc::new(HostJobs::new())
+    }
+
+    fn test_deadline() -> ProbeDeadline {
+        ProbeDeadline::after(Duration::from_secs(30))
+    }
+
+    fn test_permissions(env: &[String]) -> Arc<Permissions> {
+        Arc::new(Permissions::new(
+            "test",
+            env,
+            &Capabilities::default(),
+            &std::env::temp_dir(),
+        ))
+    }
+
     fn node_generated_aes_256_gcm_vector_for_test() -> (&'static str, &'static str, &'static str) {
         (
             "CwsLCwsLCwsLCwsLCwsLCwsLCwsLCwsLCwsLCwsLCws=",
@@ -2578,7 +3110,17 @@ mod tests {
         let ctx = Context::full(&rt).expect("context");
         ctx.with(|ctx| {
             let app_data = std::env::temp_dir();
-            inject_host_api(&ctx, "test", &app_data, "0.0.0").expect("inject host api");
+            inject_host_api(
+                &ctx,
+                "test",
+                &app_data,
+                "0.0.0",
+                &serde_json::Map::new(),
+                &test_permissions(&[]),
+                test_deadline(),
+                &test_jobs(),
+            )
+            .expect("inject host api");
             let globals = ctx.globals();
             let probe_ctx: Object = globals.get("__openusage_ctx").expect("probe ctx");
             let host: Object = probe_ctx.get("host").expect("host");
@@ -2588,6 +3130,137 @@ mod tests {
         });
     }
 
+    #[test]
+    fn host_api_features_are_all_provided() {
+        let rt = Runtime::new().expect("runtime");
+        let ctx = Context::full(&rt).expect("context");
+        ctx.with(|ctx| {
+            let app_data = std::env::temp_dir();
+            inject_host_api(
+                &ctx,
+                "test",
+                &app_data,
+                "0.0.0",
+                &serde_json::Map::new(),
+                &test_permissions(&[]),
+                test_deadline(),
+                &test_jobs(),
+            )
+            .expect("inject host api");
+            patch_http_wrapper(&ctx).expect("patch http");
+            patch_ls_wrapper(&ctx).expect("patch ls");
+            patch_sqlite_wrapper(&ctx).expect("patch sqlite");
+            patch_ccusage_wrapper(&ctx).expect("patch ccusage");
+            let globals = ctx.globals();
+            let probe_ctx: Object = globals.get("__openusage_ctx").expect("probe ctx");
+            let host: Object = probe_ctx.get("host").expect("host");
+            for feature in HOST_API_FEATURES {
+                let (namespace, name) = feature.split_once('.').expect("namespace.function");
+                let object: Object = host.get(namespace).expect(namespace);
+                let _function: Function = object
+                    .get(name)
+                    .unwrap_or_else(|_| panic!("{} is not a function", feature));
+            }
+        });
+    }
+
+    fn fake_machine(present: &'static [&'static str]) -> impl Fn(&str) -> bool {
+        move |program: &str| present.contains(&program)
+    }
+
+    #[test]
+    fn host_capabilities_follow_platform_and_installed_tools() {
+        let everything = fake_machine(&[
+            "/usr/bin/security",
+            "/bin/ps",
+            "/usr/sbin/lsof",
+            "sqlite3",
+            "/opt/homebrew/bin/bunx",
+            "npx",
+        ]);
+
+        let mac = detect_host_capabilities_with("macos", &everything, true);
+        assert!(mac.keychain && mac.ls && mac.sqlite && mac.proxy);
+        assert_eq!(mac.ccusage.runners, vec!["bunx", "npx"]);
+        assert!(mac.ccusage.available);
+        assert_eq!(mac.apis.len(), HOST_API_FEATURES.len());
+
+        let linux = detect_host_capabilities_with("linux", &everything, false);
+        assert!(!linux.keychain, "keychain is macOS only");
+        assert!(linux.ls && linux.sqlite && !linux.proxy);
+
+        let windows = detect_host_capabilities_with("windows", &everything, false);
+        assert!(!windows.keychain && !windows.ls);
+
+        let bare = detect_host_capabilities_with("macos", fake_machine(&["/bin/ps"]), false);
+        assert!(!bare.keychain && !bare.ls && !bare.sqlite);
+        assert_eq!(
+            bare.ccusage,
+            CcusageCapability {
+                available: false,
+                runners: Vec::new(),
+            }
+        );
+    }
+
+    #[cfg(target_os = "macos")]
+    #[test]
+    fn detected_capabilities_on_macos() {
+        let capabilities = detect_host_capabilities();
+        assert_eq!(capabilities.platform, "macos");
+        assert!(capabilities.keychain);
+        assert!(capabilities.ls);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn detected_capabilities_on_linux() {
+        let capabilities = detect_host_capabilities();
+        assert_eq!(capabilities.platform, "linux");
+        assert!(!capabilities.keychain);
+    }
+
+    #[cfg(target_os = "windows")]
+    #[test]
+    fn detected_capabilities_on_windows() {
+        let capabilities = detect_host_capabilities();
+        assert_eq!(capabilities.platform, "windows");
+        assert!(!capabilities.keychain);
+        assert!(!capabilities.ls);
+    }
+
+    #[test]
+    fn capabilities_are_published_on_ctx_host() {
+        let rt = Runtime::new().expect("runtime");
+        let ctx = Context::full(&rt).expect("context");
+        ctx.with(|ctx| {
+            let app_data = std::env::temp_dir();
+            inject_host_api(
+                &ctx,
+                "test",
+                &app_data,
+                "0.0.0",
+                &serde_json::Map::new(),
+                &test_permissions(&[]),
+                test_deadline(),
+                &test_jobs(),
+            )
+            .expect("inject host api");
+            let keychain: bool = ctx
+                .eval("__openusage_ctx.host.capabilities.keychain")
+                .expect("capabilities.keychain");
+            assert_eq!(keychain, cfg!(target_os = "macos"));
+            let platform: String = ctx
+                .eval("__openusage_ctx.host.capabilities.platform")
+                .expect("capabilities.platform");
+            assert_eq!(platform, std::env::consts::OS);
+            let has_query: bool = ctx
+                .eval("__openusage_ctx.host.capabilities.apis.indexOf('ccusage.query') >= 0")
+                .expect("capabilities.apis");
+            assert!(has_query);
+        });
+    }
+
     #[test]
     fn crypto_api_decrypts_node_generated_envelope_from_js() {
         let (key_b64, envelope, expected_plaintext) = node_generated_aes_256_gcm_vector_for_test();
@@ -2595,7 +3268,17 @@ mod tests {
         let ctx = Context::full(&rt).expect("context");
         ctx.with(|ctx| {
             let app_data = std::env::temp_dir();
-            inject_host_api(&ctx, "test", &app_data, "0.0.0").expect("inject host api");
+            inject_host_api(
+                &ctx,
+                "test",
+                &app_data,
+                "0.0.0",
+                &serde_json::Map::new(),
+                &test_permissions(&[]),
+                test_deadline(),
+                &test_jobs(),
+            )
+            .expect("inject host api");
             let js_expr = format!(
                 r#"__openusage_ctx.host.crypto.decryptAes256Gcm("{}", "{}")"#,
                 envelope, key_b64
@@ -2605,13 +3288,70 @@ mod tests {
         });
     }
 
+    #[test]
+    fn probe_deadline_caps_host_call_timeouts() {
+        let deadline = ProbeDeadline::after(Duration::from_secs(5));
+        assert!(deadline.cap(Duration::from_secs(60)) <= Duration::from_secs(5));
+        assert_eq!(
+            deadline.cap(Duration::from_millis(10)),
+            Duration::from_millis(10)
+        );
+        assert!(!deadline.is_expired());
+    }
+
+    #[test]
+    fn host_calls_throw_after_probe_deadline() {
+        let rt = Runtime::new().expect("runtime");
+        let ctx = Context::full(&rt).expect("context");
+        ctx.with(|ctx| {
+            let app_data = std::env::temp_dir();
+            let deadline = ProbeDeadline::after(Duration::ZERO);
+            inject_host_api(
+                &ctx,
+                "test",
+                &app_data,
+                "0.0.0",
+                &serde_json::Map::new(),
+                &test_permissions(&[]),
+                deadline,
+                &test_jobs(),
+            )
+            .expect("inject host api");
+            let message: String = ctx
+                .eval(
+                    r#"
+                    (function () {
+                        try {
+                            __openusage_ctx.host.http._requestRaw(JSON.stringify({ url: "http://127.0.0.1:9" }));
+                            return "no error";
+                        } catch (e) {
+                            return String(e && e.code) + ":" + String(e && e.message ? e.message : e);
+                        }
+                    })()
+                    "#,
+                )
+                .expect("eval");
+            assert_eq!(message, "timeout:probe deadline exceeded");
+        });
+    }
+
     #[test]
     fn keychain_api_exposes_write_variants() {
         let rt = Runtime::new().expect("runtime");
         let ctx = Context::full(&rt).expect("context");
         ctx.with(|ctx| {
             let app_data = std::env::temp_dir();
-            inject_host_api(&ctx, "test", &app_data, "0.0.0").expect("inject host api");
+            inject_host_api(
+                &ctx,
+                "test",
+                &app_data,
+                "0.0.0",
+                &serde_json::Map::new(),
+                &test_permissions(&[]),
+                test_deadline(),
+                &test_jobs(),
+            )
+            .expect("inject host api");
             let globals = ctx.globals();
             let probe_ctx: Object = globals.get("__openusage_ctx").expect("probe ctx");
             let host: Object = probe_ctx.get("host").expect("host");
@@ -2644,25 +3384,30 @@ mod tests {
             "CLAUDE_LOCAL_OAUTH_API_BASE",
         ];
 
-        for name in claude_env_vars {
-            assert!(
-                WHITELISTED_ENV_VARS.contains(&name),
-                "{name} must be whitelisted for Claude auth compatibility"
-            );
-        }
+        let allowed_env: Vec<String> = claude_env_vars.iter().map(|s| s.to_string()).collect();
 
         let rt = Runtime::new().expect("runtime");
         let ctx = Context::full(&rt).expect("context");
         ctx.with(|ctx| {
             let app_data = std::env::temp_dir();
-            inject_host_api(&ctx, "test", &app_data, "0.0.0").expect("inject host api");
+            inject_host_api(
+                &ctx,
+                "test",
+                &app_data,
+                "0.0.0",
+                &serde_json::Map::new(),
+                &test_permissions(&allowed_env),
+                test_deadline(),
+                &test_jobs(),
+            )
+            .expect("inject host api");
             let globals = ctx.globals();
             let probe_ctx: Object = globals.get("__openusage_ctx").expect("probe ctx");
             let host: Object = probe_ctx.get("host").expect("host");
             let env: Object = host.get("env").expect("env");
             let get: Function = env.get("get").expect("get");
 
-            for name in WHITELISTED_ENV_VARS {
+            for name in claude_env_vars {
                 let expected = resolve_env_value(name);
                 let value: Option<String> =
                     get.call((name.to_string(),)).expect("get whitelisted var");
@@ -2676,13 +3421,14 @@ mod tests {
                 );
             }
 
-            let blocked: Option<String> = get
-                .call(("__OPENUSAGE_TEST_NOT_WHITELISTED__".to_string(),))
-                .expect("get blocked var");
-            assert!(
-                blocked.is_none(),
-                "non-whitelisted vars must not be exposed"
-            );
+            for name in ["__OPENUSAGE_TEST_NOT_WHITELISTED__", "ZAI_API_KEY"] {
+                let blocked: Option<String> =
+                    get.call((name.to_string(),)).expect("get blocked var");
+                assert!(
+                    blocked.is_none(),
+                    "{name} is not declared and must not be exposed"
+                );
+            }
 
             let js_blocked: Option<String> = ctx
                 .eval(r#"__openusage_ctx.host.env.get("__OPENUSAGE_TEST_NOT_WHITELISTED__")"#)
@@ -2723,7 +3469,17 @@ mod tests {
         let ctx = Context::full(&rt).expect("context");
         ctx.with(|ctx| {
             let app_data = std::env::temp_dir();
-            inject_host_api(&ctx, "test", &app_data, "0.0.0").expect("inject host api");
+            inject_host_api(
+                &ctx,
+                "test",
+                &app_data,
+                "0.0.0",
+                &serde_json::Map::new(),
+                &test_permissions(&[name.to_string()]),
+                test_deadline(),
+                &test_jobs(),
+            )
+            .expect("inject host api");
             let globals = ctx.globals();
             let probe_ctx: Object = globals.get("__openusage_ctx").expect("probe ctx");
             let host: Object = probe_ctx.get("host").expect("host");
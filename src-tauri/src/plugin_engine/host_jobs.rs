@@ -0,0 +1,220 @@
+//! Background host calls for the promise-returning host APIs.
+//!
+//! An async host function starts its work on the shared Tokio runtime and immediately returns a
+//! job id to JS, where the wrapper parks a promise under that id. The work itself never touches
+//! the JS context; its result comes back over a channel and is handed to JS by the probe's job
+//! loop ([`drive_promise`]) between runs of the QuickJS microtask queue.
+
+use crate::plugin_engine::host_api::{HostCallError, ProbeDeadline};
+use rquickjs::{Ctx, Error, FromJs, Function, Object, Promise};
+use std::cell::Cell;
+use std::sync::OnceLock;
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+
+/// Upper bound on concurrent blocking host calls across all probes in the process.
+const MAX_BLOCKING_THREADS: usize = 16;
+
+pub(crate) type JobResult = Result<String, HostCallError>;
+
+struct JobCompletion {
+    id: u64,
+    result: JobResult,
+}
+
+fn tokio_runtime() -> &'static tokio::runtime::Runtime {
+    static RUNTIME: OnceLock<tokio::runtime::Runtime> = OnceLock::new();
+    RUNTIME.get_or_init(|| {
+        tokio::runtime::Builder::new_multi_thread()
+            .worker_threads(1)
+            .max_blocking_threads(MAX_BLOCKING_THREADS)
+            .thread_name("openusage-host-job")
+            .enable_all()
+            .build()
+            .expect("failed to start host job runtime")
+    })
+}
+
+/// Per-probe set of in-flight host jobs.
+pub struct HostJobs {
+    next_id: Cell<u64>,
+    in_flight: Cell<usize>,
+    tx: Sender<JobCompletion>,
+    rx: Receiver<JobCompletion>,
+}
+
+impl HostJobs {
+    pub fn new() -> Self {
+        let (tx, rx) = mpsc::channel();
+        Self {
+            next_id: Cell::new(1),
+            in_flight: Cell::new(0),
+            tx,
+            rx,
+        }
+    }
+
+    /// Run `work` on the blocking pool and return the id JS uses to wait for it.
+    pub(crate) fn spawn<F>(&self, work: F) -> u64
+    where
+        F: FnOnce() -> JobResult + Send + 'static,
+    {
+        let id = self.next_id.get();
+        self.next_id.set(id + 1);
+        self.in_flight.set(self.in_flight.get() + 1);
+        let tx = self.tx.clone();
+        tokio_runtime().spawn_blocking(move || {
+            // The probe may already be gone; its receiver being dropped is fine.
+            let _ = tx.send(JobCompletion { id, result: work() });
+        });
+        id
+    }
+
+    fn in_flight(&self) -> usize {
+        self.in_flight.get()
+    }
+
+    fn wait_next(&self, deadline: &ProbeDeadline) -> Option<JobCompletion> {
+        match self.rx.recv_timeout(deadline.remaining()) {
+            Ok(completion) => {
+                self.in_flight.set(self.in_flight.get().saturating_sub(1));
+                Some(completion)
+            }
+            Err(RecvTimeoutError::Timeout) | Err(RecvTimeoutError::Disconnected) => None,
+        }
+    }
+}
+
+impl Default for HostJobs {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Define the JS half of the job protocol: `__openusage_jobs.wait(id)` returns a promise that
+/// the job loop later settles through `__openusage_jobs.settle`.
+pub fn install_job_bridge(ctx: &Ctx<'_>) -> rquickjs::Result<()> {
+    ctx.eval::<(), _>(
+        r#"
+        (function() {
+            var pending = {};
+            globalThis.__openusage_jobs = {
+                wait: function(id) {
+                    return new Promise(function(resolve, reject) {
+                        pending[id] = { resolve: resolve, reject: reject };
+                    });
+                },
+                settle: function(id, ok, payload, code) {
+                    var entry = pending[id];
+                    if (!entry) return;
+                    delete pending[id];
+                    if (ok) {
+                        entry.resolve(payload);
+                        return;
+                    }
+                    var err = new Error(payload);
+                    if (code) err.code = code;
+                    entry.reject(err);
+                }
+            };
+        })();
+        "#
+        .as_bytes(),
+    )
+}
+
+fn settle<'js>(ctx: &Ctx<'js>, completion: JobCompletion) -> rquickjs::Result<()> {
+    let jobs: Object = ctx.globals().get("__openusage_jobs")?;
+    let settle_fn: Function = jobs.get("settle")?;
+    match completion.result {
+        Ok(payload) => settle_fn.call((completion.id, true, payload, ())),
+        Err(err) => settle_fn.call((
+            completion.id,
+            false,
+            err.message,
+            err.code.map(|code| code.as_str()),
+        )),
+    }
+}
+
+/// Drive `promise` to completion: run queued microtasks, and whenever JS is idle but host jobs
+/// are still in flight, block for the next one to finish and settle it.
+///
+/// Returns `Error::WouldBlock` when the promise can never settle (nothing queued, nothing in
+/// flight) or when the deadline passes while waiting on host jobs.
+pub fn drive_promise<'js, T: FromJs<'js>>(
+    ctx: &Ctx<'js>,
+    promise: &Promise<'js>,
+    jobs: &HostJobs,
+    deadline: &ProbeDeadline,
+) -> rquickjs::Result<T> {
+    loop {
+        match promise.finish::<T>() {
+            Err(Error::WouldBlock) => {}
+            other => return other,
+        }
+        if jobs.in_flight() == 0 || deadline.is_expired() {
+            return Err(Error::WouldBlock);
+        }
+        match jobs.wait_next(deadline) {
+            Some(completion) => settle(ctx, completion)?,
+            None => return Err(Error::WouldBlock),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::plugin_engine::runtime::ProbeErrorCode;
+    use rquickjs::{Context, Runtime};
+    use std::time::Duration;
+
+    #[test]
+    fn drive_promise_settles_jobs_in_js() {
+        let rt = Runtime::new().expect("runtime");
+        let ctx = Context::full(&rt).expect("context");
+        let jobs = HostJobs::new();
+        let deadline = ProbeDeadline::after(Duration::from_secs(5));
+        ctx.with(|ctx| {
+            install_job_bridge(&ctx).expect("bridge");
+            let ok = jobs.spawn(|| Ok("done".to_string()));
+            let failed = jobs.spawn(|| {
+                Err(HostCallError {
+                    code: Some(ProbeErrorCode::RateLimited),
+                    message: "slow down".to_string(),
+                })
+            });
+            let promise: Promise = ctx
+                .eval(format!(
+                    r#"
+                    Promise.all([
+                        __openusage_jobs.wait({ok}),
+                        __openusage_jobs.wait({failed}).catch(function (e) {{
+                            return e.code + ":" + e.message;
+                        }}),
+                    ]).then(function (values) {{ return {{ values: values.join("|") }}; }})
+                    "#
+                ))
+                .expect("eval");
+            let result: Object = drive_promise(&ctx, &promise, &jobs, &deadline).expect("settled");
+            let values: String = result.get("values").expect("values");
+            assert_eq!(values, "done|rate_limited:slow down");
+        });
+    }
+
+    #[test]
+    fn drive_promise_reports_promise_that_can_never_settle() {
+        let rt = Runtime::new().expect("runtime");
+        let ctx = Context::full(&rt).expect("context");
+        let jobs = HostJobs::new();
+        let deadline = ProbeDeadline::after(Duration::from_secs(5));
+        ctx.with(|ctx| {
+            install_job_bridge(&ctx).expect("bridge");
+            let promise: Promise = ctx.eval("new Promise(function () {})").expect("eval");
+            assert!(matches!(
+                drive_promise::<Object>(&ctx, &promise, &jobs, &deadline),
+                Err(Error::WouldBlock)
+            ));
+        });
+    }
+}
@@ -0,0 +1,306 @@
+//! Turns plugin icon files into data URLs.
+//!
+//! The format is sniffed from the bytes rather than the extension, icons over
+//! `MAX_ICON_BYTES` are rejected, and SVGs have active content and external references removed
+//! before they reach the webview.
+
+use base64::{Engine, engine::general_purpose::STANDARD};
+use regex_lite::{Captures, Regex};
+use std::path::Path;
+
+pub const MAX_ICON_BYTES: usize = 256 * 1024;
+
+const PNG_MAGIC: &[u8] = b"\x89PNG\r\n\x1a\n";
+
+/// Elements dropped with their content.
+/// Animation elements are blocked too: they can rewrite an allowed `href` after sanitizing.
+const BLOCKED_ELEMENTS: [&str; 9] = [
+    "script",
+    "foreignObject",
+    "iframe",
+    "embed",
+    "object",
+    "set",
+    "animate",
+    "animateMotion",
+    "animateTransform",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IconFormat {
+    Svg,
+    Png,
+    Webp,
+}
+
+impl IconFormat {
+    pub fn mime(self) -> &'static str {
+        match self {
+            IconFormat::Svg => "image/svg+xml",
+            IconFormat::Png => "image/png",
+            IconFormat::Webp => "image/webp",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum IconError {
+    /// Absent, unreadable, or outside the plugin directory.
+    Missing(String),
+    TooLarge(String, usize),
+    Unsupported(String),
+}
+
+impl IconError {
+    /// Stable code used by the validator.
+    pub fn code(&self) -> &'static str {
+        match self {
+            IconError::Missing(_) => "icon_missing",
+            IconError::TooLarge(..) => "icon_too_large",
+            IconError::Unsupported(_) => "icon_unsupported",
+        }
+    }
+}
+
+impl std::fmt::Display for IconError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            IconError::Missing(icon) => {
+                write!(
+                    f,
+                    "icon '{}' does not exist inside the plugin directory",
+                    icon
+                )
+            }
+            IconError::TooLarge(icon, size) => write!(
+                f,
+                "icon '{}' is {} bytes; the limit is {}",
+                icon, size, MAX_ICON_BYTES
+            ),
+            IconError::Unsupported(icon) => {
+                write!(f, "icon '{}' is not an SVG, PNG or WebP image", icon)
+            }
+        }
+    }
+}
+
+impl std::error::Error for IconError {}
+
+/// Read `relative` from `plugin_dir` and return it as a data URL.
+pub fn load_icon(plugin_dir: &Path, relative: &str) -> Result<String, IconError> {
+    let missing = || IconError::Missing(relative.to_string());
+    if Path::new(relative).is_absolute() {
+        return Err(missing());
+    }
+    let root = plugin_dir.canonicalize().map_err(|_| missing())?;
+    let path = plugin_dir
+        .join(relative)
+        .canonicalize()
+        .map_err(|_| missing())?;
+    if !path.starts_with(&root) || !path.is_file() {
+        return Err(missing());
+    }
+    let size = std::fs::metadata(&path).map_err(|_| missing())?.len() as usize;
+    if size > MAX_ICON_BYTES {
+        return Err(IconError::TooLarge(relative.to_string(), size));
+    }
+    let bytes = std::fs::read(&path).map_err(|_| missing())?;
+    icon_data_url(&bytes).ok_or_else(|| IconError::Unsupported(relative.to_string()))
+}
+
+/// Data URL for icon `bytes`, or `None` if the format is not supported.
+pub fn icon_data_url(bytes: &[u8]) -> Option<String> {
+    let format = sniff(bytes)?;
+    let encoded = match format {
+        IconFormat::Svg => {
+            let text = std::str::from_utf8(bytes).ok()?;
+            STANDARD.encode(sanitize_svg(text))
+        }
+        IconFormat::Png | IconFormat::Webp => STANDARD.encode(bytes),
+    };
+    Some(format!("data:{};base64,{}", format.mime(), encoded))
+}
+
+pub fn sniff(bytes: &[u8]) -> Option<IconFormat> {
+    if bytes.starts_with(PNG_MAGIC) {
+        return Some(IconFormat::Png);
+    }
+    if bytes.len() >= 12 && &bytes[..4] == b"RIFF" && &bytes[8..12] == b"WEBP" {
+        return Some(IconFormat::Webp);
+    }
+    let head = &bytes[..bytes.len().min(1024)];
+    let head = head.strip_prefix(b"\xEF\xBB\xBF").unwrap_or(head);
+    let text = String::from_utf8_lossy(head).to_ascii_lowercase();
+    let text = text.trim_start();
+    if text.starts_with('<') && text.contains("<svg") {
+        return Some(IconFormat::Svg);
+    }
+    None
+}
+
+/// Remove scripts, embedded documents, event handlers, DTDs and references to anything outside
+/// the document. Internal `#id` references and inline raster `data:` images are kept.
+pub fn sanitize_svg(svg: &str) -> String {
+    let mut out = Regex::new(r"(?is)<!DOCTYPE[^\[>]*(\[.*?\])?\s*>")
+        .expect("doctype regex")
+        .replace_all(svg, "")
+        .into_owned();
+    for element in BLOCKED_ELEMENTS {
+        let paired =
+            Regex::new(&format!(r"(?is)<{0}\b.*?</{0}\s*>", element)).expect("element regex");
+        let single = Regex::new(&format!(r"(?is)</?{}\b[^>]*>", element)).expect("element regex");
+        out = paired.replace_all(&out, "").into_owned();
+        out = single.replace_all(&out, "").into_owned();
+    }
+    out = Regex::new(r#"(?i)\s+on[a-z]+\s*=\s*("[^"]*"|'[^']*'|[^\s/>]+)"#)
+        .expect("handler regex")
+        .replace_all(&out, "")
+        .into_owned();
+    // Unquoted or malformed values are dropped along with external ones.
+    out = Regex::new(r#"(?i)\s+((xlink:)?href|src)\s*=\s*("([^"]*)"|'([^']*)'|([^\s>]*[^\s/>])?)"#)
+        .expect("href regex")
+        .replace_all(&out, |caps: &Captures| {
+            let value = caps
+                .get(4)
+                .or_else(|| caps.get(5))
+                .map_or("", |m| m.as_str());
+            if is_local_reference(value) {
+                caps[0].to_string()
+            } else {
+                String::new()
+            }
+        })
+        .into_owned();
+    out = Regex::new(r#"(?i)url\(\s*['"]?([^)'"]*)['"]?\s*\)"#)
+        .expect("url regex")
+        .replace_all(&out, |caps: &Captures| {
+            if is_local_reference(&caps[1]) {
+                caps[0].to_string()
+            } else {
+                "none".to_string()
+            }
+        })
+        .into_owned();
+    Regex::new(r"(?i)@import[^;]*;?")
+        .expect("import regex")
+        .replace_all(&out, "")
+        .into_owned()
+}
+
+fn is_local_reference(value: &str) -> bool {
+    let value = value.trim();
+    value.starts_with('#')
+        || ["png", "jpeg", "gif", "webp"]
+            .iter()
+            .any(|kind| value.starts_with(&format!("data:image/{};", kind)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sniffs_formats_from_bytes() {
+        let mut png = PNG_MAGIC.to_vec();
+        png.extend_from_slice(b"rest");
+        assert_eq!(sniff(&png), Some(IconFormat::Png));
+        assert_eq!(sniff(b"RIFF\x10\0\0\0WEBPVP8 "), Some(IconFormat::Webp));
+        assert_eq!(
+            sniff(
+                b"\xEF\xBB\xBF<?xml version=\"1.0\"?>\n<svg xmlns=\"http://www.w3.org/2000/svg\"/>"
+            ),
+            Some(IconFormat::Svg)
+        );
+        assert_eq!(sniff(b"GIF89a"), None);
+        assert_eq!(sniff(b"just text mentioning <svg"), None);
+
+        let url = icon_data_url(&png).expect("png url");
+        assert!(url.starts_with("data:image/png;base64,"));
+    }
+
+    #[test]
+    fn sanitize_strips_active_content_and_external_references() {
+        let svg = r##"<!DOCTYPE svg [<!ENTITY x SYSTEM "file:///etc/passwd">]>
+<svg xmlns="http://www.w3.org/2000/svg" onload="alert(1)">
+  <script>alert(2)</script>
+  <script href="https://evil.example/x.js"/>
+  <foreignObject><iframe src="https://evil.example"></iframe></foreignObject>
+  <style>@import url(https://evil.example/a.css); .a { fill: url(#grad) }</style>
+  <use href="#shape" />
+  <use xlink:href="https://evil.example/sprite.svg#icon" />
+  <image href="data:image/png;base64,AAAA" />
+  <rect style="fill: url('https://evil.example/p.png')" onclick='x()' />
+</svg>"##;
+        let clean = sanitize_svg(svg);
+        for banned in [
+            "DOCTYPE",
+            "ENTITY",
+            "script",
+            "alert",
+            "foreignObject",
+            "iframe",
+            "evil",
+            "onload",
+            "onclick",
+            "@import",
+        ] {
+            assert!(!clean.contains(banned), "{} survived:\n{}", banned, clean);
+        }
+        assert!(clean.contains(r##"<use href="#shape" />"##), "{}", clean);
+        assert!(clean.contains("url(#grad)"), "{}", clean);
+        assert!(clean.contains("data:image/png;base64,AAAA"), "{}", clean);
+        assert!(clean.contains("fill: none"), "{}", clean);
+    }
+
+    #[test]
+    fn sanitize_strips_animations_and_unquoted_references() {
+        let svg = r##"<svg xmlns="http://www.w3.org/2000/svg">
+  <a href="#ok"><set attributeName="href" to="https://evil.example"/></a>
+  <use href="#a"><animate attributeName="href" values="https://evil.example"></animate></use>
+  <g><animateTransform attributeName="transform" type="rotate"/></g>
+  <path><animateMotion dur="1s" path="M0,0"/></path>
+  <use href=https://evil.example/sprite.svg#icon />
+  <image src=#local/>
+  <use xlink:href="https://evil.example/unterminated />
+</svg>"##;
+        let clean = sanitize_svg(svg);
+        for banned in ["<set", "<animate", "attributeName", "evil", "src="] {
+            assert!(!clean.contains(banned), "{} survived:\n{}", banned, clean);
+        }
+        assert!(clean.contains(r##"<a href="#ok">"##), "{}", clean);
+        assert!(clean.contains("<image/>"), "{}", clean);
+    }
+
+    #[test]
+    fn load_icon_enforces_location_size_and_format() {
+        let dir = std::env::temp_dir().join(format!("openusage-icon-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).expect("create dir");
+        std::fs::write(dir.join("icon.svg"), "<svg><script>x</script></svg>").expect("write svg");
+        std::fs::write(dir.join("icon.gif"), "GIF89a").expect("write gif");
+        std::fs::write(dir.join("huge.png"), vec![0_u8; MAX_ICON_BYTES + 1]).expect("write huge");
+
+        let url = load_icon(&dir, "icon.svg").expect("svg icon");
+        let encoded = url
+            .strip_prefix("data:image/svg+xml;base64,")
+            .expect("svg mime");
+        assert_eq!(STANDARD.decode(encoded).unwrap(), b"<svg></svg>");
+
+        assert_eq!(
+            load_icon(&dir, "icon.gif").unwrap_err().code(),
+            "icon_unsupported"
+        );
+        assert_eq!(
+            load_icon(&dir, "huge.png").unwrap_err().code(),
+            "icon_too_large"
+        );
+        assert_eq!(
+            load_icon(&dir, "nope.svg").unwrap_err().code(),
+            "icon_missing"
+        );
+        assert_eq!(
+            load_icon(&dir, "../icon.svg").unwrap_err().code(),
+            "icon_missing"
+        );
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
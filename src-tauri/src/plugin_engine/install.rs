@@ -0,0 +1,668 @@
+//! Installing, upgrading and removing plugins in the plugins directory.
+//!
+//! A plugin can come from a directory, a `.zip` or a `.tar.gz`/`.tgz` archive. It is unpacked
+//! into a staging directory next to the installed plugins, validated with the same rules used
+//! at startup and then renamed into place. The version it replaces (or the one being
+//! uninstalled) is moved to `.previous/<id>` so it can be rolled back.
+
+use crate::plugin_engine::manifest::{self, LoadedPlugin};
+use serde::Serialize;
+use std::fs::File;
+use std::io::{Read, Write};
+use std::path::{Component, Path, PathBuf};
+
+const PREVIOUS_DIR_NAME: &str = ".previous";
+const STAGING_PREFIX: &str = ".staging-";
+/// Upper bound on the unpacked size of one plugin, to keep a hostile archive from filling the disk.
+const MAX_UNPACKED_BYTES: u64 = 50 * 1024 * 1024;
+const MAX_ENTRIES: usize = 2_000;
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct InstalledPluginInfo {
+    pub id: String,
+    pub name: String,
+    pub version: String,
+    /// Shipped with the app; uninstalling it is refused.
+    pub bundled: bool,
+    /// A previous version is kept and `rollback_plugin` can restore it.
+    pub has_previous: bool,
+}
+
+/// Plugin ids double as directory names, so keep them to a conservative alphabet.
+pub fn is_valid_plugin_id(id: &str) -> bool {
+    !id.is_empty()
+        && id.len() <= 64
+        && !id.starts_with('.')
+        && id
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || matches!(c, '-' | '_' | '.'))
+}
+
+/// Install (or upgrade) the plugin at `source` into `plugins_dir`. Ids of bundled plugins are
+/// refused unless `force` is set.
+pub fn install_plugin(
+    source: &Path,
+    plugins_dir: &Path,
+    bundled_ids: &[String],
+    force: bool,
+) -> Result<LoadedPlugin, String> {
+    stage_plugin(source, plugins_dir, bundled_ids, force)?.install()
+}
+
+/// A plugin unpacked and validated in a staging directory next to the installed plugins, not
+/// yet moved into place. The staging directory is removed when this is dropped.
+pub struct StagedPlugin {
+    staging: StagingDir,
+    root: PathBuf,
+    plugins_dir: PathBuf,
+    id: String,
+}
+
+struct StagingDir(PathBuf);
+
+impl Drop for StagingDir {
+    fn drop(&mut self) {
+        if let Err(e) = std::fs::remove_dir_all(&self.0)
+            && self.0.exists()
+        {
+            log::warn!("failed to remove {}: {}", self.0.display(), e);
+        }
+    }
+}
+
+/// Unpack and validate the plugin at `source` without touching the installed plugins, so the
+/// slow part of an install can run while they are still in use.
+pub fn stage_plugin(
+    source: &Path,
+    plugins_dir: &Path,
+    bundled_ids: &[String],
+    force: bool,
+) -> Result<StagedPlugin, String> {
+    std::fs::create_dir_all(plugins_dir)
+        .map_err(|e| format!("failed to create {}: {}", plugins_dir.display(), e))?;
+    let staging = plugins_dir.join(format!("{}{}", STAGING_PREFIX, uuid::Uuid::new_v4()));
+    std::fs::create_dir_all(&staging)
+        .map_err(|e| format!("failed to create staging dir: {}", e))?;
+    let staging = StagingDir(staging);
+
+    unpack_source(source, &staging.0)?;
+    let root = find_plugin_root(&staging.0)?;
+    let candidate =
+        manifest::load_single_plugin(&root).map_err(|e| format!("invalid plugin: {}", e))?;
+    let id = candidate.manifest.id.clone();
+    if !is_valid_plugin_id(&id) {
+        return Err(format!(
+            "invalid plugin id {:?}: use lowercase letters, digits, '-', '_' and '.'",
+            id
+        ));
+    }
+    if bundled_ids.contains(&id) && !force {
+        return Err(format!(
+            "plugin id {} belongs to a bundled plugin; use force to replace it",
+            id
+        ));
+    }
+    Ok(StagedPlugin {
+        staging,
+        root,
+        plugins_dir: plugins_dir.to_path_buf(),
+        id,
+    })
+}
+
+impl StagedPlugin {
+    /// Move the staged plugin into place, keeping the version it replaces for rollback.
+    pub fn install(self) -> Result<LoadedPlugin, String> {
+        let installed = move_into_place(&self.root, &self.plugins_dir, &self.id);
+        drop(self.staging);
+        installed
+    }
+}
+
+/// Rename `root` to `plugins_dir/<id>`. If the plugin cannot be renamed or does not load from
+/// its new place, it is moved back to `root` and the replaced version is restored.
+fn move_into_place(root: &Path, plugins_dir: &Path, id: &str) -> Result<LoadedPlugin, String> {
+    let target = plugins_dir.join(id);
+    let backup = plugins_dir.join(PREVIOUS_DIR_NAME).join(id);
+    let had_previous = target.exists();
+    if had_previous {
+        move_to_backup(&target, &backup)?;
+    }
+    let restore_previous = || {
+        if had_previous && let Err(e) = std::fs::rename(&backup, &target) {
+            log::warn!("failed to restore previous version of {}: {}", id, e);
+        }
+    };
+    if let Err(e) = std::fs::rename(root, &target) {
+        restore_previous();
+        return Err(format!("failed to install {}: {}", id, e));
+    }
+
+    let installed = match manifest::load_single_plugin(&target) {
+        Ok(installed) => installed,
+        Err(e) => {
+            if std::fs::rename(&target, root).is_err() {
+                let _ = std::fs::remove_dir_all(&target);
+            }
+            restore_previous();
+            return Err(format!("installed plugin {} failed to load: {}", id, e));
+        }
+    };
+    log::info!(
+        "installed plugin {} {}{}",
+        id,
+        installed.manifest.version,
+        if had_previous {
+            " (previous version kept)"
+        } else {
+            ""
+        }
+    );
+    Ok(installed)
+}
+
+/// Remove an installed plugin, keeping it as the rollback copy.
+pub fn uninstall_plugin(
+    plugin_id: &str,
+    plugins_dir: &Path,
+    bundled_ids: &[String],
+) -> Result<(), String> {
+    if !is_valid_plugin_id(plugin_id) {
+        return Err(format!("invalid plugin id {:?}", plugin_id));
+    }
+    if bundled_ids.iter().any(|id| id == plugin_id) {
+        return Err(format!(
+            "{} is a bundled plugin and cannot be uninstalled",
+            plugin_id
+        ));
+    }
+    let target = plugins_dir.join(plugin_id);
+    if !target.join("plugin.json").exists() {
+        return Err(format!("plugin {} is not installed", plugin_id));
+    }
+    move_to_backup(
+        &target,
+        &plugins_dir.join(PREVIOUS_DIR_NAME).join(plugin_id),
+    )?;
+    log::info!("uninstalled plugin {}", plugin_id);
+    Ok(())
+}
+
+/// Swap the installed plugin with the kept previous version (or restore an uninstalled one).
+pub fn rollback_plugin(plugin_id: &str, plugins_dir: &Path) -> Result<LoadedPlugin, String> {
+    if !is_valid_plugin_id(plugin_id) {
+        return Err(format!("invalid plugin id {:?}", plugin_id));
+    }
+    let target = plugins_dir.join(plugin_id);
+    let backup = plugins_dir.join(PREVIOUS_DIR_NAME).join(plugin_id);
+    if !backup.join("plugin.json").exists() {
+        return Err(format!(
+            "no previous version of {} to roll back to",
+            plugin_id
+        ));
+    }
+
+    let swap = plugins_dir.join(format!("{}{}", STAGING_PREFIX, uuid::Uuid::new_v4()));
+    let had_current = target.exists();
+    if had_current {
+        std::fs::rename(&target, &swap).map_err(|e| format!("rollback failed: {}", e))?;
+    }
+    if let Err(e) = std::fs::rename(&backup, &target) {
+        if had_current {
+            let _ = std::fs::rename(&swap, &target);
+        }
+        return Err(format!("rollback failed: {}", e));
+    }
+    if had_current && let Err(e) = std::fs::rename(&swap, &backup) {
+        log::warn!("failed to keep {} as previous version: {}", plugin_id, e);
+        let _ = std::fs::remove_dir_all(&swap);
+    }
+
+    let restored = manifest::load_single_plugin(&target)
+        .map_err(|e| format!("restored plugin {} failed to load: {}", plugin_id, e))?;
+    log::info!(
+        "rolled back plugin {} to {}",
+        plugin_id,
+        restored.manifest.version
+    );
+    Ok(restored)
+}
+
+pub fn list_installed_plugins(
+    plugins: &[LoadedPlugin],
+    plugins_dir: &Path,
+    bundled_ids: &[String],
+) -> Vec<InstalledPluginInfo> {
+    plugins
+        .iter()
+        .map(|plugin| {
+            let id = &plugin.manifest.id;
+            InstalledPluginInfo {
+                id: id.clone(),
+                name: plugin.manifest.name.clone(),
+                version: plugin.manifest.version.clone(),
+                bundled: bundled_ids.contains(id),
+                has_previous: plugins_dir
+                    .join(PREVIOUS_DIR_NAME)
+                    .join(id)
+                    .join("plugin.json")
+                    .exists(),
+            }
+        })
+        .collect()
+}
+
+fn move_to_backup(target: &Path, backup: &Path) -> Result<(), String> {
+    if backup.exists() {
+        std::fs::remove_dir_all(backup)
+            .map_err(|e| format!("failed to drop old backup {}: {}", backup.display(), e))?;
+    }
+    if let Some(parent) = backup.parent() {
+        std::fs::create_dir_all(parent)
+            .map_err(|e| format!("failed to create {}: {}", parent.display(), e))?;
+    }
+    std::fs::rename(target, backup)
+        .map_err(|e| format!("failed to back up {}: {}", target.display(), e))
+}
+
+/// The plugin may sit at the top of the archive or inside a single wrapping directory.
+fn find_plugin_root(staging: &Path) -> Result<PathBuf, String> {
+    if staging.join("plugin.json").is_file() {
+        return Ok(staging.to_path_buf());
+    }
+    let entries = std::fs::read_dir(staging).map_err(|e| e.to_string())?;
+    let dirs: Vec<PathBuf> = entries
+        .flatten()
+        .filter(|entry| {
+            let name = entry.file_name();
+            let name = name.to_string_lossy();
+            !name.starts_with('.') && name != "__MACOSX"
+        })
+        .map(|entry| entry.path())
+        .collect();
+    match dirs.as_slice() {
+        [dir] if dir.join("plugin.json").is_file() => Ok(dir.clone()),
+        _ => Err("no plugin.json found at the top of the plugin".to_string()),
+    }
+}
+
+fn unpack_source(source: &Path, staging: &Path) -> Result<(), String> {
+    if source.is_dir() {
+        let mut budget = Budget::default();
+        return copy_tree(source, staging, &mut budget);
+    }
+    let name = source
+        .file_name()
+        .map(|name| name.to_string_lossy().to_ascii_lowercase())
+        .unwrap_or_default();
+    let file =
+        File::open(source).map_err(|e| format!("failed to open {}: {}", source.display(), e))?;
+    if name.ends_with(".zip") {
+        unpack_zip(file, staging)
+    } else if name.ends_with(".tar.gz") || name.ends_with(".tgz") {
+        unpack_tar_gz(file, staging)
+    } else {
+        Err("unsupported plugin source: expected a directory, .zip, .tar.gz or .tgz".to_string())
+    }
+}
+
+/// Running totals checked against `MAX_ENTRIES` and `MAX_UNPACKED_BYTES`.
+#[derive(Default)]
+struct Budget {
+    entries: usize,
+    bytes: u64,
+}
+
+impl Budget {
+    fn add_entry(&mut self) -> Result<(), String> {
+        self.entries += 1;
+        if self.entries > MAX_ENTRIES {
+            return Err(format!("plugin has more than {} files", MAX_ENTRIES));
+        }
+        Ok(())
+    }
+
+    /// Copy at most the remaining byte budget from `reader` into a new file at `dest`.
+    fn write_file(&mut self, reader: &mut dyn Read, dest: &Path) -> Result<(), String> {
+        if let Some(parent) = dest.parent() {
+            std::fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+        }
+        let mut out = File::create(dest).map_err(|e| e.to_string())?;
+        let remaining = MAX_UNPACKED_BYTES - self.bytes;
+        let written = std::io::copy(&mut reader.take(remaining + 1), &mut out)
+            .map_err(|e| format!("failed to unpack {}: {}", dest.display(), e))?;
+        self.bytes += written;
+        if self.bytes > MAX_UNPACKED_BYTES {
+            return Err(format!(
+                "plugin unpacks to more than {} MB",
+                MAX_UNPACKED_BYTES / 1024 / 1024
+            ));
+        }
+        out.flush().map_err(|e| e.to_string())
+    }
+}
+
+/// Only plain relative paths may come out of an archive.
+fn safe_relative_path(path: &Path) -> Option<PathBuf> {
+    let mut clean = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::Normal(part) => clean.push(part),
+            Component::CurDir => {}
+            _ => return None,
+        }
+    }
+    (!clean.as_os_str().is_empty()).then_some(clean)
+}
+
+fn unpack_zip(file: File, staging: &Path) -> Result<(), String> {
+    let mut archive = zip::ZipArchive::new(file).map_err(|e| format!("invalid zip: {}", e))?;
+    let mut budget = Budget::default();
+    for index in 0..archive.len() {
+        let mut entry = archive
+            .by_index(index)
+            .map_err(|e| format!("invalid zip: {}", e))?;
+        budget.add_entry()?;
+        let Some(relative) = entry
+            .enclosed_name()
+            .as_deref()
+            .and_then(safe_relative_path)
+        else {
+            return Err(format!("zip entry has an unsafe path: {}", entry.name()));
+        };
+        if entry.is_symlink() {
+            return Err(format!("zip entry is a symlink: {}", entry.name()));
+        }
+        let dest = staging.join(relative);
+        if entry.is_dir() {
+            std::fs::create_dir_all(&dest).map_err(|e| e.to_string())?;
+        } else {
+            budget.write_file(&mut entry, &dest)?;
+        }
+    }
+    Ok(())
+}
+
+fn unpack_tar_gz(file: File, staging: &Path) -> Result<(), String> {
+    let mut archive = tar::Archive::new(flate2::read::GzDecoder::new(file));
+    let mut budget = Budget::default();
+    let entries = archive
+        .entries()
+        .map_err(|e| format!("invalid archive: {}", e))?;
+    for entry in entries {
+        let mut entry = entry.map_err(|e| format!("invalid archive: {}", e))?;
+        budget.add_entry()?;
+        let path = entry
+            .path()
+            .map_err(|e| format!("invalid archive: {}", e))?
+            .into_owned();
+        let Some(relative) = safe_relative_path(&path) else {
+            // A bare "./" root entry is harmless; anything else escaping the root is not.
+            if path.components().all(|c| c == Component::CurDir) {
+                continue;
+            }
+            return Err(format!(
+                "archive entry has an unsafe path: {}",
+                path.display()
+            ));
+        };
+        let dest = staging.join(relative);
+        match entry.header().entry_type() {
+            tar::EntryType::Directory => {
+                std::fs::create_dir_all(&dest).map_err(|e| e.to_string())?
+            }
+            tar::EntryType::Regular | tar::EntryType::Continuous => {
+                budget.write_file(&mut entry, &dest)?
+            }
+            // PAX/GNU metadata records describe the next entry and carry no file of their own.
+            tar::EntryType::XHeader
+            | tar::EntryType::XGlobalHeader
+            | tar::EntryType::GNULongName => {}
+            other => {
+                return Err(format!(
+                    "archive entry {} has unsupported type {:?}",
+                    path.display(),
+                    other
+                ));
+            }
+        }
+    }
+    Ok(())
+}
+
+fn copy_tree(src: &Path, dst: &Path, budget: &mut Budget) -> Result<(), String> {
+    let entries =
+        std::fs::read_dir(src).map_err(|e| format!("failed to read {}: {}", src.display(), e))?;
+    for entry in entries {
+        let entry = entry.map_err(|e| e.to_string())?;
+        let file_type = entry.file_type().map_err(|e| e.to_string())?;
+        let dest = dst.join(entry.file_name());
+        budget.add_entry()?;
+        if file_type.is_symlink() {
+            return Err(format!(
+                "plugin contains a symlink: {}",
+                entry.path().display()
+            ));
+        }
+        if file_type.is_dir() {
+            std::fs::create_dir_all(&dest).map_err(|e| e.to_string())?;
+            copy_tree(&entry.path(), &dest, budget)?;
+        } else {
+            let mut file = File::open(entry.path()).map_err(|e| e.to_string())?;
+            budget.write_file(&mut file, &dest)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    fn temp_dir(label: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!(
+            "openusage-install-{}-{}",
+            label,
+            uuid::Uuid::new_v4()
+        ));
+        std::fs::create_dir_all(&dir).expect("create dir");
+        dir
+    }
+
+    fn plugin_files(id: &str, version: &str) -> Vec<(String, String)> {
+        vec![
+            (
+                "plugin.json".to_string(),
+                format!(
+                    r#"{{"schemaVersion":1,"id":"{}","name":"Demo","version":"{}","entry":"plugin.js","icon":"icon.svg","brandColor":null,"lines":[]}}"#,
+                    id, version
+                ),
+            ),
+            (
+                "plugin.js".to_string(),
+                format!(
+                    "globalThis.__openusage_plugin = {{ id: \"{}\", probe() {{ return {{ lines: [] }} }} }}",
+                    id
+                ),
+            ),
+            ("icon.svg".to_string(), "<svg/>".to_string()),
+        ]
+    }
+
+    fn write_plugin_dir(dir: &Path, id: &str, version: &str) {
+        std::fs::create_dir_all(dir).expect("create plugin dir");
+        for (name, content) in plugin_files(id, version) {
+            std::fs::write(dir.join(name), content).expect("write plugin file");
+        }
+    }
+
+    fn write_zip(path: &Path, entries: &[(String, String)]) {
+        let mut writer = zip::ZipWriter::new(File::create(path).expect("create zip"));
+        for (name, content) in entries {
+            writer
+                .start_file(name.as_str(), zip::write::SimpleFileOptions::default())
+                .expect("start file");
+            writer.write_all(content.as_bytes()).expect("write");
+        }
+        writer.finish().expect("finish zip");
+    }
+
+    fn write_tar_gz(path: &Path, entries: &[(String, String)]) {
+        let encoder = flate2::write::GzEncoder::new(
+            File::create(path).expect("create tgz"),
+            flate2::Compression::default(),
+        );
+        let mut builder = tar::Builder::new(encoder);
+        for (name, content) in entries {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(content.len() as u64);
+            header.set_mode(0o644);
+            header.set_cksum();
+            builder
+                .append_data(&mut header, name, Cursor::new(content.as_bytes()))
+                .expect("append");
+        }
+        builder
+            .into_inner()
+            .expect("tar")
+            .finish()
+            .expect("finish gz");
+    }
+
+    #[test]
+    fn installs_upgrades_and_rolls_back_from_archives() {
+        let root = temp_dir("upgrade");
+        let plugins_dir = root.join("plugins");
+
+        let zip_path = root.join("demo.zip");
+        let nested: Vec<_> = plugin_files("demo", "1.0.0")
+            .into_iter()
+            .map(|(name, content)| (format!("demo-1.0.0/{}", name), content))
+            .collect();
+        write_zip(&zip_path, &nested);
+        let installed = install_plugin(&zip_path, &plugins_dir, &[], false).expect("install zip");
+        assert_eq!(installed.manifest.version, "1.0.0");
+        assert_eq!(installed.plugin_dir, plugins_dir.join("demo"));
+
+        let tgz_path = root.join("demo.tar.gz");
+        write_tar_gz(&tgz_path, &plugin_files("demo", "2.0.0"));
+        let upgraded = install_plugin(&tgz_path, &plugins_dir, &[], false).expect("upgrade");
+        assert_eq!(upgraded.manifest.version, "2.0.0");
+
+        let loaded = manifest::load_plugins_from_dir(&plugins_dir);
+        let info = list_installed_plugins(&loaded, &plugins_dir, &[]);
+        assert_eq!(info.len(), 1, "staging and backup dirs must not load");
+        assert_eq!(info[0].version, "2.0.0");
+        assert!(info[0].has_previous);
+
+        let restored = rollback_plugin("demo", &plugins_dir).expect("rollback");
+        assert_eq!(restored.manifest.version, "1.0.0");
+        let again = rollback_plugin("demo", &plugins_dir).expect("roll forward");
+        assert_eq!(again.manifest.version, "2.0.0");
+
+        uninstall_plugin("demo", &plugins_dir, &[]).expect("uninstall");
+        assert!(manifest::load_plugins_from_dir(&plugins_dir).is_empty());
+        let restored = rollback_plugin("demo", &plugins_dir).expect("restore uninstalled");
+        assert_eq!(restored.manifest.version, "2.0.0");
+
+        let leftovers: Vec<_> = std::fs::read_dir(&plugins_dir)
+            .expect("read")
+            .flatten()
+            .filter(|e| e.file_name().to_string_lossy().starts_with(STAGING_PREFIX))
+            .collect();
+        assert!(leftovers.is_empty(), "staging dirs must be cleaned up");
+        let _ = std::fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn bundled_ids_need_force_and_cannot_be_uninstalled() {
+        let root = temp_dir("bundled");
+        let plugins_dir = root.join("plugins");
+        let source = root.join("src");
+        write_plugin_dir(&source, "claude", "9.0.0");
+        let bundled = vec!["claude".to_string()];
+
+        let err = install_plugin(&source, &plugins_dir, &bundled, false).unwrap_err();
+        assert_eq!(
+            err,
+            "plugin id claude belongs to a bundled plugin; use force to replace it"
+        );
+        let forced = install_plugin(&source, &plugins_dir, &bundled, true).expect("forced");
+        assert_eq!(forced.manifest.version, "9.0.0");
+        assert!(uninstall_plugin("claude", &plugins_dir, &bundled).is_err());
+        let _ = std::fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn rejects_invalid_plugins_and_unsafe_archives() {
+        let root = temp_dir("invalid");
+        let plugins_dir = root.join("plugins");
+
+        let missing_entry = root.join("missing-entry.zip");
+        let files: Vec<_> = plugin_files("demo", "1.0.0")
+            .into_iter()
+            .filter(|(name, _)| name != "plugin.js")
+            .collect();
+        write_zip(&missing_entry, &files);
+        let err = install_plugin(&missing_entry, &plugins_dir, &[], false).unwrap_err();
+        assert!(err.starts_with("invalid plugin:"), "{}", err);
+
+        let escaping = root.join("escape.zip");
+        let mut files = plugin_files("demo", "1.0.0");
+        files.push(("../../evil.txt".to_string(), "x".to_string()));
+        write_zip(&escaping, &files);
+        let err = install_plugin(&escaping, &plugins_dir, &[], false).unwrap_err();
+        assert!(err.contains("unsafe path"), "{}", err);
+        assert!(!root.join("evil.txt").exists());
+
+        let bad_id = root.join("bad-id");
+        write_plugin_dir(&bad_id, "../Escape", "1.0.0");
+        assert!(install_plugin(&bad_id, &plugins_dir, &[], false).is_err());
+
+        let unknown = root.join("plugin.rar");
+        std::fs::write(&unknown, "x").expect("write");
+        assert!(install_plugin(&unknown, &plugins_dir, &[], false).is_err());
+
+        assert!(manifest::load_plugins_from_dir(&plugins_dir).is_empty());
+        let _ = std::fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn restores_previous_version_when_installed_plugin_fails_to_load() {
+        let root = temp_dir("restore");
+        let plugins_dir = root.join("plugins");
+        let source = root.join("src");
+        write_plugin_dir(&source, "demo", "1.0.0");
+        install_plugin(&source, &plugins_dir, &[], false).expect("install");
+
+        let broken = root.join("broken");
+        write_plugin_dir(&broken, "demo", "2.0.0");
+        std::fs::remove_file(broken.join("plugin.js")).expect("remove entry");
+        let err = move_into_place(&broken, &plugins_dir, "demo").unwrap_err();
+        assert!(
+            err.starts_with("installed plugin demo failed to load"),
+            "{}",
+            err
+        );
+
+        let current = manifest::load_single_plugin(&plugins_dir.join("demo")).expect("load");
+        assert_eq!(current.manifest.version, "1.0.0");
+        assert!(
+            broken.join("plugin.json").exists(),
+            "failed install is moved back"
+        );
+        let _ = std::fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn plugin_id_alphabet() {
+        assert!(is_valid_plugin_id("opencode-go"));
+        assert!(is_valid_plugin_id("jetbrains-ai-assistant"));
+        assert!(!is_valid_plugin_id(""));
+        assert!(!is_valid_plugin_id(".hidden"));
+        assert!(!is_valid_plugin_id("Claude"));
+        assert!(!is_valid_plugin_id("a/b"));
+    }
+}
@@ -0,0 +1,263 @@
+//! Unpacking of values thrown by plugin code.
+//!
+//! A thrown value is read once into a [`ThrownValue`] while the JS context is still alive, then
+//! classified into a [`ProbeError`] for the UI and rendered into a readable trace for the log.
+
+use crate::plugin_engine::runtime::{ProbeError, ProbeErrorCode};
+use rquickjs::{Ctx, Value};
+
+pub(crate) const GENERIC_FAILURE_MESSAGE: &str =
+    "The plugin failed, try again or contact plugin author.";
+
+/// Filename QuickJS reports for code evaluated without an explicit name (the host wrappers).
+const HOST_SCRIPT_NAME: &str = "eval_script";
+const MAX_CAUSE_DEPTH: usize = 8;
+const MAX_SOURCE_EXCERPT_CHARS: usize = 160;
+
+/// Errors raised by the engine itself; these always point at a bug in the plugin.
+const ENGINE_ERROR_NAMES: [&str; 7] = [
+    "TypeError",
+    "ReferenceError",
+    "SyntaxError",
+    "RangeError",
+    "InternalError",
+    "EvalError",
+    "URIError",
+];
+
+#[derive(Debug, Default, Clone, PartialEq)]
+pub(crate) struct ThrownValue {
+    pub name: Option<String>,
+    pub message: Option<String>,
+    pub code: Option<ProbeErrorCode>,
+    pub retryable: Option<bool>,
+    pub stack: Option<String>,
+    pub cause: Option<Box<ThrownValue>>,
+}
+
+/// Take the pending exception off the context.
+pub(crate) fn catch(ctx: &Ctx<'_>) -> ThrownValue {
+    ThrownValue::from_value(&ctx.catch(), 0)
+}
+
+fn non_empty(value: Option<String>) -> Option<String> {
+    value
+        .map(|value| value.trim().to_string())
+        .filter(|value| !value.is_empty())
+}
+
+impl ThrownValue {
+    fn from_value(value: &Value<'_>, depth: usize) -> Self {
+        if let Some(s) = value.as_string() {
+            return ThrownValue {
+                message: non_empty(s.to_string().ok()),
+                ..Default::default()
+            };
+        }
+        let Some(obj) = value.as_object() else {
+            return ThrownValue::default();
+        };
+        let cause = if depth < MAX_CAUSE_DEPTH {
+            obj.get::<_, Value>("cause")
+                .ok()
+                .filter(|cause| !cause.is_undefined() && !cause.is_null())
+                .map(|cause| Box::new(ThrownValue::from_value(&cause, depth + 1)))
+        } else {
+            None
+        };
+        ThrownValue {
+            name: non_empty(obj.get::<_, String>("name").ok()),
+            message: non_empty(obj.get::<_, String>("message").ok()),
+            code: obj
+                .get::<_, String>("code")
+                .ok()
+                .and_then(|code| ProbeErrorCode::parse(&code)),
+            retryable: obj
+                .get::<_, Value>("retryable")
+                .ok()
+                .and_then(|value| value.as_bool()),
+            stack: obj
+                .get::<_, String>("stack")
+                .ok()
+                .filter(|stack| !stack.trim().is_empty()),
+            cause,
+        }
+    }
+
+    fn chain(&self) -> impl Iterator<Item = &ThrownValue> {
+        std::iter::successors(Some(self), |thrown| thrown.cause.as_deref())
+    }
+
+    fn is_engine_error(&self) -> bool {
+        self.name
+            .as_deref()
+            .is_some_and(|name| ENGINE_ERROR_NAMES.contains(&name))
+    }
+
+    /// Classify for the probe result. A `code` anywhere in the `cause` chain wins, so a plugin
+    /// can wrap a host error without losing its classification; the outermost message is shown.
+    pub fn to_probe_error(&self) -> ProbeError {
+        if let Some(coded) = self.chain().find(|thrown| thrown.code.is_some()) {
+            let code = coded.code.unwrap_or(ProbeErrorCode::Unknown);
+            return ProbeError {
+                code,
+                message: self
+                    .message
+                    .clone()
+                    .or_else(|| coded.message.clone())
+                    .unwrap_or_else(|| GENERIC_FAILURE_MESSAGE.to_string()),
+                retryable: coded.retryable.unwrap_or_else(|| code.default_retryable()),
+            };
+        }
+        match &self.message {
+            Some(message) if self.is_engine_error() => ProbeError::plugin_bug(format!(
+                "{}: {}",
+                self.name.as_deref().unwrap_or("Error"),
+                message
+            )),
+            Some(message) => ProbeError::new(ProbeErrorCode::Unknown, message.clone()),
+            None => ProbeError::plugin_bug(GENERIC_FAILURE_MESSAGE),
+        }
+    }
+
+    pub fn headline(&self) -> String {
+        match (&self.name, &self.message) {
+            (Some(name), Some(message)) => format!("{}: {}", name, message),
+            (Some(name), None) => name.clone(),
+            (None, Some(message)) => message.clone(),
+            (None, None) => "<non-error value>".to_string(),
+        }
+    }
+
+    /// Multi-line trace for logs: each error in the `cause` chain with its stack. Frames in the
+    /// plugin's entry file get the offending source line attached; host wrapper frames are
+    /// labelled `<host>`.
+    pub fn trace(&self, entry: &str, entry_script: &str) -> String {
+        let mut out = String::new();
+        for (i, thrown) in self.chain().enumerate() {
+            if i > 0 {
+                out.push_str("\nCaused by: ");
+            }
+            out.push_str(&thrown.headline());
+            if let Some(stack) = &thrown.stack {
+                push_stack(&mut out, stack, entry, entry_script);
+            }
+        }
+        out
+    }
+}
+
+fn push_stack(out: &mut String, stack: &str, entry: &str, entry_script: &str) {
+    let mut excerpt_shown = false;
+    for frame in stack.lines().map(str::trim).filter(|line| !line.is_empty()) {
+        out.push_str("\n    ");
+        out.push_str(&frame.replace(HOST_SCRIPT_NAME, "<host>"));
+        if excerpt_shown {
+            continue;
+        }
+        let source = frame_location(frame)
+            .filter(|(file, _)| *file == entry)
+            .and_then(|(_, line)| entry_script.lines().nth(line.checked_sub(1)?));
+        if let Some(source) = source {
+            let source: String = source
+                .trim()
+                .chars()
+                .take(MAX_SOURCE_EXCERPT_CHARS)
+                .collect();
+            out.push_str("\n        | ");
+            out.push_str(&source);
+            excerpt_shown = true;
+        }
+    }
+}
+
+/// Parse `at fn (file:line:col)` / `at file:line:col` into `(file, line)`.
+fn frame_location(frame: &str) -> Option<(&str, usize)> {
+    let rest = frame.strip_prefix("at ")?;
+    let location = match (rest.rfind('('), rest.ends_with(')')) {
+        (Some(open), true) => &rest[open + 1..rest.len() - 1],
+        _ => rest,
+    };
+    let mut parts = location.rsplitn(3, ':');
+    let last = parts.next()?;
+    let second = parts.next()?;
+    match parts.next() {
+        Some(file) => Some((file, second.parse().ok()?)),
+        None => Some((second, last.parse().ok()?)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn error(name: &str, message: &str) -> ThrownValue {
+        ThrownValue {
+            name: Some(name.to_string()),
+            message: Some(message.to_string()),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn plain_error_message_is_surfaced() {
+        let probe_error = error("Error", "token expired").to_probe_error();
+        assert_eq!(probe_error.code, ProbeErrorCode::Unknown);
+        assert_eq!(probe_error.message, "token expired");
+    }
+
+    #[test]
+    fn engine_errors_are_plugin_bugs() {
+        let probe_error = error("TypeError", "x is not a function").to_probe_error();
+        assert_eq!(probe_error.code, ProbeErrorCode::PluginBug);
+        assert_eq!(probe_error.message, "TypeError: x is not a function");
+    }
+
+    #[test]
+    fn code_from_cause_is_kept() {
+        let mut outer = error("Error", "usage fetch failed");
+        outer.cause = Some(Box::new(ThrownValue {
+            code: Some(ProbeErrorCode::Network),
+            message: Some("connection refused".to_string()),
+            ..Default::default()
+        }));
+        let probe_error = outer.to_probe_error();
+        assert_eq!(probe_error.code, ProbeErrorCode::Network);
+        assert_eq!(probe_error.message, "usage fetch failed");
+        assert!(probe_error.retryable);
+    }
+
+    #[test]
+    fn non_error_values_fall_back_to_generic_message() {
+        let probe_error = ThrownValue::default().to_probe_error();
+        assert_eq!(probe_error.code, ProbeErrorCode::PluginBug);
+        assert_eq!(probe_error.message, GENERIC_FAILURE_MESSAGE);
+    }
+
+    #[test]
+    fn frame_location_parses_quickjs_frames() {
+        assert_eq!(
+            frame_location("at probe (plugin.js:3:15)"),
+            Some(("plugin.js", 3))
+        );
+        assert_eq!(frame_location("at plugin.js:7:1"), Some(("plugin.js", 7)));
+        assert_eq!(frame_location("at parse (native)"), None);
+    }
+
+    #[test]
+    fn trace_maps_frames_to_entry_source() {
+        let mut thrown = error("Error", "token expired");
+        thrown.stack = Some(
+            "    at refresh (plugin.js:2:9)\n    at probe (plugin.js:5:3)\n    at request (eval_script:1:35)\n"
+                .to_string(),
+        );
+        thrown.cause = Some(Box::new(error("Error", "401")));
+        let script = "function refresh() {\n  throw new Error(\"token expired\")\n}\nfunction probe() {\n  refresh()\n}";
+
+        let trace = thrown.trace("plugin.js", script);
+        assert_eq!(
+            trace,
+            "Error: token expired\n    at refresh (plugin.js:2:9)\n        | throw new Error(\"token expired\")\n    at probe (plugin.js:5:3)\n    at request (<host>:1:35)\nCaused by: Error: 401"
+        );
+    }
+}
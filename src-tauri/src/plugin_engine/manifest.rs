@@ -1,47 +1,338 @@
-use base64::{Engine, engine::general_purpose::STANDARD};
-use serde::Deserialize;
+use crate::plugin_engine::bundled_sync::compare_versions;
+use crate::plugin_engine::declarative;
+use crate::plugin_engine::host_api::HOST_API_FEATURES;
+use crate::plugin_engine::icon;
+use crate::plugin_engine::modules;
+use crate::plugin_engine::runtime::ProgressFormat;
+use crate::plugin_engine::signing::PluginTrust;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::path::{Path, PathBuf};
 
-#[derive(Debug, Clone, Deserialize)]
+/// Manifest `schemaVersion`s this build understands. Version 1 manifests are upgraded to the
+/// current shape in memory by `upgrade_v1`.
+pub const SUPPORTED_SCHEMA_VERSIONS: [u32; 2] = [1, 2];
+pub const CURRENT_SCHEMA_VERSION: u32 = 2;
+
+/// Version of the running app, checked against `minAppVersion`.
+pub const APP_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// Line types a manifest may declare; these match the `MetricLine` variants.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum LineType {
+    Text,
+    Progress,
+    Badge,
+    Segmented,
+    Series,
+}
+
+impl LineType {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            LineType::Text => "text",
+            LineType::Progress => "progress",
+            LineType::Badge => "badge",
+            LineType::Segmented => "segmented",
+            LineType::Series => "series",
+        }
+    }
+}
+
+/// Where a line is shown: the compact overview or the expanded detail view.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum LineScope {
+    Overview,
+    Detail,
+}
+
+impl LineScope {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            LineScope::Overview => "overview",
+            LineScope::Detail => "detail",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct ManifestLine {
+    /// Stable identifier that survives label changes; unique within the plugin.
+    pub id: String,
     #[serde(rename = "type")]
-    pub line_type: String,
+    pub line_type: LineType,
     pub label: String,
-    pub scope: String,
+    pub scope: LineScope,
     /// Lower number = higher priority for primary metric selection.
     /// Only progress lines with primary_order are candidates.
     pub primary_order: Option<u32>,
 }
 
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct PluginLink {
     pub label: String,
     pub url: String,
 }
 
-#[derive(Debug, Clone, Deserialize)]
+/// A user-editable setting; resolved values reach the probe as `ctx.config[key]`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SettingDefinition {
+    pub key: String,
+    pub label: String,
+    #[serde(default)]
+    pub description: Option<String>,
+    #[serde(flatten)]
+    pub kind: SettingKind,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum SettingKind {
+    String {
+        #[serde(default)]
+        default: Option<String>,
+    },
+    /// Kept in the OS keychain, never in the settings file.
+    Secret,
+    Enum {
+        options: Vec<String>,
+        #[serde(default)]
+        default: Option<String>,
+    },
+    Boolean {
+        #[serde(default)]
+        default: bool,
+    },
+    /// An http(s) URL, e.g. a custom endpoint or regional API base.
+    Url {
+        #[serde(default)]
+        default: Option<String>,
+    },
+}
+
+/// How the entry file is evaluated.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum EntryType {
+    /// Global script that assigns `globalThis.__openusage_plugin`.
+    #[default]
+    Script,
+    /// ES module that exports `probe(ctx)` and may import sibling modules.
+    Module,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct PluginManifest {
     pub schema_version: u32,
     pub id: String,
     pub name: String,
     pub version: String,
+    /// Empty for declarative plugins, which describe their probe in `http` instead.
+    #[serde(default)]
     pub entry: String,
+    #[serde(default)]
+    pub entry_type: EntryType,
+    /// Oldest app version the plugin works with.
+    #[serde(default)]
+    pub min_app_version: Option<String>,
+    /// Host API functions the plugin calls, as `namespace.function` (e.g. `ccusage.query`).
+    #[serde(default)]
+    pub requires: Vec<String>,
     pub icon: String,
+    /// Theme-specific icons; `icon` is used for a theme without its own.
+    #[serde(default)]
+    pub icons: IconVariants,
     pub brand_color: Option<String>,
     pub lines: Vec<ManifestLine>,
     #[serde(default)]
     pub links: Vec<PluginLink>,
+    /// Wall-clock budget for a single probe; falls back to the runtime default when absent.
+    #[serde(default)]
+    pub probe_timeout_ms: Option<u64>,
+    #[serde(default)]
+    pub settings: Vec<SettingDefinition>,
+    /// Environment variables `host.env.get` may read for this plugin.
+    #[serde(default)]
+    pub env: Vec<String>,
+    #[serde(default)]
+    pub capabilities: Capabilities,
+    #[serde(default)]
+    pub detect: DetectRules,
+    /// Makes this a declarative plugin: the probe is run by the app from this description
+    /// rather than by a JavaScript entry.
+    #[serde(default)]
+    pub http: Option<HttpPluginSpec>,
+}
+
+/// Host APIs a plugin may use. Logging, crypto and the plugin's own data directory are
+/// always available; everything else is denied unless listed here.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct Capabilities {
+    pub fs: FsCapabilities,
+    /// Host patterns for `host.http`, e.g. `api.example.com`, `*.example.com`, `127.0.0.1:8080`.
+    pub http: Vec<String>,
+    /// Keychain service names; `*` matches any run of characters.
+    pub keychain: Vec<String>,
+    /// Path globs of databases `host.sqlite` may open.
+    pub sqlite: Vec<String>,
+    pub ls: bool,
+    pub ccusage: bool,
+    /// Allows `dangerouslyIgnoreTls` on HTTP requests.
+    pub insecure_tls: bool,
+}
+
+/// Light and dark variants of an icon. In the manifest these are paths relative to the plugin
+/// directory; on `LoadedPlugin` they are data URLs.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct IconVariants {
+    pub light: Option<String>,
+    pub dark: Option<String>,
+}
+
+/// Signs that the provider's tool is installed on this machine; any one match counts.
+/// Paths may start with `~/` or `${VAR}` for a variable listed in `env`.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct DetectRules {
+    /// Files or directories that exist.
+    pub files: Vec<String>,
+    /// Environment variables that are set; each must also be listed in `env`.
+    pub env: Vec<String>,
+    /// SQLite databases that exist.
+    pub sqlite: Vec<String>,
+    /// Running process names (executable name, case-insensitive).
+    pub processes: Vec<String>,
+    /// Enable the plugin by default for new users when detected; otherwise it is only suggested.
+    pub auto_enable: bool,
+}
+
+/// Requests to make and how their JSON responses become lines. Values written as `$.…` are
+/// paths into the responses, keyed by request id (e.g. `$.quota.data.used`); see `declarative`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HttpPluginSpec {
+    /// Tried in order; the first credential found replaces `{{auth}}` in requests.
+    #[serde(default)]
+    pub auth: Vec<AuthSource>,
+    pub requests: Vec<HttpRequestSpec>,
+    /// Path to the plan name.
+    #[serde(default)]
+    pub plan: Option<String>,
+    pub lines: Vec<LineMapping>,
+}
+
+/// Where a declarative plugin's credential comes from. Each source must also be allowed by
+/// `env` or `capabilities`, like the matching host API.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "source", rename_all = "camelCase")]
+pub enum AuthSource {
+    Env {
+        name: String,
+    },
+    /// The whole file, trimmed, or the value at `field` when the file is JSON.
+    File {
+        path: String,
+        #[serde(default)]
+        field: Option<String>,
+    },
+    /// A macOS keychain generic password.
+    Keychain {
+        service: String,
+    },
+    /// A value from `settings`, usually a `secret`.
+    Setting {
+        key: String,
+    },
+}
+
+/// `url`, `headers` and `body` may contain `{{auth}}` and `{{config.<key>}}`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HttpRequestSpec {
+    /// Names the response in value paths; unique within the plugin.
+    pub id: String,
+    pub url: String,
+    #[serde(default)]
+    pub method: Option<String>,
+    #[serde(default)]
+    pub headers: std::collections::BTreeMap<String, String>,
+    #[serde(default)]
+    pub body: Option<String>,
+    #[serde(default)]
+    pub timeout_ms: Option<u64>,
+}
+
+/// Fills one of the manifest `lines`, which supplies its type and label. Values are numbers or
+/// strings, or `$.…` paths; a line whose values are missing from the response is left out.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LineMapping {
+    /// Id of the manifest line.
+    pub line: String,
+    /// Progress: either `used` or `remaining` (counted down from `limit`).
+    #[serde(default)]
+    pub used: Option<serde_json::Value>,
+    #[serde(default)]
+    pub remaining: Option<serde_json::Value>,
+    /// Progress: may be left out for `percent`, which is always out of 100.
+    #[serde(default)]
+    pub limit: Option<serde_json::Value>,
+    #[serde(default)]
+    pub format: Option<ProgressFormat>,
+    /// Progress: an RFC 3339 timestamp or Unix time in seconds or milliseconds.
+    #[serde(default)]
+    pub resets_at: Option<serde_json::Value>,
+    #[serde(default)]
+    pub period_duration_ms: Option<u64>,
+    /// Text.
+    #[serde(default)]
+    pub value: Option<serde_json::Value>,
+    /// Badge.
+    #[serde(default)]
+    pub text: Option<serde_json::Value>,
+    #[serde(default)]
+    pub color: Option<String>,
+}
+
+/// Path globs: `*` and `?` match within one path segment, `**` matches any number of segments.
+/// Paths may start with `~/` or `${VAR}` for a variable listed in `env`.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FsCapabilities {
+    pub read: Vec<String>,
+    /// Write access implies read access.
+    pub write: Vec<String>,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
 pub struct LoadedPlugin {
     pub manifest: PluginManifest,
     pub plugin_dir: PathBuf,
+    /// Empty for declarative plugins.
     pub entry_script: String,
+    /// Every other `.js`/`.mjs` file of a module plugin, keyed by its `/`-separated path inside
+    /// `plugin_dir`. Read with `entry_script` so imports run the bytes that were verified.
+    #[serde(default)]
+    pub module_sources: BTreeMap<String, String>,
     pub icon_data_url: String,
+    #[serde(default)]
+    pub icon_variant_data_urls: IconVariants,
+    /// Filled in by `signing::apply_trust`; plugins start out unsigned and disabled.
+    #[serde(default)]
+    pub trust: PluginTrust,
+    /// Why this app cannot run the plugin (too old, or missing host APIs it requires).
+    /// Incompatible plugins are still listed, but never probed.
+    #[serde(default)]
+    pub incompatibility: Option<String>,
 }
 
 pub fn load_plugins_from_dir(plugins_dir: &std::path::Path) -> Vec<LoadedPlugin> {
@@ -56,39 +347,102 @@ pub fn load_plugins_from_dir(plugins_dir: &std::path::Path) -> Vec<LoadedPlugin>
         if !path.is_dir() {
             continue;
         }
+        // Hidden dirs hold install staging and rollback copies, never live plugins.
+        if entry.file_name().to_string_lossy().starts_with('.') {
+            continue;
+        }
         let manifest_path = path.join("plugin.json");
         if !manifest_path.exists() {
             continue;
         }
-        if let Ok(p) = load_single_plugin(&path) {
-            plugins.push(p);
+        match load_single_plugin(&path) {
+            Ok(p) => plugins.push(p),
+            Err(err) => log::warn!("skipping plugin {}: {}", path.display(), err),
         }
     }
 
     plugins.sort_by(|a, b| a.manifest.id.cmp(&b.manifest.id));
+    log_env_requests(&plugins);
     plugins
 }
 
-fn load_single_plugin(
+/// One line per plugin listing the environment variables it may read.
+fn log_env_requests(plugins: &[LoadedPlugin]) {
+    for plugin in plugins {
+        if !plugin.manifest.env.is_empty() {
+            log::info!(
+                "plugin {} requests env vars: {}",
+                plugin.manifest.id,
+                plugin.manifest.env.join(", ")
+            );
+        }
+    }
+}
+
+pub(crate) fn load_single_plugin(
     plugin_dir: &std::path::Path,
 ) -> Result<LoadedPlugin, Box<dyn std::error::Error>> {
     let manifest_path = plugin_dir.join("plugin.json");
     let manifest_text = std::fs::read_to_string(&manifest_path)?;
-    let mut manifest: PluginManifest = serde_json::from_str(&manifest_text)?;
+    let mut manifest = parse_manifest(&manifest_text)?;
     manifest.links = sanitize_plugin_links(&manifest.id, std::mem::take(&mut manifest.links));
+    manifest.settings =
+        sanitize_plugin_settings(&manifest.id, std::mem::take(&mut manifest.settings));
+    manifest.env = sanitize_plugin_env(&manifest.id, std::mem::take(&mut manifest.env));
 
     // Validate primary_order: only progress lines can have it
     for line in manifest.lines.iter() {
-        if line.primary_order.is_some() && line.line_type != "progress" {
+        if line.primary_order.is_some() && line.line_type != LineType::Progress {
             log::warn!(
                 "plugin {} line '{}' has primaryOrder but type is '{}'; will be ignored",
                 manifest.id,
                 line.label,
-                line.line_type
+                line.line_type.as_str()
             );
         }
     }
 
+    let entry_script = if manifest.http.is_some() {
+        if let Some(problem) = declarative::check_spec(&manifest).into_iter().next() {
+            return Err(problem.into());
+        }
+        String::new()
+    } else {
+        read_entry(plugin_dir, &manifest)?
+    };
+    let module_sources = if manifest.http.is_none() && manifest.entry_type == EntryType::Module {
+        read_module_sources(plugin_dir, &manifest.entry)?
+    } else {
+        BTreeMap::new()
+    };
+
+    let incompatibility = check_compatibility(&manifest, APP_VERSION, HOST_API_FEATURES);
+    if let Some(reason) = &incompatibility {
+        log::warn!("plugin {} is incompatible: {}", manifest.id, reason);
+    }
+
+    let icon_data_url = icon::load_icon(plugin_dir, &manifest.icon)?;
+    let icon_variant_data_urls = IconVariants {
+        light: load_icon_variant(&manifest.id, plugin_dir, manifest.icons.light.as_deref()),
+        dark: load_icon_variant(&manifest.id, plugin_dir, manifest.icons.dark.as_deref()),
+    };
+
+    Ok(LoadedPlugin {
+        manifest,
+        plugin_dir: plugin_dir.to_path_buf(),
+        entry_script,
+        module_sources,
+        icon_data_url,
+        icon_variant_data_urls,
+        trust: PluginTrust::default(),
+        incompatibility,
+    })
+}
+
+fn read_entry(
+    plugin_dir: &Path,
+    manifest: &PluginManifest,
+) -> Result<String, Box<dyn std::error::Error>> {
     if manifest.entry.trim().is_empty() {
         return Err("plugin entry field cannot be empty".into());
     }
@@ -106,18 +460,211 @@ fn load_single_plugin(
         return Err("plugin entry must be a file".into());
     }
 
-    let entry_script = std::fs::read_to_string(&canonical_entry_path)?;
+    Ok(std::fs::read_to_string(&canonical_entry_path)?)
+}
 
-    let icon_file = plugin_dir.join(&manifest.icon);
-    let icon_bytes = std::fs::read(&icon_file)?;
-    let icon_data_url = format!("data:image/svg+xml;base64,{}", STANDARD.encode(&icon_bytes));
+/// Read every `.js`/`.mjs` file under `plugin_dir` except the entry. Symlinks are skipped, the
+/// same as in `signing::hash_files`, so nothing outside the directory is ever read.
+fn read_module_sources(
+    plugin_dir: &Path,
+    entry: &str,
+) -> Result<BTreeMap<String, String>, Box<dyn std::error::Error>> {
+    let canonical_plugin_dir = plugin_dir.canonicalize()?;
+    let entry_name = modules::resolve_in_plugin_dir(&canonical_plugin_dir, entry)?;
+    let mut sources = BTreeMap::new();
+    read_module_sources_into(&canonical_plugin_dir, "", &mut sources)?;
+    sources.remove(&entry_name);
+    Ok(sources)
+}
 
-    Ok(LoadedPlugin {
-        manifest,
-        plugin_dir: plugin_dir.to_path_buf(),
-        entry_script,
-        icon_data_url,
-    })
+fn read_module_sources_into(
+    dir: &Path,
+    prefix: &str,
+    sources: &mut BTreeMap<String, String>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    for entry in std::fs::read_dir(dir)? {
+        let entry = entry?;
+        let name = entry.file_name();
+        let Some(name) = name.to_str() else {
+            continue;
+        };
+        let relative = format!("{}{}", prefix, name);
+        let file_type = entry.file_type()?;
+        if file_type.is_symlink() {
+            continue;
+        }
+        if file_type.is_dir() {
+            read_module_sources_into(&entry.path(), &format!("{}/", relative), sources)?;
+        } else if name.ends_with(".js") || name.ends_with(".mjs") {
+            let source = std::fs::read_to_string(entry.path())
+                .map_err(|e| format!("failed to read {}: {}", relative, e))?;
+            sources.insert(relative, source);
+        }
+    }
+    Ok(())
+}
+
+/// A broken variant only loses the variant; the plugin falls back to `icon`.
+fn load_icon_variant(plugin_id: &str, plugin_dir: &Path, relative: Option<&str>) -> Option<String> {
+    match icon::load_icon(plugin_dir, relative?) {
+        Ok(url) => Some(url),
+        Err(err) => {
+            log::warn!("plugin {} {}; using the default icon", plugin_id, err);
+            None
+        }
+    }
+}
+
+/// Reasons `manifest` cannot run on an app at `app_version` providing `host_features`, joined
+/// into one message, or `None` when it can.
+pub fn check_compatibility(
+    manifest: &PluginManifest,
+    app_version: &str,
+    host_features: &[&str],
+) -> Option<String> {
+    let mut reasons = Vec::new();
+    if let Some(min) = manifest.min_app_version.as_deref()
+        && compare_versions(app_version, min).is_lt()
+    {
+        reasons.push(format!(
+            "requires app version {} or newer (this is {})",
+            min, app_version
+        ));
+    }
+    let missing: Vec<&str> = manifest
+        .requires
+        .iter()
+        .map(String::as_str)
+        .filter(|feature| !host_features.contains(feature))
+        .collect();
+    if !missing.is_empty() {
+        reasons.push(format!(
+            "requires host APIs this app does not provide: {}",
+            missing.join(", ")
+        ));
+    }
+    (!reasons.is_empty()).then(|| reasons.join("; "))
+}
+
+/// Parse `plugin.json`, rejecting unknown schema versions and upgrading version 1 manifests.
+/// `schema_version` keeps the version the file declared.
+pub fn parse_manifest(text: &str) -> Result<PluginManifest, String> {
+    let mut value: serde_json::Value =
+        serde_json::from_str(text).map_err(|e| format!("invalid plugin.json: {}", e))?;
+    let schema_version = match value.get("schemaVersion") {
+        Some(serde_json::Value::Number(n)) => n.as_u64(),
+        Some(_) => None,
+        None => return Err("plugin.json is missing schemaVersion".to_string()),
+    };
+    let schema_version = schema_version
+        .and_then(|v| u32::try_from(v).ok())
+        .filter(|v| SUPPORTED_SCHEMA_VERSIONS.contains(v))
+        .ok_or_else(|| {
+            format!(
+                "unsupported schemaVersion {}; this app supports {}",
+                value["schemaVersion"],
+                SUPPORTED_SCHEMA_VERSIONS
+                    .iter()
+                    .map(|v| v.to_string())
+                    .collect::<Vec<_>>()
+                    .join(" and ")
+            )
+        })?;
+    if schema_version == 1 {
+        upgrade_v1(&mut value);
+    }
+
+    let manifest: PluginManifest =
+        serde_json::from_value(value).map_err(|e| format!("invalid plugin.json: {}", e))?;
+    let mut seen = std::collections::HashSet::new();
+    for line in &manifest.lines {
+        if !is_line_id(&line.id) {
+            return Err(format!(
+                "line '{}' has invalid id '{}': use lowercase letters, digits, '-' and '_'",
+                line.label, line.id
+            ));
+        }
+        if !seen.insert(line.id.as_str()) {
+            return Err(format!("duplicate line id '{}'", line.id));
+        }
+    }
+    Ok(manifest)
+}
+
+pub(crate) fn is_line_id(id: &str) -> bool {
+    !id.is_empty()
+        && id
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-' || c == '_')
+}
+
+/// Bring a version 1 manifest to the current shape: lines get ids derived from their labels,
+/// and lines with a type or scope version 2 does not know are dropped (version 1 only warned).
+fn upgrade_v1(value: &mut serde_json::Value) {
+    let plugin_id = value["id"].as_str().unwrap_or("?").to_string();
+    let Some(lines) = value
+        .get_mut("lines")
+        .and_then(serde_json::Value::as_array_mut)
+    else {
+        return;
+    };
+    let mut used_ids = std::collections::HashSet::new();
+    lines.retain_mut(|line| {
+        let label = line["label"].as_str().unwrap_or_default().to_string();
+        for (field, known) in [
+            (
+                "type",
+                &["text", "progress", "badge", "segmented", "series"][..],
+            ),
+            ("scope", &["overview", "detail"][..]),
+        ] {
+            let actual = line[field].as_str().unwrap_or_default();
+            if !known.contains(&actual) {
+                log::warn!(
+                    "plugin {} line '{}' has unknown {} '{}'; dropped",
+                    plugin_id,
+                    label,
+                    field,
+                    actual
+                );
+                return false;
+            }
+        }
+        if let Some(object) = line.as_object_mut()
+            && !object.contains_key("id")
+        {
+            let base = slugify(&label);
+            let mut id = base.clone();
+            let mut n = 2;
+            while used_ids.contains(&id) {
+                id = format!("{}-{}", base, n);
+                n += 1;
+            }
+            object.insert("id".to_string(), serde_json::Value::String(id));
+        }
+        if let Some(id) = line["id"].as_str() {
+            used_ids.insert(id.to_string());
+        }
+        true
+    });
+}
+
+/// `"Weekly (Sonnet)"` -> `"weekly-sonnet"`; falls back to `"line"` for labels with no letters.
+fn slugify(label: &str) -> String {
+    let mut slug = String::new();
+    for c in label.chars() {
+        if c.is_ascii_alphanumeric() {
+            slug.push(c.to_ascii_lowercase());
+        } else if !slug.is_empty() && !slug.ends_with('-') {
+            slug.push('-');
+        }
+    }
+    let slug = slug.trim_end_matches('-').to_string();
+    if slug.is_empty() {
+        "line".to_string()
+    } else {
+        slug
+    }
 }
 
 fn sanitize_plugin_links(plugin_id: &str, links: Vec<PluginLink>) -> Vec<PluginLink> {
@@ -134,7 +681,7 @@ fn sanitize_plugin_links(plugin_id: &str, links: Vec<PluginLink>) -> Vec<PluginL
                 );
                 return None;
             }
-            if !(url.starts_with("https://") || url.starts_with("http://")) {
+            if !is_http_url(&url) {
                 log::warn!(
                     "plugin {} link '{}' has non-http(s) url '{}'; skipping",
                     plugin_id,
@@ -149,12 +696,99 @@ fn sanitize_plugin_links(plugin_id: &str, links: Vec<PluginLink>) -> Vec<PluginL
         .collect()
 }
 
+pub(crate) fn is_http_url(url: &str) -> bool {
+    url.starts_with("https://") || url.starts_with("http://")
+}
+
+fn is_setting_key(key: &str) -> bool {
+    let mut chars = key.chars();
+    chars.next().is_some_and(|c| c.is_ascii_alphabetic())
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+fn sanitize_plugin_settings(
+    plugin_id: &str,
+    settings: Vec<SettingDefinition>,
+) -> Vec<SettingDefinition> {
+    let mut kept: Vec<SettingDefinition> = Vec::with_capacity(settings.len());
+    for setting in settings {
+        let key = setting.key.trim();
+        let problem = if !is_setting_key(key) {
+            Some("key must start with a letter and use only letters, digits and '_'")
+        } else if kept.iter().any(|existing| existing.key == key) {
+            Some("duplicate key")
+        } else if setting.label.trim().is_empty() {
+            Some("empty label")
+        } else {
+            match &setting.kind {
+                SettingKind::Enum { options, .. } if options.is_empty() => {
+                    Some("enum needs at least one option")
+                }
+                SettingKind::Enum {
+                    options,
+                    default: Some(default),
+                } if !options.contains(default) => Some("enum default is not one of its options"),
+                SettingKind::Url {
+                    default: Some(default),
+                } if !is_http_url(default) => Some("url default must be http(s)"),
+                _ => None,
+            }
+        };
+        if let Some(problem) = problem {
+            log::warn!(
+                "plugin {} setting '{}': {}; skipping",
+                plugin_id,
+                setting.key,
+                problem
+            );
+            continue;
+        }
+        kept.push(SettingDefinition {
+            key: key.to_string(),
+            label: setting.label.trim().to_string(),
+            ..setting
+        });
+    }
+    kept
+}
+
+fn is_env_var_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+fn sanitize_plugin_env(plugin_id: &str, env: Vec<String>) -> Vec<String> {
+    let mut kept: Vec<String> = Vec::with_capacity(env.len());
+    for name in env {
+        let name = name.trim();
+        if !is_env_var_name(name) {
+            log::warn!(
+                "plugin {} env var '{}' is not a valid name; skipping",
+                plugin_id,
+                name
+            );
+            continue;
+        }
+        if !kept.iter().any(|existing| existing == name) {
+            kept.push(name.to_string());
+        }
+    }
+    kept
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    fn try_parse_manifest(json: &str) -> Result<PluginManifest, String> {
+        super::parse_manifest(json)
+    }
+
     fn parse_manifest(json: &str) -> PluginManifest {
-        serde_json::from_str::<PluginManifest>(json).expect("manifest parse failed")
+        try_parse_manifest(json).expect("manifest parse failed")
     }
 
     #[test]
@@ -232,7 +866,7 @@ mod tests {
         let mut candidates: Vec<_> = manifest
             .lines
             .iter()
-            .filter(|l| l.line_type == "progress" && l.primary_order.is_some())
+            .filter(|l| l.line_type == LineType::Progress && l.primary_order.is_some())
             .collect();
         candidates.sort_by_key(|l| l.primary_order.unwrap());
         let labels: Vec<_> = candidates.iter().map(|l| l.label.as_str()).collect();
@@ -290,4 +924,221 @@ mod tests {
         assert_eq!(sanitized[0].label, "Status");
         assert_eq!(sanitized[0].url, "https://status.example.com");
     }
+
+    #[test]
+    fn probe_timeout_ms_parsed_when_present() {
+        let manifest = parse_manifest(
+            r#"
+            {
+              "schemaVersion": 1,
+              "id": "x",
+              "name": "X",
+              "version": "0.0.1",
+              "entry": "plugin.js",
+              "icon": "icon.svg",
+              "brandColor": null,
+              "probeTimeoutMs": 45000,
+              "lines": []
+            }
+            "#,
+        );
+
+        assert_eq!(manifest.probe_timeout_ms, Some(45000));
+    }
+
+    #[test]
+    fn settings_parse_and_invalid_ones_are_dropped() {
+        let manifest = parse_manifest(
+            r#"
+            {
+              "schemaVersion": 1,
+              "id": "x",
+              "name": "X",
+              "version": "0.0.1",
+              "entry": "plugin.js",
+              "icon": "icon.svg",
+              "brandColor": null,
+              "lines": [],
+              "settings": [
+                { "key": "apiKey", "label": "API key", "type": "secret" },
+                { "key": "region", "label": "Region", "type": "enum", "options": ["global", "cn"], "default": "global" },
+                { "key": "baseUrl", "label": "Endpoint", "type": "url" },
+                { "key": "verbose", "label": "Verbose", "type": "boolean" },
+                { "key": "region", "label": "Again", "type": "string" },
+                { "key": "bad-key", "label": "Bad", "type": "string" },
+                { "key": "zone", "label": "Zone", "type": "enum", "options": ["a"], "default": "b" },
+                { "key": "mirror", "label": "Mirror", "type": "url", "default": "ftp://x" }
+              ]
+            }
+            "#,
+        );
+
+        let settings = sanitize_plugin_settings("x", manifest.settings);
+        let keys: Vec<_> = settings.iter().map(|s| s.key.as_str()).collect();
+        assert_eq!(keys, vec!["apiKey", "region", "baseUrl", "verbose"]);
+        assert_eq!(settings[0].kind, SettingKind::Secret);
+        assert_eq!(settings[3].kind, SettingKind::Boolean { default: false });
+    }
+
+    #[test]
+    fn env_defaults_to_empty_and_invalid_names_are_dropped() {
+        let manifest = parse_manifest(
+            r#"
+            {
+              "schemaVersion": 1,
+              "id": "x",
+              "name": "X",
+              "version": "0.0.1",
+              "entry": "plugin.js",
+              "icon": "icon.svg",
+              "brandColor": null,
+              "lines": []
+            }
+            "#,
+        );
+        assert!(manifest.env.is_empty());
+
+        let env = sanitize_plugin_env(
+            "x",
+            vec![
+                "ZAI_API_KEY".to_string(),
+                " GLM_API_KEY ".to_string(),
+                "ZAI_API_KEY".to_string(),
+                "1BAD".to_string(),
+                "PATH=/tmp".to_string(),
+                "".to_string(),
+            ],
+        );
+        assert_eq!(env, vec!["ZAI_API_KEY", "GLM_API_KEY"]);
+    }
+
+    #[test]
+    fn entry_type_defaults_to_script() {
+        let manifest = parse_manifest(
+            r#"
+            {
+              "schemaVersion": 1,
+              "id": "x",
+              "name": "X",
+              "version": "0.0.1",
+              "entry": "plugin.js",
+              "icon": "icon.svg",
+              "brandColor": null,
+              "lines": []
+            }
+            "#,
+        );
+        assert_eq!(manifest.entry_type, EntryType::Script);
+
+        let manifest = parse_manifest(
+            r#"
+            {
+              "schemaVersion": 1,
+              "id": "x",
+              "name": "X",
+              "version": "0.0.1",
+              "entry": "src/main.js",
+              "entryType": "module",
+              "icon": "icon.svg",
+              "brandColor": null,
+              "lines": []
+            }
+            "#,
+        );
+        assert_eq!(manifest.entry_type, EntryType::Module);
+    }
+
+    #[test]
+    fn unknown_schema_versions_are_rejected() {
+        let err = try_parse_manifest(r#"{"schemaVersion": 3, "id": "x"}"#).unwrap_err();
+        assert_eq!(
+            err,
+            "unsupported schemaVersion 3; this app supports 1 and 2"
+        );
+        let err = try_parse_manifest(r#"{"id": "x"}"#).unwrap_err();
+        assert_eq!(err, "plugin.json is missing schemaVersion");
+        assert!(try_parse_manifest(r#"{"schemaVersion": "2"}"#).is_err());
+    }
+
+    #[test]
+    fn v1_lines_are_upgraded_with_ids_and_unknown_types_dropped() {
+        let manifest = parse_manifest(
+            r#"
+            {
+              "schemaVersion": 1,
+              "id": "x",
+              "name": "X",
+              "version": "0.0.1",
+              "entry": "plugin.js",
+              "icon": "icon.svg",
+              "brandColor": null,
+              "lines": [
+                { "type": "progress", "label": "Weekly (Sonnet)", "scope": "overview" },
+                { "type": "text", "label": "Weekly Sonnet", "scope": "detail" },
+                { "type": "chart", "label": "Unknown", "scope": "detail" },
+                { "type": "badge", "label": "Plan", "scope": "sidebar" }
+              ]
+            }
+            "#,
+        );
+        assert_eq!(manifest.schema_version, 1);
+        let ids: Vec<_> = manifest.lines.iter().map(|l| l.id.as_str()).collect();
+        assert_eq!(ids, vec!["weekly-sonnet", "weekly-sonnet-2"]);
+        assert_eq!(manifest.lines[1].line_type, LineType::Text);
+        assert_eq!(manifest.lines[1].scope, LineScope::Detail);
+    }
+
+    #[test]
+    fn v2_requires_known_enums_and_unique_line_ids() {
+        let v2 = |lines: &str| {
+            try_parse_manifest(&format!(
+                r#"{{"schemaVersion":2,"id":"x","name":"X","version":"1.0.0","entry":"plugin.js","icon":"icon.svg","brandColor":null,"minAppVersion":"0.7.0","lines":[{}]}}"#,
+                lines
+            ))
+        };
+        let manifest =
+            v2(r#"{"id":"session","type":"progress","label":"Session","scope":"overview"}"#)
+                .expect("valid v2");
+        assert_eq!(manifest.min_app_version.as_deref(), Some("0.7.0"));
+        assert_eq!(manifest.lines[0].id, "session");
+
+        let err = v2(r#"{"id":"a","type":"chart","label":"A","scope":"overview"}"#).unwrap_err();
+        assert!(err.contains("unknown variant `chart`"), "{}", err);
+        let err = v2(r#"{"type":"text","label":"A","scope":"overview"}"#).unwrap_err();
+        assert!(err.contains("missing field `id`"), "{}", err);
+        let err = v2(concat!(
+            r#"{"id":"a","type":"text","label":"A","scope":"overview"},"#,
+            r#"{"id":"a","type":"text","label":"B","scope":"detail"}"#
+        ))
+        .unwrap_err();
+        assert_eq!(err, "duplicate line id 'a'");
+        let err =
+            v2(r#"{"id":"Bad Id","type":"text","label":"A","scope":"overview"}"#).unwrap_err();
+        assert!(err.starts_with("line 'A' has invalid id"), "{}", err);
+    }
+
+    #[test]
+    fn compatibility_checks_app_version_and_required_host_apis() {
+        let mut manifest = parse_manifest(
+            r#"{"schemaVersion":2,"id":"x","name":"X","version":"1.0.0","entry":"plugin.js","icon":"icon.svg","brandColor":null,"minAppVersion":"0.7.0","requires":["ccusage.query"],"lines":[]}"#,
+        );
+        assert_eq!(manifest.requires, vec!["ccusage.query"]);
+        assert_eq!(
+            check_compatibility(&manifest, "0.7.0", &["ccusage.query"]),
+            None
+        );
+        assert_eq!(
+            check_compatibility(&manifest, "0.6.20", &["ccusage.query"]).as_deref(),
+            Some("requires app version 0.7.0 or newer (this is 0.6.20)")
+        );
+
+        manifest.min_app_version = None;
+        manifest
+            .requires
+            .push("keychain.deleteGenericPassword".to_string());
+        assert_eq!(
+            check_compatibility(&manifest, "0.6.20", &["ccusage.query"]).as_deref(),
+            Some("requires host APIs this app does not provide: keychain.deleteGenericPassword")
+        );
+    }
 }
@@ -1,6 +1,21 @@
+pub mod bundled_sync;
+pub mod bytecode;
+pub mod declarative;
+pub mod detect;
 pub mod host_api;
+pub mod host_jobs;
+pub mod icon;
+pub mod install;
+mod js_error;
 pub mod manifest;
+mod modules;
+pub mod permissions;
+pub mod probe_runner;
 pub mod runtime;
+pub mod settings;
+pub mod signing;
+pub mod validate;
+pub mod watcher;
 
 use manifest::LoadedPlugin;
 use std::path::{Path, PathBuf};
@@ -27,13 +42,38 @@ pub fn initialize_plugins(
 
     let bundled_dir = resolve_bundled_dir(resource_dir);
     if bundled_dir.exists() {
-        copy_dir_recursive(&bundled_dir, &install_dir);
+        let entries =
+            bundled_sync::sync_bundled_plugins(&bundled_dir, &install_dir, cfg!(debug_assertions));
+        bundled_sync::append_sync_log(
+            &app_data_dir.join(bundled_sync::SYNC_LOG_FILE_NAME),
+            &entries,
+        );
     }
 
     let plugins = manifest::load_plugins_from_dir(&install_dir);
     (install_dir, plugins)
 }
 
+/// Builds the trust policy for plugins loaded from `plugins_dir`. Plugins served straight from
+/// the development checkout count as bundled.
+pub fn trust_policy(
+    plugins_dir: &Path,
+    resource_dir: &Path,
+    config: &crate::config::PluginsConfig,
+) -> signing::TrustPolicy {
+    let bundled_dir = if is_dev_plugins_dir(plugins_dir) {
+        plugins_dir.to_path_buf()
+    } else {
+        resolve_bundled_dir(resource_dir)
+    };
+    signing::TrustPolicy::from_config(config, Some(bundled_dir))
+}
+
+/// True when plugins are served from the development checkout rather than the app data dir.
+pub fn is_dev_plugins_dir(plugins_dir: &Path) -> bool {
+    find_dev_plugins_dir().as_deref() == Some(plugins_dir)
+}
+
 fn find_dev_plugins_dir() -> Option<PathBuf> {
     let cwd = std::env::current_dir().ok()?;
     let direct = cwd.join("plugins");
@@ -47,6 +87,18 @@ fn find_dev_plugins_dir() -> Option<PathBuf> {
     None
 }
 
+/// Ids of the plugins shipped with the app; installs may only replace them when forced.
+pub fn bundled_plugin_ids(resource_dir: &Path) -> Vec<String> {
+    let Ok(entries) = std::fs::read_dir(resolve_bundled_dir(resource_dir)) else {
+        return Vec::new();
+    };
+    entries
+        .flatten()
+        .filter_map(|entry| manifest::load_single_plugin(&entry.path()).ok())
+        .map(|plugin| plugin.manifest.id)
+        .collect()
+}
+
 fn resolve_bundled_dir(resource_dir: &Path) -> PathBuf {
     let nested = resource_dir.join("resources/bundled_plugins");
     if nested.exists() {
@@ -65,54 +117,3 @@ fn is_dir_empty(path: &Path) -> bool {
         }
     }
 }
-
-fn copy_dir_recursive(src: &Path, dst: &Path) {
-    match std::fs::read_dir(src) {
-        Ok(entries) => {
-            for entry in entries {
-                let entry = match entry {
-                    Ok(entry) => entry,
-                    Err(err) => {
-                        log::warn!("failed to read entry in {}: {}", src.display(), err);
-                        continue;
-                    }
-                };
-                let src_path = entry.path();
-                let dst_path = dst.join(entry.file_name());
-                let file_type = match entry.file_type() {
-                    Ok(file_type) => file_type,
-                    Err(err) => {
-                        log::warn!(
-                            "failed to read file type for {}: {}",
-                            src_path.display(),
-                            err
-                        );
-                        continue;
-                    }
-                };
-                if file_type.is_symlink() {
-                    continue;
-                }
-                if file_type.is_dir() {
-                    if let Err(err) = std::fs::create_dir_all(&dst_path) {
-                        log::warn!("failed to create dir {}: {}", dst_path.display(), err);
-                        continue;
-                    }
-                    copy_dir_recursive(&src_path, &dst_path);
-                } else if file_type.is_file() {
-                    if let Err(err) = std::fs::copy(&src_path, &dst_path) {
-                        log::warn!(
-                            "failed to copy {} to {}: {}",
-                            src_path.display(),
-                            dst_path.display(),
-                            err
-                        );
-                    }
-                }
-            }
-        }
-        Err(err) => {
-            log::warn!("failed to read dir {}: {}", src.display(), err);
-        }
-    }
-}
@@ -0,0 +1,246 @@
+//! ES module support for plugins whose manifest sets `"entryType": "module"`.
+//!
+//! Imports are confined to the plugin's own directory: only `./` and `../` specifiers are
+//! accepted, and the resolved file must canonicalize to a file inside the canonical plugin
+//! directory, the same escape check applied to `entry`. The only other importable modules are
+//! the `openusage:*` built-ins, which re-export the helpers also available on `ctx`.
+
+use crate::plugin_engine::bytecode::{self, BytecodeCache};
+use crate::plugin_engine::manifest::LoadedPlugin;
+use rquickjs::loader::{Loader, Resolver};
+use rquickjs::module::Declared;
+use rquickjs::{Ctx, Error, Module, Object};
+use std::path::{Path, PathBuf};
+
+const BUILTIN_PREFIX: &str = "openusage:";
+
+/// Built-in modules and the `ctx` member each one re-exports.
+const BUILTIN_MODULES: [&str; 6] = ["line", "fmt", "util", "base64", "jwt", "host"];
+
+fn is_builtin(name: &str) -> bool {
+    name.strip_prefix(BUILTIN_PREFIX)
+        .is_some_and(|member| BUILTIN_MODULES.contains(&member))
+}
+
+/// Join a relative specifier onto the directory of `base`, lexically. Returns `None` for
+/// anything that is not a relative path or that climbs above the plugin root.
+fn join_relative(base: &str, specifier: &str) -> Option<String> {
+    if !specifier.starts_with("./") && !specifier.starts_with("../") {
+        return None;
+    }
+    let mut parts: Vec<&str> = base.split('/').collect();
+    parts.pop();
+    for segment in specifier.split('/') {
+        match segment {
+            "" | "." => {}
+            ".." => {
+                parts.pop()?;
+            }
+            other => parts.push(other),
+        }
+    }
+    Some(parts.join("/"))
+}
+
+/// Canonicalize `relative` inside `plugin_dir` (already canonical) and return the module name,
+/// the file's path relative to the plugin directory with `/` separators.
+pub fn resolve_in_plugin_dir(plugin_dir: &Path, relative: &str) -> Result<String, String> {
+    if relative.trim().is_empty() || Path::new(relative).is_absolute() {
+        return Err("module path must be relative".to_string());
+    }
+    let canonical = plugin_dir
+        .join(relative)
+        .canonicalize()
+        .map_err(|_| "module not found".to_string())?;
+    let inside = canonical
+        .strip_prefix(plugin_dir)
+        .map_err(|_| "module must remain within plugin directory".to_string())?;
+    if !canonical.is_file() {
+        return Err("module must be a file".to_string());
+    }
+    let parts: Vec<String> = inside
+        .components()
+        .map(|part| part.as_os_str().to_string_lossy().into_owned())
+        .collect();
+    Ok(parts.join("/"))
+}
+
+/// Resolves `openusage:*` built-ins and relative imports inside one plugin's directory.
+pub struct PluginResolver {
+    plugin_dir: PathBuf,
+}
+
+impl PluginResolver {
+    pub fn new(plugin_dir: PathBuf) -> Self {
+        Self { plugin_dir }
+    }
+}
+
+impl Resolver for PluginResolver {
+    fn resolve<'js>(
+        &mut self,
+        _ctx: &Ctx<'js>,
+        base: &str,
+        name: &str,
+    ) -> rquickjs::Result<String> {
+        if name.starts_with(BUILTIN_PREFIX) {
+            if is_builtin(name) {
+                return Ok(name.to_string());
+            }
+            return Err(Error::new_resolving_message(
+                base,
+                name,
+                "unknown built-in module",
+            ));
+        }
+        // Built-ins have no location, so nothing can be relative to them.
+        let relative = if base.starts_with(BUILTIN_PREFIX) {
+            None
+        } else {
+            join_relative(base, name)
+        };
+        let relative = relative.ok_or_else(|| {
+            Error::new_resolving_message(
+                base,
+                name,
+                "only relative imports inside the plugin directory are allowed",
+            )
+        })?;
+        resolve_in_plugin_dir(&self.plugin_dir, &relative)
+            .map_err(|message| Error::new_resolving_message(base, name, message))
+    }
+}
+
+/// Loads plugin sources (through the bytecode cache) and generates the built-in modules. Plugin
+/// files are served from the copies read by `load_single_plugin`, never from disk.
+pub struct PluginLoader {
+    plugin: LoadedPlugin,
+    entry_name: String,
+    cache: BytecodeCache,
+    app_version: String,
+}
+
+impl PluginLoader {
+    pub fn new(
+        plugin: &LoadedPlugin,
+        entry_name: String,
+        cache: BytecodeCache,
+        app_version: &str,
+    ) -> Self {
+        Self {
+            plugin: plugin.clone(),
+            entry_name,
+            cache,
+            app_version: app_version.to_string(),
+        }
+    }
+}
+
+impl Loader for PluginLoader {
+    fn load<'js>(&mut self, ctx: &Ctx<'js>, name: &str) -> rquickjs::Result<Module<'js, Declared>> {
+        if is_builtin(name) {
+            return Module::declare(ctx.clone(), name, builtin_source(ctx, name)?);
+        }
+        // Every source was read (and checked) when the plugin loaded; a file changed or added
+        // on disk since then is not what the signature covered, so it is never picked up.
+        let source = if name == self.entry_name {
+            self.plugin.entry_script.clone()
+        } else {
+            self.plugin
+                .module_sources
+                .get(name)
+                .cloned()
+                .ok_or_else(|| {
+                    Error::new_loading_message(name, "module was not part of the loaded plugin")
+                })?
+        };
+        let key = bytecode::module_cache_key(&self.plugin, &self.app_version, name, &source);
+        self.cache.declare_module(ctx, &key, name, &source)
+    }
+}
+
+fn is_identifier(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '$')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '$')
+}
+
+/// Source for `openusage:<member>`: a default export of `ctx.<member>` plus one named export
+/// per public property, read from the live object so the two never drift apart.
+fn builtin_source(ctx: &Ctx<'_>, name: &str) -> rquickjs::Result<String> {
+    let member = name.strip_prefix(BUILTIN_PREFIX).unwrap_or(name);
+    let probe_ctx: Object = ctx.globals().get("__openusage_ctx")?;
+    let target: Object = probe_ctx.get(member)?;
+    let exports: Vec<String> = target
+        .keys::<String>()
+        .filter_map(Result::ok)
+        .filter(|key| is_identifier(key))
+        .collect();
+    Ok(format!(
+        "const m = globalThis.__openusage_ctx.{member};\nexport default m;\nexport const {{ {} }} = m;\n",
+        exports.join(", ")
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_plugin_dir(label: &str) -> PathBuf {
+        let nanos = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .expect("time")
+            .as_nanos();
+        let dir = std::env::temp_dir().join(format!("openusage-modules-{}-{}", label, nanos));
+        std::fs::create_dir_all(dir.join("plugin/lib")).expect("create dir");
+        dir
+    }
+
+    #[test]
+    fn join_relative_stays_lexical() {
+        assert_eq!(
+            join_relative("plugin.js", "./lib/a.js"),
+            Some("lib/a.js".into())
+        );
+        assert_eq!(join_relative("lib/a.js", "../b.js"), Some("b.js".into()));
+        assert_eq!(join_relative("lib/a.js", "./c.js"), Some("lib/c.js".into()));
+        assert_eq!(join_relative("plugin.js", "../escape.js"), None);
+        assert_eq!(join_relative("plugin.js", "lodash"), None);
+        assert_eq!(join_relative("plugin.js", "/etc/passwd"), None);
+    }
+
+    #[test]
+    fn resolve_in_plugin_dir_rejects_escapes() {
+        let root = temp_plugin_dir("escape");
+        std::fs::write(root.join("plugin/lib/a.js"), "export const a = 1;").expect("write");
+        std::fs::write(root.join("outside.js"), "export const x = 1;").expect("write");
+        let plugin_dir = root.join("plugin").canonicalize().expect("canonical");
+
+        assert_eq!(
+            resolve_in_plugin_dir(&plugin_dir, "lib/a.js"),
+            Ok("lib/a.js".to_string())
+        );
+        assert!(resolve_in_plugin_dir(&plugin_dir, "lib/../../outside.js").is_err());
+        assert!(resolve_in_plugin_dir(&plugin_dir, "lib").is_err());
+        assert!(resolve_in_plugin_dir(&plugin_dir, "missing.js").is_err());
+
+        #[cfg(unix)]
+        {
+            std::os::unix::fs::symlink(root.join("outside.js"), root.join("plugin/link.js"))
+                .expect("symlink");
+            assert!(resolve_in_plugin_dir(&plugin_dir, "link.js").is_err());
+        }
+
+        let _ = std::fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn builtin_names_are_fixed() {
+        assert!(is_builtin("openusage:line"));
+        assert!(is_builtin("openusage:host"));
+        assert!(!is_builtin("openusage:fs"));
+        assert!(!is_builtin("line"));
+    }
+}
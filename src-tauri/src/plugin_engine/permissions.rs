@@ -0,0 +1,642 @@
+//! Enforcement of the capabilities a plugin declares in its manifest.
+//!
+//! Patterns are expanded once when the probe starts (`~/`, `${VAR}` for declared env vars) and
+//! every host API call is checked against them. Denials are logged and returned as a message
+//! the caller throws as a `permission_denied` error.
+//!
+//! Paths are compared after symlinks are resolved, on both sides: a link inside an allowed tree
+//! that points outside it is judged by its target.
+
+use crate::plugin_engine::host_api;
+use crate::plugin_engine::manifest::Capabilities;
+use std::path::{Component, Path, PathBuf};
+
+#[derive(Debug, Clone)]
+pub struct Permissions {
+    plugin_id: String,
+    env: Vec<String>,
+    fs_read: Vec<String>,
+    fs_write: Vec<String>,
+    http: Vec<String>,
+    keychain: Vec<String>,
+    sqlite: Vec<String>,
+    ls: bool,
+    ccusage: bool,
+    insecure_tls: bool,
+}
+
+impl Permissions {
+    pub fn new(
+        plugin_id: &str,
+        env: &[String],
+        capabilities: &Capabilities,
+        plugin_data_dir: &Path,
+    ) -> Self {
+        Self::with_env_lookup(
+            plugin_id,
+            env,
+            capabilities,
+            plugin_data_dir,
+            &host_api::resolve_env_value,
+        )
+    }
+
+    fn with_env_lookup(
+        plugin_id: &str,
+        env: &[String],
+        capabilities: &Capabilities,
+        plugin_data_dir: &Path,
+        lookup: &dyn Fn(&str) -> Option<String>,
+    ) -> Self {
+        let expand = |patterns: &[String]| -> Vec<String> {
+            patterns
+                .iter()
+                .filter_map(|pattern| expand_env(plugin_id, pattern, env, lookup))
+                .collect()
+        };
+        let expand_paths = |patterns: &[String]| -> Vec<String> {
+            expand(patterns)
+                .iter()
+                .map(|pattern| resolve_pattern(&host_api::expand_path(pattern)))
+                .collect()
+        };
+        // The plugin's own data directory is always readable and writable.
+        let data_dir_pattern = resolve_pattern(&format!("{}/**", plugin_data_dir.display()));
+
+        let mut fs_write = expand_paths(&capabilities.fs.write);
+        fs_write.push(data_dir_pattern.clone());
+        let mut sqlite = expand_paths(&capabilities.sqlite);
+        sqlite.push(data_dir_pattern);
+        let http = expand(&capabilities.http)
+            .iter()
+            .map(|pattern| url_host_pattern(pattern))
+            .collect();
+
+        Self {
+            plugin_id: plugin_id.to_string(),
+            env: env.to_vec(),
+            fs_read: expand_paths(&capabilities.fs.read),
+            fs_write,
+            http,
+            keychain: capabilities.keychain.clone(),
+            sqlite,
+            ls: capabilities.ls,
+            ccusage: capabilities.ccusage,
+            insecure_tls: capabilities.insecure_tls,
+        }
+    }
+
+    fn deny(&self, message: String) -> Result<(), String> {
+        log::warn!("[plugin:{}] {}", self.plugin_id, message);
+        Err(message)
+    }
+
+    /// Env reads are soft-denied: `host.env.get` returns `null` for undeclared names.
+    pub fn allows_env(&self, name: &str) -> bool {
+        if self.env.iter().any(|allowed| allowed == name) {
+            return true;
+        }
+        log::warn!(
+            "[plugin:{}] env read denied: {} is not listed in plugin.json \"env\"",
+            self.plugin_id,
+            name
+        );
+        false
+    }
+
+    pub fn check_fs_read(&self, path: &str) -> Result<(), String> {
+        if let Some(resolved) = resolve_path(&host_api::expand_path(path))
+            && (matches_any_path(&self.fs_read, &resolved)
+                || matches_any_path(&self.fs_write, &resolved))
+        {
+            return Ok(());
+        }
+        self.deny(format!(
+            "permission denied: reading {} is not declared in capabilities.fs.read",
+            path
+        ))
+    }
+
+    pub fn check_fs_write(&self, path: &str) -> Result<(), String> {
+        if let Some(resolved) = resolve_path(&host_api::expand_path(path))
+            && matches_any_path(&self.fs_write, &resolved)
+        {
+            return Ok(());
+        }
+        self.deny(format!(
+            "permission denied: writing {} is not declared in capabilities.fs.write",
+            path
+        ))
+    }
+
+    pub fn check_http(&self, url: &str, ignore_tls: bool) -> Result<(), String> {
+        let parsed = match reqwest::Url::parse(url) {
+            Ok(parsed) if matches!(parsed.scheme(), "http" | "https") => parsed,
+            _ => {
+                return self.deny(format!("permission denied: {} is not an http(s) URL", url));
+            }
+        };
+        let host = parsed.host_str().unwrap_or("").to_ascii_lowercase();
+        let port = parsed.port_or_known_default();
+        if !self
+            .http
+            .iter()
+            .any(|pattern| host_pattern_matches(pattern, &host, port))
+        {
+            return self.deny(format!(
+                "permission denied: HTTP requests to {} are not declared in capabilities.http",
+                host
+            ));
+        }
+        if ignore_tls && !self.insecure_tls {
+            return self.deny(
+                "permission denied: dangerouslyIgnoreTls requires capabilities.insecureTls"
+                    .to_string(),
+            );
+        }
+        Ok(())
+    }
+
+    pub fn check_keychain(&self, service: &str) -> Result<(), String> {
+        if self
+            .keychain
+            .iter()
+            .any(|pattern| wildcard_match(pattern, service))
+        {
+            return Ok(());
+        }
+        self.deny(format!(
+            "permission denied: keychain service \"{}\" is not declared in capabilities.keychain",
+            service
+        ))
+    }
+
+    pub fn check_sqlite(&self, db_path: &str) -> Result<(), String> {
+        if let Some(resolved) = resolve_path(&host_api::expand_path(db_path))
+            && matches_any_path(&self.sqlite, &resolved)
+        {
+            return Ok(());
+        }
+        self.deny(format!(
+            "permission denied: database {} is not declared in capabilities.sqlite",
+            db_path
+        ))
+    }
+
+    pub fn check_ls(&self) -> Result<(), String> {
+        if self.ls {
+            return Ok(());
+        }
+        self.deny("permission denied: host.ls requires capabilities.ls".to_string())
+    }
+
+    pub fn check_ccusage(&self) -> Result<(), String> {
+        if self.ccusage {
+            return Ok(());
+        }
+        self.deny("permission denied: host.ccusage requires capabilities.ccusage".to_string())
+    }
+}
+
+/// Substitute `${VAR}` references. Patterns referencing an unset variable are dropped, as are
+/// those referencing a variable the plugin did not declare.
+fn expand_env(
+    plugin_id: &str,
+    pattern: &str,
+    env: &[String],
+    lookup: &dyn Fn(&str) -> Option<String>,
+) -> Option<String> {
+    let mut expanded = String::with_capacity(pattern.len());
+    let mut rest = pattern;
+    while let Some(start) = rest.find("${") {
+        let end = start + rest[start..].find('}')?;
+        let name = &rest[start + 2..end];
+        if !env.iter().any(|declared| declared == name) {
+            log::warn!(
+                "plugin {} capability '{}' uses undeclared env var {}; ignoring",
+                plugin_id,
+                pattern,
+                name
+            );
+            return None;
+        }
+        let value = lookup(name)?;
+        expanded.push_str(&rest[..start]);
+        expanded.push_str(value.trim_end_matches('/'));
+        rest = &rest[end + 1..];
+    }
+    expanded.push_str(rest);
+    Some(expanded)
+}
+
+/// `https://api.example.com/v1` (e.g. from an env var) becomes `api.example.com:443`.
+fn url_host_pattern(pattern: &str) -> String {
+    if pattern.contains("://")
+        && let Ok(url) = reqwest::Url::parse(pattern)
+        && let Some(host) = url.host_str()
+    {
+        return match url.port_or_known_default() {
+            Some(port) => format!("{}:{}", host, port),
+            None => host.to_string(),
+        };
+    }
+    pattern.to_ascii_lowercase()
+}
+
+fn host_pattern_matches(pattern: &str, host: &str, port: Option<u16>) -> bool {
+    let (host_pattern, port_pattern) = match pattern.rsplit_once(':') {
+        Some((host_pattern, port_pattern))
+            if port_pattern == "*" || port_pattern.bytes().all(|b| b.is_ascii_digit()) =>
+        {
+            (host_pattern, Some(port_pattern))
+        }
+        _ => (pattern, None),
+    };
+    if let Some(port_pattern) = port_pattern {
+        let port = port.map(|port| port.to_string()).unwrap_or_default();
+        if !wildcard_match(port_pattern, &port) {
+            return false;
+        }
+    }
+    let pattern_labels: Vec<&str> = host_pattern.split('.').collect();
+    let host_labels: Vec<&str> = host.split('.').collect();
+    match_segments(&pattern_labels, &host_labels)
+}
+
+/// Lexically resolve `.` and `..` so `~/.claude/../.ssh` cannot slip past a `~/.claude/**`
+/// pattern, and join components with `/` on every platform.
+fn normalize_path(path: &str) -> String {
+    let mut prefix = String::new();
+    let mut parts: Vec<String> = Vec::new();
+    for component in Path::new(path).components() {
+        match component {
+            Component::Prefix(value) => prefix = value.as_os_str().to_string_lossy().to_string(),
+            Component::RootDir => prefix.push('/'),
+            Component::CurDir => {}
+            Component::ParentDir => {
+                parts.pop();
+            }
+            Component::Normal(part) => parts.push(part.to_string_lossy().to_string()),
+        }
+    }
+    format!("{}{}", prefix, parts.join("/"))
+}
+
+/// The path the OS would actually open: the deepest existing ancestor is canonicalized (the
+/// whole path when it exists) and the missing tail appended. `None` when that cannot be known,
+/// e.g. a dangling symlink (writing through it lands wherever it points) or a `..` below a
+/// missing directory.
+fn resolve_path(path: &str) -> Option<String> {
+    let mut existing = Path::new(path);
+    let mut missing = Vec::new();
+    loop {
+        match existing.canonicalize() {
+            Ok(canonical) => {
+                let resolved: PathBuf = canonical.join(missing.iter().rev().collect::<PathBuf>());
+                return Some(normalize_path(&resolved.to_string_lossy()));
+            }
+            Err(_) if existing.symlink_metadata().is_ok() => return None,
+            Err(_) => match existing.components().next_back()? {
+                Component::Normal(part) => {
+                    missing.push(part);
+                    existing = existing.parent()?;
+                }
+                _ => return None,
+            },
+        }
+    }
+}
+
+/// Resolve the literal directories in front of the first wildcard segment, so a pattern for a
+/// symlinked directory (say `~/.claude` pointing into a dotfiles repo) still matches paths
+/// resolved by `resolve_path`.
+fn resolve_pattern(pattern: &str) -> String {
+    let normalized = normalize_path(pattern);
+    let segments: Vec<&str> = normalized.split('/').collect();
+    let literal = segments
+        .iter()
+        .position(|segment| segment.contains(['*', '?']))
+        .unwrap_or(segments.len());
+    let (prefix, rest) = segments.split_at(literal);
+    let prefix = prefix.join("/");
+    let Some(resolved) = resolve_path(&prefix) else {
+        return normalized;
+    };
+    std::iter::once(resolved.as_str())
+        .chain(rest.iter().copied())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+fn matches_any_path(patterns: &[String], path: &str) -> bool {
+    let path_segments: Vec<&str> = path.split('/').collect();
+    patterns.iter().any(|pattern| {
+        let pattern_segments: Vec<&str> = pattern.split('/').collect();
+        match_segments(&pattern_segments, &path_segments)
+    })
+}
+
+/// `**` matches any number of segments (including none); other segments use `wildcard_match`.
+fn match_segments(pattern: &[&str], text: &[&str]) -> bool {
+    match pattern.split_first() {
+        None => text.is_empty(),
+        Some((&"**", rest)) => (0..=text.len()).any(|skip| match_segments(rest, &text[skip..])),
+        Some((segment, rest)) => text.split_first().is_some_and(|(first, text_rest)| {
+            wildcard_match(segment, first) && match_segments(rest, text_rest)
+        }),
+    }
+}
+
+/// `*` matches any run of characters, `?` exactly one.
+fn wildcard_match(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+    let (mut p, mut t) = (0, 0);
+    let mut backtrack: Option<(usize, usize)> = None;
+    while t < text.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == text[t]) {
+            p += 1;
+            t += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            backtrack = Some((p, t));
+            p += 1;
+        } else if let Some((star, matched)) = backtrack {
+            p = star + 1;
+            t = matched + 1;
+            backtrack = Some((star, matched + 1));
+        } else {
+            return false;
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::plugin_engine::manifest::FsCapabilities;
+
+    fn capabilities() -> Capabilities {
+        Capabilities {
+            fs: FsCapabilities {
+                read: vec!["~/.claude/**".to_string(), "/etc/hosts".to_string()],
+                write: vec!["${CLAUDE_CONFIG_DIR}/.credentials.json".to_string()],
+            },
+            http: vec![
+                "api.anthropic.com".to_string(),
+                "*.googleapis.com".to_string(),
+                "127.0.0.1:8080".to_string(),
+                "${CUSTOM_URL}".to_string(),
+            ],
+            keychain: vec!["Claude Code*-credentials".to_string()],
+            sqlite: vec!["~/Library/**/state.vscdb".to_string()],
+            ls: false,
+            ccusage: true,
+            insecure_tls: false,
+        }
+    }
+
+    fn permissions() -> Permissions {
+        let env = vec!["CLAUDE_CONFIG_DIR".to_string(), "CUSTOM_URL".to_string()];
+        Permissions::with_env_lookup(
+            "claude",
+            &env,
+            &capabilities(),
+            Path::new("/data/plugins_data/claude"),
+            &|name| match name {
+                "CLAUDE_CONFIG_DIR" => Some("/work/claude-home/".to_string()),
+                "CUSTOM_URL" => Some("https://oauth.corp.example:8443/base".to_string()),
+                _ => None,
+            },
+        )
+    }
+
+    #[test]
+    fn fs_patterns_cover_globs_env_vars_and_the_data_dir() {
+        let permissions = permissions();
+        assert!(permissions.check_fs_read("~/.claude").is_ok());
+        assert!(
+            permissions
+                .check_fs_read("~/.claude/projects/a.jsonl")
+                .is_ok()
+        );
+        assert!(permissions.check_fs_read("/etc/hosts").is_ok());
+        assert!(
+            permissions
+                .check_fs_read("/work/claude-home/.credentials.json")
+                .is_ok()
+        );
+        assert!(
+            permissions
+                .check_fs_write("/work/claude-home/.credentials.json")
+                .is_ok()
+        );
+        assert!(
+            permissions
+                .check_fs_write("/data/plugins_data/claude/auth.json")
+                .is_ok()
+        );
+
+        assert!(
+            permissions
+                .check_fs_write("~/.claude/settings.json")
+                .is_err()
+        );
+        assert!(permissions.check_fs_read("~/.ssh/id_rsa").is_err());
+        assert!(
+            permissions
+                .check_fs_read("~/.claude/../.ssh/id_rsa")
+                .is_err()
+        );
+        assert!(
+            permissions
+                .check_fs_write("/data/plugins_data/other/auth.json")
+                .is_err()
+        );
+        assert_eq!(
+            permissions.check_fs_read("/etc/passwd").unwrap_err(),
+            "permission denied: reading /etc/passwd is not declared in capabilities.fs.read"
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn symlinks_are_judged_by_their_target() {
+        use std::os::unix::fs::symlink;
+
+        let root =
+            std::env::temp_dir().join(format!("openusage-permissions-{}", uuid::Uuid::new_v4()));
+        let home = root.join("home");
+        let data_dir = root.join("plugins_data/claude");
+        for dir in [home.join(".claude"), home.join(".ssh"), data_dir.clone()] {
+            std::fs::create_dir_all(dir).expect("create dir");
+        }
+        std::fs::write(home.join(".claude/settings.json"), "{}").expect("write settings");
+        std::fs::write(home.join(".ssh/id_rsa"), "secret").expect("write key");
+        symlink(home.join(".ssh/id_rsa"), home.join(".claude/x")).expect("symlink file");
+        symlink(home.join(".ssh"), home.join(".claude/keys")).expect("symlink dir");
+        symlink(home.join(".ssh/id_rsa"), data_dir.join("x")).expect("symlink data file");
+        symlink(home.join(".ssh"), data_dir.join("keys")).expect("symlink data dir");
+        symlink(home.join(".ssh/new"), data_dir.join("dangling")).expect("symlink dangling");
+
+        let mut capabilities = Capabilities::default();
+        capabilities.fs.read = vec![format!("{}/.claude/**", home.display())];
+        let permissions =
+            Permissions::with_env_lookup("claude", &[], &capabilities, &data_dir, &|_| None);
+        let path = |relative: &str| root.join(relative).to_string_lossy().into_owned();
+
+        assert!(
+            permissions
+                .check_fs_read(&path("home/.claude/settings.json"))
+                .is_ok()
+        );
+        assert!(
+            permissions
+                .check_fs_write(&path("plugins_data/claude/new/auth.json"))
+                .is_ok()
+        );
+
+        assert!(permissions.check_fs_read(&path("home/.claude/x")).is_err());
+        assert!(
+            permissions
+                .check_fs_read(&path("home/.claude/keys/id_rsa"))
+                .is_err()
+        );
+        assert!(
+            permissions
+                .check_fs_read(&path("home/.claude/keys/../id_rsa"))
+                .is_err()
+        );
+        assert!(
+            permissions
+                .check_fs_read(&path("plugins_data/claude/x"))
+                .is_err()
+        );
+        assert!(
+            permissions
+                .check_fs_write(&path("plugins_data/claude/x"))
+                .is_err()
+        );
+        assert!(
+            permissions
+                .check_fs_write(&path("plugins_data/claude/keys/new"))
+                .is_err()
+        );
+        assert!(
+            permissions
+                .check_fs_write(&path("plugins_data/claude/dangling"))
+                .is_err()
+        );
+        assert!(
+            permissions
+                .check_sqlite(&path("plugins_data/claude/x"))
+                .is_err()
+        );
+
+        let _ = std::fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn http_patterns_match_hosts_ports_and_tls_bypass() {
+        let permissions = permissions();
+        assert!(
+            permissions
+                .check_http("https://api.anthropic.com/api/oauth/usage", false)
+                .is_ok()
+        );
+        assert!(
+            permissions
+                .check_http("https://API.Anthropic.com/", false)
+                .is_ok()
+        );
+        assert!(
+            permissions
+                .check_http("https://oauth2.googleapis.com/token", false)
+                .is_ok()
+        );
+        assert!(
+            permissions
+                .check_http("http://127.0.0.1:8080/x", false)
+                .is_ok()
+        );
+        assert!(
+            permissions
+                .check_http("https://oauth.corp.example:8443/v1/oauth/token", false)
+                .is_ok()
+        );
+
+        assert!(
+            permissions
+                .check_http("https://googleapis.com/", false)
+                .is_err()
+        );
+        assert!(
+            permissions
+                .check_http("http://127.0.0.1:9090/", false)
+                .is_err()
+        );
+        assert!(
+            permissions
+                .check_http("https://api.anthropic.com.evil.test/", false)
+                .is_err()
+        );
+        assert!(permissions.check_http("file:///etc/passwd", false).is_err());
+        assert_eq!(
+            permissions
+                .check_http("https://api.anthropic.com/", true)
+                .unwrap_err(),
+            "permission denied: dangerouslyIgnoreTls requires capabilities.insecureTls"
+        );
+    }
+
+    #[test]
+    fn keychain_sqlite_and_flags() {
+        let permissions = permissions();
+        assert!(
+            permissions
+                .check_keychain("Claude Code-credentials")
+                .is_ok()
+        );
+        assert!(
+            permissions
+                .check_keychain("Claude Code-staging-oauth-credentials")
+                .is_ok()
+        );
+        assert!(permissions.check_keychain("gh:github.com").is_err());
+
+        assert!(
+            permissions
+                .check_sqlite("~/Library/Application Support/Cursor/User/globalStorage/state.vscdb")
+                .is_ok()
+        );
+        assert!(permissions.check_sqlite("~/Library/other.db").is_err());
+
+        assert!(permissions.check_ccusage().is_ok());
+        assert_eq!(
+            permissions.check_ls().unwrap_err(),
+            "permission denied: host.ls requires capabilities.ls"
+        );
+    }
+
+    #[test]
+    fn patterns_with_unset_or_undeclared_env_vars_are_dropped() {
+        let mut capabilities = Capabilities::default();
+        capabilities.fs.read = vec!["${UNSET}/**".to_string(), "${SECRET}/**".to_string()];
+        let permissions = Permissions::with_env_lookup(
+            "x",
+            &["UNSET".to_string()],
+            &capabilities,
+            Path::new("/data/plugins_data/x"),
+            &|name| (name == "SECRET").then(|| "/".to_string()),
+        );
+        assert!(permissions.check_fs_read("/etc/passwd").is_err());
+        assert!(permissions.check_fs_read("/data/plugins_data/x/a").is_ok());
+    }
+
+    #[test]
+    fn env_reads_are_limited_to_declared_names() {
+        let permissions = permissions();
+        assert!(permissions.allows_env("CLAUDE_CONFIG_DIR"));
+        assert!(!permissions.allows_env("ZAI_API_KEY"));
+    }
+}
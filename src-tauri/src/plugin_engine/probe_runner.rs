@@ -0,0 +1,548 @@
+//! Out-of-process probe execution.
+//!
+//! The app re-executes its own binary with [`PROBE_RUNNER_ARG`]. The child reads
+//! [`ProbeRequest`]s as JSON lines on stdin, runs each probe in-process and answers with the
+//! resulting [`PluginOutput`] as one JSON line on stdout. Log records are forwarded to the
+//! parent over stderr. A crash, abort or hang in the child only ever costs the probe it was
+//! running.
+//!
+//! Runner processes are pooled per plugin: an idle runner is reused for the plugin's next probe,
+//! so its QuickJS runtime and loaded bytecode are set up once instead of on every refresh.
+
+use crate::plugin_engine::bytecode::BytecodeCache;
+use crate::plugin_engine::manifest::LoadedPlugin;
+use crate::plugin_engine::runtime::{self, PluginOutput, ProbeError, ProbeErrorCode, ProbeRuntime};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::io::{BufRead, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Stdio};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::sync::{Mutex, OnceLock};
+use std::time::Duration;
+
+/// First CLI argument that switches the app binary into probe-runner mode.
+pub const PROBE_RUNNER_ARG: &str = "--probe-runner";
+
+/// Extra time the child gets on top of the probe deadline to serialize its result.
+const RUNNER_GRACE_PERIOD: Duration = Duration::from_secs(5);
+const LOG_LINE_PREFIX: &str = "@openusage-log\t";
+/// A runner is replaced after this many probes so slow leaks in host code cannot accumulate.
+const MAX_PROBES_PER_RUNNER: usize = 25;
+/// Idle runners kept per plugin; extra ones from overlapping probes are shut down.
+const MAX_IDLE_RUNNERS_PER_PLUGIN: usize = 1;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProbeRequest {
+    pub plugin: LoadedPlugin,
+    pub app_data_dir: PathBuf,
+    pub app_version: String,
+}
+
+#[derive(Debug)]
+enum RunnerFailure {
+    Spawn(String),
+    TimedOut,
+    Crashed(String),
+    InvalidResponse(String),
+}
+
+/// A live runner process with its request and response pipes.
+struct Runner {
+    child: Child,
+    requests: Option<Sender<Vec<u8>>>,
+    responses: Receiver<String>,
+    probes_run: usize,
+}
+
+impl Runner {
+    fn spawn(mut command: Command, plugin_id: &str) -> Result<Self, RunnerFailure> {
+        command
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped());
+        let mut child = command
+            .spawn()
+            .map_err(|e| RunnerFailure::Spawn(e.to_string()))?;
+
+        // Feed stdin and drain stdout/stderr on their own threads so neither side can block on
+        // a full pipe buffer.
+        let (request_tx, request_rx) = mpsc::channel::<Vec<u8>>();
+        if let Some(mut stdin) = child.stdin.take() {
+            std::thread::spawn(move || {
+                for payload in request_rx {
+                    if stdin
+                        .write_all(&payload)
+                        .and_then(|_| stdin.flush())
+                        .is_err()
+                    {
+                        break;
+                    }
+                }
+            });
+        }
+        let (response_tx, response_rx) = mpsc::channel();
+        if let Some(stdout) = child.stdout.take() {
+            std::thread::spawn(move || {
+                for line in std::io::BufReader::new(stdout).lines() {
+                    let Ok(line) = line else { break };
+                    if response_tx.send(line).is_err() {
+                        break;
+                    }
+                }
+            });
+        }
+        if let Some(stderr) = child.stderr.take() {
+            let pid = plugin_id.to_string();
+            std::thread::spawn(move || {
+                for line in std::io::BufReader::new(stderr).lines() {
+                    match line {
+                        Ok(line) => forward_child_log_line(&pid, &line),
+                        Err(_) => break,
+                    }
+                }
+            });
+        }
+
+        Ok(Self {
+            child,
+            requests: Some(request_tx),
+            responses: response_rx,
+            probes_run: 0,
+        })
+    }
+
+    fn is_alive(&mut self) -> bool {
+        matches!(self.child.try_wait(), Ok(None))
+    }
+
+    fn probe(
+        &mut self,
+        request: &ProbeRequest,
+        timeout: Duration,
+    ) -> Result<PluginOutput, RunnerFailure> {
+        let mut payload =
+            serde_json::to_vec(request).map_err(|e| RunnerFailure::Spawn(e.to_string()))?;
+        payload.push(b'\n');
+        self.probes_run += 1;
+        if let Some(requests) = &self.requests {
+            let _ = requests.send(payload);
+        }
+
+        match self.responses.recv_timeout(timeout) {
+            Ok(line) => serde_json::from_str::<PluginOutput>(&line)
+                .map_err(|e| RunnerFailure::InvalidResponse(e.to_string())),
+            Err(RecvTimeoutError::Timeout) => Err(RunnerFailure::TimedOut),
+            Err(RecvTimeoutError::Disconnected) => {
+                // stdout closed without an answer: the child is exiting or already gone.
+                let status = self
+                    .child
+                    .wait()
+                    .map(|status| status.to_string())
+                    .unwrap_or_else(|e| e.to_string());
+                Err(RunnerFailure::Crashed(status))
+            }
+        }
+    }
+}
+
+impl Drop for Runner {
+    fn drop(&mut self) {
+        // Closing stdin lets an idle runner exit on its own; kill covers a busy or hung one.
+        self.requests.take();
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+fn idle_runners() -> &'static Mutex<HashMap<String, Vec<Runner>>> {
+    static IDLE: OnceLock<Mutex<HashMap<String, Vec<Runner>>>> = OnceLock::new();
+    IDLE.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+fn checkout_runner(plugin_id: &str) -> Option<Runner> {
+    let mut idle = idle_runners().lock().ok()?;
+    let runners = idle.get_mut(plugin_id)?;
+    while let Some(mut runner) = runners.pop() {
+        if runner.is_alive() {
+            return Some(runner);
+        }
+    }
+    None
+}
+
+fn checkin_runner(plugin_id: &str, runner: Runner) {
+    if runner.probes_run >= MAX_PROBES_PER_RUNNER {
+        return;
+    }
+    let Ok(mut idle) = idle_runners().lock() else {
+        return;
+    };
+    let runners = idle.entry(plugin_id.to_string()).or_default();
+    if runners.len() < MAX_IDLE_RUNNERS_PER_PLUGIN {
+        runners.push(runner);
+    }
+}
+
+/// Run a probe in a runner process, always returning an output for the batch.
+pub fn run_probe_isolated(
+    plugin: &LoadedPlugin,
+    app_data_dir: &Path,
+    app_version: &str,
+) -> PluginOutput {
+    let plugin_id = plugin.manifest.id.clone();
+    let probe_timeout = runtime::probe_timeout(&plugin.manifest);
+    let request = ProbeRequest {
+        plugin: plugin.clone(),
+        app_data_dir: app_data_dir.to_path_buf(),
+        app_version: app_version.to_string(),
+    };
+
+    let runner = match checkout_runner(&plugin_id) {
+        Some(runner) => Ok(runner),
+        None => match std::env::current_exe() {
+            Ok(exe) => {
+                let mut command = Command::new(exe);
+                command.arg(PROBE_RUNNER_ARG);
+                Runner::spawn(command, &plugin_id)
+            }
+            Err(err) => {
+                log::error!("[plugin:{}] cannot locate probe runner: {}", plugin_id, err);
+                return runtime::error_output(
+                    plugin,
+                    ProbeError::new(ProbeErrorCode::Unknown, "Probe runner unavailable"),
+                );
+            }
+        },
+    };
+
+    let result = runner.and_then(|mut runner| {
+        let output = runner.probe(&request, probe_timeout + RUNNER_GRACE_PERIOD)?;
+        Ok((output, runner))
+    });
+    // On any failure the runner has been dropped above, which kills the process.
+    match result {
+        Ok((output, runner)) => {
+            checkin_runner(&plugin_id, runner);
+            output
+        }
+        Err(RunnerFailure::TimedOut) => {
+            log::warn!("[plugin:{}] probe runner killed after deadline", plugin_id);
+            runtime::timeout_output(plugin, probe_timeout)
+        }
+        Err(RunnerFailure::Crashed(status)) => {
+            log::error!("[plugin:{}] probe runner crashed: {}", plugin_id, status);
+            // A crash can be a one-off (e.g. the memory cap on an unusually large response),
+            // so let the UI offer a retry.
+            let mut error =
+                ProbeError::plugin_bug("The plugin crashed, try again or contact plugin author.");
+            error.retryable = true;
+            runtime::error_output(plugin, error)
+        }
+        Err(RunnerFailure::Spawn(err)) => {
+            log::error!("[plugin:{}] probe runner spawn failed: {}", plugin_id, err);
+            runtime::error_output(
+                plugin,
+                ProbeError::new(ProbeErrorCode::Unknown, "Probe runner failed to start"),
+            )
+        }
+        Err(RunnerFailure::InvalidResponse(err)) => {
+            log::error!(
+                "[plugin:{}] probe runner returned invalid output: {}",
+                plugin_id,
+                err
+            );
+            runtime::error_output(
+                plugin,
+                ProbeError::new(
+                    ProbeErrorCode::Unknown,
+                    "Probe runner returned invalid output",
+                ),
+            )
+        }
+    }
+}
+
+fn forward_child_log_line(plugin_id: &str, line: &str) {
+    match parse_child_log_line(line) {
+        Some((level, message)) => log::log!(level, "{}", message),
+        None => {
+            let trimmed = line.trim();
+            if !trimmed.is_empty() {
+                log::warn!("[probe-runner:{}] {}", plugin_id, trimmed);
+            }
+        }
+    }
+}
+
+fn parse_child_log_line(line: &str) -> Option<(log::Level, String)> {
+    let rest = line.strip_prefix(LOG_LINE_PREFIX)?;
+    let (level, message) = rest.split_once('\t')?;
+    let level = level.parse::<log::Level>().ok()?;
+    Some((level, unescape_log_message(message)))
+}
+
+/// Log records travel one per stderr line, so newlines (e.g. in stack traces) are escaped.
+fn escape_log_message(message: &str) -> String {
+    message
+        .replace('\\', "\\\\")
+        .replace('\n', "\\n")
+        .replace('\r', "")
+}
+
+fn unescape_log_message(message: &str) -> String {
+    let mut out = String::with_capacity(message.len());
+    let mut chars = message.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => out.push('\n'),
+            Some(other) => out.push(other),
+            None => out.push('\\'),
+        }
+    }
+    out
+}
+
+struct ChildLogger;
+
+static CHILD_LOGGER: ChildLogger = ChildLogger;
+
+impl log::Log for ChildLogger {
+    fn enabled(&self, _metadata: &log::Metadata) -> bool {
+        true
+    }
+
+    fn log(&self, record: &log::Record) {
+        let message = escape_log_message(&record.args().to_string());
+        eprintln!("{}{}\t{}", LOG_LINE_PREFIX, record.level(), message);
+    }
+
+    fn flush(&self) {}
+}
+
+/// Entry point for the child process. Serves requests until stdin closes and returns the
+/// process exit code.
+pub fn run_from_stdio() -> i32 {
+    if log::set_logger(&CHILD_LOGGER).is_ok() {
+        log::set_max_level(log::LevelFilter::Trace);
+    }
+
+    let mut runtime: Option<ProbeRuntime> = None;
+    for line in std::io::stdin().lock().lines() {
+        let line = match line {
+            Ok(line) => line,
+            Err(err) => {
+                log::error!("probe runner failed to read request: {}", err);
+                return 2;
+            }
+        };
+        if line.trim().is_empty() {
+            continue;
+        }
+        let request: ProbeRequest = match serde_json::from_str(&line) {
+            Ok(request) => request,
+            Err(err) => {
+                log::error!("probe runner received invalid request: {}", err);
+                return 2;
+            }
+        };
+
+        if runtime.is_none() {
+            match ProbeRuntime::new(BytecodeCache::new()) {
+                Ok(created) => runtime = Some(created),
+                Err(err) => {
+                    log::error!("probe runner failed to create runtime: {}", err);
+                    return 1;
+                }
+            }
+        }
+        let Some(probe_runtime) = &runtime else {
+            return 1;
+        };
+        let output =
+            probe_runtime.run(&request.plugin, &request.app_data_dir, &request.app_version);
+
+        let mut stdout = std::io::stdout().lock();
+        if let Err(err) = serde_json::to_writer(&mut stdout, &output)
+            .and_then(|_| writeln!(stdout).map_err(serde_json::Error::io))
+            .and_then(|_| stdout.flush().map_err(serde_json::Error::io))
+        {
+            log::error!("probe runner failed to write output: {}", err);
+            return 1;
+        }
+    }
+    0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::plugin_engine::manifest::{EntryType, PluginManifest};
+    use crate::plugin_engine::runtime::MetricLine;
+    use std::time::Instant;
+
+    fn test_request() -> ProbeRequest {
+        ProbeRequest {
+            plugin: LoadedPlugin {
+                manifest: PluginManifest {
+                    schema_version: 1,
+                    id: "test".to_string(),
+                    name: "Test".to_string(),
+                    version: "0.0.0".to_string(),
+                    entry: "plugin.js".to_string(),
+                    entry_type: EntryType::Script,
+                    min_app_version: None,
+                    requires: Vec::new(),
+                    icon: "icon.svg".to_string(),
+                    icons: Default::default(),
+                    brand_color: None,
+                    lines: vec![],
+                    links: vec![],
+                    probe_timeout_ms: None,
+                    settings: Vec::new(),
+                    env: Vec::new(),
+                    capabilities: Default::default(),
+                    detect: Default::default(),
+                    http: None,
+                },
+                plugin_dir: PathBuf::from("."),
+                entry_script: "globalThis.__openusage_plugin = {};".to_string(),
+                module_sources: Default::default(),
+                icon_data_url: "data:image/svg+xml;base64,".to_string(),
+                icon_variant_data_urls: Default::default(),
+                trust: Default::default(),
+                incompatibility: None,
+            },
+            app_data_dir: std::env::temp_dir(),
+            app_version: "0.0.0".to_string(),
+        }
+    }
+
+    #[cfg(unix)]
+    fn shell(script: &str) -> Command {
+        let mut command = Command::new("/bin/sh");
+        command.args(["-c", script]);
+        command
+    }
+
+    #[cfg(unix)]
+    fn probe_once(
+        command: Command,
+        request: &ProbeRequest,
+        timeout: Duration,
+    ) -> Result<PluginOutput, RunnerFailure> {
+        Runner::spawn(command, "test")?.probe(request, timeout)
+    }
+
+    #[test]
+    fn probe_request_round_trips_through_json() {
+        let json = serde_json::to_string(&test_request()).expect("serialize");
+        let parsed: ProbeRequest = serde_json::from_str(&json).expect("deserialize");
+        assert_eq!(parsed.plugin.manifest.id, "test");
+        assert_eq!(
+            parsed.plugin.entry_script,
+            "globalThis.__openusage_plugin = {};"
+        );
+        assert_eq!(parsed.app_version, "0.0.0");
+    }
+
+    #[test]
+    fn parse_child_log_line_extracts_level_and_message() {
+        assert_eq!(
+            parse_child_log_line("@openusage-log\tWARN\t[plugin:x] hello\tworld"),
+            Some((log::Level::Warn, "[plugin:x] hello\tworld".to_string()))
+        );
+        assert_eq!(parse_child_log_line("thread 'main' panicked"), None);
+        assert_eq!(parse_child_log_line("@openusage-log\tLOUD\tx"), None);
+    }
+
+    #[test]
+    fn log_messages_with_newlines_round_trip() {
+        let message = "probe threw Error: boom\n    at probe (plugin.js:3:9)\n        | a\\nb";
+        let escaped = escape_log_message(message);
+        assert!(!escaped.contains('\n'));
+        let line = format!("{}ERROR\t{}", LOG_LINE_PREFIX, escaped);
+        assert_eq!(
+            parse_child_log_line(&line),
+            Some((log::Level::Error, message.to_string()))
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn runner_parses_output_from_stdout() {
+        let command = shell(
+            r#"read -r _; printf '%s\n' '{"providerId":"test","displayName":"Test","plan":null,"lines":[{"type":"text","label":"A","value":"B","color":null,"subtitle":null}],"iconUrl":""}'"#,
+        );
+        let output =
+            probe_once(command, &test_request(), Duration::from_secs(5)).expect("child output");
+        assert_eq!(output.provider_id, "test");
+        assert!(output.error.is_none());
+        assert!(matches!(
+            output.lines.first(),
+            Some(MetricLine::Text { value, .. }) if value == "B"
+        ));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn runner_preserves_probe_error() {
+        let command = shell(
+            r#"read -r _; printf '%s\n' '{"providerId":"test","displayName":"Test","status":"error","error":{"code":"auth_required","message":"Sign in","retryable":false},"plan":null,"lines":[],"iconUrl":""}'"#,
+        );
+        let output =
+            probe_once(command, &test_request(), Duration::from_secs(5)).expect("child output");
+        let error = output.error.expect("probe error");
+        assert_eq!(error.code, ProbeErrorCode::AuthRequired);
+        assert_eq!(error.message, "Sign in");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn runner_reports_crash_on_nonzero_exit() {
+        let command = shell("read -r _; exit 3");
+        let result = probe_once(command, &test_request(), Duration::from_secs(5));
+        assert!(matches!(result, Err(RunnerFailure::Crashed(_))));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn runner_kills_hung_runner() {
+        let command = shell("exec sleep 30");
+        let start = Instant::now();
+        let result = probe_once(command, &test_request(), Duration::from_millis(200));
+        assert!(matches!(result, Err(RunnerFailure::TimedOut)));
+        assert!(start.elapsed() < Duration::from_secs(10));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn runner_rejects_invalid_output() {
+        let command = shell("read -r _; echo not-json");
+        let result = probe_once(command, &test_request(), Duration::from_secs(5));
+        assert!(matches!(result, Err(RunnerFailure::InvalidResponse(_))));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn runner_serves_several_probes_in_one_process() {
+        let command = shell(
+            r#"while read -r _; do printf '%s\n' "{\"providerId\":\"test\",\"displayName\":\"$$\",\"plan\":null,\"lines\":[],\"iconUrl\":\"\"}"; done"#,
+        );
+        let mut runner = Runner::spawn(command, "test").expect("spawn");
+        let first = runner
+            .probe(&test_request(), Duration::from_secs(5))
+            .expect("first probe");
+        let second = runner
+            .probe(&test_request(), Duration::from_secs(5))
+            .expect("second probe");
+        assert_eq!(first.display_name, second.display_name);
+        assert_eq!(runner.probes_run, 2);
+        assert!(runner.is_alive());
+    }
+}
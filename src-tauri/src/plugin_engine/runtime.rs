@@ -1,15 +1,71 @@
-use crate::plugin_engine::host_api;
-use crate::plugin_engine::manifest::LoadedPlugin;
-use rquickjs::{Array, Context, Ctx, Error, Object, Promise, Runtime, Value};
+use crate::plugin_engine::bytecode::{self, BytecodeCache};
+use crate::plugin_engine::declarative;
+use crate::plugin_engine::host_api::{self, ProbeDeadline};
+use crate::plugin_engine::host_jobs::{self, HostJobs};
+use crate::plugin_engine::js_error::{self, ThrownValue};
+use crate::plugin_engine::manifest::{EntryType, LoadedPlugin, PluginManifest};
+use crate::plugin_engine::modules;
+use crate::plugin_engine::permissions::Permissions;
+use crate::plugin_engine::settings::{self, KeychainSecrets};
+use rquickjs::{Array, Context, Ctx, Error, Function, Module, Object, Promise, Runtime, Value};
 use serde::{Deserialize, Serialize};
 use std::path::PathBuf;
+use std::rc::Rc;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+/// Default wall-clock budget for one probe when the manifest does not set `probeTimeoutMs`.
+pub const DEFAULT_PROBE_TIMEOUT_MS: u64 = 30_000;
+const MIN_PROBE_TIMEOUT_MS: u64 = 1_000;
+const MAX_PROBE_TIMEOUT_MS: u64 = 120_000;
+const PROBE_MEMORY_LIMIT_BYTES: usize = 64 * 1024 * 1024;
+const PROBE_MAX_STACK_SIZE_BYTES: usize = 1024 * 1024;
+/// Upper bound on parts in a segmented line; more than this is unreadable in one bar.
+const MAX_SEGMENTS: usize = 8;
+/// Upper bound on points in a series line; a year of daily values.
+const MAX_SERIES_POINTS: usize = 366;
+/// Upper bound on accounts returned by one probe.
+const MAX_ACCOUNTS: usize = 16;
+const MAX_ACCOUNT_KEY_LEN: usize = 128;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(tag = "kind", rename_all = "camelCase")]
 pub enum ProgressFormat {
     Percent,
     Dollars,
-    Count { suffix: String },
+    Count {
+        suffix: String,
+    },
+    /// Money in any currency; `code` is an uppercase ISO 4217 code.
+    Currency {
+        code: String,
+    },
+    /// Token counts, displayed with SI scaling (e.g. 9.2M).
+    Tokens,
+    /// Elapsed time; `unit` is what `used` and `limit` are measured in.
+    Duration {
+        unit: DurationUnit,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum DurationUnit {
+    Seconds,
+    Minutes,
+    Hours,
+}
+
+impl DurationUnit {
+    fn parse(value: &str) -> Option<Self> {
+        match value {
+            "seconds" => Some(DurationUnit::Seconds),
+            "minutes" => Some(DurationUnit::Minutes),
+            "hours" => Some(DurationUnit::Hours),
+            _ => None,
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -38,6 +94,138 @@ pub enum MetricLine {
         color: Option<String>,
         subtitle: Option<String>,
     },
+    /// One budget split into labeled parts (e.g. included vs on-demand usage).
+    Segmented {
+        label: String,
+        segments: Vec<ProgressSegment>,
+        limit: f64,
+        format: ProgressFormat,
+        #[serde(rename = "resetsAt")]
+        resets_at: Option<String>,
+        #[serde(rename = "periodDurationMs")]
+        period_duration_ms: Option<u64>,
+    },
+    /// Timestamped values for a sparkline, oldest first.
+    Series {
+        label: String,
+        points: Vec<SeriesPoint>,
+        format: ProgressFormat,
+        color: Option<String>,
+    },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProgressSegment {
+    pub label: String,
+    pub used: f64,
+    pub color: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SeriesPoint {
+    /// RFC 3339 timestamp.
+    pub at: String,
+    pub value: f64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ProbeErrorCode {
+    AuthRequired,
+    Network,
+    RateLimited,
+    Timeout,
+    PluginBug,
+    UnsupportedPlatform,
+    /// A host API call was outside the capabilities declared in the manifest.
+    PermissionDenied,
+    /// The plugin is unsigned or its signature does not verify, so it was not run.
+    Untrusted,
+    /// The plugin needs a newer app or host APIs this app does not provide, so it was not run.
+    Incompatible,
+    /// The plugin failed without saying why (e.g. it threw a plain string).
+    Unknown,
+}
+
+impl ProbeErrorCode {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ProbeErrorCode::AuthRequired => "auth_required",
+            ProbeErrorCode::Network => "network",
+            ProbeErrorCode::RateLimited => "rate_limited",
+            ProbeErrorCode::Timeout => "timeout",
+            ProbeErrorCode::PluginBug => "plugin_bug",
+            ProbeErrorCode::UnsupportedPlatform => "unsupported_platform",
+            ProbeErrorCode::PermissionDenied => "permission_denied",
+            ProbeErrorCode::Untrusted => "untrusted",
+            ProbeErrorCode::Incompatible => "incompatible",
+            ProbeErrorCode::Unknown => "unknown",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim() {
+            "auth_required" => Some(ProbeErrorCode::AuthRequired),
+            "network" => Some(ProbeErrorCode::Network),
+            "rate_limited" => Some(ProbeErrorCode::RateLimited),
+            "timeout" => Some(ProbeErrorCode::Timeout),
+            "plugin_bug" => Some(ProbeErrorCode::PluginBug),
+            "unsupported_platform" => Some(ProbeErrorCode::UnsupportedPlatform),
+            "permission_denied" => Some(ProbeErrorCode::PermissionDenied),
+            "untrusted" => Some(ProbeErrorCode::Untrusted),
+            "incompatible" => Some(ProbeErrorCode::Incompatible),
+            "unknown" => Some(ProbeErrorCode::Unknown),
+            _ => None,
+        }
+    }
+
+    /// Whether retrying later can reasonably be expected to succeed.
+    pub fn default_retryable(self) -> bool {
+        match self {
+            ProbeErrorCode::Network
+            | ProbeErrorCode::RateLimited
+            | ProbeErrorCode::Timeout
+            | ProbeErrorCode::Unknown => true,
+            ProbeErrorCode::AuthRequired
+            | ProbeErrorCode::PluginBug
+            | ProbeErrorCode::UnsupportedPlatform
+            | ProbeErrorCode::PermissionDenied
+            | ProbeErrorCode::Untrusted
+            | ProbeErrorCode::Incompatible => false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProbeError {
+    pub code: ProbeErrorCode,
+    pub message: String,
+    pub retryable: bool,
+}
+
+impl ProbeError {
+    pub fn new(code: ProbeErrorCode, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            message: message.into(),
+            retryable: code.default_retryable(),
+        }
+    }
+
+    pub fn plugin_bug(message: impl Into<String>) -> Self {
+        Self::new(ProbeErrorCode::PluginBug, message)
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ProbeStatus {
+    #[default]
+    Ok,
+    Error,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -45,456 +233,978 @@ pub enum MetricLine {
 pub struct PluginOutput {
     pub provider_id: String,
     pub display_name: String,
+    #[serde(default)]
+    pub status: ProbeStatus,
+    /// Set when the probe as a whole failed; `lines` is empty in that case.
+    #[serde(default)]
+    pub error: Option<ProbeError>,
     pub plan: Option<String>,
     pub lines: Vec<MetricLine>,
+    /// Every account the probe reported, when it returned `accounts`. `plan` and `lines`
+    /// then repeat the first account so single-account consumers keep working.
+    #[serde(default)]
+    pub accounts: Vec<AccountOutput>,
     pub icon_url: String,
 }
 
+/// One account's usage within a multi-account probe result.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AccountOutput {
+    /// Stable key chosen by the plugin (e.g. an org id), unique within one output.
+    pub key: String,
+    pub label: String,
+    pub plan: Option<String>,
+    pub lines: Vec<MetricLine>,
+}
+
+/// Resolve the probe timeout for a plugin, clamping manifest overrides to a sane range.
+pub fn probe_timeout(manifest: &PluginManifest) -> Duration {
+    let requested = manifest
+        .probe_timeout_ms
+        .unwrap_or(DEFAULT_PROBE_TIMEOUT_MS);
+    let clamped = requested.clamp(MIN_PROBE_TIMEOUT_MS, MAX_PROBE_TIMEOUT_MS);
+    if clamped != requested {
+        log::warn!(
+            "plugin {} probeTimeoutMs {} out of range; using {}",
+            manifest.id,
+            requested,
+            clamped
+        );
+    }
+    Duration::from_millis(clamped)
+}
+
 pub fn run_probe(plugin: &LoadedPlugin, app_data_dir: &PathBuf, app_version: &str) -> PluginOutput {
-    let fallback = error_output(plugin, "runtime error".to_string());
+    match ProbeRuntime::new(BytecodeCache::new()) {
+        Ok(runtime) => runtime.run(plugin, app_data_dir, app_version),
+        Err(_) => error_output(
+            plugin,
+            ProbeError::new(ProbeErrorCode::Unknown, "runtime error"),
+        ),
+    }
+}
 
-    let rt = match Runtime::new() {
-        Ok(rt) => rt,
-        Err(_) => return fallback,
-    };
+/// A QuickJS runtime that can run many probes one after another. Declarative plugins are run
+/// without it.
+///
+/// Memory and stack limits are set once; every probe still gets its own deadline and a fresh
+/// context, so no JS state carries over between probes. Scripts are loaded from `cache`.
+pub struct ProbeRuntime {
+    rt: Runtime,
+    cache: BytecodeCache,
+}
+
+impl ProbeRuntime {
+    pub fn new(cache: BytecodeCache) -> rquickjs::Result<Self> {
+        let rt = Runtime::new()?;
+        rt.set_memory_limit(PROBE_MEMORY_LIMIT_BYTES);
+        rt.set_max_stack_size(PROBE_MAX_STACK_SIZE_BYTES);
+        Ok(Self { rt, cache })
+    }
+
+    pub fn run(
+        &self,
+        plugin: &LoadedPlugin,
+        app_data_dir: &PathBuf,
+        app_version: &str,
+    ) -> PluginOutput {
+        if let Some(spec) = &plugin.manifest.http {
+            return declarative::run_probe(
+                plugin,
+                spec,
+                app_data_dir,
+                probe_timeout(&plugin.manifest),
+            );
+        }
+        let output = run_probe_with_timeout(
+            &self.rt,
+            &self.cache,
+            plugin,
+            app_data_dir,
+            app_version,
+            probe_timeout(&plugin.manifest),
+        );
+        // Release the probe's garbage now rather than counting it against the next probe.
+        self.rt.run_gc();
+        output
+    }
+}
+
+fn run_probe_with_timeout(
+    rt: &Runtime,
+    cache: &BytecodeCache,
+    plugin: &LoadedPlugin,
+    app_data_dir: &PathBuf,
+    app_version: &str,
+    timeout: Duration,
+) -> PluginOutput {
+    let fallback = error_output(
+        plugin,
+        ProbeError::new(ProbeErrorCode::Unknown, "runtime error"),
+    );
+
+    // The interrupt handler is polled by QuickJS while bytecode runs, so even a tight
+    // `while (true) {}` is aborted once the deadline passes. Interrupts cannot be caught by JS.
+    let deadline = ProbeDeadline::after(timeout);
+    let timed_out = Arc::new(AtomicBool::new(false));
+    let interrupt_flag = Arc::clone(&timed_out);
+    rt.set_interrupt_handler(Some(Box::new(move || {
+        if deadline.is_expired() {
+            interrupt_flag.store(true, Ordering::SeqCst);
+            true
+        } else {
+            false
+        }
+    })));
+
+    // Each probe gets a loader scoped to its own plugin directory; the runtime may have served
+    // a different plugin before.
+    let plugin_dir = plugin
+        .plugin_dir
+        .canonicalize()
+        .unwrap_or_else(|_| plugin.plugin_dir.clone());
+    let entry_name = modules::resolve_in_plugin_dir(&plugin_dir, &plugin.manifest.entry)
+        .unwrap_or_else(|_| plugin.manifest.entry.clone());
+    rt.set_loader(
+        modules::PluginResolver::new(plugin_dir),
+        modules::PluginLoader::new(plugin, entry_name.clone(), cache.clone(), app_version),
+    );
 
-    let ctx = match Context::full(&rt) {
+    let ctx = match Context::full(rt) {
         Ok(ctx) => ctx,
         Err(_) => return fallback,
     };
 
     let plugin_id = plugin.manifest.id.clone();
     let display_name = plugin.manifest.name.clone();
-    let entry_script = plugin.entry_script.clone();
     let icon_url = plugin.icon_data_url.clone();
     let app_data = app_data_dir.clone();
 
-    ctx.with(|ctx| {
-        if host_api::inject_host_api(&ctx, &plugin_id, &app_data, app_version).is_err() {
-            return error_output(plugin, "host api injection failed".to_string());
-        }
-        if host_api::patch_http_wrapper(&ctx).is_err() {
-            return error_output(plugin, "http wrapper patch failed".to_string());
-        }
-        if host_api::patch_ls_wrapper(&ctx).is_err() {
-            return error_output(plugin, "ls wrapper patch failed".to_string());
-        }
-        if host_api::patch_ccusage_wrapper(&ctx).is_err() {
-            return error_output(plugin, "ccusage wrapper patch failed".to_string());
-        }
-        if host_api::inject_utils(&ctx).is_err() {
-            return error_output(plugin, "utils injection failed".to_string());
-        }
-
-        if ctx.eval::<(), _>(entry_script.as_bytes()).is_err() {
-            return error_output(plugin, "script eval failed".to_string());
-        }
-
-        let globals = ctx.globals();
-        let plugin_obj: Object = match globals.get("__openusage_plugin") {
-            Ok(obj) => obj,
-            Err(_) => return error_output(plugin, "missing __openusage_plugin".to_string()),
-        };
+    let jobs = Rc::new(HostJobs::new());
 
-        let probe_fn: rquickjs::Function = match plugin_obj.get("probe") {
-            Ok(f) => f,
-            Err(_) => return error_output(plugin, "missing probe()".to_string()),
+    let output = ctx.with(|ctx| {
+        let probe_fn = match load_plugin(
+            &ctx,
+            cache,
+            plugin,
+            &entry_name,
+            &app_data,
+            app_version,
+            deadline,
+            &jobs,
+        ) {
+            Ok(probe_fn) => probe_fn,
+            Err(output) => return *output,
         };
 
+        let globals = ctx.globals();
         let probe_ctx: Value = globals
             .get("__openusage_ctx")
             .unwrap_or_else(|_| Value::new_undefined(ctx.clone()));
 
         let result_value: Value = match probe_fn.call((probe_ctx,)) {
             Ok(r) => r,
-            Err(_) => return error_output(plugin, extract_error_string(&ctx)),
+            Err(_) => return error_output(plugin, extract_probe_error(&ctx, plugin)),
         };
         let result: Object = if result_value.is_promise() {
             let promise: Promise = match result_value.into_promise() {
                 Some(promise) => promise,
                 None => {
-                    return error_output(plugin, "probe() returned invalid promise".to_string());
+                    return error_output(
+                        plugin,
+                        ProbeError::plugin_bug("probe() returned invalid promise"),
+                    );
                 }
             };
-            match promise.finish::<Object>() {
+            match host_jobs::drive_promise(&ctx, &promise, &jobs, &deadline) {
                 Ok(obj) => obj,
                 Err(Error::WouldBlock) => {
-                    return error_output(plugin, "probe() returned unresolved promise".to_string());
+                    return error_output(
+                        plugin,
+                        ProbeError::plugin_bug("probe() returned unresolved promise"),
+                    );
                 }
-                Err(_) => return error_output(plugin, extract_error_string(&ctx)),
+                Err(_) => return error_output(plugin, extract_probe_error(&ctx, plugin)),
             }
         } else {
             match result_value.into_object() {
                 Some(obj) => obj,
-                None => return error_output(plugin, "probe() returned non-object".to_string()),
+                None => {
+                    return error_output(
+                        plugin,
+                        ProbeError::plugin_bug("probe() returned non-object"),
+                    );
+                }
             }
         };
 
-        let plan: Option<String> = result
-            .get::<_, String>("plan")
-            .ok()
-            .filter(|s| !s.is_empty());
-
-        let lines = match parse_lines(&result) {
-            Ok(lines) if !lines.is_empty() => lines,
-            Ok(_) => vec![error_line("no lines returned".to_string())],
-            Err(msg) => vec![error_line(msg)],
+        let (plan, lines, accounts) = match parse_accounts(&result) {
+            Ok(Some(accounts)) => (
+                accounts[0].plan.clone(),
+                accounts[0].lines.clone(),
+                accounts,
+            ),
+            Ok(None) => match parse_lines(&result) {
+                Ok(lines) if !lines.is_empty() => (parse_plan(&result), lines, Vec::new()),
+                Ok(_) => return error_output(plugin, ProbeError::plugin_bug("no lines returned")),
+                Err(msg) => return error_output(plugin, ProbeError::plugin_bug(msg)),
+            },
+            Err(msg) => return error_output(plugin, ProbeError::plugin_bug(msg)),
         };
 
         PluginOutput {
             provider_id: plugin_id,
             display_name,
+            status: ProbeStatus::Ok,
+            error: None,
             plan,
             lines,
+            accounts,
             icon_url,
         }
-    })
-}
+    });
 
-fn parse_lines(result: &Object) -> Result<Vec<MetricLine>, String> {
-    let lines: Array = result
-        .get("lines")
-        .map_err(|_| "missing lines".to_string())?;
+    // A host call cut short by the deadline surfaces as an ordinary exception, so also
+    // treat any failure observed after the deadline as a timeout.
+    let failed = output.error.is_some();
+    if timed_out.load(Ordering::SeqCst) || (failed && deadline.is_expired()) {
+        log::warn!(
+            "[plugin:{}] probe timed out after {}ms",
+            plugin.manifest.id,
+            timeout.as_millis()
+        );
+        return timeout_output(plugin, timeout);
+    }
+    output
+}
 
-    let mut out = Vec::new();
-    let len = lines.len();
-    for idx in 0..len {
-        let line: Object = lines
-            .get(idx)
-            .map_err(|_| format!("invalid line at index {}", idx))?;
+/// Install the host API and utility prelude into a fresh context, evaluate the plugin's entry
+/// and return its `probe` function.
+#[allow(clippy::too_many_arguments)]
+fn load_plugin<'js>(
+    ctx: &Ctx<'js>,
+    cache: &BytecodeCache,
+    plugin: &LoadedPlugin,
+    entry_name: &str,
+    app_data_dir: &PathBuf,
+    app_version: &str,
+    deadline: ProbeDeadline,
+    jobs: &Rc<HostJobs>,
+) -> Result<Function<'js>, Box<PluginOutput>> {
+    let fail = |message: &str| {
+        Err(Box::new(error_output(
+            plugin,
+            ProbeError::plugin_bug(message),
+        )))
+    };
+    let plugin_id = &plugin.manifest.id;
+    let config = settings::resolve_config(&plugin.manifest, app_data_dir, &KeychainSecrets);
+    let permissions = Arc::new(Permissions::new(
+        plugin_id,
+        &plugin.manifest.env,
+        &plugin.manifest.capabilities,
+        &host_api::plugin_data_dir(app_data_dir, plugin_id),
+    ));
+    if host_api::inject_host_api(
+        ctx,
+        plugin_id,
+        app_data_dir,
+        app_version,
+        &config,
+        &permissions,
+        deadline,
+        jobs,
+    )
+    .is_err()
+    {
+        return fail("host api injection failed");
+    }
+    if host_jobs::install_job_bridge(ctx).is_err() {
+        return fail("job bridge install failed");
+    }
+    if host_api::patch_http_wrapper(ctx).is_err() {
+        return fail("http wrapper patch failed");
+    }
+    if host_api::patch_ls_wrapper(ctx).is_err() {
+        return fail("ls wrapper patch failed");
+    }
+    if host_api::patch_sqlite_wrapper(ctx).is_err() {
+        return fail("sqlite wrapper patch failed");
+    }
+    if host_api::patch_ccusage_wrapper(ctx).is_err() {
+        return fail("ccusage wrapper patch failed");
+    }
+    if host_api::inject_utils(ctx, cache, app_version).is_err() {
+        return fail("utils injection failed");
+    }
 
-        let line_type: String = line.get("type").unwrap_or_default();
-        let label = line.get::<_, String>("label").unwrap_or_default();
-        let color = line.get::<_, String>("color").ok();
-        let subtitle = line.get::<_, String>("subtitle").ok();
+    match plugin.manifest.entry_type {
+        EntryType::Script => {
+            // Name the script after the entry file so stack frames point at it.
+            let cache_key = bytecode::plugin_cache_key(plugin, app_version);
+            if cache
+                .eval_script(
+                    ctx,
+                    &cache_key,
+                    &plugin.manifest.entry,
+                    &plugin.entry_script,
+                )
+                .is_err()
+            {
+                let thrown = js_error::catch(ctx);
+                log_thrown(plugin, &thrown);
+                let message = match thrown.message {
+                    Some(_) => format!("script eval failed: {}", thrown.headline()),
+                    None => "script eval failed".to_string(),
+                };
+                return fail(&message);
+            }
 
-        match line_type.as_str() {
-            "text" => {
-                let value = line.get::<_, String>("value").unwrap_or_default();
-                out.push(MetricLine::Text {
-                    label,
-                    value,
-                    color,
-                    subtitle,
-                });
+            let plugin_obj: Object = match ctx.globals().get("__openusage_plugin") {
+                Ok(obj) => obj,
+                Err(_) => return fail("missing __openusage_plugin"),
+            };
+            match plugin_obj.get("probe") {
+                Ok(f) => Ok(f),
+                Err(_) => fail("missing probe()"),
             }
-            "progress" => {
-                let used_value: Value = match line.get("used") {
-                    Ok(v) => v,
-                    Err(_) => {
-                        out.push(error_line(format!(
-                            "progress line at index {} missing used",
-                            idx
-                        )));
-                        continue;
-                    }
-                };
-                let used = match used_value.as_number() {
-                    Some(n) => n,
-                    None => {
-                        out.push(error_line(format!(
-                            "progress line at index {} invalid used (expected number)",
-                            idx
-                        )));
-                        continue;
-                    }
-                };
+        }
+        EntryType::Module => {
+            // Imports and top-level await in the module graph settle like any other promise.
+            let namespace = Module::import(ctx, format!("./{}", entry_name)).and_then(|promise| {
+                host_jobs::drive_promise::<Object>(ctx, &promise, jobs, &deadline)
+            });
+            let namespace = match namespace {
+                Ok(namespace) => namespace,
+                Err(Error::WouldBlock) => return fail("module evaluation never settled"),
+                Err(_) => {
+                    let thrown = js_error::catch(ctx);
+                    log_thrown(plugin, &thrown);
+                    let message = match thrown.message {
+                        Some(_) => format!("module load failed: {}", thrown.headline()),
+                        None => "module load failed".to_string(),
+                    };
+                    return fail(&message);
+                }
+            };
+            match namespace.get::<_, Option<Function>>("probe") {
+                Ok(Some(f)) => Ok(f),
+                _ => fail("missing probe() export"),
+            }
+        }
+    }
+}
 
-                let limit_value: Value = match line.get("limit") {
-                    Ok(v) => v,
-                    Err(_) => {
-                        out.push(error_line(format!(
-                            "progress line at index {} missing limit",
-                            idx
-                        )));
-                        continue;
-                    }
-                };
-                let limit = match limit_value.as_number() {
-                    Some(n) => n,
-                    None => {
-                        out.push(error_line(format!(
-                            "progress line at index {} invalid limit (expected number)",
-                            idx
-                        )));
-                        continue;
-                    }
-                };
+fn required_number(line: &Object, key: &str, line_type: &str, idx: usize) -> Result<f64, String> {
+    let value: Value = line
+        .get(key)
+        .map_err(|_| format!("{} line at index {} missing {}", line_type, idx, key))?;
+    value.as_number().ok_or_else(|| {
+        format!(
+            "{} line at index {} invalid {} (expected number)",
+            line_type, idx, key
+        )
+    })
+}
 
-                if !used.is_finite() || used < 0.0 {
-                    out.push(error_line(format!(
-                        "progress line at index {} invalid used: {}",
-                        idx, used
-                    )));
-                    continue;
-                }
-                if !limit.is_finite() || limit <= 0.0 {
-                    out.push(error_line(format!(
-                        "progress line at index {} invalid limit: {}",
-                        idx, limit
-                    )));
-                    continue;
+fn parse_progress_format(
+    line: &Object,
+    line_type: &str,
+    idx: usize,
+    limit: Option<f64>,
+) -> Result<ProgressFormat, String> {
+    let format_obj: Object = line
+        .get("format")
+        .map_err(|_| format!("{} line at index {} missing format", line_type, idx))?;
+    let kind_value: Value = format_obj
+        .get("kind")
+        .map_err(|_| format!("{} line at index {} missing format.kind", line_type, idx))?;
+    let kind = match kind_value.as_string() {
+        Some(s) => s.to_string().unwrap_or_default(),
+        None => {
+            return Err(format!(
+                "{} line at index {} invalid format.kind (expected string)",
+                line_type, idx
+            ));
+        }
+    };
+    match kind.as_str() {
+        "percent" => {
+            if let Some(limit) = limit
+                && limit != 100.0
+            {
+                return Err(format!(
+                    "{} line at index {}: percent format requires limit=100 (got {})",
+                    line_type, idx, limit
+                ));
+            }
+            Ok(ProgressFormat::Percent)
+        }
+        "dollars" => Ok(ProgressFormat::Dollars),
+        "count" => {
+            let suffix_value: Value = format_obj.get("suffix").map_err(|_| {
+                format!(
+                    "{} line at index {}: count format missing suffix",
+                    line_type, idx
+                )
+            })?;
+            let suffix = match suffix_value.as_string() {
+                Some(s) => s.to_string().unwrap_or_default(),
+                None => {
+                    return Err(format!(
+                        "{} line at index {}: count format suffix must be a string",
+                        line_type, idx
+                    ));
                 }
+            };
+            let suffix = suffix.trim().to_string();
+            if suffix.is_empty() {
+                return Err(format!(
+                    "{} line at index {}: count format suffix must be non-empty",
+                    line_type, idx
+                ));
+            }
+            Ok(ProgressFormat::Count { suffix })
+        }
+        "currency" => {
+            let code = format_obj
+                .get::<_, Value>("code")
+                .ok()
+                .and_then(|value| value.as_string().and_then(|s| s.to_string().ok()))
+                .unwrap_or_default()
+                .trim()
+                .to_ascii_uppercase();
+            if code.len() != 3 || !code.chars().all(|c| c.is_ascii_alphabetic()) {
+                return Err(format!(
+                    "{} line at index {}: currency format code must be a 3-letter ISO 4217 code (got {:?})",
+                    line_type, idx, code
+                ));
+            }
+            Ok(ProgressFormat::Currency { code })
+        }
+        "tokens" => Ok(ProgressFormat::Tokens),
+        "duration" => {
+            let unit = format_obj
+                .get::<_, Value>("unit")
+                .ok()
+                .and_then(|value| value.as_string().and_then(|s| s.to_string().ok()))
+                .unwrap_or_default();
+            let unit = DurationUnit::parse(unit.trim()).ok_or_else(|| {
+                format!(
+                    "{} line at index {}: duration format unit must be seconds, minutes or hours (got {:?})",
+                    line_type, idx, unit
+                )
+            })?;
+            Ok(ProgressFormat::Duration { unit })
+        }
+        _ => Err(format!(
+            "{} line at index {} invalid format.kind: {}",
+            line_type, idx, kind
+        )),
+    }
+}
 
-                let format_obj: Object = match line.get("format") {
-                    Ok(obj) => obj,
-                    Err(_) => {
-                        out.push(error_line(format!(
-                            "progress line at index {} missing format",
-                            idx
-                        )));
-                        continue;
-                    }
-                };
-                let kind_value: Value = match format_obj.get("kind") {
-                    Ok(v) => v,
-                    Err(_) => {
-                        out.push(error_line(format!(
-                            "progress line at index {} missing format.kind",
-                            idx
-                        )));
-                        continue;
-                    }
-                };
-                let kind = match kind_value.as_string() {
-                    Some(s) => s.to_string().unwrap_or_default(),
-                    None => {
-                        out.push(error_line(format!(
-                            "progress line at index {} invalid format.kind (expected string)",
-                            idx
-                        )));
-                        continue;
-                    }
-                };
-                let format = match kind.as_str() {
-                    "percent" => {
-                        if limit != 100.0 {
-                            out.push(error_line(format!(
-                                "progress line at index {}: percent format requires limit=100 (got {})",
-                                idx, limit
-                            )));
-                            continue;
-                        }
-                        ProgressFormat::Percent
-                    }
-                    "dollars" => ProgressFormat::Dollars,
-                    "count" => {
-                        let suffix_value: Value = match format_obj.get("suffix") {
-                            Ok(v) => v,
-                            Err(_) => {
-                                out.push(error_line(format!(
-                                    "progress line at index {}: count format missing suffix",
-                                    idx
-                                )));
-                                continue;
-                            }
-                        };
-                        let suffix = match suffix_value.as_string() {
-                            Some(s) => s.to_string().unwrap_or_default(),
-                            None => {
-                                out.push(error_line(format!(
-                                    "progress line at index {}: count format suffix must be a string",
-                                    idx
-                                )));
-                                continue;
-                            }
+fn parse_resets_at(line: &Object, idx: usize) -> Option<String> {
+    match line.get::<_, Value>("resetsAt") {
+        Ok(v) => {
+            if v.is_null() || v.is_undefined() {
+                None
+            } else if let Some(s) = v.as_string() {
+                let raw = s.to_string().unwrap_or_default();
+                let value = raw.trim().to_string();
+                if value.is_empty() {
+                    None
+                } else {
+                    let parsed = time::OffsetDateTime::parse(
+                        &value,
+                        &time::format_description::well_known::Rfc3339,
+                    );
+                    if parsed.is_ok() {
+                        Some(value)
+                    } else {
+                        // ISO-like but missing timezone: assume UTC.
+                        let is_missing_tz = value.contains('T') && !value.ends_with('Z') && {
+                            let tail = value.splitn(2, 'T').nth(1).unwrap_or("");
+                            !tail.contains('+') && !tail.contains('-')
                         };
-                        let suffix = suffix.trim().to_string();
-                        if suffix.is_empty() {
-                            out.push(error_line(format!(
-                                "progress line at index {}: count format suffix must be non-empty",
-                                idx
-                            )));
-                            continue;
-                        }
-                        ProgressFormat::Count { suffix }
-                    }
-                    _ => {
-                        out.push(error_line(format!(
-                            "progress line at index {} invalid format.kind: {}",
-                            idx, kind
-                        )));
-                        continue;
-                    }
-                };
-
-                let resets_at = match line.get::<_, Value>("resetsAt") {
-                    Ok(v) => {
-                        if v.is_null() || v.is_undefined() {
-                            None
-                        } else if let Some(s) = v.as_string() {
-                            let raw = s.to_string().unwrap_or_default();
-                            let value = raw.trim().to_string();
-                            if value.is_empty() {
-                                None
-                            } else {
-                                let parsed = time::OffsetDateTime::parse(
-                                    &value,
-                                    &time::format_description::well_known::Rfc3339,
-                                );
-                                if parsed.is_ok() {
-                                    Some(value)
-                                } else {
-                                    // ISO-like but missing timezone: assume UTC.
-                                    let is_missing_tz =
-                                        value.contains('T') && !value.ends_with('Z') && {
-                                            let tail = value.splitn(2, 'T').nth(1).unwrap_or("");
-                                            !tail.contains('+') && !tail.contains('-')
-                                        };
-                                    if is_missing_tz {
-                                        let with_z = format!("{}Z", value);
-                                        let parsed_with_z = time::OffsetDateTime::parse(
-                                            &with_z,
-                                            &time::format_description::well_known::Rfc3339,
-                                        );
-                                        if parsed_with_z.is_ok() {
-                                            Some(with_z)
-                                        } else {
-                                            log::warn!(
-                                                "invalid resetsAt at index {} (value='{}'), omitting",
-                                                idx,
-                                                raw
-                                            );
-                                            None
-                                        }
-                                    } else {
-                                        log::warn!(
-                                            "invalid resetsAt at index {} (value='{}'), omitting",
-                                            idx,
-                                            raw
-                                        );
-                                        None
-                                    }
-                                }
-                            }
-                        } else {
-                            log::warn!("invalid resetsAt at index {} (non-string), omitting", idx);
-                            None
-                        }
-                    }
-                    Err(_) => None,
-                };
-
-                // Parse optional periodDurationMs
-                let period_duration_ms: Option<u64> = match line.get::<_, Value>("periodDurationMs")
-                {
-                    Ok(val) => {
-                        if val.is_null() || val.is_undefined() {
-                            None
-                        } else if let Some(n) = val.as_number() {
-                            let ms = n as u64;
-                            if ms > 0 {
-                                Some(ms)
+                        if is_missing_tz {
+                            let with_z = format!("{}Z", value);
+                            let parsed_with_z = time::OffsetDateTime::parse(
+                                &with_z,
+                                &time::format_description::well_known::Rfc3339,
+                            );
+                            if parsed_with_z.is_ok() {
+                                Some(with_z)
                             } else {
                                 log::warn!(
-                                    "periodDurationMs at index {} must be positive, omitting",
-                                    idx
+                                    "invalid resetsAt at index {} (value='{}'), omitting",
+                                    idx,
+                                    raw
                                 );
                                 None
                             }
                         } else {
                             log::warn!(
-                                "invalid periodDurationMs at index {} (non-number), omitting",
-                                idx
+                                "invalid resetsAt at index {} (value='{}'), omitting",
+                                idx,
+                                raw
                             );
                             None
                         }
                     }
-                    Err(_) => None,
-                };
-
-                out.push(MetricLine::Progress {
-                    label,
-                    used,
-                    limit,
-                    format,
-                    resets_at,
-                    period_duration_ms,
-                    color,
-                });
-            }
-            "badge" => {
-                let text = line.get::<_, String>("text").unwrap_or_default();
-                out.push(MetricLine::Badge {
-                    label,
-                    text,
-                    color,
-                    subtitle,
-                });
-            }
-            _ => {
-                out.push(error_line(format!(
-                    "unknown line type at index {}: {}",
-                    idx, line_type
-                )));
+                }
+            } else {
+                log::warn!("invalid resetsAt at index {} (non-string), omitting", idx);
+                None
             }
         }
+        Err(_) => None,
     }
+}
 
-    Ok(out)
+fn parse_period_duration_ms(line: &Object, idx: usize) -> Option<u64> {
+    match line.get::<_, Value>("periodDurationMs") {
+        Ok(val) => {
+            if val.is_null() || val.is_undefined() {
+                None
+            } else if let Some(n) = val.as_number() {
+                let ms = n as u64;
+                if ms > 0 {
+                    Some(ms)
+                } else {
+                    log::warn!(
+                        "periodDurationMs at index {} must be positive, omitting",
+                        idx
+                    );
+                    None
+                }
+            } else {
+                log::warn!(
+                    "invalid periodDurationMs at index {} (non-number), omitting",
+                    idx
+                );
+                None
+            }
+        }
+        Err(_) => None,
+    }
 }
 
-fn error_output(plugin: &LoadedPlugin, message: String) -> PluginOutput {
-    PluginOutput {
-        provider_id: plugin.manifest.id.clone(),
-        display_name: plugin.manifest.name.clone(),
-        plan: None,
-        lines: vec![error_line(message)],
-        icon_url: plugin.icon_data_url.clone(),
+fn parse_progress(
+    line: &Object,
+    idx: usize,
+    label: String,
+    color: Option<String>,
+) -> Result<MetricLine, String> {
+    let used = required_number(line, "used", "progress", idx)?;
+    let limit = required_number(line, "limit", "progress", idx)?;
+    if !used.is_finite() || used < 0.0 {
+        return Err(format!(
+            "progress line at index {} invalid used: {}",
+            idx, used
+        ));
     }
+    if !limit.is_finite() || limit <= 0.0 {
+        return Err(format!(
+            "progress line at index {} invalid limit: {}",
+            idx, limit
+        ));
+    }
+    let format = parse_progress_format(line, "progress", idx, Some(limit))?;
+
+    Ok(MetricLine::Progress {
+        label,
+        used,
+        limit,
+        format,
+        resets_at: parse_resets_at(line, idx),
+        period_duration_ms: parse_period_duration_ms(line, idx),
+        color,
+    })
 }
 
-fn extract_error_string(ctx: &Ctx<'_>) -> String {
-    let exc = ctx.catch();
-    if exc.is_null() || exc.is_undefined() {
-        return "The plugin failed, try again or contact plugin author.".to_string();
+fn parse_segmented(line: &Object, idx: usize, label: String) -> Result<MetricLine, String> {
+    let limit = required_number(line, "limit", "segmented", idx)?;
+    if !limit.is_finite() || limit <= 0.0 {
+        return Err(format!(
+            "segmented line at index {} invalid limit: {}",
+            idx, limit
+        ));
+    }
+    let raw_segments: Array = line
+        .get("segments")
+        .map_err(|_| format!("segmented line at index {} missing segments", idx))?;
+    if raw_segments.is_empty() || raw_segments.len() > MAX_SEGMENTS {
+        return Err(format!(
+            "segmented line at index {} must have 1-{} segments (got {})",
+            idx,
+            MAX_SEGMENTS,
+            raw_segments.len()
+        ));
     }
-    if let Some(str_val) = exc.as_string() {
-        let message: String = str_val.to_string().unwrap_or_default();
-        let trimmed = message.trim();
-        if !trimmed.is_empty() {
-            return trimmed.to_string();
+
+    let mut segments = Vec::with_capacity(raw_segments.len());
+    for seg_idx in 0..raw_segments.len() {
+        let segment: Object = raw_segments.get(seg_idx).map_err(|_| {
+            format!(
+                "segmented line at index {} invalid segment {}",
+                idx, seg_idx
+            )
+        })?;
+        let seg_label = segment
+            .get::<_, String>("label")
+            .unwrap_or_default()
+            .trim()
+            .to_string();
+        if seg_label.is_empty() {
+            return Err(format!(
+                "segmented line at index {} segment {} missing label",
+                idx, seg_idx
+            ));
         }
+        let used = segment
+            .get::<_, Value>("used")
+            .ok()
+            .and_then(|value| value.as_number())
+            .ok_or_else(|| {
+                format!(
+                    "segmented line at index {} segment {} invalid used (expected number)",
+                    idx, seg_idx
+                )
+            })?;
+        if !used.is_finite() || used < 0.0 {
+            return Err(format!(
+                "segmented line at index {} segment {} invalid used: {}",
+                idx, seg_idx, used
+            ));
+        }
+        segments.push(ProgressSegment {
+            label: seg_label,
+            used,
+            color: segment.get::<_, String>("color").ok(),
+        });
     }
-    "The plugin failed, try again or contact plugin author.".to_string()
+    let format = parse_progress_format(line, "segmented", idx, Some(limit))?;
+
+    Ok(MetricLine::Segmented {
+        label,
+        segments,
+        limit,
+        format,
+        resets_at: parse_resets_at(line, idx),
+        period_duration_ms: parse_period_duration_ms(line, idx),
+    })
 }
 
-fn error_line(message: String) -> MetricLine {
-    MetricLine::Badge {
-        label: "Error".to_string(),
-        text: message,
-        color: Some("#ef4444".to_string()),
-        subtitle: None,
+fn parse_series(
+    line: &Object,
+    idx: usize,
+    label: String,
+    color: Option<String>,
+) -> Result<MetricLine, String> {
+    let raw_points: Array = line
+        .get("points")
+        .map_err(|_| format!("series line at index {} missing points", idx))?;
+    if raw_points.is_empty() || raw_points.len() > MAX_SERIES_POINTS {
+        return Err(format!(
+            "series line at index {} must have 1-{} points (got {})",
+            idx,
+            MAX_SERIES_POINTS,
+            raw_points.len()
+        ));
+    }
+
+    let mut points = Vec::with_capacity(raw_points.len());
+    let mut previous: Option<time::OffsetDateTime> = None;
+    for point_idx in 0..raw_points.len() {
+        let point: Object = raw_points
+            .get(point_idx)
+            .map_err(|_| format!("series line at index {} invalid point {}", idx, point_idx))?;
+        let at = point
+            .get::<_, String>("at")
+            .unwrap_or_default()
+            .trim()
+            .to_string();
+        let parsed =
+            time::OffsetDateTime::parse(&at, &time::format_description::well_known::Rfc3339)
+                .map_err(|_| {
+                    format!(
+                        "series line at index {} point {} invalid at (expected RFC 3339 timestamp)",
+                        idx, point_idx
+                    )
+                })?;
+        if previous.is_some_and(|prev| parsed <= prev) {
+            return Err(format!(
+                "series line at index {} point {} is not after the previous point",
+                idx, point_idx
+            ));
+        }
+        previous = Some(parsed);
+        let value = point
+            .get::<_, Value>("value")
+            .ok()
+            .and_then(|value| value.as_number())
+            .ok_or_else(|| {
+                format!(
+                    "series line at index {} point {} invalid value (expected number)",
+                    idx, point_idx
+                )
+            })?;
+        if !value.is_finite() {
+            return Err(format!(
+                "series line at index {} point {} invalid value: {}",
+                idx, point_idx, value
+            ));
+        }
+        points.push(SeriesPoint { at, value });
     }
+    let format = parse_progress_format(line, "series", idx, None)?;
+
+    Ok(MetricLine::Series {
+        label,
+        points,
+        format,
+        color,
+    })
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use crate::plugin_engine::manifest::{LoadedPlugin, PluginManifest};
-    use serde_json::Value as JsonValue;
-    use std::path::PathBuf;
-    use std::time::{SystemTime, UNIX_EPOCH};
+fn parse_plan(result: &Object) -> Option<String> {
+    result
+        .get::<_, String>("plan")
+        .ok()
+        .filter(|s| !s.is_empty())
+}
 
-    fn test_plugin(entry_script: &str) -> LoadedPlugin {
-        LoadedPlugin {
-            manifest: PluginManifest {
-                schema_version: 1,
-                id: "test".to_string(),
-                name: "Test".to_string(),
+fn is_valid_account_key(key: &str) -> bool {
+    !key.is_empty()
+        && key.len() <= MAX_ACCOUNT_KEY_LEN
+        && key
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '_' | '-' | '@'))
+}
+
+/// Parse the optional `accounts` array. `Ok(None)` means the probe used the single-account
+/// shape (`plan` + `lines` at the top level).
+fn parse_accounts(result: &Object) -> Result<Option<Vec<AccountOutput>>, String> {
+    let value: Value = result
+        .get("accounts")
+        .map_err(|_| "invalid accounts".to_string())?;
+    if value.is_undefined() || value.is_null() {
+        return Ok(None);
+    }
+    let raw_accounts = value
+        .into_array()
+        .ok_or_else(|| "accounts must be an array".to_string())?;
+    if raw_accounts.is_empty() || raw_accounts.len() > MAX_ACCOUNTS {
+        return Err(format!(
+            "accounts must have 1-{} entries (got {})",
+            MAX_ACCOUNTS,
+            raw_accounts.len()
+        ));
+    }
+
+    let mut accounts: Vec<AccountOutput> = Vec::with_capacity(raw_accounts.len());
+    for idx in 0..raw_accounts.len() {
+        let account: Object = raw_accounts
+            .get(idx)
+            .map_err(|_| format!("invalid account at index {}", idx))?;
+        let key = account
+            .get::<_, String>("key")
+            .unwrap_or_default()
+            .trim()
+            .to_string();
+        if !is_valid_account_key(&key) {
+            return Err(format!(
+                "account at index {} has invalid key {:?} (letters, digits, '.', '_', '-', '@')",
+                idx, key
+            ));
+        }
+        if accounts.iter().any(|existing| existing.key == key) {
+            return Err(format!("duplicate account key: {}", key));
+        }
+        let label = account
+            .get::<_, String>("label")
+            .map(|label| label.trim().to_string())
+            .ok()
+            .filter(|label| !label.is_empty())
+            .unwrap_or_else(|| key.clone());
+        let lines = parse_lines(&account).map_err(|msg| format!("account {}: {}", key, msg))?;
+        if lines.is_empty() {
+            return Err(format!("account {}: no lines returned", key));
+        }
+        accounts.push(AccountOutput {
+            plan: parse_plan(&account),
+            key,
+            label,
+            lines,
+        });
+    }
+    Ok(Some(accounts))
+}
+
+fn parse_lines(result: &Object) -> Result<Vec<MetricLine>, String> {
+    let lines: Array = result
+        .get("lines")
+        .map_err(|_| "missing lines".to_string())?;
+
+    let mut out = Vec::new();
+    let len = lines.len();
+    for idx in 0..len {
+        let line: Object = lines
+            .get(idx)
+            .map_err(|_| format!("invalid line at index {}", idx))?;
+
+        let line_type: String = line.get("type").unwrap_or_default();
+        let label = line.get::<_, String>("label").unwrap_or_default();
+        let color = line.get::<_, String>("color").ok();
+        let subtitle = line.get::<_, String>("subtitle").ok();
+
+        match line_type.as_str() {
+            "text" => {
+                let value = line.get::<_, String>("value").unwrap_or_default();
+                out.push(MetricLine::Text {
+                    label,
+                    value,
+                    color,
+                    subtitle,
+                });
+            }
+            "progress" => out.push(parse_progress(&line, idx, label, color)?),
+            "segmented" => out.push(parse_segmented(&line, idx, label)?),
+            "series" => out.push(parse_series(&line, idx, label, color)?),
+            "badge" => {
+                let text = line.get::<_, String>("text").unwrap_or_default();
+                out.push(MetricLine::Badge {
+                    label,
+                    text,
+                    color,
+                    subtitle,
+                });
+            }
+            _ => {
+                return Err(format!("unknown line type at index {}: {}", idx, line_type));
+            }
+        }
+    }
+
+    Ok(out)
+}
+
+pub(crate) fn error_output(plugin: &LoadedPlugin, error: ProbeError) -> PluginOutput {
+    PluginOutput {
+        provider_id: plugin.manifest.id.clone(),
+        display_name: plugin.manifest.name.clone(),
+        status: ProbeStatus::Error,
+        error: Some(error),
+        plan: None,
+        lines: Vec::new(),
+        accounts: Vec::new(),
+        icon_url: plugin.icon_data_url.clone(),
+    }
+}
+
+pub(crate) fn timeout_output(plugin: &LoadedPlugin, timeout: Duration) -> PluginOutput {
+    error_output(
+        plugin,
+        ProbeError::new(
+            ProbeErrorCode::Timeout,
+            format!(
+                "Timed out after {}. The provider may be unreachable.",
+                format_timeout(timeout)
+            ),
+        ),
+    )
+}
+
+/// `200ms`, `15s` or `2.5s`; rounding up to whole seconds would misreport short deadlines.
+fn format_timeout(timeout: Duration) -> String {
+    let millis = timeout.as_millis();
+    if millis < 1_000 {
+        format!("{}ms", millis)
+    } else if millis.is_multiple_of(1_000) {
+        format!("{}s", millis / 1_000)
+    } else {
+        format!("{:.1}s", timeout.as_secs_f64())
+    }
+}
+
+/// Classify the pending exception and log its full trace.
+fn extract_probe_error(ctx: &Ctx<'_>, plugin: &LoadedPlugin) -> ProbeError {
+    let thrown = js_error::catch(ctx);
+    log_thrown(plugin, &thrown);
+    thrown.to_probe_error()
+}
+
+fn log_thrown(plugin: &LoadedPlugin, thrown: &ThrownValue) {
+    let trace = thrown.trace(&plugin.manifest.entry, &plugin.entry_script);
+    log::error!(
+        "[plugin:{}] probe threw {}",
+        plugin.manifest.id,
+        host_api::redact_log_message(&trace)
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::plugin_engine::manifest::{self, EntryType, LoadedPlugin, PluginManifest};
+    use serde_json::Value as JsonValue;
+    use std::io::{Read, Write};
+    use std::path::PathBuf;
+    use std::sync::atomic::AtomicUsize;
+    use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
+    fn test_plugin(entry_script: &str) -> LoadedPlugin {
+        LoadedPlugin {
+            manifest: PluginManifest {
+                schema_version: 1,
+                id: "test".to_string(),
+                name: "Test".to_string(),
                 version: "0.0.0".to_string(),
                 entry: "plugin.js".to_string(),
+                entry_type: EntryType::Script,
+                min_app_version: None,
+                requires: Vec::new(),
                 icon: "icon.svg".to_string(),
+                icons: Default::default(),
                 brand_color: None,
                 lines: vec![],
                 links: vec![],
+                probe_timeout_ms: None,
+                settings: Vec::new(),
+                env: Vec::new(),
+                capabilities: Default::default(),
+                detect: Default::default(),
+                http: None,
             },
             plugin_dir: PathBuf::from("."),
             entry_script: entry_script.to_string(),
+            module_sources: Default::default(),
             icon_data_url: "data:image/svg+xml;base64,".to_string(),
+            icon_variant_data_urls: Default::default(),
+            trust: Default::default(),
+            incompatibility: None,
         }
     }
 
+    fn run_with_timeout(
+        plugin: &LoadedPlugin,
+        app_data_dir: &PathBuf,
+        app_version: &str,
+        timeout: Duration,
+    ) -> PluginOutput {
+        let runtime = ProbeRuntime::new(BytecodeCache::new()).expect("runtime");
+        run_probe_with_timeout(
+            &runtime.rt,
+            &runtime.cache,
+            plugin,
+            app_data_dir,
+            app_version,
+            timeout,
+        )
+    }
+
     fn temp_app_dir(label: &str) -> PathBuf {
         let nanos = SystemTime::now()
             .duration_since(UNIX_EPOCH)
@@ -504,10 +1214,13 @@ mod tests {
     }
 
     fn error_text(output: PluginOutput) -> String {
-        match output.lines.first() {
-            Some(MetricLine::Badge { text, .. }) => text.clone(),
-            other => panic!("expected error badge, got {:?}", other),
-        }
+        probe_error(output).message
+    }
+
+    fn probe_error(output: PluginOutput) -> ProbeError {
+        assert_eq!(output.status, ProbeStatus::Error);
+        assert!(output.lines.is_empty(), "failed probe must not carry lines");
+        output.error.expect("expected probe error")
     }
 
     #[test]
@@ -540,6 +1253,502 @@ mod tests {
         assert_eq!(error_text(output), "boom");
     }
 
+    #[test]
+    fn run_probe_exposes_resolved_settings_as_config() {
+        let mut plugin = test_plugin(
+            r#"
+            globalThis.__openusage_plugin = {
+                probe(ctx) {
+                    return {
+                        lines: [ctx.line.text({ label: "Config", value: JSON.stringify(ctx.config) })]
+                    };
+                }
+            };
+            "#,
+        );
+        plugin.manifest.settings = serde_json::from_str(
+            r#"[
+                { "key": "region", "label": "Region", "type": "enum", "options": ["global", "cn"], "default": "global" },
+                { "key": "baseUrl", "label": "Endpoint", "type": "url", "default": "https://api.example.com" }
+            ]"#,
+        )
+        .expect("settings");
+        let app_data_dir = temp_app_dir("config");
+        std::fs::create_dir_all(&app_data_dir).expect("create dir");
+        std::fs::write(
+            app_data_dir.join("plugin-settings.json"),
+            r#"{ "version": 1, "plugins": { "test": { "region": "cn" } } }"#,
+        )
+        .expect("write settings");
+
+        let output = run_probe(&plugin, &app_data_dir, "0.0.0");
+        let value = match output.lines.first() {
+            Some(MetricLine::Text { value, .. }) => value.clone(),
+            other => panic!("expected text line, got {:?}", other),
+        };
+        let config: JsonValue = serde_json::from_str(&value).expect("config json");
+        assert_eq!(
+            config,
+            serde_json::json!({ "region": "cn", "baseUrl": "https://api.example.com" })
+        );
+        let _ = std::fs::remove_dir_all(&app_data_dir);
+    }
+
+    #[test]
+    fn run_probe_classifies_structured_throw() {
+        let plugin = test_plugin(
+            r#"
+            globalThis.__openusage_plugin = {
+                probe() {
+                    throw { code: "auth_required", message: "Sign in again" };
+                }
+            };
+            "#,
+        );
+        let error = probe_error(run_probe(&plugin, &temp_app_dir("structured"), "0.0.0"));
+        assert_eq!(error.code, ProbeErrorCode::AuthRequired);
+        assert_eq!(error.message, "Sign in again");
+        assert!(!error.retryable);
+    }
+
+    #[test]
+    fn run_probe_structured_throw_can_override_retryable() {
+        let plugin = test_plugin(
+            r#"
+            globalThis.__openusage_plugin = {
+                probe: async function () {
+                    throw { code: "rate_limited", message: "Slow down", retryable: false };
+                }
+            };
+            "#,
+        );
+        let error = probe_error(run_probe(&plugin, &temp_app_dir("retryable"), "0.0.0"));
+        assert_eq!(error.code, ProbeErrorCode::RateLimited);
+        assert!(!error.retryable);
+    }
+
+    #[test]
+    fn run_probe_surfaces_error_object_message() {
+        let plugin = test_plugin(
+            r#"
+            globalThis.__openusage_plugin = {
+                probe: async function () {
+                    throw new Error("token expired");
+                }
+            };
+            "#,
+        );
+        let error = probe_error(run_probe(&plugin, &temp_app_dir("error-obj"), "0.0.0"));
+        assert_eq!(error.code, ProbeErrorCode::Unknown);
+        assert_eq!(error.message, "token expired");
+    }
+
+    #[test]
+    fn run_probe_engine_error_is_plugin_bug() {
+        let plugin = test_plugin(
+            r#"
+            globalThis.__openusage_plugin = {
+                probe() {
+                    return notDefined();
+                }
+            };
+            "#,
+        );
+        let error = probe_error(run_probe(&plugin, &temp_app_dir("ref-error"), "0.0.0"));
+        assert_eq!(error.code, ProbeErrorCode::PluginBug);
+        assert!(error.message.starts_with("ReferenceError:"));
+    }
+
+    #[test]
+    fn run_probe_keeps_code_from_error_cause() {
+        let plugin = test_plugin(
+            r#"
+            globalThis.__openusage_plugin = {
+                probe() {
+                    throw new Error("usage fetch failed", {
+                        cause: { code: "rate_limited", message: "429" },
+                    });
+                }
+            };
+            "#,
+        );
+        let error = probe_error(run_probe(&plugin, &temp_app_dir("cause"), "0.0.0"));
+        assert_eq!(error.code, ProbeErrorCode::RateLimited);
+        assert_eq!(error.message, "usage fetch failed");
+    }
+
+    #[test]
+    fn run_probe_reports_syntax_errors() {
+        let plugin = test_plugin("globalThis.__openusage_plugin = {");
+        let error = probe_error(run_probe(&plugin, &temp_app_dir("syntax"), "0.0.0"));
+        assert_eq!(error.code, ProbeErrorCode::PluginBug);
+        assert!(
+            error
+                .message
+                .starts_with("script eval failed: SyntaxError:"),
+            "{}",
+            error.message
+        );
+    }
+
+    #[test]
+    fn run_probe_stack_frames_name_entry_file() {
+        let plugin = test_plugin(
+            r#"
+            globalThis.__openusage_plugin = {
+                probe(ctx) {
+                    return { lines: [ctx.line.text({ label: "Stack", value: new Error("x").stack })] };
+                }
+            };
+            "#,
+        );
+        let output = run_probe(&plugin, &temp_app_dir("stack"), "0.0.0");
+        match output.lines.first() {
+            Some(MetricLine::Text { value, .. }) => {
+                assert!(value.contains("plugin.js:4:"), "{}", value)
+            }
+            other => panic!("expected text line, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn run_probe_thrown_string_is_unknown_and_retryable() {
+        let plugin = test_plugin(
+            r#"
+            globalThis.__openusage_plugin = {
+                probe() {
+                    throw "Not logged in";
+                }
+            };
+            "#,
+        );
+        let error = probe_error(run_probe(&plugin, &temp_app_dir("string"), "0.0.0"));
+        assert_eq!(error.code, ProbeErrorCode::Unknown);
+        assert!(error.retryable);
+    }
+
+    #[cfg(not(target_os = "macos"))]
+    #[test]
+    fn run_probe_uncaught_host_error_keeps_its_code() {
+        let plugin = test_plugin(
+            r#"
+            globalThis.__openusage_plugin = {
+                probe(ctx) {
+                    ctx.host.keychain.readGenericPassword("svc");
+                    return { lines: [] };
+                }
+            };
+            "#,
+        );
+        let error = probe_error(run_probe(&plugin, &temp_app_dir("coded"), "0.0.0"));
+        assert_eq!(error.code, ProbeErrorCode::UnsupportedPlatform);
+        assert_eq!(error.message, "keychain API is only supported on macOS");
+    }
+
+    #[test]
+    fn run_probe_missing_lines_is_plugin_bug() {
+        let plugin = test_plugin(
+            r#"
+            globalThis.__openusage_plugin = {
+                probe() {
+                    return {};
+                }
+            };
+            "#,
+        );
+        let error = probe_error(run_probe(&plugin, &temp_app_dir("no-lines"), "0.0.0"));
+        assert_eq!(error.code, ProbeErrorCode::PluginBug);
+        assert_eq!(error.message, "missing lines");
+    }
+
+    #[test]
+    fn run_probe_success_has_ok_status() {
+        let plugin = test_plugin(
+            r#"
+            globalThis.__openusage_plugin = {
+                probe(ctx) {
+                    return { lines: [ctx.line.text({ label: "A", value: "B" })] };
+                }
+            };
+            "#,
+        );
+        let output = run_probe(&plugin, &temp_app_dir("ok"), "0.0.0");
+        assert_eq!(output.status, ProbeStatus::Ok);
+        assert!(output.error.is_none());
+        assert_eq!(output.lines.len(), 1);
+    }
+
+    #[test]
+    fn plugin_output_serializes_status_and_error() {
+        let output = error_output(
+            &test_plugin(""),
+            ProbeError::new(ProbeErrorCode::Network, "offline"),
+        );
+        let json: JsonValue = serde_json::to_value(&output).expect("serialize");
+        assert_eq!(json["status"], "error");
+        assert_eq!(json["error"]["code"], "network");
+        assert_eq!(json["error"]["message"], "offline");
+        assert_eq!(json["error"]["retryable"], true);
+    }
+
+    #[test]
+    fn run_probe_times_out_infinite_loop() {
+        let plugin = test_plugin(
+            r#"
+            globalThis.__openusage_plugin = {
+                probe() {
+                    while (true) {}
+                }
+            };
+            "#,
+        );
+        let output = run_with_timeout(
+            &plugin,
+            &temp_app_dir("timeout"),
+            "0.0.0",
+            Duration::from_millis(200),
+        );
+        let error = probe_error(output);
+        assert_eq!(error.code, ProbeErrorCode::Timeout);
+        assert!(
+            error.message.starts_with("Timed out after 200ms."),
+            "{}",
+            error.message
+        );
+    }
+
+    #[test]
+    fn timeout_messages_keep_sub_second_precision() {
+        assert_eq!(format_timeout(Duration::from_millis(200)), "200ms");
+        assert_eq!(format_timeout(Duration::from_secs(15)), "15s");
+        assert_eq!(format_timeout(Duration::from_millis(2_500)), "2.5s");
+    }
+
+    #[test]
+    fn run_probe_timeout_cannot_be_caught_by_plugin() {
+        let plugin = test_plugin(
+            r#"
+            globalThis.__openusage_plugin = {
+                probe() {
+                    for (;;) {
+                        try {
+                            while (true) {}
+                        } catch (e) {}
+                    }
+                }
+            };
+            "#,
+        );
+        let output = run_with_timeout(
+            &plugin,
+            &temp_app_dir("timeout-catch"),
+            "0.0.0",
+            Duration::from_millis(200),
+        );
+        assert!(error_text(output).starts_with("Timed out"));
+    }
+
+    #[test]
+    fn run_probe_enforces_memory_limit() {
+        let plugin = test_plugin(
+            r#"
+            globalThis.__openusage_plugin = {
+                probe() {
+                    var chunks = [];
+                    while (true) {
+                        chunks.push(new Array(1024 * 1024).fill(1));
+                    }
+                }
+            };
+            "#,
+        );
+        let output = run_probe(&plugin, &temp_app_dir("memory"), "0.0.0");
+        let error = probe_error(output);
+        assert_eq!(error.code, ProbeErrorCode::PluginBug);
+        assert!(
+            error.message.contains("out of memory"),
+            "expected an out-of-memory error, got {:?}",
+            error.message
+        );
+    }
+
+    /// Serve `count` HTTP requests on a local port, answering each after `delay`.
+    fn slow_http_server(count: usize, delay: Duration) -> u16 {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").expect("bind");
+        let port = listener.local_addr().expect("local addr").port();
+        std::thread::spawn(move || {
+            for stream in listener.incoming().take(count) {
+                let Ok(mut stream) = stream else { continue };
+                std::thread::spawn(move || {
+                    let mut buf = [0u8; 2048];
+                    let _ = stream.read(&mut buf);
+                    std::thread::sleep(delay);
+                    let _ = stream.write_all(
+                        b"HTTP/1.1 200 OK\r\nContent-Length: 2\r\nConnection: close\r\n\r\nok",
+                    );
+                });
+            }
+        });
+        port
+    }
+
+    /// Serve `count` HTTP requests, holding every response until all of them are open at once
+    /// (or a generous fallback passes). Returns the port and the peak number of open requests.
+    fn gated_http_server(count: usize) -> (u16, Arc<AtomicUsize>) {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").expect("bind");
+        let port = listener.local_addr().expect("local addr").port();
+        let peak = Arc::new(AtomicUsize::new(0));
+        let gate = Arc::new((std::sync::Mutex::new(0_usize), std::sync::Condvar::new()));
+        let server_peak = Arc::clone(&peak);
+        std::thread::spawn(move || {
+            for stream in listener.incoming().take(count) {
+                let Ok(mut stream) = stream else { continue };
+                let gate = Arc::clone(&gate);
+                let peak = Arc::clone(&server_peak);
+                std::thread::spawn(move || {
+                    let mut buf = [0u8; 2048];
+                    let _ = stream.read(&mut buf);
+                    let (open, all_open) = &*gate;
+                    let mut open = open.lock().expect("gate");
+                    *open += 1;
+                    peak.fetch_max(*open, Ordering::SeqCst);
+                    all_open.notify_all();
+                    let (_open, _) = all_open
+                        .wait_timeout_while(open, Duration::from_secs(5), |open| *open < count)
+                        .expect("gate");
+                    let _ = stream.write_all(
+                        b"HTTP/1.1 200 OK\r\nContent-Length: 2\r\nConnection: close\r\n\r\nok",
+                    );
+                });
+            }
+        });
+        (port, peak)
+    }
+
+    #[test]
+    fn run_probe_runs_async_http_requests_concurrently() {
+        let (port, peak) = gated_http_server(3);
+        let mut plugin = test_plugin(&format!(
+            r#"
+            globalThis.__openusage_plugin = {{
+                probe: async function (ctx) {{
+                    var url = "http://127.0.0.1:{port}/";
+                    var responses = await Promise.all([
+                        ctx.host.http.requestAsync({{ url: url + "a" }}),
+                        ctx.host.http.requestAsync({{ url: url + "b" }}),
+                        ctx.host.http.requestAsync({{ url: url + "c" }}),
+                    ]);
+                    var value = responses.map(function (r) {{ return r.status + ":" + r.bodyText; }}).join(",");
+                    return {{ lines: [ctx.line.text({{ label: "Responses", value: value }})] }};
+                }}
+            }};
+            "#
+        ));
+        plugin.manifest.capabilities.http = vec!["127.0.0.1".to_string()];
+        let output = run_probe(&plugin, &temp_app_dir("async-http"), "0.0.0");
+        match output.lines.first() {
+            Some(MetricLine::Text { value, .. }) => assert_eq!(value, "200:ok,200:ok,200:ok"),
+            other => panic!("expected text line, got {:?}", other),
+        }
+        assert_eq!(
+            peak.load(Ordering::SeqCst),
+            3,
+            "requests were not in flight at the same time"
+        );
+    }
+
+    #[test]
+    fn run_probe_async_http_failure_rejects_with_code() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").expect("bind");
+        let port = listener.local_addr().expect("local addr").port();
+        drop(listener);
+        let mut plugin = test_plugin(&format!(
+            r#"
+            globalThis.__openusage_plugin = {{
+                probe: async function (ctx) {{
+                    await ctx.host.http.requestAsync({{ url: "http://127.0.0.1:{port}/" }});
+                    return {{ lines: [] }};
+                }}
+            }};
+            "#
+        ));
+        plugin.manifest.capabilities.http = vec!["127.0.0.1".to_string()];
+        let error = probe_error(run_probe(&plugin, &temp_app_dir("async-fail"), "0.0.0"));
+        assert_eq!(error.code, ProbeErrorCode::Network);
+    }
+
+    #[test]
+    fn run_probe_undeclared_capability_is_permission_denied() {
+        let plugin = test_plugin(
+            r#"
+            globalThis.__openusage_plugin = {
+                probe(ctx) {
+                    ctx.host.fs.readText("~/.ssh/id_rsa");
+                    return { lines: [] };
+                }
+            };
+            "#,
+        );
+        let error = probe_error(run_probe(&plugin, &temp_app_dir("denied"), "0.0.0"));
+        assert_eq!(error.code, ProbeErrorCode::PermissionDenied);
+        assert_eq!(
+            error.message,
+            "permission denied: reading ~/.ssh/id_rsa is not declared in capabilities.fs.read"
+        );
+        assert!(!error.retryable);
+    }
+
+    #[test]
+    fn run_probe_async_request_still_bound_by_deadline() {
+        let port = slow_http_server(1, Duration::from_secs(5));
+        let mut plugin = test_plugin(&format!(
+            r#"
+            globalThis.__openusage_plugin = {{
+                probe: async function (ctx) {{
+                    try {{
+                        await ctx.host.http.requestAsync({{ url: "http://127.0.0.1:{port}/" }});
+                    }} catch (e) {{}}
+                    return {{ lines: [ctx.line.text({{ label: "A", value: "B" }})] }};
+                }}
+            }};
+            "#
+        ));
+        plugin.manifest.capabilities.http = vec!["127.0.0.1".to_string()];
+        let start = Instant::now();
+        let output = run_with_timeout(
+            &plugin,
+            &temp_app_dir("async-deadline"),
+            "0.0.0",
+            Duration::from_millis(300),
+        );
+        assert_eq!(probe_error(output).code, ProbeErrorCode::Timeout);
+        assert!(start.elapsed() < Duration::from_secs(3));
+    }
+
+    #[test]
+    fn probe_timeout_uses_default_and_clamps_overrides() {
+        let mut plugin = test_plugin("");
+        assert_eq!(
+            probe_timeout(&plugin.manifest),
+            Duration::from_millis(DEFAULT_PROBE_TIMEOUT_MS)
+        );
+
+        plugin.manifest.probe_timeout_ms = Some(5_000);
+        assert_eq!(probe_timeout(&plugin.manifest), Duration::from_secs(5));
+
+        plugin.manifest.probe_timeout_ms = Some(10);
+        assert_eq!(
+            probe_timeout(&plugin.manifest),
+            Duration::from_millis(MIN_PROBE_TIMEOUT_MS)
+        );
+
+        plugin.manifest.probe_timeout_ms = Some(u64::MAX);
+        assert_eq!(
+            probe_timeout(&plugin.manifest),
+            Duration::from_millis(MAX_PROBE_TIMEOUT_MS)
+        );
+    }
+
     #[test]
     fn progress_resets_at_serializes_as_resets_at_camelcase() {
         let line = MetricLine::Progress {
@@ -560,4 +1769,596 @@ mod tests {
             "did not expect resets_at key"
         );
     }
+
+    #[test]
+    fn run_probe_parses_segmented_line_and_round_trips() {
+        let plugin = test_plugin(
+            r##"
+            globalThis.__openusage_plugin = {
+                probe(ctx) {
+                    return {
+                        lines: [
+                            ctx.line.segmented({
+                                label: "Credits",
+                                limit: 500,
+                                format: { kind: "count", suffix: "credits" },
+                                resetsAt: "2099-01-01T00:00:00Z",
+                                segments: [
+                                    { label: "Base", used: 120 },
+                                    { label: "Bonus", used: 30, color: "#22c55e" },
+                                ],
+                            }),
+                        ],
+                    };
+                },
+            };
+            "##,
+        );
+        let output = run_probe(&plugin, &temp_app_dir("segmented"), "0.0.0");
+        assert!(output.error.is_none(), "{:?}", output.error);
+
+        let json = serde_json::to_string(&output).expect("serialize");
+        let parsed: PluginOutput = serde_json::from_str(&json).expect("deserialize");
+        match parsed.lines.first() {
+            Some(MetricLine::Segmented {
+                label,
+                segments,
+                limit,
+                format: ProgressFormat::Count { suffix },
+                resets_at,
+                ..
+            }) => {
+                assert_eq!(label, "Credits");
+                assert_eq!(*limit, 500.0);
+                assert_eq!(suffix, "credits");
+                assert_eq!(resets_at.as_deref(), Some("2099-01-01T00:00:00Z"));
+                let summary: Vec<_> = segments
+                    .iter()
+                    .map(|s| (s.label.as_str(), s.used, s.color.as_deref()))
+                    .collect();
+                assert_eq!(
+                    summary,
+                    vec![("Base", 120.0, None), ("Bonus", 30.0, Some("#22c55e"))]
+                );
+            }
+            other => panic!("unexpected line: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn run_probe_rejects_invalid_segmented_lines() {
+        let cases = [
+            (
+                "segments: []",
+                "segmented line at index 0 must have 1-8 segments (got 0)",
+            ),
+            (
+                "segments: [{ label: 'A', used: -1 }]",
+                "segmented line at index 0 segment 0 invalid used: -1",
+            ),
+            (
+                "segments: [{ used: 1 }]",
+                "segmented line at index 0 segment 0 missing label",
+            ),
+            (
+                "segments: [{ label: 'A', used: 'x' }]",
+                "segmented line at index 0 segment 0 invalid used (expected number)",
+            ),
+        ];
+        for (segments, expected) in cases {
+            let plugin = test_plugin(&format!(
+                r#"
+                globalThis.__openusage_plugin = {{
+                    probe() {{
+                        return {{ lines: [{{
+                            type: "segmented", label: "Usage", limit: 10,
+                            format: {{ kind: "dollars" }}, {segments}
+                        }}] }};
+                    }},
+                }};
+                "#
+            ));
+            let output = run_probe(&plugin, &temp_app_dir("segmented-invalid"), "0.0.0");
+            let error = probe_error(output);
+            assert_eq!(error.code, ProbeErrorCode::PluginBug, "{}", segments);
+            assert_eq!(error.message, expected);
+        }
+    }
+
+    #[test]
+    fn run_probe_returns_multiple_accounts() {
+        let plugin = test_plugin(
+            r#"
+            globalThis.__openusage_plugin = {
+                probe(ctx) {
+                    return {
+                        accounts: [
+                            {
+                                key: "personal",
+                                label: "Personal",
+                                plan: "Pro",
+                                lines: [ctx.line.text({ label: "Email", value: "me@example.com" })],
+                            },
+                            {
+                                key: "org_acme",
+                                plan: "Team",
+                                lines: [ctx.line.badge({ label: "Seat", text: "Active" })],
+                            },
+                        ],
+                    };
+                },
+            };
+            "#,
+        );
+        let output = run_probe(&plugin, &temp_app_dir("accounts"), "0.0.0");
+        assert!(output.error.is_none(), "{:?}", output.error);
+
+        let summary: Vec<_> = output
+            .accounts
+            .iter()
+            .map(|a| {
+                (
+                    a.key.as_str(),
+                    a.label.as_str(),
+                    a.plan.as_deref(),
+                    a.lines.len(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                ("personal", "Personal", Some("Pro"), 1),
+                ("org_acme", "org_acme", Some("Team"), 1),
+            ]
+        );
+        // The top-level fields mirror the first account.
+        assert_eq!(output.plan.as_deref(), Some("Pro"));
+        assert!(matches!(
+            output.lines.first(),
+            Some(MetricLine::Text { value, .. }) if value == "me@example.com"
+        ));
+    }
+
+    #[test]
+    fn run_probe_rejects_invalid_accounts() {
+        let line = "lines: [{ type: 'text', label: 'A', value: 'B' }]";
+        let cases = [
+            ("[]".to_string(), "accounts must have 1-16 entries (got 0)"),
+            (
+                format!("[{{ key: 'a b', {line} }}]"),
+                r#"account at index 0 has invalid key "a b" (letters, digits, '.', '_', '-', '@')"#,
+            ),
+            (
+                format!("[{{ key: 'a', {line} }}, {{ key: 'a', {line} }}]"),
+                "duplicate account key: a",
+            ),
+            (
+                "[{ key: 'a', lines: [] }]".to_string(),
+                "account a: no lines returned",
+            ),
+            ("'nope'".to_string(), "accounts must be an array"),
+        ];
+        for (accounts, expected) in cases {
+            let plugin = test_plugin(&format!(
+                r#"
+                globalThis.__openusage_plugin = {{
+                    probe() {{ return {{ accounts: {accounts} }}; }},
+                }};
+                "#
+            ));
+            let output = run_probe(&plugin, &temp_app_dir("accounts-invalid"), "0.0.0");
+            let error = output.error.expect("probe should fail");
+            assert_eq!(error.code, ProbeErrorCode::PluginBug);
+            assert_eq!(error.message, expected, "for {}", accounts);
+        }
+    }
+
+    #[test]
+    fn run_probe_parses_extended_progress_formats() {
+        let plugin = test_plugin(
+            r#"
+            globalThis.__openusage_plugin = {
+                probe(ctx) {
+                    return {
+                        lines: [
+                            ctx.line.progress({ label: "Balance", used: 12, limit: 50, format: { kind: "currency", code: "cny" } }),
+                            ctx.line.progress({ label: "Tokens", used: 9200000, limit: 50000000, format: { kind: "tokens" } }),
+                            ctx.line.progress({ label: "Agent", used: 95, limit: 300, format: { kind: "duration", unit: "minutes" } }),
+                        ],
+                    };
+                },
+            };
+            "#,
+        );
+        let output = run_probe(&plugin, &temp_app_dir("formats"), "0.0.0");
+        let formats: Vec<serde_json::Value> = output
+            .lines
+            .iter()
+            .map(|line| match line {
+                MetricLine::Progress { format, .. } => serde_json::to_value(format).expect("json"),
+                other => panic!("unexpected line: {:?}", other),
+            })
+            .collect();
+        assert_eq!(
+            formats,
+            vec![
+                serde_json::json!({ "kind": "currency", "code": "CNY" }),
+                serde_json::json!({ "kind": "tokens" }),
+                serde_json::json!({ "kind": "duration", "unit": "minutes" }),
+            ]
+        );
+    }
+
+    #[test]
+    fn run_probe_rejects_invalid_progress_formats() {
+        let cases = [
+            (
+                "{ kind: 'currency' }",
+                "progress line at index 0: currency format code must be a 3-letter ISO 4217 code (got \"\")",
+            ),
+            (
+                "{ kind: 'currency', code: 'EURO' }",
+                "progress line at index 0: currency format code must be a 3-letter ISO 4217 code (got \"EURO\")",
+            ),
+            (
+                "{ kind: 'duration', unit: 'days' }",
+                "progress line at index 0: duration format unit must be seconds, minutes or hours (got \"days\")",
+            ),
+            (
+                "{ kind: 'bytes' }",
+                "progress line at index 0 invalid format.kind: bytes",
+            ),
+        ];
+        for (format, expected) in cases {
+            let plugin = test_plugin(&format!(
+                r#"
+                globalThis.__openusage_plugin = {{
+                    probe() {{
+                        return {{ lines: [{{
+                            type: "progress", label: "Usage", used: 1, limit: 10, format: {format}
+                        }}] }};
+                    }},
+                }};
+                "#
+            ));
+            let output = run_probe(&plugin, &temp_app_dir("formats-invalid"), "0.0.0");
+            let error = probe_error(output);
+            assert_eq!(error.code, ProbeErrorCode::PluginBug, "{}", format);
+            assert_eq!(error.message, expected);
+        }
+    }
+
+    #[test]
+    fn run_probe_parses_series_line_and_normalizes_timestamps() {
+        let plugin = test_plugin(
+            r#"
+            globalThis.__openusage_plugin = {
+                probe(ctx) {
+                    return {
+                        lines: [
+                            ctx.line.series({
+                                label: "Daily spend",
+                                format: { kind: "dollars" },
+                                points: [
+                                    { at: "2026-03-01", value: 1.5 },
+                                    { at: 1772409600, value: 0 },
+                                    { at: "2026-03-03T00:00:00Z", value: 4.25 },
+                                ],
+                            }),
+                        ],
+                    };
+                },
+            };
+            "#,
+        );
+        let output = run_probe(&plugin, &temp_app_dir("series"), "0.0.0");
+        assert!(output.error.is_none(), "{:?}", output.error);
+
+        let json = serde_json::to_value(&output).expect("serialize");
+        assert_eq!(json["lines"][0]["type"], "series");
+        let parsed: PluginOutput = serde_json::from_value(json).expect("deserialize");
+        match parsed.lines.first() {
+            Some(MetricLine::Series {
+                label,
+                points,
+                format: ProgressFormat::Dollars,
+                color: None,
+            }) => {
+                assert_eq!(label, "Daily spend");
+                let summary: Vec<_> = points.iter().map(|p| (p.at.as_str(), p.value)).collect();
+                assert_eq!(
+                    summary,
+                    vec![
+                        ("2026-03-01T00:00:00.000Z", 1.5),
+                        ("2026-03-02T00:00:00.000Z", 0.0),
+                        ("2026-03-03T00:00:00.000Z", 4.25),
+                    ]
+                );
+            }
+            other => panic!("unexpected line: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn run_probe_rejects_invalid_series_lines() {
+        let too_many = (0..367)
+            .map(|day| {
+                format!(
+                    "{{ at: {}, value: 1 }}",
+                    1_700_000_000_000u64 + day * 86_400_000
+                )
+            })
+            .collect::<Vec<_>>()
+            .join(", ");
+        let cases = [
+            (
+                "[]".to_string(),
+                "series line at index 0 must have 1-366 points (got 0)",
+            ),
+            (
+                format!("[{}]", too_many),
+                "series line at index 0 must have 1-366 points (got 367)",
+            ),
+            (
+                "[{ at: '2026-03-02T00:00:00Z', value: 1 }, { at: '2026-03-01T00:00:00Z', value: 2 }]"
+                    .to_string(),
+                "series line at index 0 point 1 is not after the previous point",
+            ),
+            (
+                "[{ at: 'yesterday', value: 1 }]".to_string(),
+                "series line at index 0 point 0 invalid at (expected RFC 3339 timestamp)",
+            ),
+            (
+                "[{ at: '2026-03-01T00:00:00Z', value: Infinity }]".to_string(),
+                "series line at index 0 point 0 invalid value: inf",
+            ),
+            (
+                "[{ at: '2026-03-01T00:00:00Z', value: '3' }]".to_string(),
+                "series line at index 0 point 0 invalid value (expected number)",
+            ),
+        ];
+        for (points, expected) in cases {
+            let plugin = test_plugin(&format!(
+                r#"
+                globalThis.__openusage_plugin = {{
+                    probe() {{
+                        return {{ lines: [{{
+                            type: "series", label: "Daily", format: {{ kind: "percent" }},
+                            points: {points}
+                        }}] }};
+                    }},
+                }};
+                "#
+            ));
+            let output = run_probe(&plugin, &temp_app_dir("series-invalid"), "0.0.0");
+            let error = probe_error(output);
+            assert_eq!(error.code, ProbeErrorCode::PluginBug, "{}", points);
+            assert_eq!(error.message, expected);
+        }
+    }
+
+    fn module_plugin(label: &str, files: &[(&str, &str)]) -> LoadedPlugin {
+        let dir = temp_app_dir(label).join("plugin");
+        for (name, source) in files {
+            let path = dir.join(name);
+            std::fs::create_dir_all(path.parent().expect("parent")).expect("create dir");
+            std::fs::write(&path, source).expect("write module");
+        }
+        let mut plugin = test_plugin(files[0].1);
+        plugin.manifest.entry_type = EntryType::Module;
+        plugin.manifest.entry = files[0].0.to_string();
+        plugin.module_sources = files[1..]
+            .iter()
+            .map(|(name, source)| (name.to_string(), source.to_string()))
+            .collect();
+        plugin.plugin_dir = dir;
+        plugin
+    }
+
+    #[test]
+    fn run_probe_loads_module_entry_with_imports_and_builtins() {
+        let plugin = module_plugin(
+            "module",
+            &[
+                (
+                    "plugin.js",
+                    r#"
+                    import { text } from "openusage:line";
+                    import { usageLabel } from "./lib/format.js";
+                    export async function probe(ctx) {
+                        return { lines: [text({ label: usageLabel(), value: ctx.app.version })] };
+                    }
+                    "#,
+                ),
+                (
+                    "lib/format.js",
+                    r#"
+                    import fmt from "openusage:fmt";
+                    import { prefix } from "../shared.js";
+                    export function usageLabel() {
+                        return prefix + (typeof fmt.dollars === "function" ? "ok" : "missing");
+                    }
+                    "#,
+                ),
+                ("shared.js", r#"export const prefix = "usage-";"#),
+            ],
+        );
+        let output = run_probe(&plugin, &plugin.plugin_dir.clone(), "1.2.3");
+        assert!(output.error.is_none(), "{:?}", output.error);
+        assert!(matches!(
+            output.lines.first(),
+            Some(MetricLine::Text { label, value, .. }) if label == "usage-ok" && value == "1.2.3"
+        ));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn run_probe_module_cannot_import_outside_plugin_dir() {
+        // The import itself stays inside the plugin directory; only the symlink it names points
+        // out, so this exercises the canonical-path check rather than the lexical one.
+        let plugin = module_plugin(
+            "module-escape",
+            &[(
+                "plugin.js",
+                r#"
+                import secret from "./secret.js";
+                export function probe() { return { lines: [] }; }
+                "#,
+            )],
+        );
+        let outside = plugin.plugin_dir.join("../outside.js");
+        std::fs::write(&outside, "export default 1;").expect("write outside");
+        std::os::unix::fs::symlink(&outside, plugin.plugin_dir.join("secret.js")).expect("symlink");
+        let error = probe_error(run_probe(&plugin, &plugin.plugin_dir.clone(), "0.0.0"));
+        assert_eq!(error.code, ProbeErrorCode::PluginBug);
+        assert!(
+            error.message.starts_with("module load failed")
+                && error
+                    .message
+                    .contains("module must remain within plugin directory"),
+            "{}",
+            error.message
+        );
+    }
+
+    #[test]
+    fn run_probe_module_ignores_files_changed_after_load() {
+        let plugin = module_plugin(
+            "module-changed",
+            &[
+                (
+                    "plugin.js",
+                    r#"
+                    import { label } from "./lib/label.js";
+                    import { text } from "openusage:line";
+                    export function probe() { return { lines: [text({ label, value: "x" })] }; }
+                    "#,
+                ),
+                ("lib/label.js", r#"export const label = "loaded";"#),
+            ],
+        );
+        std::fs::write(
+            plugin.plugin_dir.join("lib/label.js"),
+            r#"export const label = "swapped";"#,
+        )
+        .expect("rewrite module");
+        let output = run_probe(&plugin, &plugin.plugin_dir.clone(), "0.0.0");
+        assert!(output.error.is_none(), "{:?}", output.error);
+        assert!(matches!(
+            output.lines.first(),
+            Some(MetricLine::Text { label, .. }) if label == "loaded"
+        ));
+    }
+
+    #[test]
+    fn run_probe_module_rejects_bare_and_unknown_builtin_imports() {
+        for import in ["lodash", "openusage:fs"] {
+            let plugin = module_plugin(
+                "module-bare",
+                &[(
+                    "plugin.js",
+                    &format!(
+                        "import x from \"{}\";\nexport function probe() {{ return {{ lines: [] }}; }}",
+                        import
+                    ),
+                )],
+            );
+            let error = probe_error(run_probe(&plugin, &plugin.plugin_dir.clone(), "0.0.0"));
+            assert!(
+                error.message.starts_with("module load failed"),
+                "{}",
+                error.message
+            );
+        }
+    }
+
+    #[test]
+    fn run_probe_module_without_probe_export_is_plugin_bug() {
+        let plugin = module_plugin(
+            "module-no-probe",
+            &[("plugin.js", "export const name = 'x';")],
+        );
+        let error = probe_error(run_probe(&plugin, &plugin.plugin_dir.clone(), "0.0.0"));
+        assert_eq!(error.message, "missing probe() export");
+    }
+
+    fn setup_batch(runtimes: &[(&LoadedPlugin, ProbeRuntime)], app_data_dir: &PathBuf) -> Duration {
+        let start = Instant::now();
+        for (plugin, runtime) in runtimes {
+            let ctx = Context::full(&runtime.rt).expect("context");
+            let jobs = Rc::new(HostJobs::new());
+            let deadline = ProbeDeadline::after(Duration::from_secs(30));
+            ctx.with(|ctx| {
+                load_plugin(
+                    &ctx,
+                    &runtime.cache,
+                    plugin,
+                    &plugin.manifest.entry,
+                    app_data_dir,
+                    "0.0.0",
+                    deadline,
+                    &jobs,
+                )
+                .map_err(|output| format!("{:?}", output.error))
+                .expect("plugin loads");
+            });
+        }
+        start.elapsed()
+    }
+
+    /// Probe-setup cost of one batch over the bundled plugins: a new runtime compiling every
+    /// script from source (cold) versus a reused runtime loading cached bytecode (warm).
+    ///
+    /// `cargo test --release probe_setup_benchmark -- --ignored --nocapture`
+    #[test]
+    #[ignore]
+    fn probe_setup_benchmark() {
+        const ROUNDS: u32 = 20;
+        let plugins_dir = std::env::var_os("OPENUSAGE_PLUGINS_DIR")
+            .map(PathBuf::from)
+            .unwrap_or_else(|| PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("../plugins"));
+        let plugins = manifest::load_plugins_from_dir(&plugins_dir);
+        assert!(
+            !plugins.is_empty(),
+            "no plugins in {}",
+            plugins_dir.display()
+        );
+        let app_data_dir = temp_app_dir("bench");
+
+        let mut cold = Duration::ZERO;
+        for _ in 0..ROUNDS {
+            let start = Instant::now();
+            let runtimes: Vec<_> = plugins
+                .iter()
+                .map(|plugin| {
+                    let runtime = ProbeRuntime::new(BytecodeCache::new()).expect("runtime");
+                    (plugin, runtime)
+                })
+                .collect();
+            setup_batch(&runtimes, &app_data_dir);
+            cold += start.elapsed();
+        }
+
+        let cache = BytecodeCache::new();
+        let runtimes: Vec<_> = plugins
+            .iter()
+            .map(|plugin| (plugin, ProbeRuntime::new(cache.clone()).expect("runtime")))
+            .collect();
+        setup_batch(&runtimes, &app_data_dir);
+        let mut warm = Duration::ZERO;
+        for _ in 0..ROUNDS {
+            warm += setup_batch(&runtimes, &app_data_dir);
+        }
+
+        let cold_ms = cold.as_secs_f64() * 1000.0 / f64::from(ROUNDS);
+        let warm_ms = warm.as_secs_f64() * 1000.0 / f64::from(ROUNDS);
+        println!(
+            "probe setup for {} plugins: cold {:.2}ms, warm {:.2}ms per batch ({:.1}x)",
+            plugins.len(),
+            cold_ms,
+            warm_ms,
+            cold_ms / warm_ms
+        );
+    }
 }
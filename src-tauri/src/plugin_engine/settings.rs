@@ -0,0 +1,447 @@
+//! Stored values for the settings a plugin manifest declares.
+//!
+//! Plain values live in `plugin-settings.json` in the app data directory, keyed by plugin id.
+//! Secrets never touch that file: they are kept in the macOS keychain, one service per plugin
+//! and one account per setting key. Probes see the resolved values (the stored value, else the
+//! manifest default) as `ctx.config`.
+
+use crate::plugin_engine::host_api;
+use crate::plugin_engine::manifest::{self, PluginManifest, SettingDefinition, SettingKind};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::Mutex;
+
+const SETTINGS_FILE_NAME: &str = "plugin-settings.json";
+const SETTINGS_FILE_VERSION: u32 = 1;
+const SECRET_SERVICE_PREFIX: &str = "OpenUsage plugin setting";
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct SettingsFile {
+    version: u32,
+    plugins: HashMap<String, Map<String, Value>>,
+}
+
+/// Where secret settings are kept.
+pub trait SecretStore {
+    fn get(&self, plugin_id: &str, key: &str) -> Result<Option<String>, String>;
+    fn set(&self, plugin_id: &str, key: &str, value: &str) -> Result<(), String>;
+    fn delete(&self, plugin_id: &str, key: &str) -> Result<(), String>;
+}
+
+/// Secrets in the login keychain, via the `security` CLI like the plugin keychain API.
+pub struct KeychainSecrets;
+
+fn secret_service(plugin_id: &str) -> String {
+    format!("{} {}", SECRET_SERVICE_PREFIX, plugin_id)
+}
+
+impl SecretStore for KeychainSecrets {
+    fn get(&self, plugin_id: &str, key: &str) -> Result<Option<String>, String> {
+        if !cfg!(target_os = "macos") {
+            return Ok(None);
+        }
+        let output = std::process::Command::new("security")
+            .args(host_api::keychain_find_generic_password_args_for_account(
+                &secret_service(plugin_id),
+                key,
+            ))
+            .output()
+            .map_err(|e| format!("keychain read failed: {}", e))?;
+        if !output.status.success() {
+            return Ok(None);
+        }
+        let value = String::from_utf8_lossy(&output.stdout)
+            .trim_end_matches('\n')
+            .to_string();
+        Ok(Some(value))
+    }
+
+    fn set(&self, plugin_id: &str, key: &str, value: &str) -> Result<(), String> {
+        if !cfg!(target_os = "macos") {
+            return Err("secret settings require the macOS keychain".to_string());
+        }
+        let output = std::process::Command::new("security")
+            .args(host_api::keychain_add_generic_password_args_for_account(
+                &secret_service(plugin_id),
+                key,
+                value,
+            ))
+            .output()
+            .map_err(|e| format!("keychain write failed: {}", e))?;
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            return Err(format!("keychain write failed: {}", stderr.trim()));
+        }
+        Ok(())
+    }
+
+    fn delete(&self, plugin_id: &str, key: &str) -> Result<(), String> {
+        if !cfg!(target_os = "macos") {
+            return Ok(());
+        }
+        // A missing item is already deleted, so the exit status is not checked.
+        std::process::Command::new("security")
+            .args([
+                "delete-generic-password",
+                "-a",
+                key,
+                "-s",
+                &secret_service(plugin_id),
+            ])
+            .output()
+            .map_err(|e| format!("keychain delete failed: {}", e))?;
+        Ok(())
+    }
+}
+
+/// What the settings UI needs for one plugin.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PluginSettingsView {
+    pub definitions: Vec<SettingDefinition>,
+    /// Stored non-secret values; settings left at their default are absent.
+    pub values: Map<String, Value>,
+    /// Secret keys that currently have a value. Secret values never leave Rust.
+    pub secrets_set: Vec<String>,
+}
+
+fn write_lock() -> &'static Mutex<()> {
+    static LOCK: Mutex<()> = Mutex::new(());
+    &LOCK
+}
+
+fn load_file(app_data_dir: &Path) -> SettingsFile {
+    let path = app_data_dir.join(SETTINGS_FILE_NAME);
+    let Ok(data) = std::fs::read_to_string(&path) else {
+        return SettingsFile::default();
+    };
+    match serde_json::from_str::<SettingsFile>(&data) {
+        Ok(file) if file.version == SETTINGS_FILE_VERSION => file,
+        Ok(_) => {
+            log::warn!("{} has unsupported version, ignoring", SETTINGS_FILE_NAME);
+            SettingsFile::default()
+        }
+        Err(e) => {
+            log::warn!("failed to parse {}: {}, ignoring", SETTINGS_FILE_NAME, e);
+            SettingsFile::default()
+        }
+    }
+}
+
+fn save_file(app_data_dir: &Path, file: &SettingsFile) -> Result<(), String> {
+    let json = serde_json::to_string_pretty(file).map_err(|e| e.to_string())?;
+    let path = app_data_dir.join(SETTINGS_FILE_NAME);
+    let tmp_path = app_data_dir.join(format!(".{}.tmp", SETTINGS_FILE_NAME));
+    std::fs::write(&tmp_path, json).map_err(|e| format!("failed to write settings: {}", e))?;
+    std::fs::rename(&tmp_path, &path).map_err(|e| format!("failed to save settings: {}", e))
+}
+
+/// Check `value` against its definition. `Ok(None)` means "clear back to the default".
+fn validate_value(definition: &SettingDefinition, value: &Value) -> Result<Option<Value>, String> {
+    if value.is_null() {
+        return Ok(None);
+    }
+    let invalid = |expected: &str| Err(format!("{}: expected {}", definition.key, expected));
+    match &definition.kind {
+        SettingKind::Boolean { .. } => match value {
+            Value::Bool(_) => Ok(Some(value.clone())),
+            _ => invalid("a boolean"),
+        },
+        SettingKind::String { .. } | SettingKind::Secret => match value.as_str() {
+            Some("") => Ok(None),
+            Some(text) => Ok(Some(Value::String(text.to_string()))),
+            None => invalid("a string"),
+        },
+        SettingKind::Enum { options, .. } => match value.as_str() {
+            Some(option) if options.iter().any(|o| o == option) => Ok(Some(value.clone())),
+            _ => invalid(&format!("one of {}", options.join(", "))),
+        },
+        SettingKind::Url { .. } => match value.as_str().map(str::trim) {
+            Some("") => Ok(None),
+            Some(url) if manifest::is_http_url(url) => Ok(Some(Value::String(url.to_string()))),
+            _ => invalid("an http(s) URL"),
+        },
+    }
+}
+
+fn default_value(kind: &SettingKind) -> Value {
+    match kind {
+        SettingKind::Boolean { default } => Value::Bool(*default),
+        SettingKind::String { default }
+        | SettingKind::Enum { default, .. }
+        | SettingKind::Url { default } => default.clone().map(Value::String).unwrap_or(Value::Null),
+        SettingKind::Secret => Value::Null,
+    }
+}
+
+pub fn read_settings(
+    manifest: &PluginManifest,
+    app_data_dir: &Path,
+    secrets: &dyn SecretStore,
+) -> PluginSettingsView {
+    let mut file = load_file(app_data_dir);
+    let stored = file.plugins.remove(&manifest.id).unwrap_or_default();
+    let mut values = Map::new();
+    let mut secrets_set = Vec::new();
+    for definition in &manifest.settings {
+        if definition.kind == SettingKind::Secret {
+            match secrets.get(&manifest.id, &definition.key) {
+                Ok(Some(_)) => secrets_set.push(definition.key.clone()),
+                Ok(None) => {}
+                Err(e) => log::warn!("[plugin:{}] {}", manifest.id, e),
+            }
+            continue;
+        }
+        if let Some(value) = stored.get(&definition.key)
+            && let Ok(Some(value)) = validate_value(definition, value)
+        {
+            values.insert(definition.key.clone(), value);
+        }
+    }
+    PluginSettingsView {
+        definitions: manifest.settings.clone(),
+        values,
+        secrets_set,
+    }
+}
+
+/// Apply `updates` (key -> new value, `null` to clear) and return the new view. Nothing is
+/// written unless every update is valid.
+pub fn write_settings(
+    manifest: &PluginManifest,
+    app_data_dir: &Path,
+    secrets: &dyn SecretStore,
+    updates: Map<String, Value>,
+) -> Result<PluginSettingsView, String> {
+    let mut validated = Vec::with_capacity(updates.len());
+    for (key, value) in &updates {
+        let definition = manifest
+            .settings
+            .iter()
+            .find(|definition| &definition.key == key)
+            .ok_or_else(|| format!("unknown setting: {}", key))?;
+        validated.push((definition, validate_value(definition, value)?));
+    }
+
+    {
+        let _guard = write_lock().lock().expect("settings lock poisoned");
+        let mut file = load_file(app_data_dir);
+        file.version = SETTINGS_FILE_VERSION;
+        let stored = file.plugins.entry(manifest.id.clone()).or_default();
+        for (definition, value) in validated {
+            let key = &definition.key;
+            match (&definition.kind, value) {
+                (SettingKind::Secret, Some(Value::String(secret))) => {
+                    secrets.set(&manifest.id, key, &secret)?
+                }
+                (SettingKind::Secret, _) => secrets.delete(&manifest.id, key)?,
+                (_, Some(value)) => {
+                    stored.insert(key.clone(), value);
+                }
+                (_, None) => {
+                    stored.remove(key);
+                }
+            }
+        }
+        if stored.is_empty() {
+            file.plugins.remove(&manifest.id);
+        }
+        save_file(app_data_dir, &file)?;
+    }
+
+    Ok(read_settings(manifest, app_data_dir, secrets))
+}
+
+/// Every declared setting's effective value, as handed to the probe in `ctx.config`.
+pub fn resolve_config(
+    manifest: &PluginManifest,
+    app_data_dir: &Path,
+    secrets: &dyn SecretStore,
+) -> Map<String, Value> {
+    if manifest.settings.is_empty() {
+        return Map::new();
+    }
+    let mut file = load_file(app_data_dir);
+    let stored = file.plugins.remove(&manifest.id).unwrap_or_default();
+    let mut config = Map::new();
+    for definition in &manifest.settings {
+        let value = if definition.kind == SettingKind::Secret {
+            match secrets.get(&manifest.id, &definition.key) {
+                Ok(secret) => secret.map(Value::String),
+                Err(e) => {
+                    log::warn!("[plugin:{}] {}", manifest.id, e);
+                    None
+                }
+            }
+        } else {
+            // A value stored before the manifest changed may no longer be valid.
+            stored
+                .get(&definition.key)
+                .and_then(|value| validate_value(definition, value).ok().flatten())
+        };
+        config.insert(
+            definition.key.clone(),
+            value.unwrap_or_else(|| default_value(&definition.kind)),
+        );
+    }
+    config
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+
+    #[derive(Default)]
+    struct MemorySecrets(RefCell<HashMap<(String, String), String>>);
+
+    impl SecretStore for MemorySecrets {
+        fn get(&self, plugin_id: &str, key: &str) -> Result<Option<String>, String> {
+            let secrets = self.0.borrow();
+            Ok(secrets.get(&(plugin_id.into(), key.into())).cloned())
+        }
+
+        fn set(&self, plugin_id: &str, key: &str, value: &str) -> Result<(), String> {
+            let mut secrets = self.0.borrow_mut();
+            secrets.insert((plugin_id.into(), key.into()), value.into());
+            Ok(())
+        }
+
+        fn delete(&self, plugin_id: &str, key: &str) -> Result<(), String> {
+            let mut secrets = self.0.borrow_mut();
+            secrets.remove(&(plugin_id.into(), key.into()));
+            Ok(())
+        }
+    }
+
+    fn temp_app_dir(label: &str) -> std::path::PathBuf {
+        let nanos = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .expect("time")
+            .as_nanos();
+        let dir = std::env::temp_dir().join(format!("openusage-settings-{}-{}", label, nanos));
+        std::fs::create_dir_all(&dir).expect("create dir");
+        dir
+    }
+
+    fn manifest() -> PluginManifest {
+        serde_json::from_str(
+            r#"
+            {
+              "schemaVersion": 1,
+              "id": "minimax",
+              "name": "MiniMax",
+              "version": "0.0.1",
+              "entry": "plugin.js",
+              "icon": "icon.svg",
+              "brandColor": null,
+              "lines": [],
+              "settings": [
+                { "key": "apiKey", "label": "API key", "type": "secret" },
+                { "key": "region", "label": "Region", "type": "enum", "options": ["global", "cn"], "default": "global" },
+                { "key": "baseUrl", "label": "Endpoint", "type": "url" },
+                { "key": "showCost", "label": "Show cost", "type": "boolean", "default": true }
+              ]
+            }
+            "#,
+        )
+        .expect("manifest")
+    }
+
+    #[test]
+    fn resolve_config_falls_back_to_defaults() {
+        let dir = temp_app_dir("defaults");
+        let config = resolve_config(&manifest(), &dir, &MemorySecrets::default());
+        assert_eq!(
+            Value::Object(config),
+            serde_json::json!({
+                "apiKey": null,
+                "region": "global",
+                "baseUrl": null,
+                "showCost": true,
+            })
+        );
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn secrets_stay_out_of_the_settings_file() {
+        let dir = temp_app_dir("secrets");
+        let secrets = MemorySecrets::default();
+        let manifest = manifest();
+        let updates = serde_json::json!({
+            "apiKey": "sk-live-123",
+            "region": "cn",
+            "showCost": false,
+        });
+        let view = write_settings(
+            &manifest,
+            &dir,
+            &secrets,
+            updates.as_object().expect("object").clone(),
+        )
+        .expect("write");
+
+        assert_eq!(view.secrets_set, vec!["apiKey".to_string()]);
+        assert!(!view.values.contains_key("apiKey"));
+        let on_disk = std::fs::read_to_string(dir.join(SETTINGS_FILE_NAME)).expect("file");
+        assert!(!on_disk.contains("sk-live-123"));
+
+        let config = resolve_config(&manifest, &dir, &secrets);
+        assert_eq!(config["apiKey"], "sk-live-123");
+        assert_eq!(config["region"], "cn");
+        assert_eq!(config["showCost"], false);
+
+        // null clears a value back to its default, including secrets.
+        let clear = serde_json::json!({ "apiKey": null, "region": null });
+        write_settings(
+            &manifest,
+            &dir,
+            &secrets,
+            clear.as_object().expect("object").clone(),
+        )
+        .expect("clear");
+        let config = resolve_config(&manifest, &dir, &secrets);
+        assert_eq!(config["apiKey"], Value::Null);
+        assert_eq!(config["region"], "global");
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn write_settings_rejects_invalid_updates_without_writing() {
+        let dir = temp_app_dir("invalid");
+        let secrets = MemorySecrets::default();
+        let manifest = manifest();
+        let cases = [
+            (
+                serde_json::json!({ "region": "eu" }),
+                "region: expected one of global, cn",
+            ),
+            (
+                serde_json::json!({ "baseUrl": "file:///etc" }),
+                "baseUrl: expected an http(s) URL",
+            ),
+            (
+                serde_json::json!({ "showCost": "yes" }),
+                "showCost: expected a boolean",
+            ),
+            (
+                serde_json::json!({ "region": "cn", "nope": 1 }),
+                "unknown setting: nope",
+            ),
+        ];
+        for (updates, expected) in cases {
+            let result = write_settings(
+                &manifest,
+                &dir,
+                &secrets,
+                updates.as_object().expect("object").clone(),
+            );
+            assert_eq!(result.unwrap_err(), expected);
+        }
+        assert!(!dir.join(SETTINGS_FILE_NAME).exists());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,528 @@
+//! Plugin bundle signatures and the trust level each loaded plugin ends up with.
+//!
+//! A signed bundle carries `signature.json` next to `plugin.json`: the SHA-256 of every other
+//! file in the plugin directory plus an ed25519 signature over a canonical rendering of that
+//! list. A plugin is trusted when it is a byte-for-byte copy of a bundled plugin or when its
+//! signature verifies against one of the configured publisher keys. Unsigned plugins only run
+//! when the user opts in; plugins whose signature or files do not check out never run.
+
+use crate::plugin_engine::manifest::LoadedPlugin;
+use crate::plugin_engine::modules;
+use base64::{Engine as _, engine::general_purpose::STANDARD as BASE64_STANDARD};
+use ed25519_dalek::{Signature, Signer, SigningKey, Verifier, VerifyingKey};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+pub const SIGNATURE_FILE_NAME: &str = "signature.json";
+const SIGNATURE_FORMAT_VERSION: u32 = 1;
+const CANONICAL_HEADER: &str = "openusage-plugin-signature:v1\n";
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum TrustLevel {
+    /// Identical to a plugin shipped with the app.
+    Bundled,
+    /// Signed by a trusted publisher key.
+    Signed,
+    #[default]
+    Unsigned,
+    /// Carries a signature that does not verify, or files that no longer match it.
+    Invalid,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PluginTrust {
+    pub level: TrustLevel,
+    /// Name of the trusted publisher whose key signed the bundle.
+    pub publisher: Option<String>,
+    /// Whether the plugin may run under the current trust policy.
+    pub allowed: bool,
+    /// Why verification failed, for `invalid` plugins.
+    pub detail: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct TrustedPublisher {
+    pub name: String,
+    pub key: VerifyingKey,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct TrustPolicy {
+    pub trusted_publishers: Vec<TrustedPublisher>,
+    pub allow_unsigned: bool,
+    /// Pristine copies of the bundled plugins, one directory per id.
+    pub bundled_dir: Option<PathBuf>,
+}
+
+impl TrustPolicy {
+    /// Build the policy from the `plugins` section of the app config. Keys that do not parse
+    /// are logged and skipped.
+    pub fn from_config(
+        config: &crate::config::PluginsConfig,
+        bundled_dir: Option<PathBuf>,
+    ) -> Self {
+        let trusted_publishers = config
+            .trusted_publishers
+            .iter()
+            .filter_map(|publisher| match parse_public_key(&publisher.public_key) {
+                Ok(key) => Some(TrustedPublisher {
+                    name: publisher.name.clone(),
+                    key,
+                }),
+                Err(e) => {
+                    log::warn!("ignoring trusted publisher {}: {}", publisher.name, e);
+                    None
+                }
+            })
+            .collect();
+        Self {
+            trusted_publishers,
+            allow_unsigned: config.allow_unsigned,
+            bundled_dir,
+        }
+    }
+}
+
+/// Contents of `signature.json`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SignatureFile {
+    pub version: u32,
+    /// Base64 ed25519 public key of the signer.
+    pub public_key: String,
+    /// Base64 ed25519 signature over the canonical file list.
+    pub signature: String,
+    /// Relative path (always `/`-separated) to lowercase hex SHA-256.
+    pub files: BTreeMap<String, String>,
+}
+
+/// Work out the trust level of every plugin and log the ones that will not run.
+pub fn apply_trust(plugins: &mut [LoadedPlugin], policy: &TrustPolicy) {
+    for plugin in plugins.iter_mut() {
+        plugin.trust = assess_plugin(plugin, policy);
+        if !plugin.trust.allowed {
+            log::warn!(
+                "plugin {} is disabled: {:?}{}",
+                plugin.manifest.id,
+                plugin.trust.level,
+                plugin
+                    .trust
+                    .detail
+                    .as_deref()
+                    .map(|detail| format!(" ({})", detail))
+                    .unwrap_or_default()
+            );
+        }
+    }
+}
+
+pub fn assess_plugin(plugin: &LoadedPlugin, policy: &TrustPolicy) -> PluginTrust {
+    let signature_path = plugin.plugin_dir.join(SIGNATURE_FILE_NAME);
+    if !signature_path.exists() {
+        if is_bundled_copy(plugin, policy) {
+            return trust(TrustLevel::Bundled, None, true, None);
+        }
+        return trust(TrustLevel::Unsigned, None, policy.allow_unsigned, None);
+    }
+    match verify_bundle(plugin, &policy.trusted_publishers) {
+        Ok(publisher) => trust(TrustLevel::Signed, Some(publisher), true, None),
+        Err(VerifyError::UntrustedKey) => trust(
+            TrustLevel::Unsigned,
+            None,
+            policy.allow_unsigned,
+            Some("signed with a key that is not trusted".to_string()),
+        ),
+        Err(VerifyError::Invalid(detail)) => trust(TrustLevel::Invalid, None, false, Some(detail)),
+    }
+}
+
+fn trust(
+    level: TrustLevel,
+    publisher: Option<String>,
+    allowed: bool,
+    detail: Option<String>,
+) -> PluginTrust {
+    PluginTrust {
+        level,
+        publisher,
+        allowed,
+        detail,
+    }
+}
+
+fn is_bundled_copy(plugin: &LoadedPlugin, policy: &TrustPolicy) -> bool {
+    let Some(bundled_dir) = policy.bundled_dir.as_deref() else {
+        return false;
+    };
+    let reference = bundled_dir.join(&plugin.manifest.id);
+    if !reference.join("plugin.json").is_file() {
+        return false;
+    }
+    if reference == plugin.plugin_dir {
+        return true;
+    }
+    match (hash_files(&plugin.plugin_dir), hash_files(&reference)) {
+        (Ok(installed), Ok(bundled)) => {
+            installed == bundled && check_loaded_sources(plugin, &bundled).is_ok()
+        }
+        _ => false,
+    }
+}
+
+#[derive(Debug)]
+enum VerifyError {
+    UntrustedKey,
+    Invalid(String),
+}
+
+/// Check a bundle's signature and file hashes; returns the publisher name.
+fn verify_bundle(
+    plugin: &LoadedPlugin,
+    publishers: &[TrustedPublisher],
+) -> Result<String, VerifyError> {
+    let plugin_dir = &plugin.plugin_dir;
+    let invalid = |msg: String| VerifyError::Invalid(msg);
+    let text = std::fs::read_to_string(plugin_dir.join(SIGNATURE_FILE_NAME))
+        .map_err(|e| invalid(format!("unreadable {}: {}", SIGNATURE_FILE_NAME, e)))?;
+    let file: SignatureFile = serde_json::from_str(&text)
+        .map_err(|e| invalid(format!("malformed {}: {}", SIGNATURE_FILE_NAME, e)))?;
+    if file.version != SIGNATURE_FORMAT_VERSION {
+        return Err(invalid(format!(
+            "unsupported signature version {}",
+            file.version
+        )));
+    }
+
+    let key = parse_public_key(&file.public_key).map_err(invalid)?;
+    let signature_bytes = BASE64_STANDARD
+        .decode(file.signature.trim())
+        .map_err(|_| invalid("signature is not valid base64".to_string()))?;
+    let signature = Signature::from_slice(&signature_bytes)
+        .map_err(|_| invalid("signature has the wrong length".to_string()))?;
+    key.verify(canonical_message(&file.files).as_bytes(), &signature)
+        .map_err(|_| invalid("signature does not match the file list".to_string()))?;
+
+    let actual = hash_files(plugin_dir).map_err(invalid)?;
+    if actual != file.files {
+        let changed = actual
+            .keys()
+            .chain(file.files.keys())
+            .find(|path| actual.get(*path) != file.files.get(*path))
+            .cloned()
+            .unwrap_or_default();
+        return Err(invalid(format!("{} does not match the signature", changed)));
+    }
+    check_loaded_sources(plugin, &file.files).map_err(invalid)?;
+
+    publishers
+        .iter()
+        .find(|publisher| publisher.key == key)
+        .map(|publisher| publisher.name.clone())
+        .ok_or(VerifyError::UntrustedKey)
+}
+
+/// The scripts that run are the copies read when the plugin loaded, not the files on disk now;
+/// each of them must hash to its entry in `files`.
+fn check_loaded_sources(
+    plugin: &LoadedPlugin,
+    files: &BTreeMap<String, String>,
+) -> Result<(), String> {
+    let mut sources: Vec<(String, &str)> = plugin
+        .module_sources
+        .iter()
+        .map(|(path, source)| (path.clone(), source.as_str()))
+        .collect();
+    if !plugin.entry_script.is_empty() {
+        let plugin_dir = plugin
+            .plugin_dir
+            .canonicalize()
+            .map_err(|e| format!("failed to read {}: {}", plugin.plugin_dir.display(), e))?;
+        let entry = modules::resolve_in_plugin_dir(&plugin_dir, &plugin.manifest.entry)?;
+        sources.push((entry, plugin.entry_script.as_str()));
+    }
+    for (path, source) in sources {
+        if files.get(&path) != Some(&hex_sha256(source.as_bytes())) {
+            return Err(format!("loaded {} does not match the signature", path));
+        }
+    }
+    Ok(())
+}
+
+fn parse_public_key(value: &str) -> Result<VerifyingKey, String> {
+    let bytes = BASE64_STANDARD
+        .decode(value.trim())
+        .map_err(|_| "public key is not valid base64".to_string())?;
+    let bytes: [u8; 32] = bytes
+        .try_into()
+        .map_err(|_| "public key must be 32 bytes".to_string())?;
+    VerifyingKey::from_bytes(&bytes)
+        .map_err(|_| "public key is not a valid ed25519 key".to_string())
+}
+
+/// The exact bytes that get signed: a header line, then `<sha256>  <path>` per file in path order.
+fn canonical_message(files: &BTreeMap<String, String>) -> String {
+    let mut message = String::from(CANONICAL_HEADER);
+    for (path, hash) in files {
+        message.push_str(hash);
+        message.push_str("  ");
+        message.push_str(path);
+        message.push('\n');
+    }
+    message
+}
+
+/// SHA-256 of every file under `plugin_dir` except the signature itself. Symlinks are refused
+/// so a bundle cannot point outside its own directory.
+pub fn hash_files(plugin_dir: &Path) -> Result<BTreeMap<String, String>, String> {
+    let mut files = BTreeMap::new();
+    hash_files_into(plugin_dir, "", &mut files)?;
+    files.remove(SIGNATURE_FILE_NAME);
+    Ok(files)
+}
+
+fn hash_files_into(
+    dir: &Path,
+    prefix: &str,
+    files: &mut BTreeMap<String, String>,
+) -> Result<(), String> {
+    let entries =
+        std::fs::read_dir(dir).map_err(|e| format!("failed to read {}: {}", dir.display(), e))?;
+    for entry in entries {
+        let entry = entry.map_err(|e| e.to_string())?;
+        let name = entry.file_name();
+        let Some(name) = name.to_str() else {
+            return Err(format!("non UTF-8 file name in {}", dir.display()));
+        };
+        let relative = format!("{}{}", prefix, name);
+        let file_type = entry.file_type().map_err(|e| e.to_string())?;
+        if file_type.is_symlink() {
+            return Err(format!("{} is a symlink", relative));
+        }
+        if file_type.is_dir() {
+            hash_files_into(&entry.path(), &format!("{}/", relative), files)?;
+        } else {
+            let bytes = std::fs::read(entry.path())
+                .map_err(|e| format!("failed to read {}: {}", relative, e))?;
+            files.insert(relative, hex_sha256(&bytes));
+        }
+    }
+    Ok(())
+}
+
+fn hex_sha256(bytes: &[u8]) -> String {
+    Sha256::digest(bytes)
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+/// Produce `signature.json` contents for `plugin_dir`. Used by publisher tooling and tests.
+pub fn sign_bundle(plugin_dir: &Path, signing_key: &SigningKey) -> Result<SignatureFile, String> {
+    let files = hash_files(plugin_dir)?;
+    let signature = signing_key.sign(canonical_message(&files).as_bytes());
+    Ok(SignatureFile {
+        version: SIGNATURE_FORMAT_VERSION,
+        public_key: BASE64_STANDARD.encode(signing_key.verifying_key().as_bytes()),
+        signature: BASE64_STANDARD.encode(signature.to_bytes()),
+        files,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::plugin_engine::manifest::load_plugins_from_dir;
+
+    fn temp_dir(label: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!(
+            "openusage-signing-{}-{}",
+            label,
+            uuid::Uuid::new_v4()
+        ));
+        std::fs::create_dir_all(&dir).expect("create dir");
+        dir
+    }
+
+    fn write_plugin(root: &Path, id: &str) -> PathBuf {
+        let dir = root.join(id);
+        std::fs::create_dir_all(dir.join("lib")).expect("create plugin dir");
+        std::fs::write(
+            dir.join("plugin.json"),
+            format!(
+                r#"{{"schemaVersion":1,"id":"{}","name":"Demo","version":"1.0.0","entry":"plugin.js","icon":"icon.svg","brandColor":null,"lines":[]}}"#,
+                id
+            ),
+        )
+        .expect("write manifest");
+        std::fs::write(dir.join("plugin.js"), "globalThis.__openusage_plugin = {}")
+            .expect("write entry");
+        std::fs::write(dir.join("icon.svg"), "<svg/>").expect("write icon");
+        std::fs::write(dir.join("lib/util.js"), "export const x = 1").expect("write lib");
+        dir
+    }
+
+    fn sign(dir: &Path, key: &SigningKey) {
+        let file = sign_bundle(dir, key).expect("sign");
+        std::fs::write(
+            dir.join(SIGNATURE_FILE_NAME),
+            serde_json::to_string_pretty(&file).expect("serialize"),
+        )
+        .expect("write signature");
+    }
+
+    fn load(root: &Path, id: &str) -> LoadedPlugin {
+        load_plugins_from_dir(root)
+            .into_iter()
+            .find(|plugin| plugin.manifest.id == id)
+            .expect("plugin loads")
+    }
+
+    fn publisher(name: &str, key: &SigningKey) -> TrustedPublisher {
+        TrustedPublisher {
+            name: name.to_string(),
+            key: key.verifying_key(),
+        }
+    }
+
+    #[test]
+    fn signed_bundle_verifies_against_trusted_key() {
+        let root = temp_dir("signed");
+        let dir = write_plugin(&root, "demo");
+        let key = SigningKey::from_bytes(&[7; 32]);
+        sign(&dir, &key);
+
+        let policy = TrustPolicy {
+            trusted_publishers: vec![publisher("Acme", &key)],
+            ..Default::default()
+        };
+        let trust = assess_plugin(&load(&root, "demo"), &policy);
+        assert_eq!(trust.level, TrustLevel::Signed);
+        assert_eq!(trust.publisher.as_deref(), Some("Acme"));
+        assert!(trust.allowed);
+
+        let other = SigningKey::from_bytes(&[9; 32]);
+        let policy = TrustPolicy {
+            trusted_publishers: vec![publisher("Other", &other)],
+            ..Default::default()
+        };
+        let trust = assess_plugin(&load(&root, "demo"), &policy);
+        assert_eq!(trust.level, TrustLevel::Unsigned);
+        assert!(!trust.allowed, "untrusted signer is treated as unsigned");
+        let _ = std::fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn tampered_or_extra_files_invalidate_the_signature() {
+        let root = temp_dir("tampered");
+        let dir = write_plugin(&root, "demo");
+        let key = SigningKey::from_bytes(&[7; 32]);
+        sign(&dir, &key);
+        let policy = TrustPolicy {
+            trusted_publishers: vec![publisher("Acme", &key)],
+            allow_unsigned: true,
+            ..Default::default()
+        };
+
+        std::fs::write(dir.join("lib/util.js"), "export const x = 2").expect("tamper");
+        let trust = assess_plugin(&load(&root, "demo"), &policy);
+        assert_eq!(trust.level, TrustLevel::Invalid);
+        assert!(
+            !trust.allowed,
+            "invalid plugins never run, even with unsigned allowed"
+        );
+        assert_eq!(
+            trust.detail.as_deref(),
+            Some("lib/util.js does not match the signature")
+        );
+
+        std::fs::write(dir.join("lib/util.js"), "export const x = 1").expect("restore");
+        assert_eq!(
+            assess_plugin(&load(&root, "demo"), &policy).level,
+            TrustLevel::Signed
+        );
+        std::fs::write(dir.join("extra.js"), "").expect("add file");
+        assert_eq!(
+            assess_plugin(&load(&root, "demo"), &policy).level,
+            TrustLevel::Invalid
+        );
+        let _ = std::fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn signature_covers_the_loaded_module_sources() {
+        let root = temp_dir("loaded");
+        let dir = write_plugin(&root, "demo");
+        let manifest = std::fs::read_to_string(dir.join("plugin.json")).expect("read manifest");
+        std::fs::write(
+            dir.join("plugin.json"),
+            manifest.replace(
+                r#""entry":"plugin.js""#,
+                r#""entry":"plugin.js","entryType":"module""#,
+            ),
+        )
+        .expect("write manifest");
+        let key = SigningKey::from_bytes(&[7; 32]);
+        sign(&dir, &key);
+        let policy = TrustPolicy {
+            trusted_publishers: vec![publisher("Acme", &key)],
+            ..Default::default()
+        };
+
+        let plugin = load(&root, "demo");
+        assert_eq!(
+            plugin.module_sources.get("lib/util.js").map(String::as_str),
+            Some("export const x = 1")
+        );
+        assert_eq!(assess_plugin(&plugin, &policy).level, TrustLevel::Signed);
+
+        // The disk now matches a fresh signature, but the copy that would run does not.
+        std::fs::write(dir.join("lib/util.js"), "export const x = 2").expect("rewrite");
+        sign(&dir, &key);
+        let trust = assess_plugin(&plugin, &policy);
+        assert_eq!(trust.level, TrustLevel::Invalid);
+        assert_eq!(
+            trust.detail.as_deref(),
+            Some("loaded lib/util.js does not match the signature")
+        );
+        let _ = std::fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn unsigned_plugins_need_opt_in_unless_bundled() {
+        let root = temp_dir("unsigned");
+        let bundled = root.join("bundled");
+        let installed = root.join("installed");
+        write_plugin(&bundled, "demo");
+        write_plugin(&installed, "demo");
+        write_plugin(&installed, "thirdparty");
+
+        let mut plugins = load_plugins_from_dir(&installed);
+        apply_trust(
+            &mut plugins,
+            &TrustPolicy {
+                bundled_dir: Some(bundled.clone()),
+                ..Default::default()
+            },
+        );
+        assert_eq!(plugins[0].trust.level, TrustLevel::Bundled);
+        assert!(plugins[0].trust.allowed);
+        assert_eq!(plugins[1].trust.level, TrustLevel::Unsigned);
+        assert!(!plugins[1].trust.allowed);
+
+        // A modified copy of a bundled plugin is just an unsigned plugin.
+        std::fs::write(installed.join("demo/plugin.js"), "// changed").expect("modify");
+        let policy = TrustPolicy {
+            bundled_dir: Some(bundled),
+            allow_unsigned: true,
+            ..Default::default()
+        };
+        let trust = assess_plugin(&load(&installed, "demo"), &policy);
+        assert_eq!(trust.level, TrustLevel::Unsigned);
+        assert!(trust.allowed);
+        let _ = std::fs::remove_dir_all(&root);
+    }
+}
@@ -0,0 +1,569 @@
+//! Checks plugin directories and reports every problem found, instead of the loader's
+//! "skip what does not load" behaviour.
+//!
+//! Used by the `validate_plugins` command and by `--validate-plugins <dir>` on the command line.
+
+use crate::plugin_engine::bytecode::compile_script;
+use crate::plugin_engine::declarative;
+use crate::plugin_engine::host_api::HOST_API_FEATURES;
+use crate::plugin_engine::icon;
+use crate::plugin_engine::manifest::{self, EntryType, LineType, PluginManifest};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+pub const VALIDATE_PLUGINS_ARG: &str = "--validate-plugins";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Severity {
+    /// The plugin will not load or will fail at probe time.
+    Error,
+    /// The plugin loads but something is ignored or likely wrong.
+    Warning,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Diagnostic {
+    pub severity: Severity,
+    /// Stable identifier such as `manifest_parse` or `script_syntax`.
+    pub code: String,
+    pub message: String,
+    /// Plugin directory name; the id may be unknown when the manifest does not parse.
+    pub plugin_dir: String,
+    pub plugin_id: Option<String>,
+    /// File the diagnostic points at, relative to the plugin directory.
+    pub file: Option<String>,
+    pub line: Option<usize>,
+    pub column: Option<usize>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ValidationReport {
+    pub plugins_dir: PathBuf,
+    /// Plugin directories checked (those containing a `plugin.json`).
+    pub checked: usize,
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+impl ValidationReport {
+    pub fn has_errors(&self) -> bool {
+        self.diagnostics
+            .iter()
+            .any(|diagnostic| diagnostic.severity == Severity::Error)
+    }
+}
+
+/// Collects diagnostics for one plugin directory.
+struct Collector {
+    plugin_dir: String,
+    plugin_id: Option<String>,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl Collector {
+    fn push(
+        &mut self,
+        severity: Severity,
+        code: &str,
+        message: impl Into<String>,
+        file: Option<&str>,
+        position: Option<(usize, usize)>,
+    ) {
+        self.diagnostics.push(Diagnostic {
+            severity,
+            code: code.to_string(),
+            message: message.into(),
+            plugin_dir: self.plugin_dir.clone(),
+            plugin_id: self.plugin_id.clone(),
+            file: file.map(str::to_string),
+            line: position.map(|(line, _)| line),
+            column: position.map(|(_, column)| column),
+        });
+    }
+
+    fn error(&mut self, code: &str, message: impl Into<String>, file: Option<&str>) {
+        self.push(Severity::Error, code, message, file, None);
+    }
+
+    fn warning(&mut self, code: &str, message: impl Into<String>, file: Option<&str>) {
+        self.push(Severity::Warning, code, message, file, None);
+    }
+}
+
+/// Validate every plugin directory under `plugins_dir`.
+pub fn validate_plugins_dir(plugins_dir: &Path) -> ValidationReport {
+    let mut dirs: Vec<PathBuf> = std::fs::read_dir(plugins_dir)
+        .map(|entries| {
+            entries
+                .flatten()
+                .map(|entry| entry.path())
+                .filter(|path| {
+                    path.is_dir()
+                        && path.join("plugin.json").exists()
+                        && !path
+                            .file_name()
+                            .is_some_and(|name| name.to_string_lossy().starts_with('.'))
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+    dirs.sort();
+
+    let mut diagnostics = Vec::new();
+    let mut ids: HashMap<String, Vec<String>> = HashMap::new();
+    for dir in &dirs {
+        let (plugin_id, mut found) = validate_plugin_dir(dir);
+        if let Some(id) = plugin_id {
+            ids.entry(id).or_default().push(dir_name(dir));
+        }
+        diagnostics.append(&mut found);
+    }
+
+    let mut duplicates: Vec<_> = ids.into_iter().filter(|(_, dirs)| dirs.len() > 1).collect();
+    duplicates.sort();
+    for (id, dirs) in duplicates {
+        for dir in &dirs {
+            diagnostics.push(Diagnostic {
+                severity: Severity::Error,
+                code: "duplicate_id".to_string(),
+                message: format!("plugin id {} is used by {}", id, dirs.join(", ")),
+                plugin_dir: dir.clone(),
+                plugin_id: Some(id.clone()),
+                file: Some("plugin.json".to_string()),
+                line: None,
+                column: None,
+            });
+        }
+    }
+
+    ValidationReport {
+        plugins_dir: plugins_dir.to_path_buf(),
+        checked: dirs.len(),
+        diagnostics,
+    }
+}
+
+/// Validate one plugin directory; returns the plugin id (if the manifest parsed) and diagnostics.
+pub fn validate_plugin_dir(plugin_dir: &Path) -> (Option<String>, Vec<Diagnostic>) {
+    let mut out = Collector {
+        plugin_dir: dir_name(plugin_dir),
+        plugin_id: None,
+        diagnostics: Vec::new(),
+    };
+    let Some(manifest) = check_manifest(plugin_dir, &mut out) else {
+        return (None, out.diagnostics);
+    };
+    out.plugin_id = Some(manifest.id.clone());
+
+    check_brand_color(&manifest, &mut out);
+    for line in &manifest.lines {
+        if line.primary_order.is_some() && line.line_type != LineType::Progress {
+            out.warning(
+                "primary_order_ignored",
+                format!(
+                    "line '{}' has primaryOrder but type is '{}'; it will be ignored",
+                    line.label,
+                    line.line_type.as_str()
+                ),
+                Some("plugin.json"),
+            );
+        }
+    }
+    if let Some(reason) =
+        manifest::check_compatibility(&manifest, manifest::APP_VERSION, HOST_API_FEATURES)
+    {
+        out.error("incompatible", reason, Some("plugin.json"));
+    }
+    check_icon(plugin_dir, &manifest, &mut out);
+    if manifest.http.is_some() {
+        for problem in declarative::check_spec(&manifest) {
+            out.error("http_invalid", problem, Some("plugin.json"));
+        }
+    } else if let Some(entry_path) = check_entry(plugin_dir, &manifest, &mut out) {
+        check_scripts(plugin_dir, &entry_path, &manifest, &mut out);
+    }
+    (Some(manifest.id), out.diagnostics)
+}
+
+fn check_manifest(plugin_dir: &Path, out: &mut Collector) -> Option<PluginManifest> {
+    let file = Some("plugin.json");
+    let text = match std::fs::read_to_string(plugin_dir.join("plugin.json")) {
+        Ok(text) => text,
+        Err(e) => {
+            out.error("manifest_unreadable", e.to_string(), file);
+            return None;
+        }
+    };
+    // Syntax errors first, so they carry a position.
+    if let Err(e) = serde_json::from_str::<serde_json::Value>(&text) {
+        out.push(
+            Severity::Error,
+            "manifest_parse",
+            e.to_string(),
+            file,
+            Some((e.line(), e.column())),
+        );
+        return None;
+    }
+    match manifest::parse_manifest(&text) {
+        Ok(manifest) => Some(manifest),
+        Err(message) => {
+            out.error("manifest_invalid", message, file);
+            None
+        }
+    }
+}
+
+fn check_brand_color(manifest: &PluginManifest, out: &mut Collector) {
+    let Some(color) = manifest.brand_color.as_deref() else {
+        return;
+    };
+    let hex = color.strip_prefix('#').unwrap_or("");
+    if !matches!(hex.len(), 3 | 6) || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        out.error(
+            "brand_color_invalid",
+            format!("brandColor '{}' must be #rgb or #rrggbb", color),
+            Some("plugin.json"),
+        );
+    }
+}
+
+/// The main icon must load; a broken light/dark variant only loses the variant.
+fn check_icon(plugin_dir: &Path, manifest: &PluginManifest, out: &mut Collector) {
+    if let Err(err) = icon::load_icon(plugin_dir, &manifest.icon) {
+        out.error(err.code(), err.to_string(), Some("plugin.json"));
+    }
+    for variant in [&manifest.icons.light, &manifest.icons.dark]
+        .into_iter()
+        .flatten()
+    {
+        if let Err(err) = icon::load_icon(plugin_dir, variant) {
+            out.warning(err.code(), err.to_string(), Some("plugin.json"));
+        }
+    }
+}
+
+fn check_entry(
+    plugin_dir: &Path,
+    manifest: &PluginManifest,
+    out: &mut Collector,
+) -> Option<PathBuf> {
+    let entry = manifest.entry.as_str();
+    if entry.trim().is_empty() {
+        out.error(
+            "entry_missing",
+            "entry cannot be empty",
+            Some("plugin.json"),
+        );
+        return None;
+    }
+    if Path::new(entry).is_absolute() {
+        out.error(
+            "entry_outside_dir",
+            format!("entry '{}' must be a relative path", entry),
+            Some("plugin.json"),
+        );
+        return None;
+    }
+    let entry_path = plugin_dir.join(entry);
+    if !entry_path.is_file() {
+        out.error(
+            "entry_missing",
+            format!("entry '{}' does not exist", entry),
+            Some("plugin.json"),
+        );
+        return None;
+    }
+    if resolve_inside(plugin_dir, entry).is_none() {
+        out.error(
+            "entry_outside_dir",
+            format!("entry '{}' resolves outside the plugin directory", entry),
+            Some("plugin.json"),
+        );
+        return None;
+    }
+    Some(entry_path)
+}
+
+/// Compile the entry (and, for module plugins, every other `.js` file) without running it.
+fn check_scripts(
+    plugin_dir: &Path,
+    entry_path: &Path,
+    manifest: &PluginManifest,
+    out: &mut Collector,
+) {
+    let Ok(rt) = rquickjs::Runtime::new() else {
+        return;
+    };
+    let Ok(ctx) = rquickjs::Context::full(&rt) else {
+        return;
+    };
+
+    let mut files = vec![manifest.entry.clone()];
+    if manifest.entry_type == EntryType::Module {
+        let mut others = Vec::new();
+        collect_module_files(plugin_dir, plugin_dir, &mut others);
+        others.sort();
+        files.extend(others.into_iter().filter(|file| *file != manifest.entry));
+    }
+
+    for file in files {
+        let path = if file == manifest.entry {
+            entry_path.to_path_buf()
+        } else {
+            plugin_dir.join(&file)
+        };
+        let Ok(source) = std::fs::read_to_string(&path) else {
+            out.error(
+                "script_unreadable",
+                format!("{} is unreadable", file),
+                Some(&file),
+            );
+            continue;
+        };
+        let failure = ctx.with(|ctx| {
+            let compiled = match manifest.entry_type {
+                EntryType::Script => compile_script(&ctx, &file, &source).map(|_| ()),
+                EntryType::Module => {
+                    rquickjs::Module::declare(ctx.clone(), file.as_str(), source).map(|_| ())
+                }
+            };
+            compiled.err().map(|_| {
+                let thrown = ctx.catch();
+                let exception = thrown.as_exception();
+                let message = exception
+                    .and_then(|e| e.message())
+                    .unwrap_or_else(|| "failed to compile".to_string());
+                let position = exception
+                    .and_then(|e| e.stack())
+                    .and_then(|stack| stack_position(&stack, &file));
+                (message, position)
+            })
+        });
+        if let Some((message, position)) = failure {
+            out.push(
+                Severity::Error,
+                "script_syntax",
+                format!("SyntaxError: {}", message),
+                Some(&file),
+                position,
+            );
+        }
+    }
+}
+
+fn collect_module_files(root: &Path, dir: &Path, files: &mut Vec<String>) {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if path.is_dir() {
+            collect_module_files(root, &path, files);
+            continue;
+        }
+        let name = entry.file_name().to_string_lossy().to_string();
+        if !(name.ends_with(".js") || name.ends_with(".mjs")) || name.ends_with(".test.js") {
+            continue;
+        }
+        if let Ok(relative) = path.strip_prefix(root) {
+            let relative: Vec<_> = relative
+                .components()
+                .map(|c| c.as_os_str().to_string_lossy().to_string())
+                .collect();
+            files.push(relative.join("/"));
+        }
+    }
+}
+
+/// First `file:line:column` frame for `file` in a QuickJS stack trace.
+fn stack_position(stack: &str, file: &str) -> Option<(usize, usize)> {
+    let needle = format!("{}:", file);
+    stack.lines().find_map(|frame| {
+        let rest = &frame[frame.find(&needle)? + needle.len()..];
+        let rest = rest.trim_end_matches(')');
+        let mut parts = rest.split(':');
+        let line = parts.next()?.parse().ok()?;
+        let column = parts.next().and_then(|c| c.parse().ok()).unwrap_or(1);
+        Some((line, column))
+    })
+}
+
+/// `relative` resolved against `plugin_dir`, if it exists and stays inside it.
+fn resolve_inside(plugin_dir: &Path, relative: &str) -> Option<PathBuf> {
+    if Path::new(relative).is_absolute() {
+        return None;
+    }
+    let root = plugin_dir.canonicalize().ok()?;
+    let path = plugin_dir.join(relative).canonicalize().ok()?;
+    path.starts_with(&root).then_some(path)
+}
+
+fn dir_name(path: &Path) -> String {
+    path.file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default()
+}
+
+/// Entry point for `--validate-plugins [dir]`: prints the report as JSON and returns the exit
+/// code (0 clean or warnings only, 1 errors, 2 no directory).
+pub fn run_cli(dir: Option<PathBuf>) -> i32 {
+    let Some(dir) = dir.or_else(super::find_dev_plugins_dir) else {
+        eprintln!("usage: {} <plugins dir>", VALIDATE_PLUGINS_ARG);
+        return 2;
+    };
+    let report = validate_plugins_dir(&dir);
+    match serde_json::to_string_pretty(&report) {
+        Ok(json) => println!("{}", json),
+        Err(e) => eprintln!("failed to serialize report: {}", e),
+    }
+    if report.has_errors() { 1 } else { 0 }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dir() -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("openusage-validate-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).expect("create dir");
+        dir
+    }
+
+    fn write_plugin(root: &Path, dir: &str, manifest: &str, script: &str, icon: &str) {
+        let path = root.join(dir);
+        std::fs::create_dir_all(&path).expect("create plugin dir");
+        std::fs::write(path.join("plugin.json"), manifest).expect("write manifest");
+        std::fs::write(path.join("plugin.js"), script).expect("write script");
+        std::fs::write(path.join("icon.svg"), icon).expect("write icon");
+    }
+
+    fn manifest(id: &str, extra: &str) -> String {
+        format!(
+            r#"{{"schemaVersion":1,"id":"{}","name":"X","version":"1.0.0","entry":"plugin.js","icon":"icon.svg","brandColor":null,"lines":[]{}}}"#,
+            id, extra
+        )
+    }
+
+    fn codes(report: &ValidationReport, dir: &str) -> Vec<String> {
+        report
+            .diagnostics
+            .iter()
+            .filter(|d| d.plugin_dir == dir)
+            .map(|d| d.code.clone())
+            .collect()
+    }
+
+    const SCRIPT: &str = "globalThis.__openusage_plugin = { id: 'x', probe() {} }";
+
+    #[test]
+    fn reports_each_kind_of_problem() {
+        let root = temp_dir();
+        write_plugin(&root, "ok", &manifest("ok", ""), SCRIPT, "<svg/>");
+        write_plugin(
+            &root,
+            "broken-json",
+            "{\n  \"id\": \"x\",\n}",
+            SCRIPT,
+            "<svg/>",
+        );
+        write_plugin(&root, "dup-a", &manifest("dup", ""), SCRIPT, "<svg/>");
+        write_plugin(&root, "dup-b", &manifest("dup", ""), SCRIPT, "<svg/>");
+        write_plugin(
+            &root,
+            "gif-icon",
+            &manifest("gif-icon", r#","icons":{"dark":"missing.svg"}"#),
+            SCRIPT,
+            "GIF89a",
+        );
+        write_plugin(
+            &root,
+            "colors",
+            &manifest(
+                "colors",
+                r##","brandColor":"red","lines":[{"type":"text","label":"A","scope":"overview","primaryOrder":1}]"##,
+            )
+            .replace(r#""brandColor":null,"lines":[],"#, ""),
+            SCRIPT,
+            "<svg/>",
+        );
+        write_plugin(
+            &root,
+            "escape",
+            &manifest("escape", "").replace("\"plugin.js\"", "\"../ok/plugin.js\""),
+            SCRIPT,
+            "<svg/>",
+        );
+        write_plugin(
+            &root,
+            "syntax",
+            &manifest("syntax", ""),
+            "const a = 1;\nconst b = ;\n",
+            "<svg/>",
+        );
+
+        let report = validate_plugins_dir(&root);
+        assert_eq!(report.checked, 8);
+        assert!(report.has_errors());
+        assert!(codes(&report, "ok").is_empty(), "{:?}", report.diagnostics);
+
+        let parse = &report
+            .diagnostics
+            .iter()
+            .find(|d| d.plugin_dir == "broken-json")
+            .unwrap();
+        assert_eq!(parse.code, "manifest_parse");
+        assert_eq!((parse.line, parse.column), (Some(3), Some(1)));
+
+        assert_eq!(codes(&report, "dup-a"), vec!["duplicate_id"]);
+        assert_eq!(codes(&report, "dup-b"), vec!["duplicate_id"]);
+        assert_eq!(
+            codes(&report, "gif-icon"),
+            vec!["icon_unsupported", "icon_missing"]
+        );
+        assert_eq!(
+            codes(&report, "colors"),
+            vec!["brand_color_invalid", "primary_order_ignored"]
+        );
+        assert_eq!(codes(&report, "escape"), vec!["entry_outside_dir"]);
+
+        let syntax = report
+            .diagnostics
+            .iter()
+            .find(|d| d.plugin_dir == "syntax")
+            .unwrap();
+        assert_eq!(syntax.code, "script_syntax");
+        assert_eq!(syntax.file.as_deref(), Some("plugin.js"));
+        assert_eq!(syntax.line, Some(2));
+        let _ = std::fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn schema_errors_are_reported_without_position() {
+        let root = temp_dir();
+        write_plugin(
+            &root,
+            "future",
+            &manifest("future", "").replace("\"schemaVersion\":1", "\"schemaVersion\":9"),
+            SCRIPT,
+            "<svg/>",
+        );
+        let report = validate_plugins_dir(&root);
+        assert_eq!(report.diagnostics.len(), 1);
+        let diagnostic = &report.diagnostics[0];
+        assert_eq!(diagnostic.code, "manifest_invalid");
+        assert!(diagnostic.message.contains("unsupported schemaVersion 9"));
+        assert_eq!(diagnostic.line, None);
+        let _ = std::fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn stack_position_reads_the_first_matching_frame() {
+        let stack = "    at <eval> (host.js:1:1)\n    at plugin.js:12:7\n";
+        assert_eq!(stack_position(stack, "plugin.js"), Some((12, 7)));
+        assert_eq!(stack_position(stack, "other.js"), None);
+    }
+}
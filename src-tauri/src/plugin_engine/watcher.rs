@@ -0,0 +1,235 @@
+//! Polls the active plugins directory so edits show up without restarting the app.
+//!
+//! Each plugin directory is fingerprinted by file count, total size and newest modification
+//! time. Polling keeps this dependency-free and works the same on every platform; the plugin
+//! directories are small enough that a walk per interval is cheap.
+
+use crate::plugin_engine::manifest::{self, LoadedPlugin};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+pub const POLL_INTERVAL: Duration = Duration::from_millis(750);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Fingerprint {
+    files: usize,
+    bytes: u64,
+    newest: Option<SystemTime>,
+}
+
+pub struct PluginDirWatcher {
+    plugins_dir: PathBuf,
+    snapshot: HashMap<String, Fingerprint>,
+}
+
+impl PluginDirWatcher {
+    pub fn new(plugins_dir: &Path) -> Self {
+        Self {
+            plugins_dir: plugins_dir.to_path_buf(),
+            snapshot: snapshot(plugins_dir),
+        }
+    }
+
+    /// Directory names of plugins added, modified or removed since the last poll.
+    pub fn poll(&mut self) -> Vec<String> {
+        let current = snapshot(&self.plugins_dir);
+        let mut changed: Vec<String> = current
+            .iter()
+            .filter(|(name, print)| self.snapshot.get(*name) != Some(print))
+            .map(|(name, _)| name.clone())
+            .chain(
+                self.snapshot
+                    .keys()
+                    .filter(|name| !current.contains_key(*name))
+                    .cloned(),
+            )
+            .collect();
+        changed.sort();
+        self.snapshot = current;
+        changed
+    }
+}
+
+/// Watch `plugins_dir` on a background thread. `on_change` gets the changed directory names once
+/// a burst of writes has settled.
+pub fn spawn<F>(plugins_dir: PathBuf, on_change: F)
+where
+    F: Fn(Vec<String>) + Send + 'static,
+{
+    std::thread::spawn(move || {
+        let mut watcher = PluginDirWatcher::new(&plugins_dir);
+        log::info!("watching {} for plugin changes", plugins_dir.display());
+        loop {
+            std::thread::sleep(POLL_INTERVAL);
+            let mut changed = watcher.poll();
+            if changed.is_empty() {
+                continue;
+            }
+            // Editors often write in several steps; wait for one quiet interval.
+            loop {
+                std::thread::sleep(POLL_INTERVAL);
+                let more = watcher.poll();
+                if more.is_empty() {
+                    break;
+                }
+                changed.extend(more);
+            }
+            changed.sort();
+            changed.dedup();
+            log::info!("plugin files changed: {:?}", changed);
+            on_change(changed);
+        }
+    });
+}
+
+/// Reload the plugins in `changed_dirs`, replacing, adding or dropping them in `plugins`.
+/// Returns the ids of every plugin that was affected.
+pub fn reload_changed(
+    plugins: &mut Vec<LoadedPlugin>,
+    plugins_dir: &Path,
+    changed_dirs: &[String],
+) -> Vec<String> {
+    let mut affected = Vec::new();
+    for dir_name in changed_dirs {
+        let plugin_dir = plugins_dir.join(dir_name);
+        plugins.retain(|plugin| {
+            if plugin.plugin_dir == plugin_dir {
+                affected.push(plugin.manifest.id.clone());
+                false
+            } else {
+                true
+            }
+        });
+        if !plugin_dir.join("plugin.json").is_file() {
+            continue;
+        }
+        match manifest::load_single_plugin(&plugin_dir) {
+            Ok(plugin) => {
+                let id = plugin.manifest.id.clone();
+                plugins.retain(|existing| existing.manifest.id != id);
+                affected.push(id);
+                plugins.push(plugin);
+            }
+            Err(err) => log::warn!("failed to reload plugin {}: {}", dir_name, err),
+        }
+    }
+    plugins.sort_by(|a, b| a.manifest.id.cmp(&b.manifest.id));
+    affected.sort();
+    affected.dedup();
+    affected
+}
+
+fn snapshot(plugins_dir: &Path) -> HashMap<String, Fingerprint> {
+    let Ok(entries) = std::fs::read_dir(plugins_dir) else {
+        return HashMap::new();
+    };
+    entries
+        .flatten()
+        .filter(|entry| entry.path().is_dir())
+        .filter_map(|entry| {
+            let name = entry.file_name().to_string_lossy().to_string();
+            if name.starts_with('.') {
+                return None;
+            }
+            let mut print = Fingerprint {
+                files: 0,
+                bytes: 0,
+                newest: None,
+            };
+            fingerprint_into(&entry.path(), &mut print);
+            Some((name, print))
+        })
+        .collect()
+}
+
+fn fingerprint_into(dir: &Path, print: &mut Fingerprint) {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        if entry.file_name().to_string_lossy().starts_with('.') {
+            continue;
+        }
+        let Ok(metadata) = entry.metadata() else {
+            continue;
+        };
+        if metadata.is_dir() {
+            fingerprint_into(&entry.path(), print);
+            continue;
+        }
+        print.files += 1;
+        print.bytes += metadata.len();
+        if let Ok(modified) = metadata.modified() {
+            print.newest = print.newest.max(Some(modified));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dir() -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("openusage-watch-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).expect("create dir");
+        dir
+    }
+
+    fn write_plugin(root: &Path, id: &str, name: &str) {
+        let dir = root.join(id);
+        std::fs::create_dir_all(&dir).expect("create plugin dir");
+        std::fs::write(
+            dir.join("plugin.json"),
+            format!(
+                r#"{{"schemaVersion":1,"id":"{}","name":"{}","version":"1.0.0","entry":"plugin.js","icon":"icon.svg","brandColor":null,"lines":[]}}"#,
+                id, name
+            ),
+        )
+        .expect("write manifest");
+        std::fs::write(dir.join("plugin.js"), "globalThis.__openusage_plugin = {}")
+            .expect("write entry");
+        std::fs::write(dir.join("icon.svg"), "<svg/>").expect("write icon");
+    }
+
+    #[test]
+    fn poll_reports_added_modified_and_removed_plugins() {
+        let root = temp_dir();
+        write_plugin(&root, "alpha", "Alpha");
+        write_plugin(&root, "beta", "Beta");
+        let mut watcher = PluginDirWatcher::new(&root);
+        assert!(watcher.poll().is_empty());
+
+        std::fs::write(root.join("alpha/plugin.js"), "// edited and longer").expect("edit");
+        write_plugin(&root, "gamma", "Gamma");
+        std::fs::remove_dir_all(root.join("beta")).expect("remove");
+        std::fs::create_dir_all(root.join(".staging-x")).expect("hidden dir");
+        assert_eq!(watcher.poll(), vec!["alpha", "beta", "gamma"]);
+        assert!(watcher.poll().is_empty());
+        let _ = std::fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn reload_changed_swaps_adds_and_drops_plugins() {
+        let root = temp_dir();
+        write_plugin(&root, "alpha", "Alpha");
+        write_plugin(&root, "beta", "Beta");
+        let mut plugins = manifest::load_plugins_from_dir(&root);
+
+        write_plugin(&root, "alpha", "Alpha Renamed");
+        write_plugin(&root, "gamma", "Gamma");
+        std::fs::remove_dir_all(root.join("beta")).expect("remove");
+        let changed = ["alpha", "beta", "gamma"].map(String::from);
+        let affected = reload_changed(&mut plugins, &root, &changed);
+
+        assert_eq!(affected, vec!["alpha", "beta", "gamma"]);
+        let names: Vec<&str> = plugins.iter().map(|p| p.manifest.name.as_str()).collect();
+        assert_eq!(names, vec!["Alpha Renamed", "Gamma"]);
+
+        std::fs::write(root.join("gamma/plugin.json"), "{").expect("break manifest");
+        let affected = reload_changed(&mut plugins, &root, &["gamma".to_string()]);
+        assert_eq!(affected, vec!["gamma"]);
+        assert_eq!(plugins.len(), 1, "a broken plugin is dropped until fixed");
+        let _ = std::fs::remove_dir_all(&root);
+    }
+}
@@ -775,7 +775,7 @@ describe("App", () => {
 
   it("toggles plugins in settings", async () => {
     // Use already-normalised settings so no init save fires (b is disabled
-    // because "b" is not in DEFAULT_ENABLED_PLUGINS = ["claude","codex","cursor"])
+    // because "b" is not marked enabledByDefault)
     state.loadPluginSettingsMock.mockResolvedValue({ order: ["a", "b"], disabled: ["b"] })
     render(<App />)
     const settingsButtons = await screen.findAllByRole("button", { name: "Settings" })
@@ -943,13 +943,29 @@ describe("App", () => {
       providerId: "a",
       displayName: "Alpha",
       iconUrl: "icon-a",
-      lines: [{ type: "badge", label: "Error", text: "Bad" }],
+      status: "error",
+      error: { code: "network", message: "Bad", retryable: true },
+      lines: [],
     })
     const retry = await screen.findByRole("button", { name: "Retry" })
     await userEvent.click(retry)
     expect(state.startBatchMock).toHaveBeenCalledWith(["a"])
   })
 
+  it("shows the message of a typed probe error", async () => {
+    render(<App />)
+    await waitFor(() => expect(state.startBatchMock).toHaveBeenCalled())
+    state.probeHandlers?.onResult({
+      providerId: "a",
+      displayName: "Alpha",
+      iconUrl: "icon-a",
+      status: "error",
+      error: { code: "auth_required", message: "Sign in again", retryable: false },
+      lines: [],
+    })
+    expect(await screen.findByText("Sign in again")).toBeInTheDocument()
+  })
+
   it("reloads plugin from sidebar context menu", async () => {
     state.loadPluginSettingsMock.mockResolvedValueOnce({ order: ["a", "b"], disabled: [] })
     render(<App />)
@@ -1345,7 +1361,9 @@ describe("App", () => {
       providerId: "a",
       displayName: "Alpha",
       iconUrl: "icon-a",
-      lines: [{ type: "badge", label: "Error", text: "Something failed" }],
+      status: "error",
+      error: { code: "network", message: "Something failed", retryable: true },
+      lines: [],
     })
 
     // Make startBatch reject on next call (the retry)
@@ -1572,7 +1590,9 @@ describe("App", () => {
         providerId: "a",
         displayName: "Alpha",
         iconUrl: "icon-a",
-        lines: [{ type: "badge", label: "Error", text: "Network error" }],
+        status: "error",
+        error: { code: "network", message: "Network error", retryable: true },
+        lines: [],
       })
       await screen.findByRole("button", { name: "Retry" })
     } finally {
@@ -1589,7 +1609,9 @@ describe("App", () => {
       providerId: "a",
       displayName: "Alpha",
       iconUrl: "icon-a",
-      lines: [{ type: "badge", label: "Error", text: "Network error" }],
+      status: "error",
+      error: { code: "network", message: "Network error", retryable: true },
+      lines: [],
     })
 
     const retryButton = await screen.findByRole("button", { name: "Retry" })
@@ -1626,7 +1648,9 @@ describe("App", () => {
       providerId: "a",
       displayName: "Alpha",
       iconUrl: "icon-a",
-      lines: [{ type: "badge", label: "Error", text: "Network error" }],
+      status: "error",
+      error: { code: "network", message: "Network error", retryable: true },
+      lines: [],
     })
 
     // Find and prepare to click retry
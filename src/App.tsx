@@ -2,6 +2,7 @@ import { useCallback, useEffect, useRef } from "react"
 import { useShallow } from "zustand/react/shallow"
 import { AppShell } from "@/components/app/app-shell"
 import { useAppPluginViews } from "@/hooks/app/use-app-plugin-views"
+import { usePluginHotReload } from "@/hooks/app/use-plugin-hot-reload"
 import { useProbe } from "@/hooks/app/use-probe"
 import { useSettingsBootstrap } from "@/hooks/app/use-settings-bootstrap"
 import { useSettingsDisplayActions } from "@/hooks/app/use-settings-display-actions"
@@ -125,6 +126,13 @@ function App() {
     startBatch,
   })
 
+  usePluginHotReload({
+    setPluginSettings,
+    setPluginsMeta,
+    setLoadingForPlugins,
+    startBatch,
+  })
+
   useSettingsTheme(themeMode)
 
   const {
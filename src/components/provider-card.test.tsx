@@ -110,6 +110,96 @@ describe("ProviderCard", () => {
     expect(screen.getByText("342 credits")).toBeInTheDocument()
   })
 
+  it("renders segmented lines as a stacked bar with a legend", () => {
+    const { container } = render(
+      <ProviderCard
+        name="Segments"
+        displayMode="used"
+        lines={[
+          {
+            type: "segmented",
+            label: "Spend",
+            segments: [
+              { label: "Opus", used: 12, color: "#22c55e" },
+              { label: "Sonnet", used: 3.5 },
+            ],
+            limit: 50,
+            format: { kind: "dollars" },
+          },
+        ]}
+      />
+    )
+    expect(screen.getByText("Spend")).toBeInTheDocument()
+    expect(screen.getByText("Opus")).toBeInTheDocument()
+    expect(screen.getByText("Sonnet")).toBeInTheDocument()
+    expect(screen.getByText(`$${formatFixedPrecisionNumber(15.5)}`)).toBeInTheDocument()
+    expect(screen.getByText(`$${formatFixedPrecisionNumber(50)} limit`)).toBeInTheDocument()
+    const segments = container.querySelectorAll<HTMLElement>('[data-slot="progress-segment"]')
+    expect(segments).toHaveLength(2)
+    expect(segments[0].style.width).toBe("24%")
+    expect(segments[0].style.backgroundColor).toBe("rgb(34, 197, 94)")
+    expect(segments[1].style.width).toBe("7%")
+    expect(screen.getByRole("progressbar")).toHaveAttribute("aria-valuenow", "31")
+  })
+
+  it("renders series lines as a sparkline with the latest value", () => {
+    render(
+      <ProviderCard
+        name="Series"
+        displayMode="used"
+        lines={[
+          {
+            type: "series",
+            label: "Daily cost",
+            points: [
+              { at: "2026-03-01T00:00:00.000Z", value: 2 },
+              { at: "2026-03-02T00:00:00.000Z", value: 6 },
+              { at: "2026-03-03T00:00:00.000Z", value: 4 },
+            ],
+            format: { kind: "dollars" },
+          },
+        ]}
+      />
+    )
+    expect(screen.getByText("Daily cost")).toBeInTheDocument()
+    expect(screen.getByText(`$${formatFixedPrecisionNumber(4)}`)).toBeInTheDocument()
+    const chart = screen.getByRole("img", { name: "Daily cost: 3 points" })
+    expect(chart.querySelector("polyline")).toHaveAttribute("points", "0,22 50,2 100,12")
+  })
+
+  it("renders one section per account for multi-account output", () => {
+    render(
+      <ProviderCard
+        name="Claude"
+        plan="Pro"
+        displayMode="used"
+        lines={[{ type: "text", label: "Email", value: "me@example.com" }]}
+        accounts={[
+          {
+            key: "personal",
+            label: "Personal",
+            plan: "Pro",
+            lines: [{ type: "text", label: "Email", value: "me@example.com" }],
+          },
+          {
+            key: "org-acme",
+            label: "Acme",
+            plan: "Team",
+            lines: [{ type: "text", label: "Email", value: "me@acme.dev" }],
+          },
+        ]}
+      />
+    )
+    const personal = screen.getByRole("region", { name: "Personal" })
+    const acme = screen.getByRole("region", { name: "Acme" })
+    expect(within(personal).getByText("me@example.com")).toBeInTheDocument()
+    expect(within(personal).getByText("Pro")).toBeInTheDocument()
+    expect(within(acme).getByText("me@acme.dev")).toBeInTheDocument()
+    expect(within(acme).getByText("Team")).toBeInTheDocument()
+    // The card-level plan badge gives way to the per-account ones.
+    expect(screen.getAllByText("Pro")).toHaveLength(1)
+  })
+
   it("renders quick links and opens URL", async () => {
     render(
       <ProviderCard
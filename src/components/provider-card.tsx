@@ -10,9 +10,10 @@ import { SkeletonLines } from "@/components/skeleton-lines"
 import { PluginError } from "@/components/plugin-error"
 import { useNowTicker } from "@/hooks/use-now-ticker"
 import { REFRESH_COOLDOWN_MS, type DisplayMode, type ResetTimerDisplayMode } from "@/lib/settings"
-import type { ManifestLine, MetricLine, PluginLink } from "@/lib/plugin-types"
+import type { AccountOutput, ManifestLine, MetricLine, PluginLink, ProgressSegment, SeriesPoint } from "@/lib/plugin-types"
+import { formatProgressAmount, formatProgressLimit } from "@/lib/progress-format"
 import { groupLinesByType } from "@/lib/group-lines-by-type"
-import { clamp01, formatCountNumber, formatFixedPrecisionNumber } from "@/lib/utils"
+import { clamp01, cn } from "@/lib/utils"
 import { calculateDeficit, calculatePaceStatus, type PaceStatus } from "@/lib/pace-status"
 import { buildPaceDetailText, formatDeficitText, formatRunsOutText, getPaceStatusText } from "@/lib/pace-tooltip"
 import { formatResetAbsoluteLabel, formatResetRelativeLabel, formatResetTooltipText } from "@/lib/reset-tooltip"
@@ -25,6 +26,7 @@ interface ProviderCardProps {
   loading?: boolean
   error?: string | null
   lines?: MetricLine[]
+  accounts?: AccountOutput[]
   skeletonLines?: ManifestLine[]
   lastManualRefreshAt?: number | null
   lastUpdatedAt?: number | null
@@ -99,6 +101,7 @@ export function ProviderCard({
   loading = false,
   error = null,
   lines = [],
+  accounts,
   skeletonLines = [],
   lastManualRefreshAt,
   lastUpdatedAt,
@@ -123,12 +126,17 @@ export function ProviderCard({
   const filteredSkeletonLines = scopeFilter === "all"
     ? skeletonLines
     : skeletonLines.filter(line => line.scope === "overview")
-  const filteredLines = scopeFilter === "all"
-    ? lines
-    : lines.filter(line => overviewLabels.has(line.label))
+  const filterByScope = (candidates: MetricLine[]) => scopeFilter === "all"
+    ? candidates
+    : candidates.filter(line => overviewLabels.has(line.label))
+  const filteredLines = filterByScope(lines)
+  // A probe that reports several accounts gets one section per account.
+  const accountSections = accounts && accounts.length > 1
+    ? accounts.map(account => ({ account, lines: filterByScope(account.lines) }))
+    : null
 
-  const hasResetCountdown = filteredLines.some(
-    (line) => line.type === "progress" && Boolean(line.resetsAt)
+  const hasResetCountdown = (accountSections?.flatMap(section => section.lines) ?? filteredLines).some(
+    (line) => (line.type === "progress" || line.type === "segmented") && Boolean(line.resetsAt)
   )
 
   // "has ever loaded" — true if either we have a prior success timestamp,
@@ -178,6 +186,14 @@ export function ProviderCard({
     return `Available in ${seconds}s`
   }
 
+  const lineProps = {
+    displayMode,
+    resetTimerDisplayMode,
+    onResetTimerDisplayModeToggle,
+    now,
+    refreshing: isRefreshingWithData,
+  }
+
   return (
     <div>
       <div className="py-3">
@@ -247,7 +263,7 @@ export function ProviderCard({
               )
             )}
           </div>
-          {plan && (
+          {plan && !accountSections && (
             <Badge
               variant="outline"
               className="truncate min-w-0 max-w-[40%]"
@@ -300,39 +316,31 @@ export function ProviderCard({
           <SkeletonLines lines={filteredSkeletonLines} />
         )}
 
-        {hasStaleData && (
-          <div className="space-y-4">
-            {groupLinesByType(filteredLines).map((group, gi) =>
-              group.kind === "text" ? (
-                <div key={gi} className="space-y-1">
-                  {group.lines.map((line, li) => (
-                    <MetricLineRenderer
-                      key={`${line.label}-${gi}-${li}`}
-                      line={line}
-                      displayMode={displayMode}
-                      resetTimerDisplayMode={resetTimerDisplayMode}
-                      onResetTimerDisplayModeToggle={onResetTimerDisplayModeToggle}
-                      now={now}
-                      refreshing={isRefreshingWithData}
-                    />
-                  ))}
+        {hasStaleData && !accountSections && (
+          <MetricLineGroups lines={filteredLines} {...lineProps} />
+        )}
+
+        {hasStaleData && accountSections && (
+          <div className="space-y-5">
+            {accountSections.map(({ account, lines: accountLines }) => (
+              <section key={account.key} aria-label={account.label}>
+                <div className="flex items-center justify-between mb-2">
+                  <h3 className="text-xs font-semibold text-muted-foreground truncate min-w-0">
+                    {account.label}
+                  </h3>
+                  {account.plan && (
+                    <Badge
+                      variant="outline"
+                      className="truncate min-w-0 max-w-[40%]"
+                      title={account.plan}
+                    >
+                      {account.plan}
+                    </Badge>
+                  )}
                 </div>
-              ) : (
-                <Fragment key={gi}>
-                  {group.lines.map((line, li) => (
-                    <MetricLineRenderer
-                      key={`${line.label}-${gi}-${li}`}
-                      line={line}
-                      displayMode={displayMode}
-                      resetTimerDisplayMode={resetTimerDisplayMode}
-                      onResetTimerDisplayModeToggle={onResetTimerDisplayModeToggle}
-                      now={now}
-                      refreshing={isRefreshingWithData}
-                    />
-                  ))}
-                </Fragment>
-              )
-            )}
+                <MetricLineGroups lines={accountLines} {...lineProps} />
+              </section>
+            ))}
           </div>
         )}
 
@@ -342,21 +350,198 @@ export function ProviderCard({
   )
 }
 
-function MetricLineRenderer({
-  line,
-  displayMode,
-  resetTimerDisplayMode,
+/** Secondary text under a bar; a reset label toggles between relative and absolute time. */
+function ResetSecondaryText({
+  text,
+  resetLabel,
+  resetTooltipText,
   onResetTimerDisplayModeToggle,
-  now,
+}: {
+  text: string
+  resetLabel: string | null
+  resetTooltipText: string | null
+  onResetTimerDisplayModeToggle?: () => void
+}) {
+  if (resetTooltipText) {
+    return (
+      <Tooltip>
+        <TooltipTrigger
+          render={(props) =>
+            resetLabel && onResetTimerDisplayModeToggle ? (
+              <button
+                {...props}
+                type="button"
+                onClick={onResetTimerDisplayModeToggle}
+                className="text-xs text-muted-foreground tabular-nums hover:text-foreground transition-colors"
+              >
+                {text}
+              </button>
+            ) : (
+              <span {...props} className="text-xs text-muted-foreground tabular-nums">
+                {text}
+              </span>
+            )
+          }
+        />
+        <TooltipContent side="top">{resetTooltipText}</TooltipContent>
+      </Tooltip>
+    )
+  }
+
+  if (resetLabel && onResetTimerDisplayModeToggle) {
+    return (
+      <button
+        type="button"
+        onClick={onResetTimerDisplayModeToggle}
+        className="text-xs text-muted-foreground tabular-nums hover:text-foreground transition-colors"
+      >
+        {text}
+      </button>
+    )
+  }
+
+  return <span className="text-xs text-muted-foreground">{text}</span>
+}
+
+/** Fallback fills for segments without a color, cycled in order. */
+const SEGMENT_FALLBACK_CLASSES = ["bg-primary", "bg-primary/60", "bg-primary/35"]
+
+function segmentColorClass(segment: ProgressSegment, index: number): string | undefined {
+  return segment.color ? undefined : SEGMENT_FALLBACK_CLASSES[index % SEGMENT_FALLBACK_CLASSES.length]
+}
+
+/** Stacked bar: one fill per segment, each sized against the shared limit. */
+function SegmentedProgress({
+  segments,
+  limit,
   refreshing,
 }: {
-  line: MetricLine
+  segments: ProgressSegment[]
+  limit: number
+  refreshing?: boolean
+}) {
+  const total = segments.reduce((sum, segment) => sum + segment.used, 0)
+  const percent = Math.round(clamp01(total / limit) * 10000) / 100
+
+  return (
+    <div
+      role="progressbar"
+      aria-valuenow={percent}
+      aria-valuemin={0}
+      aria-valuemax={100}
+      className="relative flex h-3 w-full overflow-hidden rounded-full bg-muted dark:bg-[#353537]"
+    >
+      {segments.map((segment, index) => (
+        <div
+          key={`${segment.label}-${index}`}
+          data-slot="progress-segment"
+          className={cn("h-full flex-shrink-0 transition-all", segmentColorClass(segment, index))}
+          style={{
+            width: `${clamp01(segment.used / limit) * 100}%`,
+            ...(segment.color ? { backgroundColor: segment.color } : undefined),
+          }}
+        />
+      ))}
+      {refreshing && (
+        <div
+          data-slot="progress-refreshing"
+          aria-hidden="true"
+          className="absolute inset-0 overflow-hidden rounded-full"
+        >
+          <div className="h-full w-full animate-shimmer bg-gradient-to-r from-transparent via-white/20 to-transparent" />
+        </div>
+      )}
+    </div>
+  )
+}
+
+/** Line chart of a series, scaled to fill its box; x follows the timestamps. */
+function Sparkline({
+  label,
+  points,
+  color,
+}: {
+  label: string
+  points: SeriesPoint[]
+  color?: string
+}) {
+  const times = points.map((point) => Date.parse(point.at))
+  const values = points.map((point) => point.value)
+  const firstTime = times[0]
+  const timeSpan = times[times.length - 1] - firstTime
+  const minValue = Math.min(...values)
+  const valueSpan = Math.max(...values) - minValue
+  const coords: [number, number][] = points.map((point, index) => [
+    timeSpan > 0 ? ((times[index] - firstTime) / timeSpan) * 100 : 0,
+    valueSpan > 0 ? 22 - ((point.value - minValue) / valueSpan) * 20 : 12,
+  ])
+  // A single point has no span to draw; show it as a flat line.
+  if (coords.length === 1) coords.push([100, coords[0][1]])
+  const polylinePoints = coords
+    .map(([x, y]) => `${Math.round(x * 100) / 100},${Math.round(y * 100) / 100}`)
+    .join(" ")
+
+  return (
+    <svg
+      role="img"
+      aria-label={`${label}: ${points.length} points`}
+      viewBox="0 0 100 24"
+      preserveAspectRatio="none"
+      className="h-6 w-full text-primary"
+      style={color ? { color } : undefined}
+    >
+      <polyline
+        points={polylinePoints}
+        fill="none"
+        stroke="currentColor"
+        strokeWidth={1.5}
+        strokeLinejoin="round"
+        strokeLinecap="round"
+        vectorEffect="non-scaling-stroke"
+      />
+    </svg>
+  )
+}
+
+type MetricLineOptions = {
   displayMode: DisplayMode
   resetTimerDisplayMode: ResetTimerDisplayMode
   onResetTimerDisplayModeToggle?: () => void
   now: number
   refreshing?: boolean
-}) {
+}
+
+/** Lines with consecutive text lines packed together. */
+function MetricLineGroups({ lines, ...options }: MetricLineOptions & { lines: MetricLine[] }) {
+  return (
+    <div className="space-y-4">
+      {groupLinesByType(lines).map((group, gi) =>
+        group.kind === "text" ? (
+          <div key={gi} className="space-y-1">
+            {group.lines.map((line, li) => (
+              <MetricLineRenderer key={`${line.label}-${gi}-${li}`} line={line} {...options} />
+            ))}
+          </div>
+        ) : (
+          <Fragment key={gi}>
+            {group.lines.map((line, li) => (
+              <MetricLineRenderer key={`${line.label}-${gi}-${li}`} line={line} {...options} />
+            ))}
+          </Fragment>
+        )
+      )}
+    </div>
+  )
+}
+
+function MetricLineRenderer({
+  line,
+  displayMode,
+  resetTimerDisplayMode,
+  onResetTimerDisplayModeToggle,
+  now,
+  refreshing,
+}: MetricLineOptions & { line: MetricLine }) {
   if (line.type === "text") {
     return (
       <div>
@@ -414,12 +599,7 @@ function MetricLineRenderer({
     const percent = Math.round(clamp01(shownAmount / line.limit) * 10000) / 100
     const leftSuffix = displayMode === "left" ? " left" : ""
 
-    const primaryText =
-      line.format.kind === "percent"
-        ? `${Math.round(shownAmount)}%${leftSuffix}`
-        : line.format.kind === "dollars"
-          ? `$${formatFixedPrecisionNumber(shownAmount)}${leftSuffix}`
-          : `${formatCountNumber(shownAmount)} ${line.format.suffix}${leftSuffix}`
+    const primaryText = `${formatProgressAmount(shownAmount, line.format)}${leftSuffix}`
 
     const resetLabel = line.resetsAt
       ? resetTimerDisplayMode === "absolute"
@@ -434,13 +614,7 @@ function MetricLineRenderer({
         })
       : null
 
-    const secondaryText =
-      resetLabel ??
-      (line.format.kind === "percent"
-        ? `${line.limit}% cap`
-        : line.format.kind === "dollars"
-          ? `$${formatFixedPrecisionNumber(line.limit)} limit`
-          : `${formatCountNumber(line.limit)} ${line.format.suffix}`)
+    const secondaryText = resetLabel ?? formatProgressLimit(line.limit, line.format)
 
     // Calculate pace status if we have reset time and period duration
     const paceResult = hasPaceContext
@@ -505,41 +679,12 @@ function MetricLineRenderer({
             {primaryText}
           </span>
           {secondaryText && (
-            resetTooltipText ? (
-              <Tooltip>
-                <TooltipTrigger
-                  render={(props) =>
-                    resetLabel && onResetTimerDisplayModeToggle ? (
-                      <button
-                        {...props}
-                        type="button"
-                        onClick={onResetTimerDisplayModeToggle}
-                        className="text-xs text-muted-foreground tabular-nums hover:text-foreground transition-colors"
-                      >
-                        {secondaryText}
-                      </button>
-                    ) : (
-                      <span {...props} className="text-xs text-muted-foreground tabular-nums">
-                        {secondaryText}
-                      </span>
-                    )
-                  }
-                />
-                <TooltipContent side="top">{resetTooltipText}</TooltipContent>
-              </Tooltip>
-            ) : resetLabel && onResetTimerDisplayModeToggle ? (
-              <button
-                type="button"
-                onClick={onResetTimerDisplayModeToggle}
-                className="text-xs text-muted-foreground tabular-nums hover:text-foreground transition-colors"
-              >
-                {secondaryText}
-              </button>
-            ) : (
-              <span className="text-xs text-muted-foreground">
-                {secondaryText}
-              </span>
-            )
+            <ResetSecondaryText
+              text={secondaryText}
+              resetLabel={resetLabel}
+              resetTooltipText={resetTooltipText}
+              onResetTimerDisplayModeToggle={onResetTimerDisplayModeToggle}
+            />
           )}
         </div>
         {(deficitText || runsOutText) && (
@@ -560,5 +705,88 @@ function MetricLineRenderer({
     )
   }
 
+  if (line.type === "segmented") {
+    const total = line.segments.reduce((sum, segment) => sum + segment.used, 0)
+    const shownAmount =
+      displayMode === "used"
+        ? total
+        : Math.max(0, line.limit - total)
+    const leftSuffix = displayMode === "left" ? " left" : ""
+    const primaryText = `${formatProgressAmount(shownAmount, line.format)}${leftSuffix}`
+
+    const resetLabel = line.resetsAt
+      ? resetTimerDisplayMode === "absolute"
+        ? formatResetAbsoluteLabel(now, line.resetsAt)
+        : formatResetRelativeLabel(now, line.resetsAt)
+      : null
+    const resetTooltipText = line.resetsAt
+      ? formatResetTooltipText({
+          nowMs: now,
+          resetsAtIso: line.resetsAt,
+          visibleMode: resetTimerDisplayMode,
+        })
+      : null
+    const secondaryText = resetLabel ?? formatProgressLimit(line.limit, line.format)
+
+    return (
+      <div>
+        <div className="text-sm font-medium mb-1.5">{line.label}</div>
+        <SegmentedProgress
+          segments={line.segments}
+          limit={line.limit}
+          refreshing={refreshing}
+        />
+        <div className="flex justify-between items-center mt-1.5">
+          <span className="text-xs text-muted-foreground tabular-nums">
+            {primaryText}
+          </span>
+          <ResetSecondaryText
+            text={secondaryText}
+            resetLabel={resetLabel}
+            resetTooltipText={resetTooltipText}
+            onResetTimerDisplayModeToggle={onResetTimerDisplayModeToggle}
+          />
+        </div>
+        <div className="mt-1 space-y-0.5">
+          {line.segments.map((segment, index) => (
+            <div
+              key={`${segment.label}-${index}`}
+              className="flex justify-between items-center text-xs text-muted-foreground"
+            >
+              <span className="flex items-center gap-1.5 min-w-0">
+                <span
+                  aria-hidden="true"
+                  className={cn("size-2 rounded-full flex-shrink-0", segmentColorClass(segment, index))}
+                  style={segment.color ? { backgroundColor: segment.color } : undefined}
+                />
+                <span className="truncate">{segment.label}</span>
+              </span>
+              <span className="tabular-nums">{formatProgressAmount(segment.used, line.format)}</span>
+            </div>
+          ))}
+        </div>
+      </div>
+    )
+  }
+
+  if (line.type === "series") {
+    const latest = line.points[line.points.length - 1]
+    return (
+      <div>
+        <div className="flex justify-between items-center mb-1.5">
+          <span className="text-sm font-medium">{line.label}</span>
+          {latest && (
+            <span className="text-xs text-muted-foreground tabular-nums">
+              {formatProgressAmount(latest.value, line.format)}
+            </span>
+          )}
+        </div>
+        {line.points.length > 0 && (
+          <Sparkline label={line.label} points={line.points} color={line.color} />
+        )}
+      </div>
+    )
+  }
+
   return null
 }
@@ -81,6 +81,48 @@ describe("SideNav", () => {
     expect(p2Style).toContain("rgb(255, 255, 255)")
   })
 
+  it("uses the icon variant for the current theme", () => {
+    const plugin = {
+      id: "p",
+      name: "P",
+      iconUrl: "default.svg",
+      iconUrlLight: "light.svg",
+      iconUrlDark: "dark.svg",
+    }
+
+    darkModeState.useDarkModeMock.mockReturnValueOnce(true)
+    const { rerender } = render(
+      <SideNav activeView="home" onViewChange={vi.fn()} plugins={[plugin]} />
+    )
+    expect(screen.getByRole("img", { name: "P" }).getAttribute("style")).toContain("dark.svg")
+
+    darkModeState.useDarkModeMock.mockReturnValueOnce(false)
+    rerender(
+      <SideNav
+        activeView="home"
+        onViewChange={vi.fn()}
+        plugins={[{ ...plugin, id: "p2", iconUrlLight: null }]}
+      />
+    )
+    expect(screen.getByRole("img", { name: "P" }).getAttribute("style")).toContain("default.svg")
+  })
+
+  it("renders PNG icons as images instead of a tinted mask", () => {
+    const iconUrl = "data:image/png;base64,iVBORw0KGgo="
+    render(
+      <SideNav
+        activeView="home"
+        onViewChange={vi.fn()}
+        plugins={[{ id: "p", name: "P", iconUrl, brandColor: "#ff0000" }]}
+      />
+    )
+
+    const icon = screen.getByRole("img", { name: "P" })
+    expect(icon.tagName).toBe("IMG")
+    expect(icon).toHaveAttribute("src", iconUrl)
+    expect(icon.getAttribute("style") ?? "").not.toMatch(/mask/i)
+  })
+
   it("opens the issues page and hides the panel from Help", async () => {
     const onViewChange = vi.fn()
     render(<SideNav activeView="home" onViewChange={onViewChange} plugins={[]} />)
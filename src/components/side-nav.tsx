@@ -26,7 +26,7 @@ function GaugeIcon({ className }: { className?: string }) {
     </svg>
   )
 }
-import { cn } from "@/lib/utils"
+import { cn, isRasterIconUrl } from "@/lib/utils"
 import { getRelativeLuminance } from "@/lib/color"
 import { useDarkMode } from "@/hooks/use-dark-mode"
 
@@ -38,6 +38,8 @@ interface NavPlugin {
   id: string
   name: string
   iconUrl: string
+  iconUrlLight?: string | null
+  iconUrlDark?: string | null
   brandColor?: string
 }
 
@@ -109,6 +111,7 @@ function SortableNavPlugin({ plugin, isActive, isDark, onClick, onContextMenu }:
     transition,
     opacity: isDragging ? 0.5 : undefined,
   }
+  const iconUrl = (isDark ? plugin.iconUrlDark : plugin.iconUrlLight) || plugin.iconUrl
 
   return (
     <div ref={setNodeRef} style={style} {...attributes} {...listeners} role="presentation">
@@ -118,22 +121,26 @@ function SortableNavPlugin({ plugin, isActive, isDark, onClick, onContextMenu }:
         onContextMenu={onContextMenu}
         aria-label={plugin.name}
       >
-        <span
-          role="img"
-          aria-label={plugin.name}
-          className="size-6 inline-block"
-          style={{
-            backgroundColor: getIconColor(plugin.brandColor, isDark),
-            WebkitMaskImage: `url(${plugin.iconUrl})`,
-            WebkitMaskSize: "contain",
-            WebkitMaskRepeat: "no-repeat",
-            WebkitMaskPosition: "center",
-            maskImage: `url(${plugin.iconUrl})`,
-            maskSize: "contain",
-            maskRepeat: "no-repeat",
-            maskPosition: "center",
-          }}
-        />
+        {isRasterIconUrl(iconUrl) ? (
+          <img src={iconUrl} alt={plugin.name} className="size-6 object-contain" draggable={false} />
+        ) : (
+          <span
+            role="img"
+            aria-label={plugin.name}
+            className="size-6 inline-block"
+            style={{
+              backgroundColor: getIconColor(plugin.brandColor, isDark),
+              WebkitMaskImage: `url(${iconUrl})`,
+              WebkitMaskSize: "contain",
+              WebkitMaskRepeat: "no-repeat",
+              WebkitMaskPosition: "center",
+              maskImage: `url(${iconUrl})`,
+              maskSize: "contain",
+              maskRepeat: "no-repeat",
+              maskPosition: "center",
+            }}
+          />
+        )}
       </NavButton>
     </div>
   )
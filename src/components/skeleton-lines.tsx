@@ -34,6 +34,18 @@ function SkeletonProgress({ label }: { label: string }) {
   )
 }
 
+function SkeletonSeries({ label }: { label: string }) {
+  return (
+    <div>
+      <div className="flex justify-between items-center mb-1.5">
+        <span className="text-sm font-medium">{label}</span>
+        <Skeleton className="h-4 w-12" />
+      </div>
+      <Skeleton className="h-6 w-full" />
+    </div>
+  )
+}
+
 export function SkeletonLine({ line }: { line: ManifestLine }) {
   switch (line.type) {
     case "text":
@@ -41,7 +53,10 @@ export function SkeletonLine({ line }: { line: ManifestLine }) {
     case "badge":
       return <SkeletonBadge label={line.label} />
     case "progress":
+    case "segmented":
       return <SkeletonProgress label={line.label} />
+    case "series":
+      return <SkeletonSeries label={line.label} />
     default:
       return <SkeletonText label={line.label} />
   }
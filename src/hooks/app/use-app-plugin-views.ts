@@ -51,6 +51,8 @@ export function useAppPluginViews({
         id: plugin.id,
         name: plugin.name,
         iconUrl: plugin.iconUrl,
+        iconUrlLight: plugin.iconUrlLight,
+        iconUrlDark: plugin.iconUrlDark,
         brandColor: plugin.brandColor,
       }))
   }, [pluginSettings, pluginsMeta])
@@ -0,0 +1,115 @@
+import { renderHook, waitFor } from "@testing-library/react"
+import { beforeEach, describe, expect, it, vi } from "vitest"
+
+const {
+  arePluginSettingsEqualMock,
+  getEnabledPluginIdsMock,
+  invokeMock,
+  listenMock,
+  loadPluginSettingsMock,
+  normalizePluginSettingsMock,
+  savePluginSettingsMock,
+} = vi.hoisted(() => ({
+  arePluginSettingsEqualMock: vi.fn(),
+  getEnabledPluginIdsMock: vi.fn(),
+  invokeMock: vi.fn(),
+  listenMock: vi.fn(),
+  loadPluginSettingsMock: vi.fn(),
+  normalizePluginSettingsMock: vi.fn(),
+  savePluginSettingsMock: vi.fn(),
+}))
+
+vi.mock("@tauri-apps/api/core", () => ({
+  invoke: invokeMock,
+}))
+
+vi.mock("@tauri-apps/api/event", () => ({
+  listen: listenMock,
+}))
+
+vi.mock("@/lib/settings", () => ({
+  arePluginSettingsEqual: arePluginSettingsEqualMock,
+  getEnabledPluginIds: getEnabledPluginIdsMock,
+  loadPluginSettings: loadPluginSettingsMock,
+  normalizePluginSettings: normalizePluginSettingsMock,
+  savePluginSettings: savePluginSettingsMock,
+}))
+
+import { usePluginHotReload } from "@/hooks/app/use-plugin-hot-reload"
+
+function createArgs() {
+  return {
+    setPluginSettings: vi.fn(),
+    setPluginsMeta: vi.fn(),
+    setLoadingForPlugins: vi.fn(),
+    startBatch: vi.fn().mockResolvedValue(undefined),
+  }
+}
+
+describe("usePluginHotReload", () => {
+  let changedCallback: ((event: { payload: { pluginIds: string[] } }) => void) | null
+
+  beforeEach(() => {
+    changedCallback = null
+    invokeMock.mockReset()
+    listenMock.mockReset()
+    arePluginSettingsEqualMock.mockReset()
+    getEnabledPluginIdsMock.mockReset()
+    loadPluginSettingsMock.mockReset()
+    normalizePluginSettingsMock.mockReset()
+    savePluginSettingsMock.mockReset()
+
+    listenMock.mockImplementation(async (_event: string, callback: typeof changedCallback) => {
+      changedCallback = callback
+      return vi.fn()
+    })
+    invokeMock.mockResolvedValue([{ id: "a" }, { id: "b" }])
+    loadPluginSettingsMock.mockResolvedValue({ order: ["a"], disabled: [] })
+    normalizePluginSettingsMock.mockReturnValue({ order: ["a", "b"], disabled: ["b"] })
+    arePluginSettingsEqualMock.mockReturnValue(false)
+    getEnabledPluginIdsMock.mockReturnValue(["a"])
+  })
+
+  it("reloads metadata and re-probes changed enabled plugins", async () => {
+    const args = createArgs()
+    renderHook(() => usePluginHotReload(args))
+
+    await waitFor(() => expect(listenMock).toHaveBeenCalledWith("plugins:changed", expect.any(Function)))
+    changedCallback?.({ payload: { pluginIds: ["a", "b"] } })
+
+    await waitFor(() => expect(args.startBatch).toHaveBeenCalledWith(["a"]))
+    expect(invokeMock).toHaveBeenCalledWith("list_plugins")
+    expect(args.setPluginsMeta).toHaveBeenCalledWith([{ id: "a" }, { id: "b" }])
+    expect(savePluginSettingsMock).toHaveBeenCalledWith({ order: ["a", "b"], disabled: ["b"] })
+    expect(args.setPluginSettings).toHaveBeenCalledWith({ order: ["a", "b"], disabled: ["b"] })
+    expect(args.setLoadingForPlugins).toHaveBeenCalledWith(["a"])
+  })
+
+  it("does not start a batch when only disabled plugins changed", async () => {
+    const args = createArgs()
+    renderHook(() => usePluginHotReload(args))
+
+    await waitFor(() => expect(listenMock).toHaveBeenCalled())
+    changedCallback?.({ payload: { pluginIds: ["b"] } })
+
+    await waitFor(() => expect(args.setPluginSettings).toHaveBeenCalled())
+    expect(args.startBatch).not.toHaveBeenCalled()
+  })
+
+  it("unlistens when unmounted before setup resolves", async () => {
+    const unlisten = vi.fn()
+    let resolveListen: ((value: () => void) => void) | null = null
+    listenMock.mockImplementationOnce(
+      () =>
+        new Promise((resolve) => {
+          resolveListen = resolve
+        })
+    )
+
+    const { unmount } = renderHook(() => usePluginHotReload(createArgs()))
+    unmount()
+    resolveListen?.(unlisten)
+
+    await waitFor(() => expect(unlisten).toHaveBeenCalledTimes(1))
+  })
+})
@@ -0,0 +1,79 @@
+import { useEffect } from "react"
+import { invoke } from "@tauri-apps/api/core"
+import { listen } from "@tauri-apps/api/event"
+import type { PluginMeta } from "@/lib/plugin-types"
+import {
+  arePluginSettingsEqual,
+  getEnabledPluginIds,
+  loadPluginSettings,
+  normalizePluginSettings,
+  savePluginSettings,
+  type PluginSettings,
+} from "@/lib/settings"
+
+type PluginsChanged = {
+  pluginIds: string[]
+}
+
+type UsePluginHotReloadArgs = {
+  setPluginSettings: (value: PluginSettings | null) => void
+  setPluginsMeta: (value: PluginMeta[]) => void
+  setLoadingForPlugins: (ids: string[]) => void
+  startBatch: (pluginIds?: string[]) => Promise<string[] | undefined>
+}
+
+/**
+ * Reacts to `plugins:changed` (emitted when files in the dev plugins directory change):
+ * reloads plugin metadata and re-probes the changed plugins that are enabled.
+ */
+export function usePluginHotReload({
+  setPluginSettings,
+  setPluginsMeta,
+  setLoadingForPlugins,
+  startBatch,
+}: UsePluginHotReloadArgs) {
+  useEffect(() => {
+    let cancelled = false
+    let unlisten: (() => void) | null = null
+
+    const handleChanged = async (pluginIds: string[]) => {
+      const availablePlugins = await invoke<PluginMeta[]>("list_plugins")
+      if (cancelled) return
+      setPluginsMeta(availablePlugins)
+
+      const storedSettings = await loadPluginSettings()
+      const normalized = normalizePluginSettings(storedSettings, availablePlugins)
+      if (!arePluginSettingsEqual(storedSettings, normalized)) {
+        await savePluginSettings(normalized)
+      }
+      if (cancelled) return
+      setPluginSettings(normalized)
+
+      const enabledIds = new Set(getEnabledPluginIds(normalized))
+      const reprobeIds = pluginIds.filter((id) => enabledIds.has(id))
+      if (reprobeIds.length === 0) return
+      setLoadingForPlugins(reprobeIds)
+      await startBatch(reprobeIds)
+    }
+
+    const setup = async () => {
+      const u = await listen<PluginsChanged>("plugins:changed", (event) => {
+        handleChanged(event.payload.pluginIds).catch((error) => {
+          console.error("Failed to reload changed plugins:", error)
+        })
+      })
+      if (cancelled) {
+        u()
+        return
+      }
+      unlisten = u
+    }
+
+    void setup()
+
+    return () => {
+      cancelled = true
+      unlisten?.()
+    }
+  }, [setLoadingForPlugins, setPluginSettings, setPluginsMeta, startBatch])
+}
@@ -17,12 +17,8 @@ export function useProbeState({ onProbeResult }: UseProbeStateArgs) {
   const manualRefreshIdsRef = useRef<Set<string>>(new Set())
 
   const getErrorMessage = useCallback((output: PluginOutput) => {
-    if (output.lines.length !== 1) return null
-    const line = output.lines[0]
-    if (line.type === "badge" && line.label === "Error") {
-      return line.text || "Couldn't update data. Try again?"
-    }
-    return null
+    if (!output.error) return null
+    return output.error.message || "Couldn't update data. Try again?"
   }, [])
 
   const setLoadingForPlugins = useCallback((ids: string[]) => {
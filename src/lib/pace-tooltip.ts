@@ -1,7 +1,7 @@
 import type { PaceResult, PaceStatus } from "@/lib/pace-status"
 import type { ProgressFormat } from "@/lib/plugin-types"
 import type { DisplayMode } from "@/lib/settings"
-import { formatCountNumber, formatFixedPrecisionNumber } from "@/lib/utils"
+import { formatProgressAmount } from "@/lib/progress-format"
 
 export function getPaceStatusText(status: PaceStatus): string {
   return status === "ahead" ? "Plenty of room" : status === "on-track" ? "Right on target" : "Will run out"
@@ -116,6 +116,5 @@ export function formatDeficitText(
   const roundedToCents = Math.round(deficit * 100) / 100
   if (roundedToCents <= 0) return null
 
-  if (format.kind === "dollars") return `$${formatFixedPrecisionNumber(roundedToCents)} ${suffix}`
-  return `${formatCountNumber(roundedToCents)} ${format.suffix} ${suffix}`
+  return `${formatProgressAmount(roundedToCents, format)} ${suffix}`
 }
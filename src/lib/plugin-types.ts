@@ -1,7 +1,12 @@
+export type DurationUnit = "seconds" | "minutes" | "hours"
+
 export type ProgressFormat =
   | { kind: "percent" }
   | { kind: "dollars" }
   | { kind: "count"; suffix: string }
+  | { kind: "currency"; code: string }
+  | { kind: "tokens" }
+  | { kind: "duration"; unit: DurationUnit }
 
 export type MetricLine =
   | { type: "text"; label: string; value: string; color?: string; subtitle?: string }
@@ -16,9 +21,39 @@ export type MetricLine =
       color?: string
     }
   | { type: "badge"; label: string; text: string; color?: string; subtitle?: string }
+  | {
+      type: "segmented"
+      label: string
+      segments: ProgressSegment[]
+      limit: number
+      format: ProgressFormat
+      resetsAt?: string
+      periodDurationMs?: number
+    }
+  | {
+      type: "series"
+      label: string
+      points: SeriesPoint[]
+      format: ProgressFormat
+      color?: string
+    }
+
+export type ProgressSegment = {
+  label: string
+  used: number
+  color?: string
+}
+
+export type SeriesPoint = {
+  /** ISO timestamp; points are ordered oldest first. */
+  at: string
+  value: number
+}
 
 export type ManifestLine = {
-  type: "text" | "progress" | "badge"
+  /** Stable line id (derived from the label for schemaVersion 1 manifests). */
+  id?: string
+  type: "text" | "progress" | "badge" | "segmented" | "series"
   label: string
   scope: "overview" | "detail"
 }
@@ -28,23 +63,134 @@ export type PluginLink = {
   url: string
 }
 
+export type ProbeErrorCode =
+  | "auth_required"
+  | "network"
+  | "rate_limited"
+  | "timeout"
+  | "plugin_bug"
+  | "unsupported_platform"
+  | "permission_denied"
+  | "untrusted"
+  | "incompatible"
+  | "unknown"
+
+export type ProbeError = {
+  code: ProbeErrorCode
+  message: string
+  retryable: boolean
+}
+
 export type PluginOutput = {
   providerId: string
   displayName: string
+  status?: "ok" | "error"
+  /** Set when the whole probe failed; `lines` is empty in that case. */
+  error?: ProbeError | null
   plan?: string
   lines: MetricLine[]
+  /** Set when the probe reported several accounts; `plan` and `lines` mirror the first. */
+  accounts?: AccountOutput[]
   iconUrl: string
 }
 
+export type AccountOutput = {
+  /** Stable plugin-chosen key, unique within one output. */
+  key: string
+  label: string
+  plan?: string
+  lines: MetricLine[]
+}
+
+export type PluginTrustLevel = "bundled" | "signed" | "unsigned" | "invalid"
+
+export type PluginTrust = {
+  level: PluginTrustLevel
+  /** Trusted publisher that signed the bundle, for `signed` plugins. */
+  publisher: string | null
+  /** Whether the plugin runs under the current trust settings. */
+  allowed: boolean
+  /** Why verification failed, for `invalid` plugins. */
+  detail: string | null
+}
+
 export type PluginMeta = {
   id: string
   name: string
   iconUrl: string
+  /** Theme-specific icons; fall back to `iconUrl` when absent. */
+  iconUrlLight?: string | null
+  iconUrlDark?: string | null
   brandColor?: string
   lines: ManifestLine[]
   links?: PluginLink[]
   /** Ordered list of primary metric candidates. Frontend picks first available. */
   primaryCandidates: string[]
+  trust?: PluginTrust
+  /** Why this app cannot run the plugin (too old, or missing host APIs). Such plugins are never probed. */
+  incompatibility?: string | null
+  /** Detected on this machine by the manifest `detect` rules and enabled for new users. */
+  enabledByDefault?: boolean
+}
+
+export type PluginSettingDefinition = {
+  key: string
+  label: string
+  description?: string | null
+} & (
+  | { type: "string"; default?: string | null }
+  | { type: "secret" }
+  | { type: "enum"; options: string[]; default?: string | null }
+  | { type: "boolean"; default: boolean }
+  | { type: "url"; default?: string | null }
+)
+
+/** Returned by `get_plugin_settings` / `set_plugin_settings`. Secret values never reach the UI. */
+export type PluginSettingsView = {
+  definitions: PluginSettingDefinition[]
+  /** Stored non-secret values; settings left at their default are absent. */
+  values: Record<string, string | boolean>
+  /** Keys of secret settings that currently have a value. */
+  secretsSet: string[]
+}
+
+/** Returned by `list_installed_plugins` and `install_plugin`. */
+export type InstalledPluginInfo = {
+  id: string
+  name: string
+  version: string
+  bundled: boolean
+  /** A previous version is kept and `rollback_plugin` can restore it. */
+  hasPrevious: boolean
+}
+
+export type ValidationDiagnostic = {
+  severity: "error" | "warning"
+  /** Stable identifier such as `manifest_parse` or `script_syntax`. */
+  code: string
+  message: string
+  pluginDir: string
+  pluginId: string | null
+  /** Relative to the plugin directory. */
+  file: string | null
+  line: number | null
+  column: number | null
+}
+
+/** Returned by `detect_plugins`. */
+export type PluginDetection = {
+  pluginId: string
+  detected: boolean
+  /** The rules that matched, e.g. `file ~/.codex/auth.json`. */
+  matched: string[]
+  enabledByDefault: boolean
+}
+
+/** Returned by `validate_plugins`. */
+export type ValidationReport = {
+  pluginsDir: string
+  checked: number
+  diagnostics: ValidationDiagnostic[]
 }
 
 export type PluginDisplayState = {
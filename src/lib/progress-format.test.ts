@@ -0,0 +1,51 @@
+import { describe, expect, it } from "vitest"
+import {
+  formatCurrencyAmount,
+  formatDurationAmount,
+  formatProgressAmount,
+  formatProgressLimit,
+  formatTokenCount,
+} from "@/lib/progress-format"
+
+describe("formatProgressAmount", () => {
+  it("keeps the original kinds unchanged", () => {
+    expect(formatProgressAmount(32.4, { kind: "percent" })).toBe("32%")
+    expect(formatProgressAmount(12.5, { kind: "dollars" })).toBe("$12.50")
+    expect(formatProgressAmount(342, { kind: "count", suffix: "credits" })).toBe("342 credits")
+  })
+
+  it("formats currency codes", () => {
+    expect(formatProgressAmount(12.5, { kind: "currency", code: "EUR" })).toBe("€12.50")
+    expect(formatProgressAmount(50, { kind: "currency", code: "CNY" })).toBe("CN¥50")
+  })
+
+  it("scales tokens", () => {
+    expect(formatProgressAmount(9_200_000, { kind: "tokens" })).toBe("9.2M tokens")
+    expect(formatProgressAmount(850, { kind: "tokens" })).toBe("850 tokens")
+  })
+
+  it("formats durations from their unit", () => {
+    expect(formatProgressAmount(95, { kind: "duration", unit: "minutes" })).toBe("1h 35m")
+    expect(formatProgressAmount(26, { kind: "duration", unit: "hours" })).toBe("1d 2h")
+    expect(formatProgressAmount(42, { kind: "duration", unit: "seconds" })).toBe("42s")
+  })
+})
+
+describe("formatProgressLimit", () => {
+  it("labels limits by kind", () => {
+    expect(formatProgressLimit(100, { kind: "percent" })).toBe("100% cap")
+    expect(formatProgressLimit(100, { kind: "dollars" })).toBe("$100 limit")
+    expect(formatProgressLimit(1000, { kind: "count", suffix: "credits" })).toBe("1,000 credits")
+    expect(formatProgressLimit(50, { kind: "currency", code: "EUR" })).toBe("€50 limit")
+    expect(formatProgressLimit(50_000_000, { kind: "tokens" })).toBe("50M tokens")
+    expect(formatProgressLimit(300, { kind: "duration", unit: "minutes" })).toBe("5h 0m limit")
+  })
+})
+
+describe("format helpers", () => {
+  it("treats non-finite values as zero", () => {
+    expect(formatCurrencyAmount(Number.NaN, "USD")).toBe("$0")
+    expect(formatTokenCount(Number.POSITIVE_INFINITY)).toBe("0")
+    expect(formatDurationAmount(-5, "minutes")).toBe("0m")
+  })
+})
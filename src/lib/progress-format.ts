@@ -0,0 +1,77 @@
+import type { DurationUnit, ProgressFormat } from "@/lib/plugin-types"
+import { formatCountNumber, formatFixedPrecisionNumber } from "@/lib/utils"
+
+const DURATION_UNIT_SECONDS: Record<DurationUnit, number> = {
+  seconds: 1,
+  minutes: 60,
+  hours: 3600,
+}
+
+export function formatCurrencyAmount(value: number, code: string): string {
+  const amount = Number.isFinite(value) ? value : 0
+  const fractionDigits = Number.isInteger(amount) ? 0 : 2
+  try {
+    return new Intl.NumberFormat("en-US", {
+      style: "currency",
+      currency: code,
+      minimumFractionDigits: fractionDigits,
+      maximumFractionDigits: fractionDigits,
+    }).format(amount)
+  } catch {
+    // Unknown to this Intl build; fall back to "12.50 XYZ".
+    return `${formatFixedPrecisionNumber(amount)} ${code}`
+  }
+}
+
+/** SI-scaled token count, e.g. 9_200_000 -> "9.2M". */
+export function formatTokenCount(value: number): string {
+  if (!Number.isFinite(value)) return "0"
+  return new Intl.NumberFormat("en-US", {
+    notation: "compact",
+    maximumFractionDigits: 1,
+  }).format(value)
+}
+
+/** Compact duration in the same style as `ctx.fmt.resetIn`, e.g. "1h 35m". */
+export function formatDurationAmount(value: number, unit: DurationUnit): string {
+  if (!Number.isFinite(value) || value <= 0) return "0m"
+  const totalSeconds = Math.round(value * DURATION_UNIT_SECONDS[unit])
+  const totalMinutes = Math.floor(totalSeconds / 60)
+  const totalHours = Math.floor(totalMinutes / 60)
+  const days = Math.floor(totalHours / 24)
+  if (days > 0) return `${days}d ${totalHours % 24}h`
+  if (totalHours > 0) return `${totalHours}h ${totalMinutes % 60}m`
+  if (totalMinutes > 0) return `${totalMinutes}m`
+  return `${totalSeconds}s`
+}
+
+/** A used/left amount in the line's format. */
+export function formatProgressAmount(amount: number, format: ProgressFormat): string {
+  switch (format.kind) {
+    case "percent":
+      return `${Math.round(amount)}%`
+    case "dollars":
+      return `$${formatFixedPrecisionNumber(amount)}`
+    case "count":
+      return `${formatCountNumber(amount)} ${format.suffix}`
+    case "currency":
+      return formatCurrencyAmount(amount, format.code)
+    case "tokens":
+      return `${formatTokenCount(amount)} tokens`
+    case "duration":
+      return formatDurationAmount(amount, format.unit)
+  }
+}
+
+/** The limit shown opposite the amount when there is no reset label. */
+export function formatProgressLimit(limit: number, format: ProgressFormat): string {
+  switch (format.kind) {
+    case "percent":
+      return `${limit}% cap`
+    case "count":
+    case "tokens":
+      return formatProgressAmount(limit, format)
+    default:
+      return `${formatProgressAmount(limit, format)} limit`
+  }
+}
@@ -91,9 +91,9 @@ describe("settings", () => {
     expect(normalized).toEqual({ order: ["b", "a"], disabled: ["a"] })
   })
 
-  it("auto-disables new non-default plugins", () => {
+  it("auto-disables new plugins not enabled by default", () => {
     const plugins: PluginMeta[] = [
-      { id: "claude", name: "Claude", iconUrl: "", lines: [], primaryCandidates: [] },
+      { id: "claude", name: "Claude", iconUrl: "", lines: [], primaryCandidates: [], enabledByDefault: true },
       { id: "copilot", name: "Copilot", iconUrl: "", lines: [], primaryCandidates: [] },
       { id: "windsurf", name: "Windsurf", iconUrl: "", lines: [], primaryCandidates: [] },
     ]
@@ -4,7 +4,7 @@ import type { PluginMeta } from "@/lib/plugin-types";
 // Refresh cooldown duration in milliseconds (5 minutes)
 export const REFRESH_COOLDOWN_MS = 300_000;
 
-// Spec: persist plugin order + disabled list; new plugins append, default disabled unless in DEFAULT_ENABLED_PLUGINS.
+// Spec: persist plugin order + disabled list; new plugins append, default disabled unless the backend marks them enabledByDefault.
 export type PluginSettings = {
   order: string[];
   disabled: string[];
@@ -78,8 +78,6 @@ export const RESET_TIMER_DISPLAY_OPTIONS: { value: ResetTimerDisplayMode; label:
 
 const store = new LazyStore(SETTINGS_STORE_PATH);
 
-const DEFAULT_ENABLED_PLUGINS = new Set(["claude", "codex", "cursor"]);
-
 export const DEFAULT_PLUGIN_SETTINGS: PluginSettings = {
   order: [],
   disabled: [],
@@ -142,9 +140,12 @@ export function normalizePluginSettings(
     }
   }
 
+  const enabledByDefault = new Set(
+    plugins.filter((plugin) => plugin.enabledByDefault).map((plugin) => plugin.id)
+  );
   const disabled = settings.disabled.filter((id) => knownSet.has(id));
   for (const id of newlyAdded) {
-    if (!DEFAULT_ENABLED_PLUGINS.has(id) && !disabled.includes(id)) {
+    if (!enabledByDefault.has(id) && !disabled.includes(id)) {
       disabled.push(id);
     }
   }
@@ -1,5 +1,5 @@
 import { describe, expect, it } from "vitest"
-import { clamp01, cn } from "@/lib/utils"
+import { clamp01, cn, isRasterIconUrl } from "@/lib/utils"
 
 describe("cn", () => {
   it("merges class names", () => {
@@ -11,6 +11,15 @@ describe("cn", () => {
   })
 })
 
+describe("isRasterIconUrl", () => {
+  it("detects PNG and WebP data URLs only", () => {
+    expect(isRasterIconUrl("data:image/png;base64,AAAA")).toBe(true)
+    expect(isRasterIconUrl("data:image/webp;base64,AAAA")).toBe(true)
+    expect(isRasterIconUrl("data:image/svg+xml;base64,AAAA")).toBe(false)
+    expect(isRasterIconUrl("icon.png")).toBe(false)
+  })
+})
+
 describe("clamp01", () => {
   it("clamps non-finite and out-of-range values", () => {
     expect(clamp01(Number.NaN)).toBe(0)
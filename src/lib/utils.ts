@@ -5,6 +5,11 @@ export function cn(...inputs: ClassValue[]) {
   return twMerge(clsx(inputs))
 }
 
+/** PNG and WebP plugin icons keep their own colors; only SVG icons are tinted through a mask. */
+export function isRasterIconUrl(url: string): boolean {
+  return /^data:image\/(png|webp)[;,]/i.test(url)
+}
+
 export function clamp01(value: number): number {
   if (!Number.isFinite(value)) return 0
   if (value < 0) return 0
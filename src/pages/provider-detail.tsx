@@ -34,6 +34,7 @@ export function ProviderDetailPage({
       loading={plugin.loading}
       error={plugin.error}
       lines={plugin.data?.lines ?? []}
+      accounts={plugin.data?.accounts}
       skeletonLines={plugin.meta.lines}
       lastManualRefreshAt={plugin.lastManualRefreshAt}
       lastUpdatedAt={plugin.lastUpdatedAt}
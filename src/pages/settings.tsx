@@ -34,7 +34,7 @@ import {
   type ThemeMode,
 } from "@/lib/settings";
 import type { TraySettingsPreview } from "@/hooks/app/use-tray-icon";
-import { cn } from "@/lib/utils";
+import { cn, isRasterIconUrl } from "@/lib/utils";
 
 interface PluginConfig {
   id: string;
@@ -64,6 +64,17 @@ function ProviderIconMask({
   sizePx: number;
 }) {
   const colorClass = isActive ? "bg-primary-foreground" : "bg-foreground";
+  if (iconUrl && isRasterIconUrl(iconUrl)) {
+    return (
+      <img
+        aria-hidden
+        alt=""
+        src={iconUrl}
+        className="shrink-0 object-contain"
+        style={{ width: `${sizePx}px`, height: `${sizePx}px` }}
+      />
+    );
+  }
   if (iconUrl) {
     return (
       <div